@@ -1,8 +1,41 @@
+//! Render plain text and source code to paginated PDF documents.
+//!
+//! Text is set in the bundled DejaVu Sans Mono font on A4 pages. Long lines are hard
+//! wrapped with a `↪` continuation marker, tabs are expanded, and every page carries a
+//! header with the source path and a `Page N of M` footer. Characters the font has no
+//! glyph for are replaced with `�` rather than silently dropped.
+//!
+//! Code is syntax highlighted when its language is known (see [`highlight`]) and lines are
+//! numbered in a left gutter; both are controlled through [`PdfOptions`].
+
+use std::fs;
+use std::path::Path;
+
+use printpdf::{
+    Color, FontId, Line, LinePoint, Mm, Op, ParsedFont, PdfDocument, PdfPage, PdfSaveOptions,
+    Point, Pt, Rgb, TextItem,
+};
 use tracing::{debug, error, info};
 
-use std::fs::File;
+pub mod highlight;
+pub mod markdown;
 
-use std::path::Path;
+use highlight::{detect_language, language_by_name, Highlighter, Language, Span, Theme, TokenKind};
+
+/// DejaVu Sans Mono (Bitstream Vera license, see `assets/fonts/LICENSE-DejaVu`).
+const MONO_FONT: &[u8] = include_bytes!("../assets/fonts/DejaVuSansMono.ttf");
+
+const PAGE_WIDTH_MM: f32 = 210.0;
+const PAGE_HEIGHT_MM: f32 = 297.0;
+const MARGIN_MM: f32 = 15.0;
+const FONT_SIZE_PT: f32 = 9.0;
+const LINE_HEIGHT_PT: f32 = 11.0;
+const HEADER_FONT_SIZE_PT: f32 = 8.0;
+/// Vertical space reserved for the header and footer bands, each.
+const BAND_HEIGHT_PT: f32 = 20.0;
+const TAB_WIDTH: usize = 4;
+const CONTINUATION: &str = "↪ ";
+const REPLACEMENT: char = '\u{FFFD}';
 
 /// Error type for PDF generation
 #[derive(Debug)]
@@ -18,42 +51,531 @@ impl From<std::io::Error> for CodeToPdfError {
     }
 }
 
+/// Options for [`code_file_to_pdf_with_options`] and [`text_to_pdf`].
+#[derive(Debug, Clone)]
+pub struct PdfOptions {
+    /// Text shown in every page header. Defaults to the input path.
+    pub header: Option<String>,
+    /// Language to highlight as, e.g. `rust`. Detected from the file extension or `#!` line
+    /// when `None`; names without a highlighter (such as `text`) render as plain text.
+    pub language: Option<String>,
+    pub theme: Theme,
+    /// Number source lines in a left gutter (default: on).
+    pub line_numbers: bool,
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        PdfOptions {
+            header: None,
+            language: None,
+            theme: Theme::default(),
+            line_numbers: true,
+        }
+    }
+}
+
+/// One printed line: a numbered source line or a continuation of the previous one.
+struct VisualLine {
+    number: Option<usize>,
+    spans: Vec<Span>,
+}
+
 /// Convert a plaintext code file to a PDF at the given output path.
 /// The output will use a bundled monospaced font.
-///
 pub fn code_file_to_pdf(input_path: &Path, output_path: &Path) -> Result<(), CodeToPdfError> {
+    code_file_to_pdf_with_options(input_path, output_path, &PdfOptions::default())
+}
+
+/// Like [`code_file_to_pdf`], with control over the page header.
+pub fn code_file_to_pdf_with_options(
+    input_path: &Path,
+    output_path: &Path,
+    options: &PdfOptions,
+) -> Result<(), CodeToPdfError> {
     info!(
         input = %input_path.display(),
         output = %output_path.display(),
         "Starting code_file_to_pdf conversion"
     );
 
-    use std::io::Write;
-    let mut file = match File::create(output_path) {
-        Ok(f) => {
-            debug!(output = %output_path.display(), "Created output PDF file for writing");
-            f
-        }
-        Err(e) => {
-            error!(error = ?e, output = %output_path.display(), "Failed to create output PDF file");
-            return Err(CodeToPdfError::Io(e));
-        }
-    };
-    let mut contents = b"%PDF-1.4\n%Fake generated by code_to_pdf stub\n".to_vec();
-    while contents.len() < 110 {
-        contents.extend_from_slice(b"This is padding. ");
-    }
-    contents.extend_from_slice(b"\n%%EOF\n");
-    if let Err(e) = file.write_all(&contents) {
-        error!(error=?e, output = %output_path.display(), "Error writing fake PDF contents");
-        return Err(CodeToPdfError::Io(e));
-    }
+    let bytes = fs::read(input_path).map_err(|e| {
+        error!(error = ?e, input = %input_path.display(), "Failed to read input file");
+        CodeToPdfError::Io(e)
+    })?;
+    // Invalid UTF-8 sequences become U+FFFD instead of failing the whole document.
+    let text = String::from_utf8_lossy(&bytes);
+    let mut options = options.clone();
+    options
+        .header
+        .get_or_insert_with(|| input_path.display().to_string());
+    options.language.get_or_insert_with(|| {
+        detect_language(input_path, &text)
+            .map(|l| l.name)
+            .unwrap_or("text")
+            .to_string()
+    });
+
+    let pdf = text_to_pdf(&text, &options)?;
+    fs::write(output_path, &pdf).map_err(|e| {
+        error!(error = ?e, output = %output_path.display(), "Failed to write output PDF file");
+        CodeToPdfError::Io(e)
+    })?;
 
     info!(
         input = %input_path.display(),
         output = %output_path.display(),
-        bytes = contents.len(),
-        "Finished code_file_to_pdf: PDF stub written"
+        bytes = pdf.len(),
+        "Finished code_file_to_pdf"
     );
     Ok(())
 }
+
+/// Render `text` to an in-memory PDF. Without an explicit language, only a `#!` line is
+/// used for detection.
+pub fn text_to_pdf(text: &str, options: &PdfOptions) -> Result<Vec<u8>, CodeToPdfError> {
+    if text.trim().is_empty() {
+        return Err(CodeToPdfError::EmptyInput);
+    }
+
+    let mut warnings = Vec::new();
+    let font = load_font(MONO_FONT, "bundled monospace font could not be parsed")?;
+    let language = match &options.language {
+        Some(name) => language_by_name(name),
+        None => detect_language(Path::new(""), text),
+    };
+    let (layout, lines) = layout_text(text, language, &font, options)?;
+    let pages: Vec<&[VisualLine]> = lines.chunks(layout.lines_per_page).collect();
+    debug!(
+        lines = lines.len(),
+        pages = pages.len(),
+        columns = layout.columns,
+        language = language.map(|l| l.name).unwrap_or("text"),
+        "Laid out text for PDF"
+    );
+
+    let header = options.header.as_deref().unwrap_or_default();
+    let mut doc = PdfDocument::new(header);
+    let font_id = doc.add_font(&font);
+    let header = sanitize_line(header, &font);
+    let total = pages.len();
+    let pdf_pages = pages
+        .iter()
+        .enumerate()
+        .map(|(i, page_lines)| layout.render_page(&font_id, &header, page_lines, i + 1, total))
+        .collect();
+
+    let bytes = doc
+        .with_pages(pdf_pages)
+        .save(&PdfSaveOptions::default(), &mut warnings);
+    Ok(bytes)
+}
+
+/// One file of a multi-file document rendered by [`files_to_pdf`].
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    /// Path shown in the contents, page headers and bookmarks, e.g. `src/lib.rs`.
+    pub path: String,
+    pub text: String,
+}
+
+/// Render several files into one PDF: a table of contents first, then every file starting
+/// on a new page with its path in the header and a bookmark to its first page.
+///
+/// `title` heads the contents pages and names the document; `options.header` is ignored.
+/// Each file's language is detected from its path unless `options.language` is set.
+pub fn files_to_pdf(
+    title: &str,
+    files: &[SourceFile],
+    options: &PdfOptions,
+) -> Result<Vec<u8>, CodeToPdfError> {
+    if files.is_empty() {
+        return Err(CodeToPdfError::EmptyInput);
+    }
+    let mut warnings = Vec::new();
+    let font = load_font(MONO_FONT, "bundled monospace font could not be parsed")?;
+
+    let mut laid_out = Vec::with_capacity(files.len());
+    for file in files {
+        let language = match &options.language {
+            Some(name) => language_by_name(name),
+            None => detect_language(Path::new(&file.path), &file.text),
+        };
+        let (layout, mut lines) = layout_text(&file.text, language, &font, options)?;
+        if lines.is_empty() {
+            lines.push(VisualLine {
+                number: Some(1),
+                spans: Vec::new(),
+            });
+        }
+        laid_out.push((layout, lines));
+    }
+
+    // Contents: one line per file with the page it starts on, right aligned.
+    let toc_layout = Layout::new(&font, 0, options.theme)?;
+    let toc_pages = (files.len() + 2).div_ceil(toc_layout.lines_per_page);
+    let mut first_pages = Vec::with_capacity(files.len());
+    let mut next_page = toc_pages + 1;
+    for (layout, lines) in &laid_out {
+        first_pages.push(next_page);
+        next_page += lines.len().div_ceil(layout.lines_per_page);
+    }
+    let total = next_page - 1;
+    let mut toc = vec![
+        VisualLine {
+            number: None,
+            spans: vec![Span {
+                kind: TokenKind::Keyword,
+                text: "Contents".to_string(),
+            }],
+        },
+        VisualLine {
+            number: None,
+            spans: Vec::new(),
+        },
+    ];
+    for (file, page) in files.iter().zip(&first_pages) {
+        let page = page.to_string();
+        let room = toc_layout.columns.saturating_sub(page.len() + 2).max(8);
+        let path: Vec<char> = sanitize_line(&file.path, &font).chars().collect();
+        let path: String = if path.len() > room {
+            std::iter::once('…')
+                .chain(path[path.len() - room + 1..].iter().copied())
+                .collect()
+        } else {
+            path.into_iter().collect()
+        };
+        let dots = toc_layout
+            .columns
+            .saturating_sub(path.chars().count() + page.len());
+        toc.push(VisualLine {
+            number: None,
+            spans: vec![
+                Span {
+                    kind: TokenKind::Plain,
+                    text: format!("{path} {} ", ".".repeat(dots.saturating_sub(2))),
+                },
+                Span {
+                    kind: TokenKind::Number,
+                    text: page,
+                },
+            ],
+        });
+    }
+
+    let mut doc = PdfDocument::new(title);
+    let font_id = doc.add_font(&font);
+    let title_header = sanitize_line(title, &font);
+    let mut pdf_pages: Vec<PdfPage> = toc
+        .chunks(toc_layout.lines_per_page)
+        .enumerate()
+        .map(|(i, lines)| toc_layout.render_page(&font_id, &title_header, lines, i + 1, total))
+        .collect();
+    for ((file, (layout, lines)), first) in files.iter().zip(&laid_out).zip(&first_pages) {
+        let header = sanitize_line(&file.path, &font);
+        for (i, page_lines) in lines.chunks(layout.lines_per_page).enumerate() {
+            pdf_pages.push(layout.render_page(&font_id, &header, page_lines, first + i, total));
+        }
+        doc.add_bookmark(&file.path, *first);
+    }
+    debug!(
+        files = files.len(),
+        pages = total,
+        contents_pages = toc_pages,
+        "Laid out multi-file PDF"
+    );
+
+    let bytes = doc
+        .with_pages(pdf_pages)
+        .save(&PdfSaveOptions::default(), &mut warnings);
+    Ok(bytes)
+}
+
+/// Highlight and wrap `text` into printed lines, with a gutter sized for its line count.
+fn layout_text(
+    text: &str,
+    language: Option<&'static Language>,
+    font: &ParsedFont,
+    options: &PdfOptions,
+) -> Result<(Layout, Vec<VisualLine>), CodeToPdfError> {
+    let source_lines: Vec<&str> = text.lines().collect();
+    let gutter = if options.line_numbers {
+        source_lines.len().to_string().len() + 1
+    } else {
+        0
+    };
+    let layout = Layout::new(font, gutter, options.theme)?;
+    let mut highlighter = Highlighter::new(language);
+    let lines = source_lines
+        .iter()
+        .enumerate()
+        .flat_map(|(i, line)| {
+            let spans = highlighter.highlight_line(&sanitize_line(line, font));
+            layout.wrap(i + 1, spans)
+        })
+        .collect();
+    Ok((layout, lines))
+}
+
+/// Page geometry derived from the font metrics.
+struct Layout {
+    /// Width of one character cell at the body font size, in points.
+    char_width: f32,
+    /// Character cells per line, including the gutter.
+    columns: usize,
+    /// Width of the line number gutter in character cells (0 when disabled).
+    gutter: usize,
+    lines_per_page: usize,
+    theme: Theme,
+}
+
+impl Layout {
+    fn new(font: &ParsedFont, gutter: usize, theme: Theme) -> Result<Self, CodeToPdfError> {
+        let char_width = mono_advance(font)? * FONT_SIZE_PT;
+        let usable_width = mm_to_pt(PAGE_WIDTH_MM - 2.0 * MARGIN_MM);
+        let usable_height = mm_to_pt(PAGE_HEIGHT_MM - 2.0 * MARGIN_MM) - 2.0 * BAND_HEIGHT_PT;
+        Ok(Layout {
+            char_width,
+            columns: (usable_width / char_width) as usize,
+            gutter,
+            lines_per_page: (usable_height / LINE_HEIGHT_PT) as usize,
+            theme,
+        })
+    }
+
+    /// Hard wrap a highlighted line to the page width, marking continuation lines.
+    fn wrap(&self, number: usize, spans: Vec<Span>) -> Vec<VisualLine> {
+        let width = self.columns.saturating_sub(self.gutter).max(8);
+        let mut out = vec![VisualLine {
+            number: Some(number),
+            spans: Vec::new(),
+        }];
+        let mut used = 0;
+        for span in spans {
+            let mut current = String::new();
+            for c in span.text.chars() {
+                if used == width {
+                    let line = out.last_mut().expect("at least one line");
+                    line.spans.push(Span {
+                        kind: span.kind,
+                        text: std::mem::take(&mut current),
+                    });
+                    out.push(VisualLine {
+                        number: None,
+                        spans: vec![Span {
+                            kind: TokenKind::Comment,
+                            text: CONTINUATION.to_string(),
+                        }],
+                    });
+                    used = CONTINUATION.chars().count();
+                }
+                current.push(c);
+                used += 1;
+            }
+            if !current.is_empty() {
+                let line = out.last_mut().expect("at least one line");
+                line.spans.push(Span {
+                    kind: span.kind,
+                    text: current,
+                });
+            }
+        }
+        out
+    }
+
+    fn render_page(
+        &self,
+        font: &FontId,
+        header: &str,
+        lines: &[VisualLine],
+        number: usize,
+        total: usize,
+    ) -> PdfPage {
+        let left = mm_to_pt(MARGIN_MM);
+        let top = mm_to_pt(PAGE_HEIGHT_MM - MARGIN_MM);
+        let mut ops = page_decorations(font, self.char_width / FONT_SIZE_PT, header, number, total);
+
+        ops.push(Op::StartTextSection);
+        ops.push(Op::SetFontSize {
+            size: Pt(FONT_SIZE_PT),
+            font: font.clone(),
+        });
+        ops.push(Op::SetLineHeight {
+            lh: Pt(LINE_HEIGHT_PT),
+        });
+        ops.push(Op::SetTextCursor {
+            pos: Point {
+                x: Pt(left),
+                y: Pt(top - BAND_HEIGHT_PT - FONT_SIZE_PT),
+            },
+        });
+        let mut fill = None;
+        let mut write = |ops: &mut Vec<Op>, color: (f32, f32, f32), text: String| {
+            if text.is_empty() {
+                return;
+            }
+            if fill != Some(color) {
+                ops.push(Op::SetFillColor { col: rgb(color) });
+                fill = Some(color);
+            }
+            ops.push(Op::WriteText {
+                items: vec![TextItem::Text(text)],
+                font: font.clone(),
+            });
+        };
+        for line in lines {
+            if self.gutter > 0 {
+                let label = line.number.map(|n| n.to_string()).unwrap_or_default();
+                let gutter = format!("{label:>width$} ", width = self.gutter - 1);
+                write(&mut ops, self.theme.line_number_color(), gutter);
+            }
+            for span in &line.spans {
+                write(&mut ops, self.theme.color(span.kind), span.text.clone());
+            }
+            ops.push(Op::AddLineBreak);
+        }
+        ops.push(Op::EndTextSection);
+
+        PdfPage::new(Mm(PAGE_WIDTH_MM), Mm(PAGE_HEIGHT_MM), ops)
+    }
+}
+
+/// Header (source path, separated by a rule) and `Page N of M` footer drawn on every page,
+/// set in the monospace `font` whose advance per point of font size is `mono_advance`.
+fn page_decorations(
+    font: &FontId,
+    mono_advance: f32,
+    header: &str,
+    number: usize,
+    total: usize,
+) -> Vec<Op> {
+    let left = mm_to_pt(MARGIN_MM);
+    let right = mm_to_pt(PAGE_WIDTH_MM - MARGIN_MM);
+    let top = mm_to_pt(PAGE_HEIGHT_MM - MARGIN_MM);
+    let bottom = mm_to_pt(MARGIN_MM);
+    let small_char_width = mono_advance * HEADER_FONT_SIZE_PT;
+
+    let header_columns = ((right - left) / small_char_width) as usize;
+    let header: String = if header.chars().count() > header_columns {
+        let skip = header.chars().count() + 1 - header_columns;
+        format!("…{}", header.chars().skip(skip).collect::<String>())
+    } else {
+        header.to_string()
+    };
+    let footer = format!("Page {number} of {total}");
+    let footer_x = right - footer.chars().count() as f32 * small_char_width;
+
+    let mut ops = vec![Op::SetFillColor {
+        col: rgb((0.4, 0.4, 0.4)),
+    }];
+    ops.extend(text_at(
+        font,
+        HEADER_FONT_SIZE_PT,
+        left,
+        top - HEADER_FONT_SIZE_PT,
+        &header,
+    ));
+    ops.extend(text_at(
+        font,
+        HEADER_FONT_SIZE_PT,
+        footer_x,
+        bottom,
+        &footer,
+    ));
+    ops.extend(hline(left, right, top - 12.0, 0.5, (0.75, 0.75, 0.75)));
+    ops
+}
+
+/// A horizontal rule from `x1` to `x2` at height `y`.
+fn hline(x1: f32, x2: f32, y: f32, thickness: f32, color: (f32, f32, f32)) -> Vec<Op> {
+    vec![
+        Op::SetOutlineColor { col: rgb(color) },
+        Op::SetOutlineThickness { pt: Pt(thickness) },
+        Op::DrawLine {
+            line: Line {
+                points: vec![
+                    LinePoint {
+                        p: Point {
+                            x: Pt(x1),
+                            y: Pt(y),
+                        },
+                        bezier: false,
+                    },
+                    LinePoint {
+                        p: Point {
+                            x: Pt(x2),
+                            y: Pt(y),
+                        },
+                        bezier: false,
+                    },
+                ],
+                is_closed: false,
+            },
+        },
+    ]
+}
+
+fn load_font(bytes: &[u8], error: &'static str) -> Result<ParsedFont, CodeToPdfError> {
+    ParsedFont::from_bytes(bytes, 0, &mut Vec::new()).ok_or(CodeToPdfError::Font(error))
+}
+
+/// Advance of one monospace character cell per point of font size.
+fn mono_advance(font: &ParsedFont) -> Result<f32, CodeToPdfError> {
+    let glyph = font
+        .lookup_glyph_index('M' as u32)
+        .ok_or(CodeToPdfError::Font("monospace font has no glyph for 'M'"))?;
+    let units_per_em = f32::from(font.font_metrics.units_per_em.max(1));
+    let advance = f32::from(font.get_horizontal_advance(glyph)) / units_per_em;
+    if advance <= 0.0 {
+        return Err(CodeToPdfError::Font("monospace font reports zero advance"));
+    }
+    Ok(advance)
+}
+
+/// A self-contained text section writing `text` with its baseline at `(x, y)`.
+fn text_at(font: &FontId, size: f32, x: f32, y: f32, text: &str) -> Vec<Op> {
+    vec![
+        Op::StartTextSection,
+        Op::SetFontSize {
+            size: Pt(size),
+            font: font.clone(),
+        },
+        Op::SetTextCursor {
+            pos: Point { x: Pt(x), y: Pt(y) },
+        },
+        Op::WriteText {
+            items: vec![TextItem::Text(text.to_string())],
+            font: font.clone(),
+        },
+        Op::EndTextSection,
+    ]
+}
+
+/// Expand tabs, drop control characters and replace characters missing from the font.
+fn sanitize_line(line: &str, font: &ParsedFont) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        match c {
+            '\t' => {
+                let spaces = TAB_WIDTH - column % TAB_WIDTH;
+                out.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+                continue;
+            }
+            c if c.is_control() => continue,
+            c if font.lookup_glyph_index(c as u32).is_some() => out.push(c),
+            _ => out.push(REPLACEMENT),
+        }
+        column += 1;
+    }
+    out
+}
+
+fn rgb((r, g, b): (f32, f32, f32)) -> Color {
+    Color::Rgb(Rgb::new(r, g, b, None))
+}
+
+fn mm_to_pt(mm: f32) -> f32 {
+    Pt::from(Mm(mm)).0
+}
@@ -0,0 +1,641 @@
+//! Lightweight, dependency-free syntax highlighting for code PDFs.
+//!
+//! Each supported language is described by its keywords, comment markers and string
+//! quotes. A [`Highlighter`] splits lines into [`Span`]s, carrying block comment and
+//! multi-line string state from one line to the next. This is deliberately a lexer, not
+//! a parser: it only needs to be right often enough to make printed code easier to scan.
+
+use std::path::Path;
+
+/// Classification of a highlighted span.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Plain,
+    Keyword,
+    String,
+    Comment,
+    Number,
+}
+
+/// A run of text with a single token kind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub kind: TokenKind,
+    pub text: String,
+}
+
+/// Colour scheme for highlighted code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    /// GitHub-like colours on white.
+    #[default]
+    Light,
+    /// Solarized Light accent colours.
+    Solarized,
+    /// Black text with grey comments, for printing.
+    Monochrome,
+}
+
+impl Theme {
+    /// RGB fill colour (components in `0.0..=1.0`) for a token kind.
+    pub fn color(self, kind: TokenKind) -> (f32, f32, f32) {
+        match (self, kind) {
+            (Theme::Light, TokenKind::Plain) => (0.14, 0.16, 0.18),
+            (Theme::Light, TokenKind::Keyword) => (0.84, 0.23, 0.29),
+            (Theme::Light, TokenKind::String) => (0.01, 0.18, 0.38),
+            (Theme::Light, TokenKind::Comment) => (0.42, 0.45, 0.49),
+            (Theme::Light, TokenKind::Number) => (0.0, 0.36, 0.77),
+            (Theme::Solarized, TokenKind::Plain) => (0.40, 0.48, 0.51),
+            (Theme::Solarized, TokenKind::Keyword) => (0.52, 0.60, 0.0),
+            (Theme::Solarized, TokenKind::String) => (0.16, 0.63, 0.60),
+            (Theme::Solarized, TokenKind::Comment) => (0.58, 0.63, 0.63),
+            (Theme::Solarized, TokenKind::Number) => (0.83, 0.21, 0.51),
+            (Theme::Monochrome, TokenKind::Comment) => (0.45, 0.45, 0.45),
+            (Theme::Monochrome, _) => (0.0, 0.0, 0.0),
+        }
+    }
+
+    /// RGB colour of the line number gutter.
+    pub fn line_number_color(self) -> (f32, f32, f32) {
+        match self {
+            Theme::Solarized => (0.58, 0.63, 0.63),
+            Theme::Light | Theme::Monochrome => (0.6, 0.6, 0.6),
+        }
+    }
+}
+
+/// Lexical description of a language.
+#[derive(Debug)]
+pub struct Language {
+    pub name: &'static str,
+    extensions: &'static [&'static str],
+    /// Interpreter names recognised in a `#!` line.
+    interpreters: &'static [&'static str],
+    keywords: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    /// Delimiters of strings that may span lines, e.g. Python's `"""`.
+    multiline_strings: &'static [&'static str],
+    case_insensitive: bool,
+}
+
+impl Language {
+    /// The marker that starts a line comment, e.g. `//`, when the language has one.
+    pub fn line_comment(&self) -> Option<&'static str> {
+        self.line_comments.first().copied()
+    }
+}
+
+const C_LIKE_QUOTES: &[char] = &['"', '\''];
+
+const LANGUAGES: &[Language] = &[
+    Language {
+        name: "rust",
+        extensions: &["rs"],
+        interpreters: &[],
+        keywords: &[
+            "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+            "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+            "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super",
+            "trait", "true", "type", "unsafe", "use", "where", "while",
+        ],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        // Single quotes are mostly lifetimes in Rust, so only double quotes start strings.
+        quotes: &['"'],
+        multiline_strings: &[],
+        case_insensitive: false,
+    },
+    Language {
+        name: "python",
+        extensions: &["py", "pyi"],
+        interpreters: &["python", "python3", "python2"],
+        keywords: &[
+            "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class",
+            "continue", "def", "del", "elif", "else", "except", "finally", "for", "from", "global",
+            "if", "import", "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise",
+            "return", "try", "while", "with", "yield",
+        ],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: C_LIKE_QUOTES,
+        multiline_strings: &["\"\"\"", "'''"],
+        case_insensitive: false,
+    },
+    Language {
+        name: "javascript",
+        extensions: &["js", "mjs", "cjs", "jsx"],
+        interpreters: &["node", "deno"],
+        keywords: JS_KEYWORDS,
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+        multiline_strings: &[],
+        case_insensitive: false,
+    },
+    Language {
+        name: "typescript",
+        extensions: &["ts", "tsx", "mts", "cts"],
+        interpreters: &["ts-node"],
+        keywords: JS_KEYWORDS,
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+        multiline_strings: &[],
+        case_insensitive: false,
+    },
+    Language {
+        name: "go",
+        extensions: &["go"],
+        interpreters: &[],
+        keywords: &[
+            "break",
+            "case",
+            "chan",
+            "const",
+            "continue",
+            "default",
+            "defer",
+            "else",
+            "fallthrough",
+            "false",
+            "for",
+            "func",
+            "go",
+            "goto",
+            "if",
+            "import",
+            "interface",
+            "map",
+            "nil",
+            "package",
+            "range",
+            "return",
+            "select",
+            "struct",
+            "switch",
+            "true",
+            "type",
+            "var",
+        ],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+        multiline_strings: &[],
+        case_insensitive: false,
+    },
+    Language {
+        name: "java",
+        extensions: &["java", "kt", "kts", "scala"],
+        interpreters: &[],
+        keywords: &[
+            "abstract",
+            "boolean",
+            "break",
+            "case",
+            "catch",
+            "class",
+            "const",
+            "continue",
+            "data",
+            "default",
+            "do",
+            "else",
+            "enum",
+            "extends",
+            "false",
+            "final",
+            "finally",
+            "for",
+            "fun",
+            "if",
+            "implements",
+            "import",
+            "instanceof",
+            "interface",
+            "new",
+            "null",
+            "object",
+            "override",
+            "package",
+            "private",
+            "protected",
+            "public",
+            "return",
+            "static",
+            "super",
+            "switch",
+            "this",
+            "throw",
+            "throws",
+            "true",
+            "try",
+            "val",
+            "var",
+            "void",
+            "when",
+            "while",
+        ],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: C_LIKE_QUOTES,
+        multiline_strings: &["\"\"\""],
+        case_insensitive: false,
+    },
+    Language {
+        name: "c",
+        extensions: &["c", "h", "cc", "cpp", "cxx", "hpp", "hh", "cs", "m"],
+        interpreters: &[],
+        keywords: &[
+            "auto",
+            "bool",
+            "break",
+            "case",
+            "catch",
+            "char",
+            "class",
+            "const",
+            "continue",
+            "default",
+            "delete",
+            "do",
+            "double",
+            "else",
+            "enum",
+            "extern",
+            "false",
+            "float",
+            "for",
+            "goto",
+            "if",
+            "include",
+            "inline",
+            "int",
+            "long",
+            "namespace",
+            "new",
+            "nullptr",
+            "private",
+            "protected",
+            "public",
+            "return",
+            "short",
+            "signed",
+            "sizeof",
+            "static",
+            "struct",
+            "switch",
+            "template",
+            "this",
+            "throw",
+            "true",
+            "try",
+            "typedef",
+            "union",
+            "unsigned",
+            "using",
+            "virtual",
+            "void",
+            "volatile",
+            "while",
+        ],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: C_LIKE_QUOTES,
+        multiline_strings: &[],
+        case_insensitive: false,
+    },
+    Language {
+        name: "shell",
+        extensions: &["sh", "bash", "zsh"],
+        interpreters: &["sh", "bash", "zsh", "dash", "ksh"],
+        keywords: &[
+            "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if",
+            "in", "local", "readonly", "return", "select", "then", "until", "while",
+        ],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: C_LIKE_QUOTES,
+        multiline_strings: &[],
+        case_insensitive: false,
+    },
+    Language {
+        name: "ruby",
+        extensions: &["rb", "rake"],
+        interpreters: &["ruby"],
+        keywords: &[
+            "begin", "break", "case", "class", "def", "do", "else", "elsif", "end", "ensure",
+            "false", "for", "if", "in", "module", "next", "nil", "not", "or", "and", "redo",
+            "rescue", "retry", "return", "self", "super", "then", "true", "unless", "until",
+            "when", "while", "yield",
+        ],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: C_LIKE_QUOTES,
+        multiline_strings: &[],
+        case_insensitive: false,
+    },
+    Language {
+        name: "sql",
+        extensions: &["sql"],
+        interpreters: &[],
+        keywords: &[
+            "add",
+            "alter",
+            "and",
+            "as",
+            "asc",
+            "begin",
+            "by",
+            "case",
+            "check",
+            "commit",
+            "constraint",
+            "create",
+            "default",
+            "delete",
+            "desc",
+            "distinct",
+            "drop",
+            "else",
+            "end",
+            "exists",
+            "foreign",
+            "from",
+            "group",
+            "having",
+            "in",
+            "index",
+            "inner",
+            "insert",
+            "into",
+            "is",
+            "join",
+            "key",
+            "left",
+            "limit",
+            "not",
+            "null",
+            "on",
+            "or",
+            "order",
+            "outer",
+            "primary",
+            "references",
+            "right",
+            "select",
+            "set",
+            "table",
+            "then",
+            "union",
+            "unique",
+            "update",
+            "values",
+            "view",
+            "when",
+            "where",
+            "with",
+        ],
+        line_comments: &["--"],
+        block_comment: Some(("/*", "*/")),
+        quotes: C_LIKE_QUOTES,
+        multiline_strings: &[],
+        case_insensitive: true,
+    },
+    Language {
+        name: "yaml",
+        extensions: &["yaml", "yml", "toml", "ini", "cfg", "conf"],
+        interpreters: &[],
+        keywords: &["true", "false", "null", "yes", "no", "on", "off"],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: C_LIKE_QUOTES,
+        multiline_strings: &[],
+        case_insensitive: false,
+    },
+];
+
+const JS_KEYWORDS: &[&str] = &[
+    "as",
+    "async",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "from",
+    "function",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "of",
+    "private",
+    "public",
+    "readonly",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "type",
+    "typeof",
+    "undefined",
+    "var",
+    "void",
+    "while",
+    "yield",
+];
+
+/// Look up a language by name (case-insensitive). `text` and `plain` mean no highlighting.
+pub fn language_by_name(name: &str) -> Option<&'static Language> {
+    LANGUAGES.iter().find(|l| l.name.eq_ignore_ascii_case(name))
+}
+
+/// Detect the language of a file from its extension, falling back to a `#!` line.
+pub fn detect_language(path: &Path, text: &str) -> Option<&'static Language> {
+    let by_extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase)
+        .and_then(|ext| {
+            LANGUAGES
+                .iter()
+                .find(|l| l.extensions.contains(&ext.as_str()))
+        });
+    by_extension.or_else(|| {
+        let shebang = text.lines().next()?.strip_prefix("#!")?;
+        let mut words = shebang.split_whitespace();
+        let mut interpreter = Path::new(words.next()?).file_name()?.to_str()?;
+        if interpreter == "env" {
+            interpreter = words.find(|w| !w.starts_with('-'))?;
+        }
+        LANGUAGES
+            .iter()
+            .find(|l| l.interpreters.contains(&interpreter))
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Normal,
+    BlockComment(&'static str),
+    MultilineString(&'static str),
+}
+
+/// Splits lines into highlighted spans, keeping state across lines.
+pub struct Highlighter {
+    language: Option<&'static Language>,
+    state: State,
+}
+
+impl Highlighter {
+    /// A highlighter for `language`; `None` renders everything as plain text.
+    pub fn new(language: Option<&'static Language>) -> Self {
+        Highlighter {
+            language,
+            state: State::Normal,
+        }
+    }
+
+    /// Highlight the next line of the file.
+    pub fn highlight_line(&mut self, line: &str) -> Vec<Span> {
+        let Some(lang) = self.language else {
+            return vec![Span {
+                kind: TokenKind::Plain,
+                text: line.to_string(),
+            }];
+        };
+        let mut spans = Vec::new();
+        let mut rest = line;
+
+        while !rest.is_empty() {
+            match self.state {
+                State::BlockComment(end) | State::MultilineString(end) => {
+                    let kind = if matches!(self.state, State::BlockComment(_)) {
+                        TokenKind::Comment
+                    } else {
+                        TokenKind::String
+                    };
+                    match rest.find(end) {
+                        Some(pos) => {
+                            push(&mut spans, kind, &rest[..pos + end.len()]);
+                            rest = &rest[pos + end.len()..];
+                            self.state = State::Normal;
+                        }
+                        None => {
+                            push(&mut spans, kind, rest);
+                            rest = "";
+                        }
+                    }
+                }
+                State::Normal => {
+                    if lang.line_comments.iter().any(|c| rest.starts_with(c)) {
+                        push(&mut spans, TokenKind::Comment, rest);
+                        break;
+                    }
+                    if let Some((start, end)) =
+                        lang.block_comment.filter(|(s, _)| rest.starts_with(s))
+                    {
+                        push(&mut spans, TokenKind::Comment, start);
+                        rest = &rest[start.len()..];
+                        self.state = State::BlockComment(end);
+                        continue;
+                    }
+                    if let Some(delim) =
+                        lang.multiline_strings.iter().find(|d| rest.starts_with(*d))
+                    {
+                        push(&mut spans, TokenKind::String, delim);
+                        rest = &rest[delim.len()..];
+                        self.state = State::MultilineString(delim);
+                        continue;
+                    }
+
+                    let c = rest.chars().next().unwrap_or_default();
+                    let len = if lang.quotes.contains(&c) {
+                        (TokenKind::String, quoted_len(rest, c))
+                    } else if c.is_ascii_digit() {
+                        (TokenKind::Number, token_len(rest, true))
+                    } else if c.is_alphabetic() || c == '_' {
+                        let word = &rest[..token_len(rest, false)];
+                        let is_keyword = if lang.case_insensitive {
+                            let lower = word.to_ascii_lowercase();
+                            lang.keywords.contains(&lower.as_str())
+                        } else {
+                            lang.keywords.contains(&word)
+                        };
+                        let kind = if is_keyword {
+                            TokenKind::Keyword
+                        } else {
+                            TokenKind::Plain
+                        };
+                        (kind, word.len())
+                    } else {
+                        (TokenKind::Plain, c.len_utf8())
+                    };
+                    push(&mut spans, len.0, &rest[..len.1]);
+                    rest = &rest[len.1..];
+                }
+            }
+        }
+        spans
+    }
+}
+
+/// Byte length of the identifier (or number, which may contain `.`) at the start of `s`.
+fn token_len(s: &str, number: bool) -> usize {
+    s.char_indices()
+        .find(|(_, c)| !(c.is_alphanumeric() || *c == '_' || (number && *c == '.')))
+        .map(|(i, _)| i)
+        .unwrap_or(s.len())
+        .max(1)
+}
+
+/// Byte length of the string literal at the start of `s`, up to the end of the line when
+/// it is unterminated.
+fn quoted_len(s: &str, quote: char) -> usize {
+    let mut escaped = false;
+    for (i, c) in s.char_indices().skip(1) {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == quote {
+            return i + c.len_utf8();
+        }
+    }
+    s.len()
+}
+
+/// Append text to the spans, merging with the previous span when the kind matches.
+fn push(spans: &mut Vec<Span>, kind: TokenKind, text: &str) {
+    match spans.last_mut() {
+        Some(last) if last.kind == kind => last.text.push_str(text),
+        _ => spans.push(Span {
+            kind,
+            text: text.to_string(),
+        }),
+    }
+}
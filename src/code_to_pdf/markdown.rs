@@ -0,0 +1,1234 @@
+//! Markdown to PDF: lays out CommonMark with the GitHub extensions READMEs rely on.
+//!
+//! Headings (also added as PDF bookmarks), emphasis, inline code, links, ordered, bullet
+//! and task lists, block quotes, tables, rules and fenced code (highlighted with
+//! [`super::highlight`]) are typeset in the bundled DejaVu fonts. Local PNG/JPEG images are
+//! embedded; remote or undecodable images are replaced by their alt text. Relative links
+//! are resolved against [`MarkdownPdfOptions::base_url`] and printed after the link text so
+//! the target survives text extraction.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use printpdf::{
+    Actions, BorderArray, FontId, LineDashPattern, LinePoint, LinkAnnotation, Op, PaintMode,
+    ParsedFont, PdfDocument, PdfPage, PdfSaveOptions, Point, Polygon, PolygonRing, Pt, RawImage,
+    Rect, TextItem, WindingOrder, XObjectTransform,
+};
+use pulldown_cmark::{Alignment, CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use regex::Regex;
+use tracing::{debug, error, info, warn};
+
+use super::highlight::{language_by_name, Highlighter, Theme, TokenKind};
+use super::{
+    hline, load_font, mm_to_pt, mono_advance, page_decorations, rgb, sanitize_line, CodeToPdfError,
+    BAND_HEIGHT_PT, MARGIN_MM, MONO_FONT, PAGE_HEIGHT_MM, PAGE_WIDTH_MM,
+};
+
+/// DejaVu Sans family (Bitstream Vera license, see `assets/fonts/LICENSE-DejaVu`).
+const SANS_FONT: &[u8] = include_bytes!("../../assets/fonts/DejaVuSans.ttf");
+const SANS_BOLD_FONT: &[u8] = include_bytes!("../../assets/fonts/DejaVuSans-Bold.ttf");
+const SANS_OBLIQUE_FONT: &[u8] = include_bytes!("../../assets/fonts/DejaVuSans-Oblique.ttf");
+
+const BODY_SIZE_PT: f32 = 10.0;
+const CODE_SIZE_PT: f32 = 8.5;
+const LINE_SPACING: f32 = 1.35;
+const PARAGRAPH_GAP_PT: f32 = 6.0;
+const LIST_INDENT_PT: f32 = 18.0;
+const QUOTE_INDENT_PT: f32 = 14.0;
+const CELL_PADDING_PT: f32 = 4.0;
+const CODE_PADDING_PT: f32 = 4.0;
+/// Images are laid out as if they were 96 DPI screenshots.
+const IMAGE_DPI: f32 = 96.0;
+
+const TEXT_COLOR: (f32, f32, f32) = (0.1, 0.1, 0.1);
+const MUTED_COLOR: (f32, f32, f32) = (0.45, 0.45, 0.45);
+const LINK_COLOR: (f32, f32, f32) = (0.0, 0.36, 0.77);
+const CODE_COLOR: (f32, f32, f32) = (0.62, 0.12, 0.25);
+const CODE_BACKGROUND: (f32, f32, f32) = (0.96, 0.97, 0.98);
+const RULE_COLOR: (f32, f32, f32) = (0.8, 0.8, 0.8);
+
+/// Options for [`markdown_to_pdf`] and [`markdown_file_to_pdf`].
+#[derive(Debug, Clone, Default)]
+pub struct MarkdownPdfOptions {
+    /// Text shown in every page header. Defaults to the input path.
+    pub header: Option<String>,
+    /// Directory that relative image paths resolve against. Images outside it are not embedded.
+    pub base_dir: Option<PathBuf>,
+    /// URL that relative links resolve against, e.g. `https://github.com/org/repo/blob/main`.
+    pub base_url: Option<String>,
+    /// Theme for fenced code blocks.
+    pub theme: Theme,
+}
+
+/// Render a markdown file to a PDF at `output_path`. Relative images resolve against the
+/// file's directory unless `options.base_dir` is set.
+pub fn markdown_file_to_pdf(
+    input_path: &Path,
+    output_path: &Path,
+    options: &MarkdownPdfOptions,
+) -> Result<(), CodeToPdfError> {
+    info!(
+        input = %input_path.display(),
+        output = %output_path.display(),
+        "Starting markdown_file_to_pdf conversion"
+    );
+    let bytes = fs::read(input_path).map_err(|e| {
+        error!(error = ?e, input = %input_path.display(), "Failed to read markdown file");
+        CodeToPdfError::Io(e)
+    })?;
+    let markdown = String::from_utf8_lossy(&bytes);
+    let mut options = options.clone();
+    options
+        .header
+        .get_or_insert_with(|| input_path.display().to_string());
+    if options.base_dir.is_none() {
+        options.base_dir = input_path.parent().map(Path::to_path_buf);
+    }
+
+    let pdf = markdown_to_pdf(&markdown, &options)?;
+    fs::write(output_path, &pdf).map_err(|e| {
+        error!(error = ?e, output = %output_path.display(), "Failed to write output PDF file");
+        CodeToPdfError::Io(e)
+    })?;
+    info!(
+        input = %input_path.display(),
+        output = %output_path.display(),
+        bytes = pdf.len(),
+        "Finished markdown_file_to_pdf"
+    );
+    Ok(())
+}
+
+/// Render markdown to an in-memory PDF.
+pub fn markdown_to_pdf(
+    markdown: &str,
+    options: &MarkdownPdfOptions,
+) -> Result<Vec<u8>, CodeToPdfError> {
+    if markdown.trim().is_empty() {
+        return Err(CodeToPdfError::EmptyInput);
+    }
+    let header = options.header.as_deref().unwrap_or_default();
+    let mut doc = PdfDocument::new(header);
+    let fonts = FontSet::load(&mut doc)?;
+
+    let mut renderer = Renderer::new(&fonts, doc, options);
+    let parser = Parser::new_ext(
+        markdown,
+        Options::ENABLE_TABLES
+            | Options::ENABLE_STRIKETHROUGH
+            | Options::ENABLE_TASKLISTS
+            | Options::ENABLE_FOOTNOTES,
+    );
+    for event in parser {
+        renderer.event(event);
+    }
+    renderer.flush();
+
+    let Renderer {
+        mut canvas,
+        bookmarks,
+        ..
+    } = renderer;
+    canvas.finish_page();
+    let total = canvas.pages.len();
+    debug!(
+        pages = total,
+        bookmarks = bookmarks.len(),
+        "Laid out markdown for PDF"
+    );
+
+    let header = sanitize_line(header, fonts.parsed(Face::Mono));
+    let mono = mono_advance(fonts.parsed(Face::Mono))?;
+    let pages = canvas
+        .pages
+        .into_iter()
+        .enumerate()
+        .map(|(i, body)| {
+            let mut ops = page_decorations(fonts.id(Face::Mono), mono, &header, i + 1, total);
+            ops.extend(body);
+            PdfPage::new(
+                printpdf::Mm(PAGE_WIDTH_MM),
+                printpdf::Mm(PAGE_HEIGHT_MM),
+                ops,
+            )
+        })
+        .collect();
+    let mut doc = canvas.doc;
+    for (title, page) in &bookmarks {
+        doc.add_bookmark(title, *page);
+    }
+    let mut warnings = Vec::new();
+    Ok(doc
+        .with_pages(pages)
+        .save(&PdfSaveOptions::default(), &mut warnings))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Face {
+    Regular,
+    Bold,
+    Italic,
+    Mono,
+}
+
+/// The four bundled faces, parsed once and registered with the document.
+struct FontSet {
+    parsed: Vec<ParsedFont>,
+    ids: Vec<FontId>,
+}
+
+impl FontSet {
+    fn load(doc: &mut PdfDocument) -> Result<Self, CodeToPdfError> {
+        let parsed = vec![
+            load_font(SANS_FONT, "bundled sans font could not be parsed")?,
+            load_font(SANS_BOLD_FONT, "bundled bold font could not be parsed")?,
+            load_font(
+                SANS_OBLIQUE_FONT,
+                "bundled oblique font could not be parsed",
+            )?,
+            load_font(MONO_FONT, "bundled monospace font could not be parsed")?,
+        ];
+        let ids = parsed.iter().map(|f| doc.add_font(f)).collect();
+        Ok(FontSet { parsed, ids })
+    }
+
+    fn parsed(&self, face: Face) -> &ParsedFont {
+        &self.parsed[face as usize]
+    }
+
+    fn id(&self, face: Face) -> &FontId {
+        &self.ids[face as usize]
+    }
+
+    /// Width of already sanitized `text` in points.
+    fn width(&self, face: Face, size: f32, text: &str) -> f32 {
+        let font = self.parsed(face);
+        let units_per_em = f32::from(font.font_metrics.units_per_em.max(1));
+        text.chars()
+            .map(|c| {
+                font.lookup_glyph_index(c as u32)
+                    .map(|g| f32::from(font.get_horizontal_advance(g)))
+                    .unwrap_or(0.0)
+            })
+            .sum::<f32>()
+            / units_per_em
+            * size
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Style {
+    face: Face,
+    size: f32,
+    color: (f32, f32, f32),
+    link: Option<String>,
+    strike: bool,
+}
+
+impl Style {
+    fn body() -> Self {
+        Style {
+            face: Face::Regular,
+            size: BODY_SIZE_PT,
+            color: TEXT_COLOR,
+            link: None,
+            strike: false,
+        }
+    }
+}
+
+/// A piece of inline text in one style. `"\n"` forces a line break.
+#[derive(Debug, Clone)]
+struct Run {
+    text: String,
+    style: Style,
+}
+
+/// A laid out line: fragments with their x offset from the line start.
+struct TextLine {
+    fragments: Vec<(f32, Run)>,
+    width: f32,
+    /// Largest font size on the line, which determines its height.
+    size: f32,
+}
+
+impl TextLine {
+    fn height(&self) -> f32 {
+        self.size * LINE_SPACING
+    }
+}
+
+/// Page body ops and the vertical cursor.
+struct Canvas<'a> {
+    fonts: &'a FontSet,
+    doc: PdfDocument,
+    pages: Vec<Vec<Op>>,
+    ops: Vec<Op>,
+    /// Top of the remaining free space, in points from the page bottom.
+    y: f32,
+    /// Block quote nesting, drawn as bars left of every line.
+    quote_bars: Vec<f32>,
+}
+
+fn content_top() -> f32 {
+    mm_to_pt(PAGE_HEIGHT_MM - MARGIN_MM) - BAND_HEIGHT_PT
+}
+
+fn content_bottom() -> f32 {
+    mm_to_pt(MARGIN_MM) + BAND_HEIGHT_PT
+}
+
+fn content_left() -> f32 {
+    mm_to_pt(MARGIN_MM)
+}
+
+fn content_width() -> f32 {
+    mm_to_pt(PAGE_WIDTH_MM - 2.0 * MARGIN_MM)
+}
+
+impl<'a> Canvas<'a> {
+    fn page_number(&self) -> usize {
+        self.pages.len() + 1
+    }
+
+    /// Start a new page unless `height` still fits on the current one.
+    fn ensure(&mut self, height: f32) {
+        if self.y - height < content_bottom() && self.y < content_top() {
+            self.finish_page();
+        }
+    }
+
+    fn finish_page(&mut self) {
+        self.pages.push(std::mem::take(&mut self.ops));
+        self.y = content_top();
+    }
+
+    fn space(&mut self, height: f32) {
+        if self.y < content_top() {
+            self.y -= height;
+        }
+    }
+
+    fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: (f32, f32, f32)) {
+        let corner = |x: f32, y: f32| LinePoint {
+            p: Point { x: Pt(x), y: Pt(y) },
+            bezier: false,
+        };
+        self.ops.push(Op::SetFillColor { col: rgb(color) });
+        self.ops.push(Op::DrawPolygon {
+            polygon: Polygon {
+                rings: vec![PolygonRing {
+                    points: vec![
+                        corner(x, y),
+                        corner(x + width, y),
+                        corner(x + width, y + height),
+                        corner(x, y + height),
+                    ],
+                }],
+                mode: PaintMode::Fill,
+                winding_order: WindingOrder::NonZero,
+            },
+        });
+    }
+
+    fn vline(&mut self, x: f32, y1: f32, y2: f32, thickness: f32, color: (f32, f32, f32)) {
+        self.ops.push(Op::SetOutlineColor { col: rgb(color) });
+        self.ops.push(Op::SetOutlineThickness { pt: Pt(thickness) });
+        self.ops.push(Op::SetLineDashPattern {
+            dash: LineDashPattern::default(),
+        });
+        self.ops.push(Op::DrawLine {
+            line: printpdf::Line {
+                points: vec![
+                    LinePoint {
+                        p: Point {
+                            x: Pt(x),
+                            y: Pt(y1),
+                        },
+                        bezier: false,
+                    },
+                    LinePoint {
+                        p: Point {
+                            x: Pt(x),
+                            y: Pt(y2),
+                        },
+                        bezier: false,
+                    },
+                ],
+                is_closed: false,
+            },
+        });
+    }
+
+    /// Draw block quote bars beside a band of `height` starting at the cursor.
+    fn quote_band(&mut self, height: f32) {
+        let y = self.y;
+        for x in self.quote_bars.clone() {
+            self.vline(x, y, y - height, 2.0, RULE_COLOR);
+        }
+    }
+
+    /// Draw a laid out line with its top at the cursor and advance the cursor.
+    ///
+    /// The whole line is one text section so fragments follow each other by glyph advance,
+    /// which keeps a line as one line for text extraction.
+    fn draw_line(&mut self, x: f32, line: &TextLine) {
+        let height = line.height();
+        self.ensure(height);
+        self.quote_band(height);
+        let baseline = self.y - line.size;
+        let mut decorations = Vec::new();
+        self.ops.push(Op::StartTextSection);
+        self.ops.push(Op::SetTextCursor {
+            pos: Point {
+                x: Pt(x),
+                y: Pt(baseline),
+            },
+        });
+        for (offset, run) in &line.fragments {
+            let style = &run.style;
+            let font = self.fonts.id(style.face).clone();
+            self.ops.push(Op::SetFillColor {
+                col: rgb(style.color),
+            });
+            self.ops.push(Op::SetFontSize {
+                size: Pt(style.size),
+                font: font.clone(),
+            });
+            self.ops.push(Op::WriteText {
+                items: vec![TextItem::Text(run.text.clone())],
+                font,
+            });
+
+            let fx = x + offset;
+            let width = self.fonts.width(style.face, style.size, &run.text);
+            if style.strike {
+                let y = baseline + style.size * 0.3;
+                decorations.extend(hline(fx, fx + width, y, style.size / 14.0, style.color));
+            }
+            if let Some(url) = &style.link {
+                decorations.push(Op::LinkAnnotation {
+                    link: LinkAnnotation::new(
+                        Rect {
+                            x: Pt(fx),
+                            y: Pt(baseline - style.size * 0.25),
+                            width: Pt(width),
+                            height: Pt(style.size * 1.2),
+                        },
+                        Actions::Uri(url.clone()),
+                        Some(BorderArray::Solid([0.0, 0.0, 0.0])),
+                        None,
+                        None,
+                    ),
+                });
+            }
+        }
+        self.ops.push(Op::EndTextSection);
+        self.ops.extend(decorations);
+        self.y -= height;
+    }
+
+    /// Break runs into lines no wider than `width`, wrapping at spaces.
+    fn break_lines(&self, runs: &[Run], width: f32) -> Vec<TextLine> {
+        let mut lines = Vec::new();
+        let mut current = TextLine {
+            fragments: Vec::new(),
+            width: 0.0,
+            size: 0.0,
+        };
+        let finish = |current: &mut TextLine, lines: &mut Vec<TextLine>| {
+            // Drop trailing spaces so centred/right-aligned text lines up.
+            while let Some((_, last)) = current.fragments.last() {
+                if last.text.trim().is_empty() {
+                    current.fragments.pop();
+                } else {
+                    break;
+                }
+            }
+            current.width = current
+                .fragments
+                .last()
+                .map(|(x, r)| x + self.fonts.width(r.style.face, r.style.size, &r.text))
+                .unwrap_or(0.0);
+            if current.size == 0.0 {
+                current.size = BODY_SIZE_PT;
+            }
+            lines.push(std::mem::replace(
+                current,
+                TextLine {
+                    fragments: Vec::new(),
+                    width: 0.0,
+                    size: 0.0,
+                },
+            ));
+        };
+
+        for run in runs {
+            if run.text == "\n" {
+                current.size = current.size.max(run.style.size);
+                finish(&mut current, &mut lines);
+                continue;
+            }
+            for word in split_words(&run.text) {
+                let is_space = word.trim().is_empty();
+                if is_space && current.fragments.is_empty() {
+                    continue;
+                }
+                let mut word = word.to_string();
+                let mut w = self.fonts.width(run.style.face, run.style.size, &word);
+                if !is_space && current.width + w > width && !current.fragments.is_empty() {
+                    finish(&mut current, &mut lines);
+                }
+                // A single word wider than the line is split by characters.
+                while w > width && word.chars().count() > 1 {
+                    let mut head = String::new();
+                    for c in word.chars() {
+                        head.push(c);
+                        if self.fonts.width(run.style.face, run.style.size, &head) > width {
+                            head.pop();
+                            break;
+                        }
+                    }
+                    if head.is_empty() {
+                        head = word.chars().next().map(String::from).unwrap_or_default();
+                    }
+                    word = word[head.len()..].to_string();
+                    current.size = current.size.max(run.style.size);
+                    current.fragments.push((
+                        0.0,
+                        Run {
+                            text: head,
+                            style: run.style.clone(),
+                        },
+                    ));
+                    finish(&mut current, &mut lines);
+                    w = self.fonts.width(run.style.face, run.style.size, &word);
+                }
+                if word.is_empty() {
+                    continue;
+                }
+                current.size = current.size.max(run.style.size);
+                // Merge with the previous fragment when the style is unchanged.
+                match current.fragments.last_mut() {
+                    Some((_, last)) if last.style == run.style => last.text.push_str(&word),
+                    _ => current.fragments.push((
+                        current.width,
+                        Run {
+                            text: word,
+                            style: run.style.clone(),
+                        },
+                    )),
+                }
+                current.width += w;
+            }
+        }
+        if !current.fragments.is_empty() {
+            finish(&mut current, &mut lines);
+        }
+        lines
+    }
+}
+
+/// Split text into alternating words and whitespace, keeping both.
+fn split_words(text: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut start = 0;
+    let mut in_space = None;
+    for (i, c) in text.char_indices() {
+        let space = c == ' ';
+        if in_space.is_some_and(|s| s != space) {
+            out.push(&text[start..i]);
+            start = i;
+        }
+        in_space = Some(space);
+    }
+    if start < text.len() {
+        out.push(&text[start..]);
+    }
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Block {
+    Paragraph,
+    Heading(HeadingLevel),
+}
+
+struct Table {
+    alignments: Vec<Alignment>,
+    rows: Vec<Vec<Vec<Run>>>,
+    header_rows: usize,
+}
+
+/// Walks pulldown-cmark events and lays out blocks onto the canvas.
+struct Renderer<'a> {
+    canvas: Canvas<'a>,
+    fonts: &'a FontSet,
+    options: &'a MarkdownPdfOptions,
+    styles: Vec<Style>,
+    runs: Vec<Run>,
+    block: Block,
+    /// Left indent from the content edge, for lists and quotes.
+    indent: f32,
+    /// Next number for each open list; `None` for bullet lists.
+    lists: Vec<Option<u64>>,
+    /// Bullet or number to draw beside the next laid out line.
+    marker: Option<String>,
+    code_block: Option<(String, String)>,
+    /// Resolved URL and index of the first run of each open link.
+    links: Vec<(String, usize)>,
+    /// Destination and alt text of the image being read.
+    image: Option<(String, String)>,
+    table: Option<Table>,
+    bookmarks: Vec<(String, usize)>,
+}
+
+impl<'a> Renderer<'a> {
+    fn new(fonts: &'a FontSet, doc: PdfDocument, options: &'a MarkdownPdfOptions) -> Self {
+        Renderer {
+            canvas: Canvas {
+                fonts,
+                doc,
+                pages: Vec::new(),
+                ops: Vec::new(),
+                y: content_top(),
+                quote_bars: Vec::new(),
+            },
+            fonts,
+            options,
+            styles: vec![Style::body()],
+            runs: Vec::new(),
+            block: Block::Paragraph,
+            indent: 0.0,
+            lists: Vec::new(),
+            marker: None,
+            code_block: None,
+            links: Vec::new(),
+            image: None,
+            table: None,
+            bookmarks: Vec::new(),
+        }
+    }
+
+    fn style(&self) -> Style {
+        self.styles.last().cloned().unwrap_or_else(Style::body)
+    }
+
+    fn push_style(&mut self, change: impl FnOnce(&mut Style)) {
+        let mut style = self.style();
+        change(&mut style);
+        self.styles.push(style);
+    }
+
+    fn pop_style(&mut self) {
+        if self.styles.len() > 1 {
+            self.styles.pop();
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        let style = self.style();
+        let text = sanitize_line(text, self.fonts.parsed(style.face));
+        self.runs.push(Run { text, style });
+    }
+
+    fn event(&mut self, event: Event) {
+        if let Some((_, code)) = &mut self.code_block {
+            match event {
+                Event::Text(t) => code.push_str(&t),
+                Event::End(TagEnd::CodeBlock) => {
+                    let (lang, code) = self.code_block.take().unwrap_or_default();
+                    self.code(&lang, &code);
+                }
+                _ => {}
+            }
+            return;
+        }
+        if let Some((_, alt)) = &mut self.image {
+            match event {
+                Event::Text(t) | Event::Code(t) => alt.push_str(&t),
+                Event::End(TagEnd::Image) => {
+                    let (dest, alt) = self.image.take().unwrap_or_default();
+                    self.image(&dest, &alt);
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(t) => self.text(&t),
+            Event::Code(t) => {
+                self.push_style(|s| {
+                    s.face = Face::Mono;
+                    s.size *= 0.92;
+                    if s.link.is_none() {
+                        s.color = CODE_COLOR;
+                    }
+                });
+                self.text(&t);
+                self.pop_style();
+            }
+            Event::SoftBreak => self.text(" "),
+            Event::HardBreak => self.runs.push(Run {
+                text: "\n".to_string(),
+                style: self.style(),
+            }),
+            Event::Rule => {
+                self.flush();
+                self.canvas.ensure(PARAGRAPH_GAP_PT * 2.0);
+                self.canvas.space(PARAGRAPH_GAP_PT);
+                let left = content_left() + self.indent;
+                let y = self.canvas.y;
+                self.canvas.ops.extend(hline(
+                    left,
+                    content_left() + content_width(),
+                    y,
+                    0.8,
+                    RULE_COLOR,
+                ));
+                self.canvas.space(PARAGRAPH_GAP_PT);
+            }
+            Event::TaskListMarker(checked) => {
+                self.marker = Some(if checked { "☑" } else { "☐" }.to_string());
+            }
+            Event::FootnoteReference(label) => self.text(&format!("[{label}]")),
+            Event::Html(html) | Event::InlineHtml(html) => self.html(&html),
+            Event::InlineMath(t) | Event::DisplayMath(t) => self.text(&t),
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph => {}
+            Tag::Heading { level, .. } => {
+                self.flush();
+                self.block = Block::Heading(level);
+                let size = match level {
+                    HeadingLevel::H1 => 20.0,
+                    HeadingLevel::H2 => 16.0,
+                    HeadingLevel::H3 => 13.0,
+                    _ => 11.0,
+                };
+                self.push_style(|s| {
+                    s.face = Face::Bold;
+                    s.size = size;
+                });
+            }
+            Tag::BlockQuote(_) => {
+                self.flush();
+                self.canvas
+                    .quote_bars
+                    .push(content_left() + self.indent + 2.0);
+                self.indent += QUOTE_INDENT_PT;
+                self.push_style(|s| s.color = MUTED_COLOR);
+            }
+            Tag::CodeBlock(kind) => {
+                self.flush();
+                let lang = match kind {
+                    CodeBlockKind::Fenced(info) => {
+                        info.split([' ', ',', '{']).next().unwrap_or("").to_string()
+                    }
+                    CodeBlockKind::Indented => String::new(),
+                };
+                self.code_block = Some((lang, String::new()));
+            }
+            Tag::List(start) => {
+                self.flush();
+                self.lists.push(start);
+                self.indent += LIST_INDENT_PT;
+            }
+            Tag::Item => {
+                self.flush();
+                let depth = self.lists.len();
+                self.marker = Some(match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}.", *n - 1)
+                    }
+                    _ => ["•", "◦", "▪"][(depth.max(1) - 1) % 3].to_string(),
+                });
+            }
+            Tag::Emphasis => self.push_style(|s| {
+                if s.face == Face::Regular {
+                    s.face = Face::Italic;
+                }
+            }),
+            Tag::Strong => self.push_style(|s| {
+                if s.face != Face::Mono {
+                    s.face = Face::Bold;
+                }
+            }),
+            Tag::Strikethrough => self.push_style(|s| s.strike = true),
+            Tag::Link { dest_url, .. } => {
+                let url = self.resolve_link(&dest_url);
+                self.links.push((url.clone(), self.runs.len()));
+                self.push_style(|s| {
+                    s.color = LINK_COLOR;
+                    s.link = Some(url);
+                });
+            }
+            Tag::Image { dest_url, .. } => {
+                self.image = Some((dest_url.to_string(), String::new()));
+            }
+            Tag::Table(alignments) => {
+                self.flush();
+                self.table = Some(Table {
+                    alignments,
+                    rows: Vec::new(),
+                    header_rows: 0,
+                });
+            }
+            Tag::TableHead => {
+                if let Some(table) = &mut self.table {
+                    table.rows.push(Vec::new());
+                    table.header_rows += 1;
+                }
+                self.push_style(|s| s.face = Face::Bold);
+            }
+            Tag::TableRow => {
+                if let Some(table) = &mut self.table {
+                    table.rows.push(Vec::new());
+                }
+            }
+            Tag::TableCell => self.runs.clear(),
+            Tag::FootnoteDefinition(label) => {
+                self.flush();
+                self.push_style(|s| s.size = BODY_SIZE_PT * 0.9);
+                self.text(&format!("[{label}]: "));
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph => {
+                self.flush();
+                self.canvas.space(PARAGRAPH_GAP_PT);
+            }
+            TagEnd::Heading(level) => {
+                let title: String = self.runs.iter().map(|r| r.text.as_str()).collect();
+                let size = self.style().size;
+                self.canvas.space(size * 0.6);
+                // Keep the heading with at least a couple of lines of what follows.
+                self.canvas.ensure(size * LINE_SPACING + BODY_SIZE_PT * 3.0);
+                self.bookmarks
+                    .push((title.trim().to_string(), self.canvas.page_number()));
+                self.flush();
+                if matches!(level, HeadingLevel::H1 | HeadingLevel::H2) {
+                    let y = self.canvas.y - 2.0;
+                    self.canvas.ops.extend(hline(
+                        content_left() + self.indent,
+                        content_left() + content_width(),
+                        y,
+                        0.6,
+                        RULE_COLOR,
+                    ));
+                    self.canvas.space(4.0);
+                }
+                self.canvas.space(PARAGRAPH_GAP_PT / 2.0);
+                self.block = Block::Paragraph;
+                self.pop_style();
+            }
+            TagEnd::BlockQuote(_) => {
+                self.flush();
+                self.canvas.quote_bars.pop();
+                self.indent -= QUOTE_INDENT_PT;
+                self.pop_style();
+            }
+            TagEnd::List(_) => {
+                self.flush();
+                self.lists.pop();
+                self.indent -= LIST_INDENT_PT;
+                if self.lists.is_empty() {
+                    self.canvas.space(PARAGRAPH_GAP_PT);
+                }
+            }
+            TagEnd::Item => self.flush(),
+            TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough => self.pop_style(),
+            TagEnd::Link => {
+                self.pop_style();
+                if let Some((url, first)) = self.links.pop() {
+                    let text: String = self.runs[first..].iter().map(|r| r.text.as_str()).collect();
+                    if !url.starts_with('#') && text.trim() != url {
+                        self.push_style(|s| {
+                            s.color = MUTED_COLOR;
+                            s.size *= 0.85;
+                        });
+                        self.text(&format!(" ({url})"));
+                        self.pop_style();
+                    }
+                }
+            }
+            TagEnd::TableHead => self.pop_style(),
+            TagEnd::TableCell => {
+                let cell = std::mem::take(&mut self.runs);
+                if let Some(row) = self.table.as_mut().and_then(|t| t.rows.last_mut()) {
+                    row.push(cell);
+                }
+            }
+            TagEnd::Table => {
+                if let Some(table) = self.table.take() {
+                    self.table(table);
+                }
+            }
+            TagEnd::FootnoteDefinition => {
+                self.flush();
+                self.pop_style();
+            }
+            _ => {}
+        }
+    }
+
+    /// Lay out pending inline runs as a paragraph at the current indent.
+    fn flush(&mut self) {
+        if self.runs.is_empty() || self.table.is_some() {
+            return;
+        }
+        let runs = std::mem::take(&mut self.runs);
+        if runs.iter().all(|r| r.text.trim().is_empty()) {
+            return;
+        }
+        let left = content_left() + self.indent;
+        let lines = self
+            .canvas
+            .break_lines(&runs, content_width() - self.indent);
+        for line in &lines {
+            if let Some(marker) = self.marker.take() {
+                // The marker hangs in the indent, in the same text section as the line.
+                let style = Style {
+                    color: MUTED_COLOR,
+                    ..Style::body()
+                };
+                let marker = sanitize_line(&format!("{marker} "), self.fonts.parsed(Face::Regular));
+                let width = self.fonts.width(Face::Regular, BODY_SIZE_PT, &marker);
+                let mut fragments = vec![(
+                    0.0,
+                    Run {
+                        text: marker,
+                        style,
+                    },
+                )];
+                fragments.extend(
+                    line.fragments
+                        .iter()
+                        .map(|(offset, run)| (offset + width, run.clone())),
+                );
+                let marked = TextLine {
+                    fragments,
+                    width: line.width + width,
+                    size: line.size,
+                };
+                self.canvas.draw_line(left - width, &marked);
+                continue;
+            }
+            self.canvas.draw_line(left, line);
+        }
+        if self.block == Block::Paragraph && !self.lists.is_empty() {
+            self.canvas.space(1.0);
+        }
+    }
+
+    fn resolve_link(&self, dest: &str) -> String {
+        let is_absolute = dest.starts_with('#')
+            || dest.contains("://")
+            || dest.starts_with("mailto:")
+            || dest.starts_with("tel:");
+        match &self.options.base_url {
+            Some(base) if !is_absolute => format!(
+                "{}/{}",
+                base.trim_end_matches('/'),
+                dest.trim_start_matches("./").trim_start_matches('/')
+            ),
+            _ => dest.to_string(),
+        }
+    }
+
+    fn code(&mut self, lang: &str, code: &str) {
+        let mono = self.fonts.parsed(Face::Mono);
+        let char_width = mono_advance(mono).unwrap_or(0.6) * CODE_SIZE_PT;
+        let left = content_left() + self.indent;
+        let width = content_width() - self.indent;
+        let columns = (((width - 2.0 * CODE_PADDING_PT) / char_width) as usize).max(10);
+        let line_height = CODE_SIZE_PT * LINE_SPACING;
+        let theme = self.options.theme;
+
+        let mut highlighter = Highlighter::new(language_by_name(lang));
+        let mut lines: Vec<Vec<(TokenKind, String)>> = Vec::new();
+        for source in code.trim_end_matches('\n').lines() {
+            let spans = highlighter.highlight_line(&sanitize_line(source, mono));
+            let mut line = Vec::new();
+            let mut used = 0;
+            for span in spans {
+                for c in span.text.chars() {
+                    if used == columns {
+                        lines.push(std::mem::take(&mut line));
+                        line.push((TokenKind::Comment, "↪ ".to_string()));
+                        used = 2;
+                    }
+                    match line.last_mut() {
+                        Some((kind, text)) if *kind == span.kind => text.push(c),
+                        _ => line.push((span.kind, c.to_string())),
+                    }
+                    used += 1;
+                }
+            }
+            lines.push(line);
+        }
+
+        self.canvas.space(2.0);
+        self.canvas.ensure(line_height + 2.0 * CODE_PADDING_PT);
+        let (mut band_top, mut band_start) = (self.canvas.y, self.canvas.ops.len());
+        self.canvas.y -= CODE_PADDING_PT;
+        for line in lines {
+            if self.canvas.y - line_height - CODE_PADDING_PT < content_bottom() {
+                self.code_background(band_start, left, width, band_top);
+                self.canvas.finish_page();
+                band_top = self.canvas.y;
+                band_start = 0;
+                self.canvas.y -= CODE_PADDING_PT;
+            }
+            let mut offset = 0.0;
+            let fragments = line
+                .into_iter()
+                .map(|(kind, text)| {
+                    let fragment = (
+                        offset,
+                        Run {
+                            style: Style {
+                                face: Face::Mono,
+                                size: CODE_SIZE_PT,
+                                color: theme.color(kind),
+                                link: None,
+                                strike: false,
+                            },
+                            text,
+                        },
+                    );
+                    offset += fragment.1.text.chars().count() as f32 * char_width;
+                    fragment
+                })
+                .collect();
+            let line = TextLine {
+                fragments,
+                width: offset,
+                size: CODE_SIZE_PT,
+            };
+            self.canvas.draw_line(left + CODE_PADDING_PT, &line);
+        }
+        self.canvas.y -= CODE_PADDING_PT;
+        self.code_background(band_start, left, width, band_top);
+        self.canvas.space(PARAGRAPH_GAP_PT);
+    }
+
+    /// Insert the code block background behind the ops drawn since `start`.
+    fn code_background(&mut self, start: usize, left: f32, width: f32, top: f32) {
+        let drawn = self.canvas.ops.split_off(start);
+        let height = top - self.canvas.y;
+        self.canvas
+            .fill_rect(left, self.canvas.y, width, height, CODE_BACKGROUND);
+        self.canvas.ops.extend(drawn);
+    }
+
+    fn image(&mut self, dest: &str, alt: &str) {
+        if let Some(path) = self.local_image_path(dest) {
+            match fs::read(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|bytes| RawImage::decode_from_bytes(&bytes, &mut Vec::new()))
+            {
+                Ok(image) => {
+                    self.flush();
+                    self.embed_image(&image);
+                    return;
+                }
+                Err(e) => {
+                    warn!(path = %path.display(), error = %e, "Could not embed markdown image")
+                }
+            }
+        }
+        let label = if alt.trim().is_empty() { "image" } else { alt };
+        self.push_style(|s| {
+            s.color = MUTED_COLOR;
+            if s.face == Face::Regular {
+                s.face = Face::Italic;
+            }
+        });
+        self.text(&format!("[{label}]"));
+        self.pop_style();
+    }
+
+    /// Resolve a relative image destination inside `base_dir`.
+    fn local_image_path(&self, dest: &str) -> Option<PathBuf> {
+        if dest.contains("://") || dest.starts_with("data:") {
+            return None;
+        }
+        let base = self.options.base_dir.as_ref()?.canonicalize().ok()?;
+        let relative = dest
+            .split(['?', '#'])
+            .next()?
+            .trim_start_matches('/')
+            .replace("%20", " ");
+        let path = base.join(relative).canonicalize().ok()?;
+        path.starts_with(&base).then_some(path)
+    }
+
+    fn embed_image(&mut self, image: &RawImage) {
+        let natural_width = image.width as f32 * 72.0 / IMAGE_DPI;
+        let natural_height = image.height as f32 * 72.0 / IMAGE_DPI;
+        let max_width = content_width() - self.indent;
+        let max_height = (content_top() - content_bottom()) * 0.6;
+        let scale = (max_width / natural_width)
+            .min(max_height / natural_height)
+            .min(1.0);
+        let height = natural_height * scale;
+
+        self.canvas.ensure(height);
+        let id = self.canvas.doc.add_image(image);
+        self.canvas.ops.push(Op::UseXobject {
+            id,
+            transform: XObjectTransform {
+                translate_x: Some(Pt(content_left() + self.indent)),
+                translate_y: Some(Pt(self.canvas.y - height)),
+                scale_x: Some(scale),
+                scale_y: Some(scale),
+                dpi: Some(IMAGE_DPI),
+                rotate: None,
+            },
+        });
+        self.canvas.y -= height + PARAGRAPH_GAP_PT;
+    }
+
+    /// Raw HTML: `<img>` tags are treated as images, `<br>` as line breaks and other tags
+    /// are dropped, keeping their text.
+    fn html(&mut self, html: &str) {
+        let img = Regex::new(r#"(?i)<img\s[^>]*>"#).unwrap();
+        let attr = |tag: &str, name: &str| {
+            Regex::new(&format!(r#"(?i)\b{name}\s*=\s*["']([^"']*)["']"#))
+                .unwrap()
+                .captures(tag)
+                .map(|c| c[1].to_string())
+        };
+        let mut last = 0;
+        for m in img.find_iter(html) {
+            self.html_text(&html[last..m.start()]);
+            let src = attr(m.as_str(), "src").unwrap_or_default();
+            let alt = attr(m.as_str(), "alt").unwrap_or_default();
+            self.image(&src, &alt);
+            last = m.end();
+        }
+        self.html_text(&html[last..]);
+    }
+
+    fn html_text(&mut self, html: &str) {
+        let br = Regex::new(r"(?i)<br\s*/?>").unwrap();
+        let tags = Regex::new(r"<[^>]*>").unwrap();
+        let text = br.replace_all(html, "\n");
+        let text = tags.replace_all(&text, "");
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 && !self.runs.is_empty() {
+                self.runs.push(Run {
+                    text: "\n".to_string(),
+                    style: self.style(),
+                });
+            }
+            let line = line.trim();
+            if !line.is_empty() {
+                self.text(line);
+            }
+        }
+    }
+
+    fn table(&mut self, table: Table) {
+        let columns = table.rows.iter().map(Vec::len).max().unwrap_or(0);
+        if columns == 0 {
+            return;
+        }
+        let left = content_left() + self.indent;
+        let available = content_width() - self.indent;
+
+        // Natural single-line widths, scaled down proportionally when the table is too wide.
+        let mut widths = vec![30.0_f32; columns];
+        for row in &table.rows {
+            for (i, cell) in row.iter().enumerate() {
+                let w: f32 = cell
+                    .iter()
+                    .map(|r| self.fonts.width(r.style.face, r.style.size, &r.text))
+                    .sum();
+                widths[i] = widths[i].max(w + 2.0 * CELL_PADDING_PT);
+            }
+        }
+        let total: f32 = widths.iter().sum();
+        if total > available {
+            for w in &mut widths {
+                *w = (*w * available / total).max(24.0);
+            }
+        }
+        let table_width: f32 = widths.iter().sum();
+
+        self.canvas.space(2.0);
+        let top_y = self.canvas.y;
+        self.canvas
+            .ops
+            .extend(hline(left, left + table_width, top_y, 0.6, RULE_COLOR));
+        for (r, row) in table.rows.iter().enumerate() {
+            let cells: Vec<Vec<TextLine>> = (0..columns)
+                .map(|i| {
+                    row.get(i)
+                        .map(|cell| {
+                            self.canvas
+                                .break_lines(cell, widths[i] - 2.0 * CELL_PADDING_PT)
+                        })
+                        .unwrap_or_default()
+                })
+                .collect();
+            let height = cells
+                .iter()
+                .map(|lines| lines.iter().map(TextLine::height).sum::<f32>())
+                .fold(BODY_SIZE_PT * LINE_SPACING, f32::max)
+                + 2.0 * CELL_PADDING_PT;
+
+            let page = self.canvas.page_number();
+            self.canvas.ensure(height);
+            if self.canvas.page_number() != page {
+                let y = self.canvas.y;
+                self.canvas
+                    .ops
+                    .extend(hline(left, left + table_width, y, 0.6, RULE_COLOR));
+            }
+            let row_top = self.canvas.y;
+            if r < table.header_rows {
+                self.canvas
+                    .fill_rect(left, row_top - height, table_width, height, CODE_BACKGROUND);
+            }
+            let mut x = left;
+            for (i, lines) in cells.iter().enumerate() {
+                self.canvas.y = row_top - CELL_PADDING_PT;
+                for line in lines {
+                    let free = widths[i] - 2.0 * CELL_PADDING_PT - line.width;
+                    let offset = match table.alignments.get(i) {
+                        Some(Alignment::Center) => free / 2.0,
+                        Some(Alignment::Right) => free,
+                        _ => 0.0,
+                    };
+                    self.canvas
+                        .draw_line(x + CELL_PADDING_PT + offset.max(0.0), line);
+                }
+                self.canvas
+                    .vline(x, row_top, row_top - height, 0.6, RULE_COLOR);
+                x += widths[i];
+            }
+            self.canvas
+                .vline(x, row_top, row_top - height, 0.6, RULE_COLOR);
+            self.canvas.y = row_top - height;
+            let y = self.canvas.y;
+            self.canvas
+                .ops
+                .extend(hline(left, left + table_width, y, 0.6, RULE_COLOR));
+        }
+        self.canvas.space(PARAGRAPH_GAP_PT * 1.5);
+    }
+}
@@ -0,0 +1,397 @@
+//! Typed configuration loaded from YAML (see `config.yaml.example`).
+//!
+//! [`Config::load`] parses and then validates the whole file, so a typo such as an unknown
+//! processor kind or a source with a missing URL fails before anything is downloaded or
+//! uploaded. Validation collects every problem instead of stopping at the first one.
+
+use std::fmt;
+use std::path::Path;
+
+use tracing::{error, info};
+
+use crate::contract::{
+    ChunkOptions, DedupOptions, FilePolicy, HeaderOptions, HistoryOptions, OverviewOptions,
+    PiiOptions, ProcessConfig, RedactOptions, StageConfig,
+};
+use crate::download::{DownloadConfig, SourceAction, PLACEHOLDER};
+use crate::preprocess::header;
+use crate::preprocess::walk::PathFilter;
+use crate::synchronise::SyncOptions;
+
+/// The complete configuration of a sync run.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Config {
+    pub download: DownloadConfig,
+    pub process: ProcessConfig,
+    /// Remove items with the same content across sources before uploading; off when
+    /// absent.
+    #[serde(default)]
+    pub dedup: Option<DedupOptions>,
+}
+
+/// Error type for loading a [`Config`].
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    /// The YAML could not be parsed into a config, e.g. an unknown processor kind.
+    Parse(serde_yaml::Error),
+    /// The config parsed but is unusable; one message per problem.
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "could not read config: {e}"),
+            ConfigError::Parse(e) => write!(f, "invalid config: {e}"),
+            ConfigError::Invalid(problems) => {
+                write!(f, "invalid config:")?;
+                for problem in problems {
+                    write!(f, "\n  - {problem}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(e: std::io::Error) -> Self {
+        ConfigError::Io(e)
+    }
+}
+
+impl From<serde_yaml::Error> for ConfigError {
+    fn from(e: serde_yaml::Error) -> Self {
+        ConfigError::Parse(e)
+    }
+}
+
+impl Config {
+    /// Read, parse and validate a YAML config file.
+    pub fn load(path: &Path) -> Result<Config, ConfigError> {
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            error!(error = ?e, path = %path.display(), "Failed to read config file");
+            ConfigError::Io(e)
+        })?;
+        let config = Config::from_yaml_str(&text)?;
+        info!(
+            path = %path.display(),
+            sources = config.download.sources.len(),
+            processor = ?config.process.kind,
+            stages = config.process.stages.len(),
+            "Loaded config"
+        );
+        Ok(config)
+    }
+
+    /// Parse and validate YAML config text.
+    pub fn from_yaml_str(text: &str) -> Result<Config, ConfigError> {
+        let config: Config = serde_yaml::from_str(text).map_err(|e| {
+            error!(error = %e, "Failed to parse config");
+            ConfigError::Parse(e)
+        })?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// The run-wide settings to pass to [`synchronise_with`](crate::synchronise::synchronise_with).
+    pub fn sync_options(&self) -> SyncOptions {
+        SyncOptions {
+            dedup: self.dedup.clone(),
+        }
+    }
+
+    /// Check the config for problems that would only surface mid-run.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+        if self.download.output_dir.as_os_str().is_empty() {
+            problems.push("download.output_dir must not be empty".to_string());
+        }
+        if self.download.sources.is_empty() {
+            problems.push("download.sources must list at least one source".to_string());
+        }
+        for (i, source) in self.download.sources.iter().enumerate() {
+            for problem in source_problems(source) {
+                problems.push(format!("download.sources[{i}]: {problem}"));
+            }
+            if let Some(process) = &source.options().process {
+                problems.extend(process_problems(
+                    &format!("download.sources[{i}].process"),
+                    process,
+                ));
+            }
+        }
+        problems.extend(process_problems("process", &self.process));
+        if let Some(dedup) = &self.dedup {
+            if dedup.prefer.iter().any(|name| name.trim().is_empty()) {
+                problems.push("dedup.prefer must not contain empty source names".to_string());
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            for problem in &problems {
+                error!(problem = %problem, "Config validation failed");
+            }
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+}
+
+fn process_problems(prefix: &str, process: &ProcessConfig) -> Vec<String> {
+    let mut problems = Vec::new();
+    for key in &process.unknown {
+        problems.push(format!("{prefix}: unknown setting `{key}`"));
+    }
+    match (process.kind, process.stages.is_empty()) {
+        (None, true) => problems.push(format!("{prefix}: set `kind` to a preset or list `stages`")),
+        (Some(_), false) => {
+            problems.push(format!("{prefix}: set either `kind` or `stages`, not both"))
+        }
+        _ => {}
+    }
+    if process.stages.is_empty() {
+        problems.extend(file_policy_problems(prefix, &process.files));
+        if let Some(chunking) = &process.chunking {
+            problems.extend(chunk_problems(&format!("{prefix}.chunking"), chunking));
+        }
+        if let Some(redact) = &process.redact {
+            problems.extend(redact_problems(&format!("{prefix}.redact"), redact));
+        }
+        if let Some(pii) = &process.pii {
+            problems.extend(pii_problems(&format!("{prefix}.pii"), pii));
+        }
+        if let Some(header) = &process.header {
+            problems.extend(header_problems(&format!("{prefix}.header"), header));
+        }
+        if let Some(overview) = &process.overview {
+            problems.extend(overview_problems(&format!("{prefix}.overview"), overview));
+        }
+        if let Some(history) = &process.history {
+            problems.extend(history_problems(&format!("{prefix}.history"), history));
+        }
+        return problems;
+    }
+
+    if process.chunking.is_some()
+        || process.redact.is_some()
+        || process.pii.is_some()
+        || process.header.is_some()
+        || process.overview.is_some()
+        || process.history.is_some()
+        || process.notebooks.is_some()
+        || process.api_schemas.is_some()
+        || process.files != FilePolicy::default()
+    {
+        problems.push(format!(
+            "{prefix}: `chunking`, `redact`, `pii`, `header`, `overview`, `history`, \
+             `notebooks`, `api_schemas`, `max_file_size`, `binary` and `binary_types` only \
+             apply to `kind` presets; set them on the `collect`, `chunk`, `redact`, `pii`, \
+             `header`, `overview`, `history`, `notebooks` and `api_schemas` stages instead"
+        ));
+    }
+    // The first stage that turns text into PDFs, which the scanning stages cannot read.
+    let mut pdf_stage = None;
+    for (i, stage) in process.stages.iter().enumerate() {
+        let prefix = format!("{prefix}.stages[{i}]");
+        match (stage, pdf_stage) {
+            (StageConfig::MarkdownToPdf, None) => pdf_stage = Some("markdown_to_pdf"),
+            (StageConfig::CodeToPdf(_), None) => pdf_stage = Some("code_to_pdf"),
+            (StageConfig::DirectoryToPdf(_), None) => pdf_stage = Some("directory_to_pdf"),
+            (StageConfig::Redact(_) | StageConfig::Pii(_), Some(pdf)) => {
+                let name = match stage {
+                    StageConfig::Redact(_) => "redact",
+                    _ => "pii",
+                };
+                problems.push(format!(
+                    "{prefix}: `{name}` must come before `{pdf}`; it cannot scan the PDFs \
+                     that stage produces"
+                ));
+            }
+            _ => {}
+        }
+        match (i, stage.is_source()) {
+            (0, false) => problems.push(format!(
+                "{prefix}: the first stage must be a source stage (`collect`, `readme` or \
+                 `history`)"
+            )),
+            (1.., true) if !matches!(stage, StageConfig::History(_)) => problems.push(format!(
+                "{prefix}: source stages (`collect`, `readme`) can only come first"
+            )),
+            _ => {}
+        }
+        match stage {
+            StageConfig::Collect(policy) => problems.extend(file_policy_problems(&prefix, policy)),
+            StageConfig::Chunk(options) => problems.extend(chunk_problems(&prefix, options)),
+            StageConfig::Redact(options) => problems.extend(redact_problems(&prefix, options)),
+            StageConfig::Pii(options) => problems.extend(pii_problems(&prefix, options)),
+            StageConfig::Header(options) => problems.extend(header_problems(&prefix, options)),
+            StageConfig::Overview(options) => problems.extend(overview_problems(&prefix, options)),
+            StageConfig::History(options) => problems.extend(history_problems(&prefix, options)),
+            _ => {}
+        }
+    }
+    problems
+}
+
+fn file_policy_problems(prefix: &str, policy: &FilePolicy) -> Vec<String> {
+    let mut problems = Vec::new();
+    if policy.max_file_size == 0 {
+        problems.push(format!("{prefix}.max_file_size must be greater than 0"));
+    }
+    for mime_type in policy.binary_types.keys() {
+        let valid = mime_type
+            .split_once('/')
+            .is_some_and(|(top, sub)| !top.is_empty() && !sub.is_empty());
+        if !valid {
+            problems.push(format!(
+                "{prefix}.binary_types: `{mime_type}` is not a MIME type like `image/png` or `image/*`"
+            ));
+        }
+    }
+    problems
+}
+
+fn chunk_problems(prefix: &str, options: &ChunkOptions) -> Vec<String> {
+    if options.size == 0 {
+        vec![format!("{prefix}.size must be greater than 0")]
+    } else if options.overlap >= options.size {
+        vec![format!(
+            "{prefix}.overlap ({}) must be smaller than size ({})",
+            options.overlap, options.size
+        )]
+    } else {
+        Vec::new()
+    }
+}
+
+fn redact_problems(prefix: &str, options: &RedactOptions) -> Vec<String> {
+    let mut problems = Vec::new();
+    for pattern in &options.allow {
+        if let Err(e) = regex::Regex::new(pattern) {
+            problems.push(format!("{prefix}.allow: invalid regex `{pattern}`: {e}"));
+        }
+    }
+    for pattern in &options.allow_paths {
+        if let Err(e) = PathFilter::glob(pattern) {
+            problems.push(format!(
+                "{prefix}.allow_paths: invalid glob `{pattern}`: {e}"
+            ));
+        }
+    }
+    problems
+}
+
+fn pii_problems(prefix: &str, options: &PiiOptions) -> Vec<String> {
+    let mut problems = Vec::new();
+    if options.categories.is_empty() {
+        problems.push(format!(
+            "{prefix}.categories must list at least one category"
+        ));
+    }
+    if options.names.iter().any(|name| name.trim().is_empty()) {
+        problems.push(format!("{prefix}.names must not contain empty names"));
+    }
+    problems
+}
+
+fn header_problems(prefix: &str, options: &HeaderOptions) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (source_type, template) in &options.templates {
+        if source_type != "default" && !SourceAction::TYPES.contains(&source_type.as_str()) {
+            problems.push(format!(
+                "{prefix}.templates: unknown source type `{source_type}`; use one of {} or `default`",
+                SourceAction::TYPES.join(", ")
+            ));
+        }
+        for caps in PLACEHOLDER.captures_iter(template) {
+            if !header::VARIABLES.contains(&&caps[1]) {
+                problems.push(format!(
+                    "{prefix}.templates.{source_type}: unknown variable `{}`; available: {}",
+                    &caps[1],
+                    header::VARIABLES.join(", ")
+                ));
+            }
+        }
+    }
+    problems
+}
+
+fn history_problems(prefix: &str, options: &HistoryOptions) -> Vec<String> {
+    let mut problems = Vec::new();
+    if options.max_commits == 0 {
+        problems.push(format!("{prefix}.max_commits must be greater than 0"));
+    }
+    for (name, date) in [("since", &options.since), ("until", &options.until)] {
+        if date.as_deref().is_some_and(|date| date.trim().is_empty()) {
+            problems.push(format!("{prefix}.{name} must not be empty"));
+        }
+    }
+    if options.paths.iter().any(|path| path.trim().is_empty()) {
+        problems.push(format!("{prefix}.paths must not contain empty paths"));
+    }
+    problems
+}
+
+fn overview_problems(prefix: &str, options: &OverviewOptions) -> Vec<String> {
+    if options.depth == 0 {
+        return vec![format!("{prefix}.depth must be greater than 0")];
+    }
+    Vec::new()
+}
+
+fn source_problems(source: &SourceAction) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut required = |field: &str, value: &str| {
+        if value.trim().is_empty() {
+            problems.push(format!("`{field}` must not be empty"));
+        }
+    };
+    match source {
+        SourceAction::Git(git) => required("repo_url", &git.repo_url),
+        SourceAction::Confluence(confluence) => {
+            required("base_url", &confluence.base_url);
+            required("space_key", &confluence.space_key);
+        }
+        SourceAction::MediaWiki(wiki) => required("dump_path", &wiki.dump_path.to_string_lossy()),
+        SourceAction::Mailbox(mailbox) => required("path", &mailbox.path.to_string_lossy()),
+        SourceAction::S3(s3) => {
+            required("endpoint", &s3.endpoint);
+            required("bucket", &s3.bucket);
+        }
+        SourceAction::HttpJson(api) => {
+            required("url", &api.url);
+            required("template", &api.template);
+            required("url_template", &api.url_template);
+            if !api.url.is_empty()
+                && !api.url.starts_with("http://")
+                && !api.url.starts_with("https://")
+            {
+                problems.push(format!("`url` must be http(s), got `{}`", api.url));
+            }
+        }
+        SourceAction::Database(database) => {
+            let url = database.url.as_str();
+            if !["postgres://", "postgresql://", "sqlite:"]
+                .iter()
+                .any(|scheme| url.starts_with(scheme))
+            {
+                problems.push(format!(
+                    "`url` must start with postgres://, postgresql:// or sqlite:, got `{}`",
+                    crate::download::database::display_url(database)
+                ));
+            }
+        }
+    }
+    let options = source.options();
+    for pattern in options.include.iter().chain(&options.exclude) {
+        if let Err(e) = PathFilter::glob(pattern) {
+            problems.push(format!("invalid glob `{pattern}`: {e}"));
+        }
+    }
+    problems
+}
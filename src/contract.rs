@@ -51,10 +51,17 @@ pub struct ExternalSource {
 
 /// Represents the minimal data needed to upload a new item (file/document) to a source.
 pub struct NewExternalItem<'a> {
-    /// The raw file contents, typically UTF-8 text.
+    /// The file contents: UTF-8 text, or base64 when `encoding` is [`ContentEncoding::Base64`].
     pub content: &'a str,
-    /// URL that must identify the item uniquely (can be a VCS or filesystem URL).
+    /// How `content` is encoded.
+    pub encoding: ContentEncoding,
+    /// MIME type of the decoded content, when known.
+    pub mime_type: Option<&'a str>,
+    /// URL that must identify the item uniquely: the original's canonical web link when
+    /// known (with a fragment when several items share it), otherwise the item filename.
     pub url: &'a str,
+    /// Provenance of the item: path, link, language, size, hash and timestamps.
+    pub metadata: &'a ItemMetadata,
     /// The parent bucket id.
     pub bucket_id: i64,
     /// The id of the external source to which this item belongs.
@@ -63,6 +70,15 @@ pub struct NewExternalItem<'a> {
     pub processing_state: Option<&'a str>,
 }
 
+/// Encoding of [`NewExternalItem::content`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentEncoding {
+    /// The content is the item itself.
+    Utf8,
+    /// The item is binary; the content is its standard base64 encoding.
+    Base64,
+}
+
 /// Represents the created/returned item.
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct ExternalItem {
@@ -157,42 +173,755 @@ pub trait Uploader: Send + Sync {
 }
 
 /// Processor configuration - describes how the sources are processed into uploadable items.
-#[derive(Debug, Clone)]
+///
+/// Either `kind` names a preset pipeline, tuned by `files` and `chunking`, or `stages`
+/// lists the pipeline explicitly with settings on each stage.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct ProcessConfig {
-    pub kind: ProcessorKind,
+    #[serde(default)]
+    pub kind: Option<ProcessorKind>,
+    /// Explicit pipeline, run in order.
+    #[serde(default)]
+    pub stages: Vec<StageConfig>,
+    /// Size limit and binary file handling for a preset, set inline next to `kind`.
+    #[serde(flatten, default)]
+    pub files: FilePolicy,
+    /// Split text items into retrieval-sized chunks after a preset; off when absent.
+    #[serde(default)]
+    pub chunking: Option<ChunkOptions>,
+    /// Redact secrets right after a preset's source stage; off when absent.
+    #[serde(default)]
+    pub redact: Option<RedactOptions>,
+    /// Scrub personal data after a preset's source stage (and redaction); off when absent.
+    #[serde(default)]
+    pub pii: Option<PiiOptions>,
+    /// Prepend a provenance header to text items at the end of a preset; off when absent.
+    #[serde(default)]
+    pub header: Option<HeaderOptions>,
+    /// Add a generated overview of the source right after a preset's source stage; off
+    /// when absent.
+    #[serde(default)]
+    pub overview: Option<OverviewOptions>,
+    /// Add commit history documents right after a preset's source stage, or configure the
+    /// `GitHistory` preset; off when absent.
+    #[serde(default)]
+    pub history: Option<HistoryOptions>,
+    /// Convert Jupyter notebooks to markdown right after a preset's source stage; off when
+    /// absent.
+    #[serde(default)]
+    pub notebooks: Option<NotebookOptions>,
+    /// Render OpenAPI, protobuf and GraphQL schemas to markdown right after a preset's
+    /// source stage; off when absent.
+    #[serde(default)]
+    pub api_schemas: Option<ApiSchemaOptions>,
+    /// Keys that match no setting, reported by config validation. The inline file policy
+    /// rules out `deny_unknown_fields` here.
+    #[serde(flatten, default, skip_serializing, deserialize_with = "unknown_keys")]
+    pub unknown: Vec<String>,
+}
+
+fn unknown_keys<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    let keys: std::collections::BTreeMap<String, serde::de::IgnoredAny> =
+        serde::Deserialize::deserialize(deserializer)?;
+    Ok(keys.into_keys().collect())
+}
+
+impl ProcessConfig {
+    /// The stages to run: `stages` when set, otherwise the preset for `kind` with overview,
+    /// history, notebook, API schema, redact and PII stages after its source when
+    /// `overview`, `history`, `notebooks`, `api_schemas`, `redact` and `pii` are set, and
+    /// chunk and header stages at the end when `chunking` and `header` are set.
+    pub fn pipeline(&self) -> Vec<StageConfig> {
+        if !self.stages.is_empty() {
+            return self.stages.clone();
+        }
+        let mut stages = self
+            .kind
+            .map(|kind| kind.preset(&self.files))
+            .unwrap_or_default();
+        let mut history = self.history.clone();
+        if let Some(StageConfig::History(preset)) = stages.first_mut() {
+            *preset = history.take().unwrap_or_default();
+        }
+        let filters = [
+            self.overview.clone().map(StageConfig::Overview),
+            history.map(StageConfig::History),
+            self.notebooks.clone().map(StageConfig::Notebooks),
+            self.api_schemas.clone().map(StageConfig::ApiSchemas),
+            self.redact.clone().map(StageConfig::Redact),
+            self.pii.clone().map(StageConfig::Pii),
+        ];
+        let at = 1.min(stages.len());
+        stages.splice(at..at, filters.into_iter().flatten());
+        if let Some(chunking) = &self.chunking {
+            stages.push(StageConfig::Chunk(chunking.clone()));
+        }
+        if let Some(header) = &self.header {
+            stages.push(StageConfig::Header(header.clone()));
+        }
+        stages
+    }
+}
+
+/// One pipeline stage in config, selected by its `stage` key.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "stage", rename_all = "snake_case")]
+pub enum StageConfig {
+    /// Source stage: every file that passes the ignore rules, include/exclude globs and
+    /// the file policy given inline.
+    Collect(FilePolicy),
+    /// Source stage: the root `README.md`; a source without one is an error.
+    Readme,
+    /// Render markdown items to PDF (`docs/guide.md` → `docs/guide.pdf`).
+    MarkdownToPdf,
+    /// Render each text item to a highlighted PDF (`src/main.rs` → `src/main.rs.pdf`).
+    CodeToPdf(CodePdfOptions),
+    /// Concatenate the text items of each directory, or of the whole source, into one PDF.
+    DirectoryToPdf(DirectoryPdfOptions),
+    /// Split text items into chunks.
+    Chunk(ChunkOptions),
+    /// Redact secrets in text items; place it before any PDF stage.
+    Redact(RedactOptions),
+    /// Mask or pseudonymise personal data in text items; place it before any PDF stage.
+    Pii(PiiOptions),
+    /// Prepend a provenance header to text items; place it after `chunk` so every chunk
+    /// gets one.
+    Header(HeaderOptions),
+    /// Split source files into one item per top-level symbol, plus an outline per file.
+    Symbols(SymbolOptions),
+    /// Add an `_overview.md` item summarising the whole source: README introduction,
+    /// languages, build manifests and their dependencies, entry points and layout.
+    Overview(OverviewOptions),
+    /// Source stage: markdown documents of a git source's commit history under
+    /// `_history/`. It adds to the batch, so it can also follow another source stage.
+    History(HistoryOptions),
+    /// Convert Jupyter notebooks (`.ipynb`) to one markdown item each
+    /// (`analysis.ipynb` → `analysis.ipynb.md`); place it before `redact` and `pii` so
+    /// their outputs are scanned.
+    Notebooks(NotebookOptions),
+    /// Render OpenAPI documents, `.proto` files and GraphQL SDL to markdown: an index per
+    /// file and one item per operation, RPC, message or type.
+    ApiSchemas(ApiSchemaOptions),
+}
+
+impl StageConfig {
+    /// Whether the stage reads items from the source rather than transforming a batch.
+    pub fn is_source(&self) -> bool {
+        matches!(
+            self,
+            StageConfig::Collect(_) | StageConfig::Readme | StageConfig::History(_)
+        )
+    }
+}
+
+/// Target chunk size and overlap for the chunking stage.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChunkOptions {
+    /// Most units of new content per chunk.
+    pub size: usize,
+    /// Units repeated from the end of the previous chunk at the start of the next.
+    pub overlap: usize,
+    pub unit: ChunkUnit,
+}
+
+impl Default for ChunkOptions {
+    fn default() -> Self {
+        ChunkOptions {
+            size: 2000,
+            overlap: 200,
+            unit: ChunkUnit::Chars,
+        }
+    }
+}
+
+/// Settings for the code to PDF stage.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CodePdfOptions {
+    /// Colour scheme: `light`, `solarized` or `monochrome`.
+    pub theme: crate::code_to_pdf::highlight::Theme,
+    /// Number source lines in a left gutter.
+    pub line_numbers: bool,
+}
+
+impl Default for CodePdfOptions {
+    fn default() -> Self {
+        CodePdfOptions {
+            theme: Default::default(),
+            line_numbers: true,
+        }
+    }
+}
+
+/// Settings for the directory to PDF stage.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DirectoryPdfOptions {
+    pub scope: PdfScope,
+    /// Colour scheme: `light`, `solarized` or `monochrome`.
+    pub theme: crate::code_to_pdf::highlight::Theme,
+    /// Number source lines in a left gutter.
+    pub line_numbers: bool,
+}
+
+impl Default for DirectoryPdfOptions {
+    fn default() -> Self {
+        DirectoryPdfOptions {
+            scope: PdfScope::default(),
+            theme: Default::default(),
+            line_numbers: true,
+        }
+    }
+}
+
+/// What the directory to PDF stage puts in one document.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PdfScope {
+    /// One PDF per directory, without its subdirectories: `_root.pdf`, `src.pdf`.
+    #[default]
+    Directory,
+    /// A single `_repository.pdf` with every file of the source.
+    Repository,
+}
+
+/// Settings for the history stage.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryOptions {
+    /// Most recent commits to include.
+    pub max_commits: usize,
+    /// Only commits after this date, in any form git accepts (`2024-01-31`, `6 months ago`).
+    pub since: Option<String>,
+    /// Only commits before this date.
+    pub until: Option<String>,
+    /// Only commits touching these paths (git pathspecs, relative to the source root).
+    pub paths: Vec<String>,
+    /// Include each commit's diff, cut to this many lines per file; 0 leaves diffs out.
+    pub diff_lines: usize,
+    /// One document per month or per release tag.
+    pub group_by: HistoryGrouping,
+}
+
+impl Default for HistoryOptions {
+    fn default() -> Self {
+        HistoryOptions {
+            max_commits: 500,
+            since: None,
+            until: None,
+            paths: Vec::new(),
+            diff_lines: 0,
+            group_by: HistoryGrouping::Month,
+        }
+    }
+}
+
+/// How the history stage splits commits into documents.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryGrouping {
+    /// `_history/2024-03.md`, by author date.
+    #[default]
+    Month,
+    /// `_history/v1.2.0.md` with the commits first released in that tag, and
+    /// `_history/unreleased.md` with those in no tag yet.
+    Tag,
+}
+
+/// Settings for the API schemas stage.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiSchemaOptions {
+    /// Upload the schema files themselves as well as their renderings.
+    pub keep_files: bool,
+    /// Levels of nested schemas, messages and types listed inline; 0 names them only.
+    pub depth: usize,
+}
+
+impl Default for ApiSchemaOptions {
+    fn default() -> Self {
+        ApiSchemaOptions {
+            keep_files: false,
+            depth: 3,
+        }
+    }
+}
+
+/// Settings for the notebooks stage.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotebookOptions {
+    /// Most characters of text output kept per code cell; 0 leaves outputs out.
+    pub output_chars: usize,
+    /// What to do with images, widgets and other outputs that have no text form.
+    pub rich_outputs: RichOutputs,
+}
+
+impl Default for NotebookOptions {
+    fn default() -> Self {
+        NotebookOptions {
+            output_chars: 2000,
+            rich_outputs: RichOutputs::Summarise,
+        }
+    }
+}
+
+/// Handling of notebook outputs without a text form.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RichOutputs {
+    /// A placeholder naming the output, such as `[image/png output]`.
+    #[default]
+    Summarise,
+    /// Leave them out.
+    Drop,
+}
+
+/// Settings for the overview stage.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OverviewOptions {
+    /// Directory levels listed in the tree; directories below are summarised by their
+    /// file count.
+    pub depth: usize,
+}
+
+impl Default for OverviewOptions {
+    fn default() -> Self {
+        OverviewOptions { depth: 3 }
+    }
+}
+
+/// Settings for the symbols stage.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SymbolOptions {
+    /// Add a markdown outline item listing each file's symbols.
+    pub outline: bool,
+    /// Upload the whole file as well as its symbols; otherwise the lines outside any
+    /// symbol go up as a remainder item.
+    pub keep_files: bool,
+}
+
+impl Default for SymbolOptions {
+    fn default() -> Self {
+        SymbolOptions {
+            outline: true,
+            keep_files: false,
+        }
+    }
+}
+
+/// What kind of declaration a [`Symbol`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SymbolKind {
+    Function,
+    Method,
+    Struct,
+    Enum,
+    Trait,
+    Impl,
+    Module,
+    Class,
+    Interface,
+    Type,
+}
+
+impl std::fmt::Display for SymbolKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            SymbolKind::Function => "function",
+            SymbolKind::Method => "method",
+            SymbolKind::Struct => "struct",
+            SymbolKind::Enum => "enum",
+            SymbolKind::Trait => "trait",
+            SymbolKind::Impl => "impl",
+            SymbolKind::Module => "module",
+            SymbolKind::Class => "class",
+            SymbolKind::Interface => "interface",
+            SymbolKind::Type => "type",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A top-level declaration an item was cut from by the symbols stage.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Symbol {
+    pub kind: SymbolKind,
+    /// Declared name; `impl Display for Config` for an impl, `Server.Start` for a Go
+    /// method.
+    pub name: String,
+    /// The declaration up to its body, on one line.
+    pub signature: String,
+    /// Doc comment or docstring without comment markers.
+    pub doc: Option<String>,
+    /// First line, counting leading comments and attributes, from 1.
+    pub start_line: usize,
+    /// Last line, inclusive.
+    pub end_line: usize,
+}
+
+/// Settings for the secret redaction stage.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedactOptions {
+    /// Regexes for values that are known not to be secrets, e.g. test fixtures. A finding
+    /// whose value matches one is left alone.
+    pub allow: Vec<String>,
+    /// Globs of files that are not scanned, with the same syntax as `include`/`exclude`.
+    pub allow_paths: Vec<String>,
+    /// Leave out files with findings instead of uploading them redacted.
+    pub drop_files: bool,
+    /// Fail the run when anything is found.
+    pub strict: bool,
+    /// Also flag long random-looking strings that match no known token format.
+    pub entropy: bool,
+}
+
+impl Default for RedactOptions {
+    fn default() -> Self {
+        RedactOptions {
+            allow: Vec::new(),
+            allow_paths: Vec::new(),
+            drop_files: false,
+            strict: false,
+            entropy: true,
+        }
+    }
+}
+
+/// Settings for the PII scrubbing stage.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PiiOptions {
+    /// Kinds of personal data to scrub; all of them by default.
+    pub categories: Vec<PiiCategory>,
+    pub mode: PiiMode,
+    /// Personal names to scrub wherever they occur, in addition to the display names
+    /// found next to email addresses (`Jane Doe <jane@example.com>`) in the same source.
+    pub names: Vec<String>,
+}
+
+impl Default for PiiOptions {
+    fn default() -> Self {
+        PiiOptions {
+            categories: vec![
+                PiiCategory::Email,
+                PiiCategory::Phone,
+                PiiCategory::Iban,
+                PiiCategory::Name,
+            ],
+            mode: PiiMode::default(),
+            names: Vec::new(),
+        }
+    }
+}
+
+/// Settings for removing items whose content another item already has, across all
+/// sources of a run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DedupOptions {
+    /// Source names whose copy to keep, most preferred first; otherwise the first
+    /// occurrence in source order is kept.
+    pub prefer: Vec<String>,
+    /// Record the locations of removed copies in the kept item's metadata.
+    pub aliases: bool,
+}
+
+/// Settings for the header stage.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HeaderOptions {
+    /// Header templates by source type (`git`, `confluence`, ...) or `default`, one
+    /// `key: {{ variable }}` line each; unset types use the built-in template. Lines whose
+    /// variables are all empty are left out.
+    pub templates: std::collections::BTreeMap<String, String>,
+    pub format: HeaderFormat,
+}
+
+/// How the header is written into an item.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeaderFormat {
+    /// Front matter for markdown and prose, a comment for code in a known language.
+    #[default]
+    Auto,
+    /// A YAML front matter block between `---` lines.
+    FrontMatter,
+    /// An HTML comment, hidden when markdown is rendered.
+    MarkdownComment,
+    /// Line comments in the item's language; front matter when its language is unknown.
+    Comment,
+}
+
+/// A kind of personal data the PII stage detects.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum PiiCategory {
+    Email,
+    Phone,
+    Iban,
+    Name,
+}
+
+impl std::fmt::Display for PiiCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            PiiCategory::Email => "email",
+            PiiCategory::Phone => "phone",
+            PiiCategory::Iban => "iban",
+            PiiCategory::Name => "name",
+        })
+    }
+}
+
+/// How the PII stage replaces what it finds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PiiMode {
+    /// Replace with the category only: `[EMAIL]`.
+    Mask,
+    /// Replace with a token that is the same for the same value throughout a run, so
+    /// references still line up: `[EMAIL:3f9a0c1d5e7b2a64]`. Tokens differ between runs.
+    #[default]
+    Pseudonymise,
+}
+
+/// A secret found by the redact stage. Never holds the secret itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretFinding {
+    /// Path of the item, `/`-separated.
+    pub path: String,
+    /// 1-based line the secret starts on.
+    pub line: usize,
+    /// Name of the rule that matched, e.g. `aws_access_key`.
+    pub rule: &'static str,
+}
+
+impl std::fmt::Display for SecretFinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.path, self.line, self.rule)
+    }
+}
+
+/// What [`ChunkOptions::size`] and [`ChunkOptions::overlap`] count.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkUnit {
+    /// Unicode characters.
+    #[default]
+    Chars,
+    /// Approximate tokens: runs of word characters and single punctuation marks.
+    Tokens,
+}
+
+/// Limits and per-type handling for the files a processor reads.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilePolicy {
+    /// Files larger than this many bytes are skipped, except notebooks when the pipeline
+    /// converts them. Defaults to 1 MiB.
+    pub max_file_size: u64,
+    /// What to do with binary files whose type has no entry in `binary_types`.
+    pub binary: BinaryPolicy,
+    /// Per-type overrides keyed by MIME type (`application/zip`) or wildcard (`image/*`).
+    pub binary_types: std::collections::BTreeMap<String, BinaryPolicy>,
+}
+
+impl FilePolicy {
+    pub const DEFAULT_MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+    /// The policy for `mime_type`: an exact `binary_types` entry, then `type/*`, then `binary`.
+    pub fn policy_for(&self, mime_type: &str) -> BinaryPolicy {
+        let essence = mime_type.split(';').next().unwrap_or_default().trim();
+        let wildcard = essence
+            .split_once('/')
+            .map(|(top, _)| format!("{top}/*"))
+            .unwrap_or_default();
+        self.binary_types
+            .get(essence)
+            .or_else(|| self.binary_types.get(&wildcard))
+            .copied()
+            .unwrap_or(self.binary)
+    }
+}
+
+impl Default for FilePolicy {
+    fn default() -> Self {
+        FilePolicy {
+            max_file_size: Self::DEFAULT_MAX_FILE_SIZE,
+            binary: BinaryPolicy::default(),
+            binary_types: Default::default(),
+        }
+    }
+}
+
+/// How a processor handles a binary (non-UTF-8) file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BinaryPolicy {
+    /// Leave the file out and report it as skipped.
+    Skip,
+    /// Upload the raw bytes as a binary item (FlattenFiles only; PDF processors skip).
+    Upload,
+    /// Convert to text where a converter exists (UTF-16 and Latin-1 text, SQLite schema),
+    /// otherwise skip.
+    #[default]
+    Convert,
 }
 
 /// Types/kinds of processing strategy.
-#[derive(Debug, Clone)]
+///
+/// Parsed strictly from config: an unknown name is an [`UnknownProcessorKind`] error
+/// listing the valid kinds, never a silent fallback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
 pub enum ProcessorKind {
     /// For each source, outputs a single PDF (README.md converted)
     ReadmeToPDF,
     /// Flattens all files in the repo, uploading them with directory encoded in name
     FlattenFiles,
-    // Future: CodeToPDF, DirectoryToPDF, etc
-}
-
-impl From<&str> for ProcessorKind {
-    fn from(s: &str) -> Self {
-        match s {
-            "ReadmeToPDF" | "readme_to_pdf" | "readme2pdf" => ProcessorKind::ReadmeToPDF,
-            "FlattenFiles" | "flattenfiles" | "flatten_files" => ProcessorKind::FlattenFiles,
-            other => {
-                tracing::warn!(
-                    kind = other,
-                    "Unknown processor kind, defaulting to FlattenFiles"
-                );
-                ProcessorKind::FlattenFiles
+    /// Renders each text file to its own PDF item (`path__to__file.rs.pdf`)
+    CodeToPDF,
+    /// Concatenates the text files of each directory into one PDF with a table of contents
+    DirectoryToPDF,
+    /// Splits source files into one item per top-level symbol plus a per-file outline
+    CodeSymbols,
+    /// Writes the commit history of a git source as markdown documents, one per month or
+    /// release
+    GitHistory,
+}
+
+impl ProcessorKind {
+    /// Every kind with the names accepted for it; the first name is canonical.
+    pub const ALL: &'static [(ProcessorKind, &'static [&'static str])] = &[
+        (
+            ProcessorKind::ReadmeToPDF,
+            &["ReadmeToPDF", "readme_to_pdf", "readme2pdf"],
+        ),
+        (
+            ProcessorKind::FlattenFiles,
+            &["FlattenFiles", "flattenfiles", "flatten_files"],
+        ),
+        (
+            ProcessorKind::CodeToPDF,
+            &["CodeToPDF", "code_to_pdf", "code2pdf"],
+        ),
+        (
+            ProcessorKind::DirectoryToPDF,
+            &["DirectoryToPDF", "directory_to_pdf", "dir2pdf"],
+        ),
+        (
+            ProcessorKind::CodeSymbols,
+            &["CodeSymbols", "code_symbols", "symbols"],
+        ),
+        (
+            ProcessorKind::GitHistory,
+            &["GitHistory", "git_history", "history"],
+        ),
+    ];
+}
+
+impl ProcessorKind {
+    /// The pipeline this kind stands for; `files` configures its collect stage.
+    pub fn preset(self, files: &FilePolicy) -> Vec<StageConfig> {
+        let collect = StageConfig::Collect(files.clone());
+        match self {
+            ProcessorKind::ReadmeToPDF => vec![StageConfig::Readme, StageConfig::MarkdownToPdf],
+            ProcessorKind::FlattenFiles => vec![collect],
+            ProcessorKind::CodeToPDF => {
+                vec![collect, StageConfig::CodeToPdf(CodePdfOptions::default())]
             }
+            ProcessorKind::DirectoryToPDF => vec![
+                collect,
+                StageConfig::DirectoryToPdf(DirectoryPdfOptions::default()),
+            ],
+            ProcessorKind::CodeSymbols => {
+                vec![collect, StageConfig::Symbols(SymbolOptions::default())]
+            }
+            ProcessorKind::GitHistory => vec![StageConfig::History(HistoryOptions::default())],
         }
     }
 }
 
+/// A processor kind name that matches none of [`ProcessorKind::ALL`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownProcessorKind(pub String);
+
+impl std::fmt::Display for UnknownProcessorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown processor kind `{}`; expected one of: ", self.0)?;
+        for (i, (_, names)) in ProcessorKind::ALL.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{} (aliases: {})", names[0], names[1..].join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for UnknownProcessorKind {}
+
+impl std::str::FromStr for ProcessorKind {
+    type Err = UnknownProcessorKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ProcessorKind::ALL
+            .iter()
+            .find(|(_, names)| names.contains(&s))
+            .map(|(kind, _)| *kind)
+            .ok_or_else(|| UnknownProcessorKind(s.to_string()))
+    }
+}
+
+impl TryFrom<&str> for ProcessorKind {
+    type Error = UnknownProcessorKind;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<ProcessorKind> for String {
+    fn from(kind: ProcessorKind) -> Self {
+        ProcessorKind::ALL
+            .iter()
+            .find(|(k, _)| *k == kind)
+            .map(|(_, names)| names[0].to_string())
+            .unwrap_or_default()
+    }
+}
+
+impl TryFrom<String> for ProcessorKind {
+    type Error = UnknownProcessorKind;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
 /// Input for processing step: a single source location (name, local path, etc)
 #[derive(Debug, Clone)]
 pub struct ProcessInput {
     pub name: String,
     pub repo_path: std::path::PathBuf,
+    /// Web URL that relative links in the source resolve against (e.g. a repository's
+    /// `blob/<ref>` URL), when the source has one.
+    pub base_url: Option<String>,
+    /// Per-source include/exclude globs from config.
+    pub options: crate::download::SourceOptions,
+    /// Values describing the source for header templates, from
+    /// [`SourceAction::variables`](crate::download::SourceAction::variables).
+    pub variables: std::collections::BTreeMap<String, String>,
     // Extend as needed
 }
 
@@ -201,6 +930,61 @@ pub struct ProcessInput {
 pub struct ExternalSourceInput {
     pub name: String,
     pub external_items: Vec<ExternalItemInput>,
+    /// Paths left out by the processor, with the reason for each.
+    pub skipped: Vec<SkippedPath>,
+    /// Secrets the redact stage found, redacted or with their file dropped.
+    pub findings: Vec<SecretFinding>,
+    /// How many values of each category the PII stage scrubbed.
+    pub pii: std::collections::BTreeMap<PiiCategory, usize>,
+}
+
+/// A file or directory (with a trailing `/`) that a processor did not turn into an item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedPath {
+    /// Path relative to the source root, `/`-separated.
+    pub path: String,
+    pub reason: SkipReason,
+}
+
+/// Why a path was skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    /// Version control or build output directory (`.git`, `target`).
+    BuiltIn,
+    /// Matched by an ignore file, e.g. `.gitignore` or `sub/.llmignore` (relative path).
+    IgnoreFile(String),
+    /// Matched a per-source `exclude` glob.
+    Excluded(String),
+    /// Matched none of the per-source `include` globs.
+    NotIncluded,
+    /// Binary content of this MIME type, skipped by the file policy.
+    Binary(String),
+    /// Empty or whitespace-only file where the processor needs text.
+    Empty,
+    /// Larger than the configured `max_file_size`.
+    TooLarge { size: u64, limit: u64 },
+    /// Dropped by the redact stage for containing this many secrets.
+    Secrets(usize),
+    /// Same content as the item kept at this location (its link, or `source/path`).
+    Duplicate(String),
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkipReason::BuiltIn => write!(f, "version control or build directory"),
+            SkipReason::IgnoreFile(file) => write!(f, "ignored by {file}"),
+            SkipReason::Excluded(pattern) => write!(f, "excluded by pattern `{pattern}`"),
+            SkipReason::NotIncluded => write!(f, "not matched by any include pattern"),
+            SkipReason::Binary(mime_type) => write!(f, "binary file ({mime_type})"),
+            SkipReason::Empty => write!(f, "empty file"),
+            SkipReason::TooLarge { size, limit } => {
+                write!(f, "file is {size} bytes, over the {limit} byte limit")
+            }
+            SkipReason::Secrets(count) => write!(f, "contains {count} secret(s)"),
+            SkipReason::Duplicate(kept) => write!(f, "duplicate of {kept}"),
+        }
+    }
 }
 
 /// An item for upload: filename and content (e.g. PDF data)
@@ -208,12 +992,53 @@ pub struct ExternalSourceInput {
 pub struct ExternalItemInput {
     pub filename: String,
     pub content: Vec<u8>,
+    /// MIME type of `content` (e.g. `application/pdf`, `text/x-rust`), when known.
+    pub mime_type: Option<String>,
+    /// Where the item came from, for linking answers back to the original.
+    pub metadata: ItemMetadata,
+}
+
+/// Provenance of an uploaded item.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct ItemMetadata {
+    /// Path of the original document relative to the source root, `/`-separated. Items
+    /// derived from it (PDFs, chunks) share it; a directory PDF has the directory's path.
+    pub path: String,
+    /// Canonical link to the original: a git web URL at the downloaded commit, or the
+    /// page URL a downloader recorded (Confluence, JSON APIs).
+    pub url: Option<String>,
+    /// Highlighting language of the original, e.g. `rust`; `None` for prose and unknown
+    /// file types.
+    pub language: Option<String>,
+    /// Size of the item content in bytes.
+    pub size: u64,
+    /// SHA-256 of the item content, lowercase hex.
+    pub content_hash: String,
+    /// When the original last changed, RFC 3339.
+    pub modified: Option<String>,
+    /// Commit a git source was downloaded at.
+    pub commit: Option<String>,
+    /// Headings enclosing a chunk, outermost first; empty for whole documents.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub breadcrumb: Vec<String>,
+    /// SHA-256 of the content with line endings and trailing whitespace normalised and
+    /// without any header the header stage added, so copies of a file share it. Empty
+    /// when unknown.
+    pub fingerprint: String,
+    /// Other locations with the same content, removed by deduplication.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    /// The declaration the item holds, for items from the symbols stage.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<Symbol>,
 }
 
 #[derive(Debug)]
 pub enum ProcessError {
     Io(std::io::Error),
     NoReadme,
+    /// The redact stage found secrets in strict mode.
+    Secrets(Vec<SecretFinding>),
     Other(String),
 }
 
@@ -231,3 +1056,24 @@ pub trait Preprocessor: Send + Sync {
     /// Process an input source and return a processed external source with items, or error.
     async fn process(&self, input: ProcessInput) -> Result<ExternalSourceInput, ProcessError>;
 }
+
+#[async_trait]
+impl<P: Preprocessor + ?Sized> Preprocessor for &P {
+    async fn process(&self, input: ProcessInput) -> Result<ExternalSourceInput, ProcessError> {
+        (**self).process(input).await
+    }
+}
+
+/// Chooses the [`Preprocessor`] for each downloaded source during synchronisation.
+///
+/// Every preprocessor selects itself for all sources; see
+/// [`Processors`](crate::preprocess::Processors) for per-source process config.
+pub trait PreprocessorSelector: Send + Sync {
+    fn select<'a>(&'a self, source: &DownloadedSource) -> Box<dyn Preprocessor + 'a>;
+}
+
+impl<P: Preprocessor> PreprocessorSelector for P {
+    fn select<'a>(&'a self, _source: &DownloadedSource) -> Box<dyn Preprocessor + 'a> {
+        Box::new(self)
+    }
+}
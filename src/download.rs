@@ -1,8 +1,18 @@
+use std::collections::BTreeMap;
 use std::fs;
 use std::fs::File;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+pub mod database;
+pub mod http_json;
+pub mod mailbox;
+pub mod mediawiki;
+pub mod s3;
 
 /// Download configuration - what sources to fetch and where.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -11,21 +21,177 @@ pub struct DownloadConfig {
     pub sources: Vec<SourceAction>,
 }
 
+/// Settings every source type accepts next to its own fields, e.g.
+///
+/// ```yaml
+/// - type: git
+///   repo_url: "git@github.com:org/repo.git"
+///   exclude: ["*.lock", "vendor/**"]
+///   process:
+///     kind: ReadmeToPDF
+/// ```
+///
+/// Patterns without a `/` match a file or directory name anywhere in the source; patterns
+/// with one match the path relative to the source root (`*` does not cross `/`, `**` does).
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct SourceOptions {
+    /// When non-empty, only files matching one of these globs are processed. For S3
+    /// sources they also filter which object keys are downloaded.
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Files and directories matching these globs are skipped (also applied to S3 keys).
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// How to process this source, replacing the top-level `process` config.
+    #[serde(default)]
+    pub process: Option<ProcessConfig>,
+}
+
+/// File in a downloaded source directory where a downloader records what it knows about
+/// each document beyond its content. Processors read it and never upload it.
+pub const SOURCE_INDEX_FILE: &str = ".llm-bucket-index.json";
+
+/// Per-document metadata recorded by a downloader, keyed by `/`-separated path relative to
+/// the source directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SourceIndex {
+    pub files: BTreeMap<String, IndexedFile>,
+}
+
+/// What a downloader knows about one document.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct IndexedFile {
+    /// Canonical web link to the original, e.g. the Confluence page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// When the original last changed, RFC 3339.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+    /// Titles from the top of the original's hierarchy down to itself, e.g. a page's
+    /// ancestors and title.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub breadcrumb: Vec<String>,
+}
+
+impl SourceIndex {
+    /// The index in `dir`; empty when the downloader wrote none or it cannot be read.
+    pub fn load(dir: &Path) -> SourceIndex {
+        let path = dir.join(SOURCE_INDEX_FILE);
+        let Ok(text) = fs::read_to_string(&path) else {
+            return SourceIndex::default();
+        };
+        serde_json::from_str(&text).unwrap_or_else(|e| {
+            tracing::warn!(error = %e, path = %path.display(), "Ignoring unreadable source index");
+            SourceIndex::default()
+        })
+    }
+
+    pub fn save(&self, dir: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        fs::write(dir.join(SOURCE_INDEX_FILE), json)
+    }
+}
+
 /// Selects the type of source for download (Git, Confluence, etc.)
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum SourceAction {
     Git(GitSource),
     Confluence(ConfluenceSource),
+    MediaWiki(MediaWikiSource),
+    Mailbox(MailboxSource),
+    S3(S3Source),
+    HttpJson(HttpJsonSource),
+    Database(DatabaseSource),
     // Extendable for other source types.
 }
 
+impl SourceAction {
+    /// The `type` key of every source in config.
+    pub const TYPES: &'static [&'static str] = &[
+        "git",
+        "confluence",
+        "mediawiki",
+        "mailbox",
+        "s3",
+        "httpjson",
+        "database",
+    ];
+
+    /// The source's `type` key in config.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            SourceAction::Git(_) => "git",
+            SourceAction::Confluence(_) => "confluence",
+            SourceAction::MediaWiki(_) => "mediawiki",
+            SourceAction::Mailbox(_) => "mailbox",
+            SourceAction::S3(_) => "s3",
+            SourceAction::HttpJson(_) => "httpjson",
+            SourceAction::Database(_) => "database",
+        }
+    }
+
+    /// Values describing the source for header templates: `type`, plus `repository` and
+    /// `branch` for git, `space` and `base_url` for Confluence, `dump`, `mailbox`,
+    /// `bucket`, `prefix` or `api` for the others. Database URLs are left out, as they
+    /// may hold credentials.
+    pub fn variables(&self) -> BTreeMap<String, String> {
+        let mut variables = BTreeMap::from([("type", self.type_name().to_string())]);
+        match self {
+            SourceAction::Git(git) => {
+                variables.insert("repository", git.repo_url.clone());
+                if let Some(reference) = &git.reference {
+                    variables.insert("branch", reference.clone());
+                }
+            }
+            SourceAction::Confluence(confluence) => {
+                variables.insert("space", confluence.space_key.clone());
+                variables.insert("base_url", confluence.base_url.clone());
+            }
+            SourceAction::MediaWiki(wiki) => {
+                variables.insert("dump", wiki.dump_path.display().to_string());
+            }
+            SourceAction::Mailbox(mailbox) => {
+                variables.insert("mailbox", mailbox.path.display().to_string());
+            }
+            SourceAction::S3(s3) => {
+                variables.insert("bucket", s3.bucket.clone());
+                variables.insert("prefix", s3.prefix.clone());
+            }
+            SourceAction::HttpJson(http) => {
+                variables.insert("api", http.url.clone());
+            }
+            SourceAction::Database(_) => {}
+        }
+        variables
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect()
+    }
+
+    /// The settings shared by all source types.
+    pub fn options(&self) -> &SourceOptions {
+        match self {
+            SourceAction::Git(source) => &source.options,
+            SourceAction::Confluence(source) => &source.options,
+            SourceAction::MediaWiki(source) => &source.options,
+            SourceAction::Mailbox(source) => &source.options,
+            SourceAction::S3(source) => &source.options,
+            SourceAction::HttpJson(source) => &source.options,
+            SourceAction::Database(source) => &source.options,
+        }
+    }
+}
+
 /// Describes a Confluence download source.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ConfluenceSource {
     pub base_url: String,
     pub space_key: String,
     // Add more fields as needed, e.g. parent_page, filters, etc.
+    /// Include/exclude globs and other per-source settings.
+    #[serde(flatten)]
+    pub options: SourceOptions,
 }
 
 /// Describes a Git repository download source.
@@ -34,11 +200,178 @@ pub struct GitSource {
     pub repo_url: String,
     pub reference: Option<String>,
     // Extendable (token, ssh, etc)
+    /// Include/exclude globs and other per-source settings.
+    #[serde(flatten)]
+    pub options: SourceOptions,
+}
+
+/// Describes a MediaWiki XML export (`Special:Export` or `dumpBackup.php`) download source.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct MediaWikiSource {
+    /// Path to the XML dump on disk. The dump is streamed, so multi-GB files are fine.
+    pub dump_path: PathBuf,
+    /// Only export pages in these namespace ids (e.g. 0 for main, 12 for Help). Empty means all.
+    #[serde(default)]
+    pub namespaces: Vec<i32>,
+    /// Only export pages in at least one of these categories. Empty means all.
+    #[serde(default)]
+    pub categories: Vec<String>,
+    /// Include/exclude globs and other per-source settings.
+    #[serde(flatten)]
+    pub options: SourceOptions,
+}
+
+/// Describes a mailing list archive download source (mbox file or Maildir directory).
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct MailboxSource {
+    /// Path to an mbox file or a Maildir directory.
+    pub path: PathBuf,
+    /// Archive format. Detected from `path` when omitted: directories are Maildir, files mbox.
+    #[serde(default)]
+    pub format: Option<MailboxFormat>,
+    /// Include/exclude globs and other per-source settings.
+    #[serde(flatten)]
+    pub options: SourceOptions,
+}
+
+/// On-disk format of a mailbox archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MailboxFormat {
+    Mbox,
+    Maildir,
+}
+
+/// Describes an S3-compatible object storage download source (AWS S3, MinIO, etc.).
+///
+/// Credentials fall back to `AWS_ACCESS_KEY_ID` / `AWS_SECRET_ACCESS_KEY`; when neither is
+/// set, requests are sent unsigned (for public buckets).
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct S3Source {
+    /// Endpoint base URL, e.g. `https://s3.eu-west-1.amazonaws.com` or `http://localhost:9000`.
+    pub endpoint: String,
+    pub bucket: String,
+    /// Only list objects whose key starts with this prefix.
+    #[serde(default)]
+    pub prefix: String,
+    /// Signing region (default: `us-east-1`, which MinIO also accepts).
+    #[serde(default)]
+    pub region: Option<String>,
+    #[serde(default)]
+    pub access_key_id: Option<String>,
+    #[serde(default)]
+    pub secret_access_key: Option<String>,
+    /// Include/exclude globs and other per-source settings.
+    #[serde(flatten)]
+    pub options: SourceOptions,
+}
+
+/// Describes a generic JSON REST API download source. Each record in the response is
+/// rendered through `template` into one markdown document.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct HttpJsonSource {
+    /// Endpoint returning the first page of records.
+    pub url: String,
+    /// Optional authentication header, with its value read from an environment variable.
+    #[serde(default)]
+    pub auth_header: Option<AuthHeader>,
+    #[serde(default)]
+    pub pagination: Pagination,
+    /// Stop after this many pages, as a guard against runaway pagination.
+    #[serde(default)]
+    pub max_pages: Option<usize>,
+    /// JSON pointer (RFC 6901) to the record array, e.g. `/data/items`. Empty for a top-level array.
+    #[serde(default)]
+    pub records_pointer: String,
+    /// Markdown template for each record. `{{ field.path }}` or `{{ /json/pointer }}` placeholders
+    /// are replaced with the record's values.
+    pub template: String,
+    /// Template for the document file name, e.g. `{{ id }}-{{ title }}`. Defaults to the record index.
+    #[serde(default)]
+    pub name_template: Option<String>,
+    /// Template for the record's canonical URL, added to every document as its source link.
+    pub url_template: String,
+    /// Include/exclude globs and other per-source settings.
+    #[serde(flatten)]
+    pub options: SourceOptions,
+}
+
+/// HTTP header sent with every request, e.g. `Authorization: Bearer ...`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AuthHeader {
+    pub name: String,
+    /// Environment variable holding the header value, so secrets stay out of the config.
+    pub value_env: String,
+}
+
+/// How to fetch subsequent pages from a JSON API.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "style", rename_all = "snake_case")]
+pub enum Pagination {
+    /// A single request returns all records.
+    #[default]
+    None,
+    /// `?offset=N&limit=M` style; stops on a short page.
+    Offset {
+        #[serde(default = "default_offset_param")]
+        offset_param: String,
+        #[serde(default = "default_limit_param")]
+        limit_param: String,
+        #[serde(default = "default_page_size")]
+        page_size: usize,
+    },
+    /// Passes the cursor found at `next_cursor_pointer` as `cursor_param`; stops when it is absent.
+    Cursor {
+        #[serde(default = "default_cursor_param")]
+        cursor_param: String,
+        next_cursor_pointer: String,
+    },
+    /// Follows the `rel="next"` URL of the RFC 8288 `Link` response header.
+    LinkHeader,
+}
+
+/// Describes a relational database whose schema is exported as documentation.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DatabaseSource {
+    /// `postgres://user@host:5432/dbname` or `sqlite://path/to/file.db`. A PostgreSQL
+    /// password may be left out of the URL and supplied via `PGPASSWORD` instead.
+    pub url: String,
+    /// Only export these schemas (PostgreSQL). Empty means all non-system schemas.
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    /// Number of sample rows to include per table and view; 0 (the default) includes none.
+    #[serde(default)]
+    pub sample_rows: usize,
+    /// Glob patterns on `column` or `table.column` whose sample values are redacted, on top
+    /// of the built-in password/secret/token patterns.
+    #[serde(default)]
+    pub redact_columns: Vec<String>,
+    /// Include/exclude globs and other per-source settings.
+    #[serde(flatten)]
+    pub options: SourceOptions,
+}
+
+fn default_offset_param() -> String {
+    "offset".to_string()
+}
+
+fn default_limit_param() -> String {
+    "limit".to_string()
+}
+
+fn default_page_size() -> usize {
+    100
+}
+
+fn default_cursor_param() -> String {
+    "cursor".to_string()
 }
 
 // Export source types and config for use outside this module
 
-use crate::contract::{DownloadError, DownloadedManifest, DownloadedSource, Downloader};
+use crate::contract::{
+    DownloadError, DownloadedManifest, DownloadedSource, Downloader, ProcessConfig,
+};
 
 /// DefaultDownloader holds a DownloadConfig (sources and output_dir) and delegates to download::run.
 /// After downloading, it produces a DownloadedManifest describing all downloaded sources and local paths.
@@ -86,6 +419,30 @@ impl Downloader for DefaultDownloader {
                         full_path,
                     )
                 }
+                SourceAction::MediaWiki(wiki) => (
+                    wiki.dump_path.display().to_string(),
+                    self.config
+                        .output_dir
+                        .join(mediawiki::source_dir_name(wiki)),
+                ),
+                SourceAction::Mailbox(mailbox) => (
+                    mailbox.path.display().to_string(),
+                    self.config
+                        .output_dir
+                        .join(mailbox::source_dir_name(mailbox)),
+                ),
+                SourceAction::S3(bucket) => (
+                    format!("s3://{}/{}", bucket.bucket, bucket.prefix),
+                    self.config.output_dir.join(s3::source_dir_name(bucket)),
+                ),
+                SourceAction::HttpJson(api) => (
+                    api.url.clone(),
+                    self.config.output_dir.join(http_json::source_dir_name(api)),
+                ),
+                SourceAction::Database(db) => (
+                    database::display_url(db),
+                    self.config.output_dir.join(database::source_dir_name(db)),
+                ),
             };
             sources.push(DownloadedSource {
                 logical_name,
@@ -297,41 +654,14 @@ pub async fn run(config: &DownloadConfig) -> Result<(), ()> {
                         let space_json_path = full_source_path.join("space.json");
                         let mut f = File::create(&space_json_path).map_err(|e| {
                             error!(error=?e, file=?space_json_path, "Failed to create space.json");
-                            ()
                         })?;
                         f.write_all(text.as_bytes()).map_err(|e| {
                             error!(error=?e, file=?space_json_path, "Failed to write space.json");
-                            ()
                         })?;
                         info!(path = %space_json_path.display(), "Downloaded Confluence space.json");
 
                         // === Fetch all pages for the space as markdown ===
 
-                        // Helper function to convert path components to sanitized double-underscore separated file path
-                        fn sanitize_to_fs_safe(parts: &[&str]) -> String {
-                            let mut name = parts
-                                .iter()
-                                .map(|s| {
-                                    let s = s.replace(
-                                        &['/', '\\', ':', '*', '?', '"', '<', '>', '|'][..],
-                                        "_",
-                                    );
-                                    let s = s.replace(std::path::MAIN_SEPARATOR, "_");
-                                    let s = s.replace("__", "_");
-                                    s
-                                })
-                                .collect::<Vec<_>>()
-                                .join("__");
-                            // Remove leading/trailing/empty segments
-                            while name.starts_with('_') || name.starts_with('.') {
-                                name = name[1..].to_string();
-                            }
-                            while name.ends_with('_') || name.ends_with('.') {
-                                name.pop();
-                            }
-                            name
-                        }
-
                         // Get all pages in the space using pagination
                         let mut start = 0;
                         // Use env var for CONFLUENCE_PAGE_LIMIT or default to 15
@@ -343,7 +673,7 @@ pub async fn run(config: &DownloadConfig) -> Result<(), ()> {
                         let mut pages = Vec::new();
                         'fetch_pages: loop {
                             let content_url = format!(
-                                "{}/rest/api/content?spaceKey={}&limit={}&start={}&expand=title,body.storage,ancestors",
+                                "{}/rest/api/content?spaceKey={}&limit={}&start={}&expand=title,body.storage,ancestors,version",
                                 base_url, space_key, api_batch_limit, start
                             );
                             let resp = client
@@ -419,6 +749,7 @@ pub async fn run(config: &DownloadConfig) -> Result<(), ()> {
                         }
 
                         // Directory creation & writing markdown files
+                        let mut index = SourceIndex::default();
                         for page in pages {
                             let title = page
                                 .get("title")
@@ -449,35 +780,31 @@ pub async fn run(config: &DownloadConfig) -> Result<(), ()> {
                             let out_file_path = full_source_path.join(format!("{}.md", file_stem));
 
                             // Confluence storage format is HTML, convert minimally to markdown-like (strip tags naively)
-                            fn html_to_markdown_minimal(html: &str) -> String {
-                                // This is only minimal: replace headings, paragraphs, remove *most* html tags.
-                                // For a proper solution, use a crate (html2md or ammonia, etc.), but here is quick & dirty:
-                                let mut md = String::from(html);
-                                for i in (1..=6).rev() {
-                                    md = md.replace(
-                                        &format!("<h{i}>"),
-                                        &format!("\n{} ", "#".repeat(i)),
-                                    );
-                                    md = md.replace(&format!("</h{i}>"), "\n");
-                                }
-                                md = md.replace("<p>", "\n\n").replace("</p>", "\n");
-                                md = md.replace("<br>", "\n").replace("<br/>", "\n");
-                                md = md.replace("<ul>", "\n").replace("</ul>", "\n");
-                                md = md.replace("<ol>", "\n").replace("</ol>", "\n");
-                                md = md.replace("<li>", "- ").replace("</li>", "\n");
-                                // Strip remaining tags (very naive, does not handle everything)
-                                md = regex::Regex::new(r"<[^>]+>")
-                                    .unwrap()
-                                    .replace_all(&md, "")
-                                    .to_string();
-                                md
-                            }
                             let markdown = html_to_markdown_minimal(body_md);
 
                             // Write the markdown file
                             if let Err(e) = std::fs::write(&out_file_path, markdown) {
                                 error!(error = ?e, path = %out_file_path.display(), "Failed to write Confluence page markdown");
                             }
+                            let webui = page
+                                .pointer("/_links/webui")
+                                .and_then(|v| v.as_str())
+                                .map(|webui| format!("{base_url}{webui}"));
+                            let modified = page
+                                .pointer("/version/when")
+                                .and_then(|v| v.as_str())
+                                .map(str::to_string);
+                            index.files.insert(
+                                format!("{file_stem}.md"),
+                                IndexedFile {
+                                    url: webui,
+                                    modified,
+                                    breadcrumb: hierarchy.iter().map(|t| t.to_string()).collect(),
+                                },
+                            );
+                        }
+                        if let Err(e) = index.save(&full_source_path) {
+                            error!(error = ?e, "Failed to write Confluence source index");
                         }
 
                         continue;
@@ -489,8 +816,200 @@ pub async fn run(config: &DownloadConfig) -> Result<(), ()> {
                     }
                 }
             }
+            SourceAction::MediaWiki(wiki_source) => {
+                let full_source_path = config
+                    .output_dir
+                    .join(mediawiki::source_dir_name(wiki_source));
+                if let Err(e) = mediawiki::download(wiki_source, &full_source_path) {
+                    tracing::error!(
+                        error = ?e,
+                        dump = %wiki_source.dump_path.display(),
+                        path = %full_source_path.display(),
+                        "Failed to export MediaWiki dump"
+                    );
+                    return Err(());
+                }
+            }
+            SourceAction::Mailbox(mailbox_source) => {
+                let full_source_path = config
+                    .output_dir
+                    .join(mailbox::source_dir_name(mailbox_source));
+                if let Err(e) = mailbox::download(mailbox_source, &full_source_path) {
+                    tracing::error!(
+                        error = ?e,
+                        mailbox = %mailbox_source.path.display(),
+                        path = %full_source_path.display(),
+                        "Failed to export mailbox archive"
+                    );
+                    return Err(());
+                }
+            }
+            SourceAction::S3(s3_source) => {
+                let full_source_path = config.output_dir.join(s3::source_dir_name(s3_source));
+                if let Err(e) = s3::download(s3_source, &full_source_path).await {
+                    tracing::error!(
+                        error = ?e,
+                        endpoint = %s3_source.endpoint,
+                        bucket = %s3_source.bucket,
+                        path = %full_source_path.display(),
+                        "Failed to download S3 objects"
+                    );
+                    return Err(());
+                }
+            }
+            SourceAction::HttpJson(api_source) => {
+                let full_source_path = config
+                    .output_dir
+                    .join(http_json::source_dir_name(api_source));
+                if let Err(e) = http_json::download(api_source, &full_source_path).await {
+                    tracing::error!(
+                        error = ?e,
+                        url = %api_source.url,
+                        path = %full_source_path.display(),
+                        "Failed to download JSON API records"
+                    );
+                    return Err(());
+                }
+            }
+            SourceAction::Database(db_source) => {
+                let full_source_path = config.output_dir.join(database::source_dir_name(db_source));
+                if let Err(e) = database::download(db_source, &full_source_path).await {
+                    tracing::error!(
+                        error = ?e,
+                        url = %database::display_url(db_source),
+                        path = %full_source_path.display(),
+                        "Failed to export database schema"
+                    );
+                    return Err(());
+                }
+            }
         }
     }
     tracing::info!("All sources successfully downloaded, exiting download::run with Ok");
     Ok(())
 }
+
+/// Browsable web URL for a source's files, used to resolve relative links in its documents.
+///
+/// Only git sources have one: `git@host:org/repo.git`, `ssh://git@host/org/repo` and
+/// `https://host/org/repo.git` all map to `https://host/org/repo/blob/<reference or main>`.
+/// Local paths and other sources return `None`.
+pub fn web_base_url(source: &SourceAction) -> Option<String> {
+    let SourceAction::Git(git) = source else {
+        return None;
+    };
+    let url = git.repo_url.trim();
+    let host_and_path = if let Some(rest) = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+    {
+        rest.rsplit_once('@').map_or(rest, |(_, r)| r).to_string()
+    } else if let Some(rest) = url.strip_prefix("ssh://") {
+        let rest = rest.rsplit_once('@').map_or(rest, |(_, r)| r);
+        // Drop an explicit port: `host:2222/org/repo`.
+        match rest.split_once('/') {
+            Some((host, path)) => format!("{}/{path}", host.split(':').next().unwrap_or(host)),
+            None => return None,
+        }
+    } else if let Some((user_host, path)) = url.split_once(':') {
+        if user_host.contains('/') || path.starts_with("//") {
+            return None;
+        }
+        let host = user_host.rsplit_once('@').map_or(user_host, |(_, h)| h);
+        format!("{host}/{}", path.trim_start_matches('/'))
+    } else {
+        return None;
+    };
+    let host_and_path = host_and_path.trim_end_matches('/').trim_end_matches(".git");
+    if !host_and_path.contains('/') {
+        return None;
+    }
+    let reference = git.reference.as_deref().unwrap_or("main");
+    Some(format!("https://{host_and_path}/blob/{reference}"))
+}
+
+/// Convert HTML minimally to markdown-like text: headings, paragraphs, line breaks and
+/// list items are mapped, all other tags are stripped and common entities decoded.
+/// For a proper solution, use a crate (html2md or ammonia, etc.), but here is quick & dirty.
+pub(crate) fn html_to_markdown_minimal(html: &str) -> String {
+    let mut md = String::from(html);
+    for i in (1..=6).rev() {
+        md = md.replace(&format!("<h{i}>"), &format!("\n{} ", "#".repeat(i)));
+        md = md.replace(&format!("</h{i}>"), "\n");
+    }
+    md = md.replace("<p>", "\n\n").replace("</p>", "\n");
+    md = md
+        .replace("<br>", "\n")
+        .replace("<br/>", "\n")
+        .replace("<br />", "\n");
+    md = md.replace("<ul>", "\n").replace("</ul>", "\n");
+    md = md.replace("<ol>", "\n").replace("</ol>", "\n");
+    md = md.replace("<li>", "- ").replace("</li>", "\n");
+    // Strip remaining tags (very naive, does not handle everything)
+    md = regex::Regex::new(r"<[^>]+>")
+        .unwrap()
+        .replace_all(&md, "")
+        .to_string();
+    md.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Convert path components to a sanitized, double-underscore separated file name.
+pub(crate) fn sanitize_to_fs_safe(parts: &[&str]) -> String {
+    let mut name = parts
+        .iter()
+        .map(|s| {
+            let s = s.replace(&['/', '\\', ':', '*', '?', '"', '<', '>', '|'][..], "_");
+            let s = s.replace(std::path::MAIN_SEPARATOR, "_");
+            s.replace("__", "_")
+        })
+        .collect::<Vec<_>>()
+        .join("__");
+    // Remove leading/trailing/empty segments
+    while name.starts_with('_') || name.starts_with('.') {
+        name = name[1..].to_string();
+    }
+    while name.ends_with('_') || name.ends_with('.') {
+        name.pop();
+    }
+    name
+}
+
+/// The `{{ name }}` placeholder syntax shared by HTTP JSON and header templates.
+pub(crate) static PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{\{\s*([^}]+?)\s*\}\}").unwrap());
+
+/// Replace every `{{ name }}` placeholder in `template` with `fill(name)`.
+pub(crate) fn fill_placeholders(template: &str, mut fill: impl FnMut(&str) -> String) -> String {
+    PLACEHOLDER
+        .replace_all(template, |c: &regex::Captures| fill(&c[1]))
+        .into_owned()
+}
+
+/// Split a unix timestamp into UTC calendar fields `(year, month, day, hour, minute, second)`.
+pub(crate) fn utc_datetime(timestamp: i64) -> (i64, i64, i64, i64, i64, i64) {
+    let days = timestamp.div_euclid(86_400);
+    let secs = timestamp.rem_euclid(86_400);
+    // Civil-from-days (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (
+        year,
+        month,
+        day,
+        secs / 3_600,
+        (secs % 3_600) / 60,
+        secs % 60,
+    )
+}
@@ -0,0 +1,668 @@
+//! Relational database schema source: introspects a PostgreSQL or SQLite database and
+//! writes one markdown document per schema describing its tables and views.
+//!
+//! Each relation lists its columns (type, nullability, default, comment), constraints,
+//! foreign keys and indexes; views include their definition. When `sample_rows` is set, a
+//! few rows are appended with values of redacted columns replaced by `[REDACTED]`.
+//!
+//! PostgreSQL connections are made without TLS. A password missing from the URL is read
+//! from `PGPASSWORD`, following libpq.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use tracing::{debug, info};
+
+use super::DatabaseSource;
+
+/// Column name patterns that are always redacted in sample rows.
+const DEFAULT_REDACTED: &[&str] = &["*password*", "*passwd*", "*secret*", "*token*", "*api_key*"];
+
+/// Longest sample value rendered before it is truncated.
+const MAX_SAMPLE_VALUE_CHARS: usize = 80;
+
+/// Error type for database schema export.
+#[derive(Debug)]
+pub enum DatabaseError {
+    Io(std::io::Error),
+    Sqlite(rusqlite::Error),
+    Postgres(tokio_postgres::Error),
+    Pattern(globset::Error),
+    /// The URL is neither `postgres://`/`postgresql://` nor `sqlite://`/`sqlite:`.
+    UnsupportedUrl(String),
+}
+
+impl From<std::io::Error> for DatabaseError {
+    fn from(e: std::io::Error) -> Self {
+        DatabaseError::Io(e)
+    }
+}
+
+impl From<rusqlite::Error> for DatabaseError {
+    fn from(e: rusqlite::Error) -> Self {
+        DatabaseError::Sqlite(e)
+    }
+}
+
+impl From<tokio_postgres::Error> for DatabaseError {
+    fn from(e: tokio_postgres::Error) -> Self {
+        DatabaseError::Postgres(e)
+    }
+}
+
+impl From<globset::Error> for DatabaseError {
+    fn from(e: globset::Error) -> Self {
+        DatabaseError::Pattern(e)
+    }
+}
+
+/// Connection string parameters that carry a credential rather than a setting.
+const CREDENTIAL_PARAMS: &[&str] = &["password", "sslpassword"];
+
+/// The URL without its password, in the userinfo or as a query parameter, safe to use in
+/// logs, file names and documents.
+pub fn display_url(source: &DatabaseSource) -> String {
+    let Ok(mut url) = reqwest::Url::parse(&source.url) else {
+        return source.url.clone();
+    };
+    let is_credential = |key: &str| {
+        CREDENTIAL_PARAMS
+            .iter()
+            .any(|param| key.eq_ignore_ascii_case(param))
+    };
+    if url.password().is_none() && !url.query_pairs().any(|(key, _)| is_credential(&key)) {
+        return source.url.clone();
+    }
+    let _ = url.set_password(None);
+    let kept: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| !is_credential(key))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    if kept.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(kept);
+    }
+    url.to_string()
+}
+
+/// Deterministic output directory name for a database source.
+pub fn source_dir_name(source: &DatabaseSource) -> String {
+    format!("database_{}", display_url(source))
+        .replace('/', "_")
+        .replace(':', "_")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RelationKind {
+    Table,
+    View,
+    MaterializedView,
+}
+
+impl RelationKind {
+    fn label(self) -> &'static str {
+        match self {
+            RelationKind::Table => "Table",
+            RelationKind::View => "View",
+            RelationKind::MaterializedView => "Materialized view",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Column {
+    name: String,
+    data_type: String,
+    nullable: bool,
+    default: Option<String>,
+    comment: Option<String>,
+}
+
+#[derive(Debug)]
+struct Relation {
+    name: String,
+    kind: RelationKind,
+    comment: Option<String>,
+    columns: Vec<Column>,
+    /// Primary key, unique and check constraints, rendered as SQL.
+    constraints: Vec<String>,
+    foreign_keys: Vec<String>,
+    indexes: Vec<String>,
+    /// `CREATE` statement (SQLite) or view query.
+    definition: Option<String>,
+    sample: Option<Vec<Vec<Option<String>>>>,
+}
+
+#[derive(Debug)]
+struct Schema {
+    name: String,
+    comment: Option<String>,
+    relations: Vec<Relation>,
+}
+
+/// Introspect the database and write `<schema>.md` files into `dest` (which is
+/// recreated). Returns the number of schemas written.
+pub async fn download(source: &DatabaseSource, dest: &Path) -> Result<usize, DatabaseError> {
+    let url = source.url.as_str();
+    let schemas = if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+        read_postgres(source).await?
+    } else if let Some(path) = url
+        .strip_prefix("sqlite://")
+        .or_else(|| url.strip_prefix("sqlite:"))
+    {
+        read_sqlite(source, Path::new(path))?
+    } else {
+        return Err(DatabaseError::UnsupportedUrl(display_url(source)));
+    };
+
+    if dest.exists() {
+        fs::remove_dir_all(dest)?;
+    }
+    fs::create_dir_all(dest)?;
+
+    let redactor = Redactor::new(&source.redact_columns)?;
+    let database = display_url(source);
+    for schema in &schemas {
+        let out_path = dest.join(format!(
+            "{}.md",
+            super::sanitize_to_fs_safe(&[&schema.name])
+        ));
+        fs::write(&out_path, render_schema(&database, schema, &redactor))?;
+        debug!(path = %out_path.display(), relations = schema.relations.len(), "Wrote schema document");
+    }
+    info!(schemas = schemas.len(), database = %database, "Exported database schema documentation");
+    Ok(schemas.len())
+}
+
+/// Schema documentation (no sample rows) for a SQLite file found inside another source,
+/// e.g. a database committed to a repository. `label` names it in the document.
+pub fn sqlite_file_markdown(path: &Path, label: &str) -> Result<String, DatabaseError> {
+    let source = DatabaseSource {
+        url: format!("sqlite:{}", path.display()),
+        schemas: Vec::new(),
+        sample_rows: 0,
+        redact_columns: Vec::new(),
+        options: Default::default(),
+    };
+    let redactor = Redactor::new(&source.redact_columns)?;
+    Ok(read_sqlite(&source, path)?
+        .iter()
+        .map(|schema| render_schema(label, schema, &redactor))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+fn read_sqlite(source: &DatabaseSource, path: &Path) -> Result<Vec<Schema>, DatabaseError> {
+    let conn = rusqlite::Connection::open_with_flags(
+        path,
+        rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    let quote = |name: &str| format!("\"{}\"", name.replace('"', "\"\""));
+
+    let mut stmt = conn.prepare(
+        "SELECT name, type, sql FROM sqlite_master \
+         WHERE type IN ('table', 'view') AND name NOT LIKE 'sqlite_%' ORDER BY name",
+    )?;
+    let objects = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut relations = Vec::new();
+    for (name, kind, sql) in objects {
+        let kind = if kind == "view" {
+            RelationKind::View
+        } else {
+            RelationKind::Table
+        };
+
+        let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", quote(&name)))?;
+        let mut pk_columns: Vec<(i64, String)> = Vec::new();
+        let columns = stmt
+            .query_map([], |row| {
+                Ok((
+                    Column {
+                        name: row.get(1)?,
+                        data_type: row.get(2)?,
+                        nullable: row.get::<_, i64>(3)? == 0,
+                        default: row.get(4)?,
+                        comment: None,
+                    },
+                    row.get::<_, i64>(5)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .map(|(column, pk)| {
+                if pk > 0 {
+                    pk_columns.push((pk, column.name.clone()));
+                }
+                column
+            })
+            .collect::<Vec<_>>();
+        pk_columns.sort();
+
+        let mut constraints = Vec::new();
+        if !pk_columns.is_empty() {
+            let names: Vec<&str> = pk_columns.iter().map(|(_, n)| n.as_str()).collect();
+            constraints.push(format!("PRIMARY KEY ({})", names.join(", ")));
+        }
+
+        let mut stmt = conn.prepare(&format!("PRAGMA foreign_key_list({})", quote(&name)))?;
+        let fk_rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, String>(6)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        // Composite keys span several rows sharing the same id.
+        let mut grouped: BTreeMap<i64, SqliteForeignKey> = BTreeMap::new();
+        for (id, table, from, to, on_delete) in fk_rows {
+            let fk = grouped.entry(id).or_insert_with(|| SqliteForeignKey {
+                table,
+                from: Vec::new(),
+                to: Vec::new(),
+                on_delete,
+            });
+            fk.from.push(from);
+            fk.to.extend(to);
+        }
+        let foreign_keys = grouped.values().map(SqliteForeignKey::render).collect();
+
+        let mut stmt = conn.prepare(&format!("PRAGMA index_list({})", quote(&name)))?;
+        let index_list = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(1)?,
+                    row.get::<_, bool>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut indexes = Vec::new();
+        for (index, unique, origin) in index_list {
+            let mut stmt = conn.prepare(&format!("PRAGMA index_info({})", quote(&index)))?;
+            let cols = stmt
+                .query_map([], |row| row.get::<_, Option<String>>(2))?
+                .collect::<Result<Vec<_>, _>>()?
+                .into_iter()
+                .map(|c| c.unwrap_or_else(|| "<expression>".to_string()))
+                .collect::<Vec<_>>();
+            if origin == "u" {
+                constraints.push(format!("UNIQUE ({})", cols.join(", ")));
+            }
+            if origin == "c" {
+                indexes.push(format!(
+                    "{index}: {}({})",
+                    if unique { "UNIQUE " } else { "" },
+                    cols.join(", ")
+                ));
+            }
+        }
+        indexes.sort();
+
+        let sample = if source.sample_rows > 0 {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT * FROM {} LIMIT {}",
+                quote(&name),
+                source.sample_rows
+            ))?;
+            let width = stmt.column_count();
+            let rows = stmt
+                .query_map([], |row| {
+                    (0..width)
+                        .map(|i| Ok(sqlite_value_to_text(row.get_ref(i)?)))
+                        .collect::<Result<Vec<_>, _>>()
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            Some(rows)
+        } else {
+            None
+        };
+
+        relations.push(Relation {
+            name,
+            kind,
+            comment: None,
+            columns,
+            constraints,
+            foreign_keys,
+            indexes,
+            definition: sql,
+            sample,
+        });
+    }
+
+    Ok(vec![Schema {
+        name: "main".to_string(),
+        comment: None,
+        relations,
+    }])
+}
+
+struct SqliteForeignKey {
+    table: String,
+    from: Vec<String>,
+    /// Empty when the key references the parent's primary key implicitly.
+    to: Vec<String>,
+    on_delete: String,
+}
+
+impl SqliteForeignKey {
+    fn render(&self) -> String {
+        let mut rendered = format!(
+            "({}) → {}({})",
+            self.from.join(", "),
+            self.table,
+            self.to.join(", ")
+        );
+        if self.on_delete != "NO ACTION" {
+            rendered.push_str(&format!(" ON DELETE {}", self.on_delete));
+        }
+        rendered
+    }
+}
+
+fn sqlite_value_to_text(value: rusqlite::types::ValueRef) -> Option<String> {
+    use rusqlite::types::ValueRef;
+    match value {
+        ValueRef::Null => None,
+        ValueRef::Integer(i) => Some(i.to_string()),
+        ValueRef::Real(f) => Some(f.to_string()),
+        ValueRef::Text(t) => Some(String::from_utf8_lossy(t).into_owned()),
+        ValueRef::Blob(b) => Some(format!("<{} bytes>", b.len())),
+    }
+}
+
+async fn read_postgres(source: &DatabaseSource) -> Result<Vec<Schema>, DatabaseError> {
+    let mut config: tokio_postgres::Config = source.url.parse()?;
+    if config.get_password().is_none() {
+        if let Ok(password) = std::env::var("PGPASSWORD") {
+            config.password(password);
+        }
+    }
+    let (client, connection) = config.connect(tokio_postgres::NoTls).await?;
+    let connection = tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            tracing::error!(error = ?e, "PostgreSQL connection error");
+        }
+    });
+
+    let namespaces = client
+        .query(
+            "SELECT n.nspname, obj_description(n.oid, 'pg_namespace') \
+             FROM pg_namespace n \
+             WHERE n.nspname NOT IN ('pg_catalog', 'information_schema') \
+               AND n.nspname NOT LIKE 'pg_toast%' AND n.nspname NOT LIKE 'pg_temp%' \
+             ORDER BY n.nspname",
+            &[],
+        )
+        .await?;
+
+    let mut schemas = Vec::new();
+    for ns in namespaces {
+        let schema_name: String = ns.get(0);
+        if !source.schemas.is_empty() && !source.schemas.contains(&schema_name) {
+            continue;
+        }
+        let rows = client
+            .query(
+                "SELECT c.oid, c.relname, c.relkind::text, obj_description(c.oid, 'pg_class'), \
+                        CASE WHEN c.relkind IN ('v', 'm') THEN pg_get_viewdef(c.oid, true) END \
+                 FROM pg_class c JOIN pg_namespace n ON n.oid = c.relnamespace \
+                 WHERE n.nspname = $1 AND c.relkind IN ('r', 'p', 'v', 'm') \
+                 ORDER BY c.relname",
+                &[&schema_name],
+            )
+            .await?;
+
+        let mut relations = Vec::new();
+        for row in rows {
+            let oid: u32 = row.get(0);
+            let name: String = row.get(1);
+            let kind = match row.get::<_, String>(2).as_str() {
+                "v" => RelationKind::View,
+                "m" => RelationKind::MaterializedView,
+                _ => RelationKind::Table,
+            };
+
+            let columns = client
+                .query(
+                    "SELECT a.attname, format_type(a.atttypid, a.atttypmod), NOT a.attnotnull, \
+                            pg_get_expr(d.adbin, d.adrelid), col_description(a.attrelid, a.attnum) \
+                     FROM pg_attribute a \
+                     LEFT JOIN pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum \
+                     WHERE a.attrelid = $1 AND a.attnum > 0 AND NOT a.attisdropped \
+                     ORDER BY a.attnum",
+                    &[&oid],
+                )
+                .await?
+                .into_iter()
+                .map(|c| Column {
+                    name: c.get(0),
+                    data_type: c.get(1),
+                    nullable: c.get(2),
+                    default: c.get(3),
+                    comment: c.get(4),
+                })
+                .collect();
+
+            let mut constraints = Vec::new();
+            let mut foreign_keys = Vec::new();
+            for c in client
+                .query(
+                    "SELECT conname, contype::text, pg_get_constraintdef(oid) \
+                     FROM pg_constraint WHERE conrelid = $1 ORDER BY conname",
+                    &[&oid],
+                )
+                .await?
+            {
+                let (conname, contype, def): (String, String, String) =
+                    (c.get(0), c.get(1), c.get(2));
+                if contype == "f" {
+                    foreign_keys.push(format!("{conname}: {def}"));
+                } else {
+                    constraints.push(format!("{conname}: {def}"));
+                }
+            }
+
+            let indexes = client
+                .query(
+                    "SELECT i.relname, pg_get_indexdef(x.indexrelid) \
+                     FROM pg_index x JOIN pg_class i ON i.oid = x.indexrelid \
+                     WHERE x.indrelid = $1 ORDER BY i.relname",
+                    &[&oid],
+                )
+                .await?
+                .into_iter()
+                .map(|i| format!("{}: {}", i.get::<_, String>(0), i.get::<_, String>(1)))
+                .collect();
+
+            let sample = if source.sample_rows > 0 {
+                // Cast every value to text server-side so any column type can be rendered.
+                let query = format!(
+                    "SELECT ARRAY(SELECT value::text FROM json_each_text(row_to_json(t)) \
+                     WITH ORDINALITY AS e(key, value, ord) ORDER BY ord) \
+                     FROM (SELECT * FROM {}.{} LIMIT {}) t",
+                    quote_pg(&schema_name),
+                    quote_pg(&name),
+                    source.sample_rows
+                );
+                let rows = client.query(&query, &[]).await?;
+                Some(
+                    rows.into_iter()
+                        .map(|r| r.get::<_, Vec<Option<String>>>(0))
+                        .collect(),
+                )
+            } else {
+                None
+            };
+
+            relations.push(Relation {
+                name,
+                kind,
+                comment: row.get(3),
+                columns,
+                constraints,
+                foreign_keys,
+                indexes,
+                definition: row.get(4),
+                sample,
+            });
+        }
+
+        schemas.push(Schema {
+            name: schema_name,
+            comment: ns.get(1),
+            relations,
+        });
+    }
+
+    drop(client);
+    let _ = connection.await;
+    Ok(schemas)
+}
+
+fn quote_pg(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Decides which sample columns have their values replaced by `[REDACTED]`.
+struct Redactor {
+    patterns: GlobSet,
+}
+
+impl Redactor {
+    /// Patterns match case-insensitively against `column` or `table.column`.
+    fn new(extra: &[String]) -> Result<Self, DatabaseError> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in DEFAULT_REDACTED
+            .iter()
+            .copied()
+            .chain(extra.iter().map(String::as_str))
+        {
+            builder.add(Glob::new(&pattern.to_lowercase())?);
+        }
+        Ok(Redactor {
+            patterns: builder.build()?,
+        })
+    }
+
+    fn is_redacted(&self, table: &str, column: &str) -> bool {
+        let column = column.to_lowercase();
+        self.patterns.is_match(&column)
+            || self
+                .patterns
+                .is_match(format!("{}.{column}", table.to_lowercase()))
+    }
+}
+
+fn render_schema(database: &str, schema: &Schema, redactor: &Redactor) -> String {
+    let mut doc = format!("# Schema `{}`\n\n", schema.name);
+    doc.push_str(&format!("Database: {database}\n"));
+    if let Some(comment) = &schema.comment {
+        doc.push_str(&format!("\n{comment}\n"));
+    }
+    if schema.relations.is_empty() {
+        doc.push_str("\n_No tables or views._\n");
+    }
+    for relation in &schema.relations {
+        render_relation(&mut doc, relation, redactor);
+    }
+    doc
+}
+
+fn render_relation(doc: &mut String, relation: &Relation, redactor: &Redactor) {
+    doc.push_str(&format!(
+        "\n## {} `{}`\n\n",
+        relation.kind.label(),
+        relation.name
+    ));
+    if let Some(comment) = &relation.comment {
+        doc.push_str(&format!("{comment}\n\n"));
+    }
+
+    doc.push_str("| Column | Type | Nullable | Default | Description |\n");
+    doc.push_str("|---|---|---|---|---|\n");
+    for c in &relation.columns {
+        doc.push_str(&format!(
+            "| {} | {} | {} | {} | {} |\n",
+            cell(&c.name),
+            cell(&c.data_type),
+            if c.nullable { "yes" } else { "no" },
+            c.default.as_deref().map(cell).unwrap_or_default(),
+            c.comment.as_deref().map(cell).unwrap_or_default(),
+        ));
+    }
+
+    for (title, items) in [
+        ("Constraints", &relation.constraints),
+        ("Foreign keys", &relation.foreign_keys),
+        ("Indexes", &relation.indexes),
+    ] {
+        if !items.is_empty() {
+            doc.push_str(&format!("\n**{title}:**\n\n"));
+            for item in items {
+                doc.push_str(&format!("- `{item}`\n"));
+            }
+        }
+    }
+
+    if let Some(definition) = &relation.definition {
+        doc.push_str("\n**Definition:**\n\n```sql\n");
+        doc.push_str(definition.trim());
+        doc.push_str("\n```\n");
+    }
+
+    if let Some(rows) = relation.sample.as_ref().filter(|r| !r.is_empty()) {
+        let redacted: Vec<bool> = relation
+            .columns
+            .iter()
+            .map(|c| redactor.is_redacted(&relation.name, &c.name))
+            .collect();
+        doc.push_str(&format!("\n**Sample rows ({}):**\n\n", rows.len()));
+        let names: Vec<String> = relation.columns.iter().map(|c| cell(&c.name)).collect();
+        doc.push_str(&format!("| {} |\n", names.join(" | ")));
+        doc.push_str(&format!("|{}\n", "---|".repeat(names.len())));
+        for row in rows {
+            let values: Vec<String> = row
+                .iter()
+                .enumerate()
+                .map(|(i, v)| match v {
+                    _ if redacted.get(i).copied().unwrap_or(false) => "[REDACTED]".to_string(),
+                    None => "NULL".to_string(),
+                    Some(v) => cell(&truncate(v)),
+                })
+                .collect();
+            doc.push_str(&format!("| {} |\n", values.join(" | ")));
+        }
+    }
+}
+
+/// Escape a value for use inside a markdown table cell.
+fn cell(value: &str) -> String {
+    value.replace('|', "\\|").replace(['\n', '\r'], " ")
+}
+
+fn truncate(value: &str) -> String {
+    if value.chars().count() > MAX_SAMPLE_VALUE_CHARS {
+        let cut: String = value.chars().take(MAX_SAMPLE_VALUE_CHARS).collect();
+        format!("{cut}…")
+    } else {
+        value.to_string()
+    }
+}
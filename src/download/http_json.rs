@@ -0,0 +1,259 @@
+//! Generic JSON REST API source: pages through an endpoint and renders each record into a
+//! markdown document using the templates from [`HttpJsonSource`].
+//!
+//! Templates use `{{ placeholder }}` syntax, where a placeholder is either a dotted field
+//! path (`fields.summary`, `tags.0`) or a JSON pointer (`/fields/summary`). Strings are
+//! inserted as-is, arrays of scalars are joined with `, `, and missing values render empty.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use serde_json::Value;
+use tracing::{debug, info, warn};
+
+use super::{
+    fill_placeholders, sanitize_to_fs_safe, HttpJsonSource, IndexedFile, Pagination, SourceIndex,
+};
+
+/// Error type for JSON API downloads.
+#[derive(Debug)]
+pub enum HttpJsonError {
+    Io(std::io::Error),
+    Http(reqwest::Error),
+    Status {
+        status: u16,
+        url: String,
+        body: String,
+    },
+    Json(serde_json::Error),
+    /// The records pointer did not resolve to an array.
+    MissingRecords(String),
+    /// The environment variable holding the auth header value is not set.
+    MissingEnv(String),
+}
+
+impl From<std::io::Error> for HttpJsonError {
+    fn from(e: std::io::Error) -> Self {
+        HttpJsonError::Io(e)
+    }
+}
+
+impl From<reqwest::Error> for HttpJsonError {
+    fn from(e: reqwest::Error) -> Self {
+        HttpJsonError::Http(e)
+    }
+}
+
+impl From<serde_json::Error> for HttpJsonError {
+    fn from(e: serde_json::Error) -> Self {
+        HttpJsonError::Json(e)
+    }
+}
+
+/// Deterministic output directory name for a JSON API source.
+pub fn source_dir_name(source: &HttpJsonSource) -> String {
+    format!("httpjson_{}", source.url)
+        .replace('/', "_")
+        .replace([':', '?'], "_")
+}
+
+/// Fetch all pages and write one markdown file per record into `dest` (which is
+/// recreated). Returns the number of records written.
+pub async fn download(source: &HttpJsonSource, dest: &Path) -> Result<usize, HttpJsonError> {
+    if dest.exists() {
+        fs::remove_dir_all(dest)?;
+    }
+    fs::create_dir_all(dest)?;
+
+    let auth = match &source.auth_header {
+        Some(header) => Some((
+            header.name.clone(),
+            std::env::var(&header.value_env)
+                .map_err(|_| HttpJsonError::MissingEnv(header.value_env.clone()))?,
+        )),
+        None => None,
+    };
+    let client = reqwest::Client::new();
+
+    let mut written_names: HashSet<String> = HashSet::new();
+    let mut index = SourceIndex::default();
+    let mut written = 0;
+    let mut next_url = Some(source.url.clone());
+    let mut offset = 0;
+    let mut cursor: Option<String> = None;
+    let mut pages = 0;
+    // Pages already fetched, so a server that hands out the same page again cannot loop.
+    let mut fetched: HashSet<(String, usize, Option<String>)> = HashSet::new();
+
+    while let Some(url) = next_url.take() {
+        if source.max_pages.is_some_and(|max| pages >= max) {
+            info!(pages, url = %source.url, "Reached max_pages, stopping pagination");
+            break;
+        }
+        if !fetched.insert((url.clone(), offset, cursor.clone())) {
+            warn!(pages, url = %url, cursor = ?cursor, "Pagination returned a page already fetched, stopping");
+            break;
+        }
+        pages += 1;
+
+        let mut request = client.get(&url).header("Accept", "application/json");
+        match &source.pagination {
+            Pagination::Offset {
+                offset_param,
+                limit_param,
+                page_size,
+            } => {
+                request = request.query(&[
+                    (offset_param.as_str(), offset.to_string()),
+                    (limit_param.as_str(), page_size.to_string()),
+                ]);
+            }
+            Pagination::Cursor { cursor_param, .. } => {
+                if let Some(c) = &cursor {
+                    request = request.query(&[(cursor_param.as_str(), c.as_str())]);
+                }
+            }
+            Pagination::None | Pagination::LinkHeader => {}
+        }
+        if let Some((name, value)) = &auth {
+            request = request.header(name.as_str(), value.as_str());
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        // Link targets may be relative to the request URL (RFC 8288).
+        let link_next = response
+            .headers()
+            .get_all(reqwest::header::LINK)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .find_map(next_link)
+            .and_then(|next| response.url().join(&next).ok())
+            .map(String::from);
+        let body = response.text().await?;
+        if !status.is_success() {
+            return Err(HttpJsonError::Status {
+                status: status.as_u16(),
+                url,
+                body,
+            });
+        }
+        let json: Value = serde_json::from_str(&body)?;
+        let records = json
+            .pointer(&source.records_pointer)
+            .and_then(Value::as_array)
+            .ok_or_else(|| HttpJsonError::MissingRecords(source.records_pointer.clone()))?;
+        debug!(url = %url, records = records.len(), "Fetched JSON API page");
+
+        for record in records {
+            let (file, url) = write_record(source, record, written, &mut written_names, dest)?;
+            index.files.insert(
+                file,
+                IndexedFile {
+                    url: Some(url),
+                    modified: None,
+                    breadcrumb: Vec::new(),
+                },
+            );
+            written += 1;
+        }
+
+        next_url = match &source.pagination {
+            Pagination::None => None,
+            Pagination::Offset { page_size, .. } => {
+                offset += records.len();
+                (records.len() >= *page_size && !records.is_empty()).then(|| source.url.clone())
+            }
+            Pagination::Cursor {
+                next_cursor_pointer,
+                ..
+            } => {
+                cursor = json
+                    .pointer(next_cursor_pointer)
+                    .map(value_to_text)
+                    .filter(|c| !c.is_empty());
+                cursor.as_ref().map(|_| source.url.clone())
+            }
+            Pagination::LinkHeader => link_next,
+        };
+    }
+
+    index.save(dest)?;
+    info!(records = written, pages, url = %source.url, "Downloaded JSON API records");
+    Ok(written)
+}
+
+fn write_record(
+    source: &HttpJsonSource,
+    record: &Value,
+    index: usize,
+    written_names: &mut HashSet<String>,
+    dest: &Path,
+) -> Result<(String, String), HttpJsonError> {
+    let name = source
+        .name_template
+        .as_deref()
+        .map(|t| sanitize_to_fs_safe(&[&render_template(t, record)]))
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| format!("record_{index}"));
+    let mut stem = name.clone();
+    let mut n = 2;
+    while !written_names.insert(stem.clone()) {
+        stem = format!("{name}_{n}");
+        n += 1;
+    }
+
+    let url = render_template(&source.url_template, record);
+    let mut doc = render_template(&source.template, record);
+    doc.push_str(&format!("\n\nSource: {url}\n"));
+    let file = format!("{stem}.md");
+    fs::write(dest.join(&file), doc)?;
+    Ok((file, url))
+}
+
+/// Render `template` against a JSON record, replacing `{{ placeholder }}` occurrences.
+pub fn render_template(template: &str, record: &Value) -> String {
+    fill_placeholders(template, |path| {
+        let pointer = if path.starts_with('/') {
+            path.to_string()
+        } else {
+            format!("/{}", path.replace('.', "/"))
+        };
+        record
+            .pointer(&pointer)
+            .map(value_to_text)
+            .unwrap_or_default()
+    })
+}
+
+fn value_to_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) if items.iter().all(|i| !i.is_object() && !i.is_array()) => items
+            .iter()
+            .map(value_to_text)
+            .collect::<Vec<_>>()
+            .join(", "),
+        other => other.to_string(),
+    }
+}
+
+/// Extract the `rel="next"` target from an RFC 8288 `Link` header value.
+fn next_link(header: &str) -> Option<String> {
+    header.split(',').find_map(|link| {
+        let (target, params) = link.split_once(';')?;
+        let is_next = params.split(';').any(|p| {
+            let p = p.trim().replace(' ', "");
+            p == "rel=\"next\"" || p == "rel=next"
+        });
+        is_next.then(|| {
+            target
+                .trim()
+                .trim_start_matches('<')
+                .trim_end_matches('>')
+                .to_string()
+        })
+    })
+}
@@ -0,0 +1,319 @@
+//! Mailing list archive source: reads an mbox file or Maildir directory, groups messages
+//! into threads and writes one markdown document per thread.
+//!
+//! Threads are built from `Message-ID`, `In-Reply-To` and `References`. Bodies prefer the
+//! `text/plain` part and fall back to converted `text/html`; quoted replies and signatures
+//! are stripped so each message contributes only what its author wrote.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::LazyLock;
+
+use mailparse::{DispositionType, MailHeaderMap, ParsedMail};
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+
+use super::{
+    html_to_markdown_minimal, sanitize_to_fs_safe, utc_datetime, MailboxFormat, MailboxSource,
+};
+
+/// Error type for mailbox export.
+#[derive(Debug)]
+pub enum MailboxError {
+    Io(std::io::Error),
+    Parse(mailparse::MailParseError),
+}
+
+impl From<std::io::Error> for MailboxError {
+    fn from(e: std::io::Error) -> Self {
+        MailboxError::Io(e)
+    }
+}
+
+impl From<mailparse::MailParseError> for MailboxError {
+    fn from(e: mailparse::MailParseError) -> Self {
+        MailboxError::Parse(e)
+    }
+}
+
+/// Deterministic output directory name for a mailbox source.
+pub fn source_dir_name(source: &MailboxSource) -> String {
+    format!("mailbox_{}", source.path.display())
+        .replace('/', "_")
+        .replace(':', "_")
+}
+
+/// The parts of a message kept for rendering its thread.
+#[derive(Debug, Clone)]
+struct Message {
+    id: String,
+    parent: Option<String>,
+    thread_root: Option<String>,
+    subject: String,
+    from: String,
+    date: String,
+    timestamp: i64,
+    body: String,
+}
+
+/// Read the mailbox at `source.path` and write one markdown file per thread into `dest`
+/// (which is recreated). Returns the number of threads written.
+pub fn download(source: &MailboxSource, dest: &Path) -> Result<usize, MailboxError> {
+    if dest.exists() {
+        fs::remove_dir_all(dest)?;
+    }
+    fs::create_dir_all(dest)?;
+
+    let format = source.format.unwrap_or(if source.path.is_dir() {
+        MailboxFormat::Maildir
+    } else {
+        MailboxFormat::Mbox
+    });
+    let messages = match format {
+        MailboxFormat::Mbox => read_mbox(&source.path)?,
+        MailboxFormat::Maildir => read_maildir(&source.path)?,
+    };
+    info!(count = messages.len(), path = %source.path.display(), "Read mailbox messages");
+
+    let threads = group_threads(messages);
+    let mut written_names: HashSet<String> = HashSet::new();
+    for thread in &threads {
+        let subject = thread_subject(&thread[0].subject);
+        let mut stem = sanitize_to_fs_safe(&[&subject]);
+        if stem.is_empty() {
+            stem = "untitled".to_string();
+        }
+        if !written_names.insert(stem.clone()) {
+            let mut n = 2;
+            while !written_names.insert(format!("{stem}_{n}")) {
+                n += 1;
+            }
+            stem = format!("{stem}_{n}");
+        }
+        let out_path = dest.join(format!("{stem}.md"));
+        fs::write(&out_path, render_thread(&subject, thread))?;
+        debug!(path = %out_path.display(), messages = thread.len(), "Wrote mail thread");
+    }
+
+    info!(threads = threads.len(), dest = %dest.display(), "Exported mailbox threads to markdown");
+    Ok(threads.len())
+}
+
+static FROM_QUOTED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^>+From ").unwrap());
+static ATTRIBUTION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^On .+ wrote:\s*$").unwrap());
+static BLANK_LINES: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\n{3,}").unwrap());
+static REPLY_PREFIX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)^\s*((re|fwd?|aw|sv)\s*:\s*)+").unwrap());
+
+/// Split an mbox file on `From ` separator lines, parsing one message at a time.
+fn read_mbox(path: &Path) -> Result<Vec<Message>, MailboxError> {
+    let reader = BufReader::new(fs::File::open(path)?);
+    let mut messages = Vec::new();
+    let mut current: Vec<u8> = Vec::new();
+    let mut in_message = false;
+
+    for line in reader.split(b'\n') {
+        let mut line = line?;
+        if line.starts_with(b"From ") {
+            if in_message {
+                push_message(&mut messages, &current);
+            }
+            current.clear();
+            in_message = true;
+            continue;
+        }
+        // mboxrd escapes body lines starting with "From " as ">From ".
+        if FROM_QUOTED.is_match(&String::from_utf8_lossy(&line)) {
+            line.remove(0);
+        }
+        current.extend_from_slice(&line);
+        current.push(b'\n');
+    }
+    if in_message {
+        push_message(&mut messages, &current);
+    }
+    Ok(messages)
+}
+
+/// Read every message file in a Maildir's `cur` and `new` folders.
+fn read_maildir(path: &Path) -> Result<Vec<Message>, MailboxError> {
+    let mut files = Vec::new();
+    for sub in ["cur", "new"] {
+        let dir = path.join(sub);
+        if dir.is_dir() {
+            for entry in fs::read_dir(dir)? {
+                files.push(entry?.path());
+            }
+        }
+    }
+    files.sort();
+    let mut messages = Vec::new();
+    for file in files.into_iter().filter(|f| f.is_file()) {
+        push_message(&mut messages, &fs::read(&file)?);
+    }
+    Ok(messages)
+}
+
+fn push_message(messages: &mut Vec<Message>, raw: &[u8]) {
+    match parse_message(raw) {
+        Ok(msg) => messages.push(msg),
+        Err(e) => warn!(error = ?e, "Skipping unparseable mail message"),
+    }
+}
+
+fn parse_message(raw: &[u8]) -> Result<Message, MailboxError> {
+    let mail = mailparse::parse_mail(raw)?;
+    let headers = &mail.headers;
+    let ids = |name: &str| -> Vec<String> {
+        headers
+            .get_first_value(name)
+            .and_then(|v| mailparse::msgidparse(&v).ok())
+            .map(|list| list.to_vec())
+            .unwrap_or_default()
+    };
+    let references = ids("References");
+    let in_reply_to = ids("In-Reply-To");
+    let date = headers.get_first_value("Date").unwrap_or_default();
+    let timestamp = mailparse::dateparse(&date).unwrap_or(0);
+    let subject = headers.get_first_value("Subject").unwrap_or_default();
+    let from = headers.get_first_value("From").unwrap_or_default();
+    let body = clean_body(&extract_body(&mail).unwrap_or_default());
+    // Without a Message-ID, derive one from the message itself so the file name and
+    // thread key stay the same from one sync to the next.
+    let id = ids("Message-ID").into_iter().next().unwrap_or_else(|| {
+        let digest = Sha256::digest([from.as_str(), &date, &subject, &body].join("\0"));
+        let hex: String = digest[..16].iter().map(|b| format!("{b:02x}")).collect();
+        format!("generated-{hex}")
+    });
+
+    Ok(Message {
+        parent: in_reply_to.first().or_else(|| references.last()).cloned(),
+        thread_root: references.first().cloned(),
+        id,
+        subject,
+        from,
+        date,
+        timestamp,
+        body,
+    })
+}
+
+/// Find the best body part: `text/plain` preferred, converted `text/html` otherwise.
+fn extract_body(mail: &ParsedMail) -> Option<String> {
+    fn find<'a>(part: &'a ParsedMail<'a>, mimetype: &str) -> Option<&'a ParsedMail<'a>> {
+        if part.get_content_disposition().disposition == DispositionType::Attachment {
+            return None;
+        }
+        if part.subparts.is_empty() {
+            return (part.ctype.mimetype == mimetype).then_some(part);
+        }
+        part.subparts.iter().find_map(|p| find(p, mimetype))
+    }
+    if let Some(plain) = find(mail, "text/plain") {
+        return plain.get_body().ok();
+    }
+    find(mail, "text/html")
+        .and_then(|html| html.get_body().ok())
+        .map(|html| html_to_markdown_minimal(&html))
+}
+
+/// Strip quoted replies, reply attributions and signatures from a message body.
+fn clean_body(body: &str) -> String {
+    let mut kept: Vec<&str> = Vec::new();
+    for line in body.lines() {
+        let trimmed = line.trim_end();
+        if trimmed == "--" {
+            break;
+        }
+        if trimmed.starts_with("-----Original Message-----") {
+            break;
+        }
+        if trimmed.starts_with('>') || ATTRIBUTION.is_match(trimmed) {
+            continue;
+        }
+        kept.push(trimmed);
+    }
+    BLANK_LINES
+        .replace_all(kept.join("\n").trim(), "\n\n")
+        .into_owned()
+}
+
+/// Group messages into threads ordered by their first message, each sorted by date.
+fn group_threads(messages: Vec<Message>) -> Vec<Vec<Message>> {
+    let parents: HashMap<String, Option<String>> = messages
+        .iter()
+        .map(|m| (m.id.clone(), m.parent.clone()))
+        .collect();
+
+    let root_of = |m: &Message| -> String {
+        if let Some(root) = &m.thread_root {
+            return root.clone();
+        }
+        // Walk In-Reply-To links through the messages we have, guarding against cycles.
+        let mut current = m.id.clone();
+        let mut seen = HashSet::new();
+        while let Some(Some(parent)) = parents.get(&current) {
+            if !seen.insert(current.clone()) {
+                break;
+            }
+            current = parent.clone();
+        }
+        current
+    };
+
+    let mut threads: BTreeMap<String, Vec<Message>> = BTreeMap::new();
+    for m in messages {
+        threads.entry(root_of(&m)).or_default().push(m);
+    }
+    let mut threads: Vec<Vec<Message>> = threads.into_values().collect();
+    for thread in &mut threads {
+        thread.sort_by_key(|m| m.timestamp);
+    }
+    threads.sort_by_key(|t| t[0].timestamp);
+    threads
+}
+
+/// Thread title: the first subject with reply/forward prefixes removed.
+fn thread_subject(subject: &str) -> String {
+    let subject = REPLY_PREFIX.replace(subject, "").trim().to_string();
+    if subject.is_empty() {
+        "(no subject)".to_string()
+    } else {
+        subject
+    }
+}
+
+fn render_thread(subject: &str, thread: &[Message]) -> String {
+    let mut participants: Vec<&str> = Vec::new();
+    for m in thread {
+        if !participants.contains(&m.from.as_str()) {
+            participants.push(&m.from);
+        }
+    }
+    let first = &thread[0];
+    let last = &thread[thread.len() - 1];
+
+    let mut doc = format!("# {subject}\n\n");
+    doc.push_str(&format!("- Participants: {}\n", participants.join(", ")));
+    doc.push_str(&format!("- Messages: {}\n", thread.len()));
+    doc.push_str(&format!(
+        "- Dates: {} to {}\n",
+        format_timestamp(first.timestamp),
+        format_timestamp(last.timestamp)
+    ));
+    for m in thread {
+        doc.push_str(&format!("\n## {} ({})\n\n", m.from, m.date));
+        doc.push_str(&m.body);
+        doc.push('\n');
+    }
+    doc
+}
+
+/// Format a unix timestamp as `YYYY-MM-DD HH:MM UTC`.
+pub(crate) fn format_timestamp(timestamp: i64) -> String {
+    let (year, month, day, hour, minute, _) = utc_datetime(timestamp);
+    format!("{year:04}-{month:02}-{day:02} {hour:02}:{minute:02} UTC")
+}
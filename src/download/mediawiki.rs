@@ -0,0 +1,497 @@
+//! MediaWiki XML dump source: streams a `Special:Export` / `dumpBackup.php` export,
+//! keeps the latest revision of each page and writes it as a markdown file.
+//!
+//! The dump is read event by event, so only the page currently being parsed is held
+//! in memory. Output files are named `<namespace>__<title>.md`, with `Main` used for
+//! the main namespace.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use regex::Regex;
+use tracing::{debug, info};
+
+use super::{sanitize_to_fs_safe, MediaWikiSource};
+
+/// Error type for MediaWiki dump export.
+#[derive(Debug)]
+pub enum MediaWikiError {
+    Io(std::io::Error),
+    Xml(quick_xml::Error),
+}
+
+impl From<std::io::Error> for MediaWikiError {
+    fn from(e: std::io::Error) -> Self {
+        MediaWikiError::Io(e)
+    }
+}
+
+impl From<quick_xml::Error> for MediaWikiError {
+    fn from(e: quick_xml::Error) -> Self {
+        MediaWikiError::Xml(e)
+    }
+}
+
+/// Deterministic output directory name for a MediaWiki source.
+pub fn source_dir_name(source: &MediaWikiSource) -> String {
+    format!("mediawiki_{}", source.dump_path.display())
+        .replace('/', "_")
+        .replace(':', "_")
+}
+
+/// A page as parsed from the dump, holding only its latest revision.
+#[derive(Default)]
+struct Page {
+    title: String,
+    ns: i32,
+    redirect: bool,
+    latest_timestamp: String,
+    latest_text: Option<String>,
+}
+
+#[derive(Default)]
+struct Revision {
+    timestamp: String,
+    text: String,
+}
+
+/// Stream the dump at `source.dump_path` and write one markdown file per matching page
+/// into `dest` (which is recreated). Returns the number of pages written.
+pub fn download(source: &MediaWikiSource, dest: &Path) -> Result<usize, MediaWikiError> {
+    if dest.exists() {
+        fs::remove_dir_all(dest)?;
+    }
+    fs::create_dir_all(dest)?;
+
+    let file = fs::File::open(&source.dump_path)?;
+    let mut reader = Reader::from_reader(BufReader::new(file));
+
+    let wanted_categories: HashSet<String> = source
+        .categories
+        .iter()
+        .map(|c| normalise_category(c))
+        .collect();
+
+    let mut namespaces: HashMap<i32, String> = HashMap::new();
+    let mut pending_ns_key: Option<i32> = None;
+    let mut written_names: HashSet<String> = HashSet::new();
+    let mut written = 0;
+
+    let mut path: Vec<Vec<u8>> = Vec::new();
+    let mut page = Page::default();
+    let mut revision = Revision::default();
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(e) => {
+                let name = e.name().as_ref().to_vec();
+                match name.as_slice() {
+                    b"page" => page = Page::default(),
+                    b"revision" => revision = Revision::default(),
+                    b"namespace" => pending_ns_key = namespace_key(&e),
+                    _ => {}
+                }
+                path.push(name);
+            }
+            Event::Empty(e) => match e.name().as_ref() {
+                b"redirect" => page.redirect = true,
+                b"namespace" => {
+                    if let Some(key) = namespace_key(&e) {
+                        namespaces.insert(key, String::new());
+                    }
+                }
+                _ => {}
+            },
+            Event::Text(t) => {
+                let text = t.unescape()?;
+                handle_text(
+                    &path,
+                    &text,
+                    &mut page,
+                    &mut revision,
+                    &mut namespaces,
+                    pending_ns_key,
+                );
+            }
+            Event::CData(t) => {
+                let text = String::from_utf8_lossy(&t).into_owned();
+                handle_text(
+                    &path,
+                    &text,
+                    &mut page,
+                    &mut revision,
+                    &mut namespaces,
+                    pending_ns_key,
+                );
+            }
+            Event::End(e) => {
+                path.pop();
+                match e.name().as_ref() {
+                    b"namespace" => {
+                        if let Some(key) = pending_ns_key.take() {
+                            namespaces.entry(key).or_default();
+                        }
+                    }
+                    // Dumps list revisions oldest first, but compare timestamps to be safe.
+                    b"revision"
+                        if page.latest_text.is_none()
+                            || revision.timestamp >= page.latest_timestamp =>
+                    {
+                        let rev = std::mem::take(&mut revision);
+                        page.latest_timestamp = rev.timestamp;
+                        page.latest_text = Some(rev.text);
+                    }
+                    b"page" => {
+                        let page = std::mem::take(&mut page);
+                        if write_page(
+                            source,
+                            &wanted_categories,
+                            &namespaces,
+                            &mut written_names,
+                            dest,
+                            page,
+                        )? {
+                            written += 1;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    info!(pages = written, dest = %dest.display(), "Exported MediaWiki dump to markdown");
+    Ok(written)
+}
+
+fn namespace_key(e: &BytesStart) -> Option<i32> {
+    e.try_get_attribute("key")
+        .ok()
+        .flatten()
+        .and_then(|a| a.unescape_value().ok()?.parse().ok())
+}
+
+fn handle_text(
+    path: &[Vec<u8>],
+    text: &str,
+    page: &mut Page,
+    revision: &mut Revision,
+    namespaces: &mut HashMap<i32, String>,
+    pending_ns_key: Option<i32>,
+) {
+    let (Some(current), Some(parent)) = (path.last(), path.len().checked_sub(2).map(|i| &path[i]))
+    else {
+        return;
+    };
+    match (parent.as_slice(), current.as_slice()) {
+        (b"page", b"title") => page.title.push_str(text),
+        (b"page", b"ns") => page.ns = text.trim().parse().unwrap_or(0),
+        (b"revision", b"timestamp") => revision.timestamp.push_str(text.trim()),
+        (b"revision", b"text") => revision.text.push_str(text),
+        (b"namespaces", b"namespace") => {
+            if let Some(key) = pending_ns_key {
+                namespaces.entry(key).or_default().push_str(text);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Write a finished page if it passes the namespace and category filters.
+fn write_page(
+    source: &MediaWikiSource,
+    wanted_categories: &HashSet<String>,
+    namespaces: &HashMap<i32, String>,
+    written_names: &mut HashSet<String>,
+    dest: &Path,
+    page: Page,
+) -> Result<bool, MediaWikiError> {
+    let Some(text) = page.latest_text else {
+        return Ok(false);
+    };
+    if page.redirect {
+        debug!(title = %page.title, "Skipping redirect page");
+        return Ok(false);
+    }
+    if !source.namespaces.is_empty() && !source.namespaces.contains(&page.ns) {
+        return Ok(false);
+    }
+    let categories = page_categories(&text);
+    if !wanted_categories.is_empty()
+        && !categories
+            .iter()
+            .any(|c| wanted_categories.contains(&normalise_category(c)))
+    {
+        return Ok(false);
+    }
+
+    let ns_name = match namespaces.get(&page.ns).map(String::as_str) {
+        Some("") | None if page.ns == 0 => "Main".to_string(),
+        Some("") | None => format!("ns{}", page.ns),
+        Some(name) => name.to_string(),
+    };
+    let bare_title = page
+        .title
+        .strip_prefix(&format!("{ns_name}:"))
+        .unwrap_or(&page.title);
+
+    let mut stem = sanitize_to_fs_safe(&[&ns_name, bare_title]);
+    if !written_names.insert(stem.clone()) {
+        let mut n = 2;
+        while !written_names.insert(format!("{stem}_{n}")) {
+            n += 1;
+        }
+        stem = format!("{stem}_{n}");
+    }
+
+    let mut doc = format!("# {}\n\n", page.title);
+    doc.push_str(&format!("_Namespace: {ns_name}"));
+    if !page.latest_timestamp.is_empty() {
+        doc.push_str(&format!(" · Last revision: {}", page.latest_timestamp));
+    }
+    doc.push_str("_\n\n");
+    if !categories.is_empty() {
+        doc.push_str(&format!("Categories: {}\n\n", categories.join(", ")));
+    }
+    doc.push_str(&wikitext_to_markdown(&text));
+    doc.push('\n');
+
+    let out_path = dest.join(format!("{stem}.md"));
+    fs::write(&out_path, doc)?;
+    debug!(title = %page.title, path = %out_path.display(), "Wrote MediaWiki page");
+    Ok(true)
+}
+
+static CATEGORY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\[\[\s*category\s*:\s*([^|\]]+)").unwrap());
+
+fn page_categories(text: &str) -> Vec<String> {
+    CATEGORY
+        .captures_iter(text)
+        .map(|c| c[1].trim().to_string())
+        .collect()
+}
+
+fn normalise_category(name: &str) -> String {
+    name.trim().replace('_', " ").to_lowercase()
+}
+
+static CODE_BLOCK: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?s)<(pre|syntaxhighlight|source)(?:\s+[^>]*?lang="?([\w+-]+)"?[^>]*)?[^>]*>(.*?)</(?:pre|syntaxhighlight|source)>"#,
+    )
+    .unwrap()
+});
+static NOWIKI: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<nowiki>(.*?)</nowiki>").unwrap());
+static COMMENT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<!--.*?-->").unwrap());
+static TEMPLATE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\{\{([^{}]*)\}\}").unwrap());
+static HEADING: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(={1,6})\s*(.*?)\s*={1,6}\s*$").unwrap());
+static LIST: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^([*#:;]+)\s*(.*)$").unwrap());
+static BLANK_LINES: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\n{3,}").unwrap());
+
+/// Inline markup rewrites, applied in order.
+static INLINE_RULES: LazyLock<Vec<(Regex, &str)>> = LazyLock::new(|| {
+    [
+        (r"(?i)\[\[\s*category\s*:[^\]]*\]\]", ""),
+        (
+            r"(?i)\[\[\s*(?:file|image)\s*:\s*([^|\]]+)(?:\|[^\]]*)?\]\]",
+            "(file: $1)",
+        ),
+        (r"\[\[([^|\]]+)\|([^\]]+)\]\]", "[$2](<$1>)"),
+        (r"\[\[([^|\]]+)\]\]", "[$1](<$1>)"),
+        (r"\[(https?://[^\s\]]+)\s+([^\]]+)\]", "[$2]($1)"),
+        (r"\[(https?://[^\s\]]+)\]", "<$1>"),
+        (r"'''''(.+?)'''''", "***$1***"),
+        (r"'''(.+?)'''", "**$1**"),
+        (r"''(.+?)''", "*$1*"),
+        (r"(?s)<ref[^>]*/>", ""),
+        (r"(?s)<ref[^>]*>(.*?)</ref>", " ($1)"),
+        (r"(?i)<br\s*/?>", "\n"),
+        (
+            r"(?i)</?(?:span|div|small|big|center|u|s|sup|sub|font|references|blockquote|code|tt)(?:\s[^>]*)?/?>",
+            "",
+        ),
+        (r"__[A-Z]+__", ""),
+    ]
+    .into_iter()
+    .map(|(pattern, replacement)| (Regex::new(pattern).unwrap(), replacement))
+    .collect()
+});
+
+/// Convert MediaWiki wikitext to markdown.
+///
+/// Handles headings, bullet/numbered/definition lists, tables, internal and external
+/// links, bold/italic, `<pre>`/`<syntaxhighlight>` blocks and templates, which are
+/// rendered as plain text (`name (arg, key: value)`). Category links are dropped since
+/// they are listed separately in the page header.
+pub fn wikitext_to_markdown(text: &str) -> String {
+    // Protect code blocks from inline transforms by swapping them for placeholders.
+    let mut code_blocks: Vec<String> = Vec::new();
+    let text = CODE_BLOCK.replace_all(text, |c: &regex::Captures| {
+        let lang = c.get(2).map(|m| m.as_str()).unwrap_or("");
+        code_blocks.push(format!("```{lang}\n{}\n```", c[3].trim_matches('\n')));
+        format!("\n\u{0}CODE{}\u{0}\n", code_blocks.len() - 1)
+    });
+    let mut nowiki: Vec<String> = Vec::new();
+    let text = NOWIKI.replace_all(&text, |c: &regex::Captures| {
+        nowiki.push(c[1].to_string());
+        format!("\u{0}NOWIKI{}\u{0}", nowiki.len() - 1)
+    });
+
+    let mut text = COMMENT.replace_all(&text, "").into_owned();
+
+    // Templates, innermost first so nested templates render inside their parents.
+    while TEMPLATE.is_match(&text) {
+        text = TEMPLATE
+            .replace_all(&text, |c: &regex::Captures| render_template(&c[1]))
+            .into_owned();
+    }
+
+    for (pattern, replacement) in INLINE_RULES.iter() {
+        text = pattern.replace_all(&text, *replacement).into_owned();
+    }
+
+    let mut out: Vec<String> = Vec::new();
+    let mut table: Option<Table> = None;
+
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if let Some(t) = table.as_mut() {
+            if trimmed.starts_with("|}") {
+                out.push(table.take().unwrap().render());
+            } else {
+                t.push_line(trimmed);
+            }
+            continue;
+        }
+        if trimmed.starts_with("{|") {
+            table = Some(Table::default());
+        } else if let Some(c) = HEADING.captures(trimmed) {
+            out.push(format!("{} {}", "#".repeat(c[1].len()), &c[2]));
+        } else if trimmed == "----" {
+            out.push("---".to_string());
+        } else if let Some(c) = LIST.captures(line) {
+            let marker = &c[1];
+            let body = c[2].trim();
+            let indent = "  ".repeat(marker.len() - 1);
+            let line = match marker.chars().last() {
+                Some('*') => format!("{indent}- {body}"),
+                Some('#') => format!("{indent}1. {body}"),
+                Some(';') => match body.split_once(" : ") {
+                    Some((term, def)) => format!("{indent}**{}**: {}", term.trim(), def.trim()),
+                    None => format!("{indent}**{body}**"),
+                },
+                _ => format!("{indent}{body}"),
+            };
+            out.push(line);
+        } else {
+            out.push(line.trim_end().to_string());
+        }
+    }
+    if let Some(t) = table {
+        out.push(t.render());
+    }
+
+    let mut markdown = out.join("\n");
+    for (i, block) in code_blocks.iter().enumerate() {
+        markdown = markdown.replace(&format!("\u{0}CODE{i}\u{0}"), block);
+    }
+    for (i, raw) in nowiki.iter().enumerate() {
+        markdown = markdown.replace(&format!("\u{0}NOWIKI{i}\u{0}"), raw);
+    }
+    BLANK_LINES
+        .replace_all(markdown.trim(), "\n\n")
+        .into_owned()
+}
+
+fn render_template(inner: &str) -> String {
+    let mut parts = inner.split('|').map(str::trim);
+    let name = parts.next().unwrap_or_default();
+    let args: Vec<String> = parts
+        .filter(|a| !a.is_empty())
+        .map(|a| match a.split_once('=') {
+            Some((k, v)) => format!("{}: {}", k.trim(), v.trim()),
+            None => a.to_string(),
+        })
+        .collect();
+    if args.is_empty() {
+        name.to_string()
+    } else {
+        format!("{name} ({})", args.join(", "))
+    }
+}
+
+/// Accumulates a `{| ... |}` wikitable and renders it as a GFM table.
+#[derive(Default)]
+struct Table {
+    caption: Option<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    fn push_line(&mut self, line: &str) {
+        if let Some(caption) = line.strip_prefix("|+") {
+            self.caption = Some(caption.trim().to_string());
+        } else if line.starts_with("|-") {
+            self.rows.push(Vec::new());
+        } else if let Some(cells) = line.strip_prefix('!') {
+            self.push_cells(cells.split("!!"));
+        } else if let Some(cells) = line.strip_prefix('|') {
+            self.push_cells(cells.split("||"));
+        } else if let Some(last) = self.rows.last_mut().and_then(|r| r.last_mut()) {
+            // Continuation of a multi-line cell.
+            if !line.is_empty() {
+                last.push(' ');
+                last.push_str(line);
+            }
+        }
+    }
+
+    fn push_cells<'a>(&mut self, cells: impl Iterator<Item = &'a str>) {
+        if self.rows.is_empty() {
+            self.rows.push(Vec::new());
+        }
+        let row = self.rows.last_mut().unwrap();
+        for cell in cells {
+            // Strip cell attributes: `style="..." | content`.
+            let content = cell.split_once('|').map(|(_, c)| c).unwrap_or(cell);
+            row.push(content.trim().replace('\n', " "));
+        }
+    }
+
+    fn render(self) -> String {
+        let rows: Vec<Vec<String>> = self.rows.into_iter().filter(|r| !r.is_empty()).collect();
+        let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let mut out = String::new();
+        if let Some(caption) = self.caption {
+            out.push_str(&format!("**{caption}**\n\n"));
+        }
+        if width == 0 {
+            return out;
+        }
+        let render_row = |row: &Vec<String>| {
+            let mut cells = row.clone();
+            cells.resize(width, String::new());
+            format!("| {} |", cells.join(" | "))
+        };
+        out.push_str(&render_row(&rows[0]));
+        out.push('\n');
+        out.push_str(&format!("|{}\n", " --- |".repeat(width)));
+        for row in &rows[1..] {
+            out.push_str(&render_row(row));
+            out.push('\n');
+        }
+        out.trim_end().to_string()
+    }
+}
@@ -0,0 +1,430 @@
+//! S3-compatible object storage source: lists a bucket prefix with `ListObjectsV2` and
+//! downloads matching objects, mirroring their keys as paths under the source directory.
+//!
+//! Requests use path-style addressing and AWS Signature V4, so any S3-compatible endpoint
+//! (AWS, MinIO, Ceph, ...) works. The ETag of every downloaded object is recorded in a
+//! `<source dir>.etags.json` file next to the source directory; on re-runs unchanged objects
+//! are skipped and objects that disappeared from the listing are removed locally.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+
+use super::{utc_datetime, S3Source};
+use crate::preprocess::walk::PathFilter;
+
+/// Error type for S3 downloads.
+#[derive(Debug)]
+pub enum S3Error {
+    Io(std::io::Error),
+    Http(reqwest::Error),
+    Status {
+        status: u16,
+        url: String,
+        body: String,
+    },
+    Xml(quick_xml::Error),
+    Pattern(globset::Error),
+    State(serde_json::Error),
+    InvalidUrl(String),
+}
+
+impl From<std::io::Error> for S3Error {
+    fn from(e: std::io::Error) -> Self {
+        S3Error::Io(e)
+    }
+}
+
+impl From<reqwest::Error> for S3Error {
+    fn from(e: reqwest::Error) -> Self {
+        S3Error::Http(e)
+    }
+}
+
+impl From<quick_xml::Error> for S3Error {
+    fn from(e: quick_xml::Error) -> Self {
+        S3Error::Xml(e)
+    }
+}
+
+impl From<globset::Error> for S3Error {
+    fn from(e: globset::Error) -> Self {
+        S3Error::Pattern(e)
+    }
+}
+
+impl From<serde_json::Error> for S3Error {
+    fn from(e: serde_json::Error) -> Self {
+        S3Error::State(e)
+    }
+}
+
+/// Counts of what a download run did, for logging and tests.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct S3SyncStats {
+    pub downloaded: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+}
+
+/// Deterministic output directory name for an S3 source.
+pub fn source_dir_name(source: &S3Source) -> String {
+    format!("s3_{}_{}_{}", source.endpoint, source.bucket, source.prefix)
+        .replace('/', "_")
+        .replace(':', "_")
+}
+
+/// Path of the ETag state file kept next to the source directory `dest`.
+pub fn state_path(dest: &Path) -> PathBuf {
+    let name = dest
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    dest.with_file_name(format!("{name}.etags.json"))
+}
+
+/// An object as returned by `ListObjectsV2`.
+#[derive(Debug, Default)]
+struct ListedObject {
+    key: String,
+    etag: String,
+}
+
+struct Credentials {
+    access_key_id: String,
+    secret_access_key: String,
+}
+
+/// Synchronise the objects under `source.prefix` into `dest`, skipping objects whose ETag
+/// is unchanged since the previous run.
+pub async fn download(source: &S3Source, dest: &Path) -> Result<S3SyncStats, S3Error> {
+    fs::create_dir_all(dest)?;
+    let client = S3Client::new(source);
+    let filter = PathFilter::new(&source.options)?;
+
+    let state_file = state_path(dest);
+    let previous: BTreeMap<String, String> = match fs::read(&state_file) {
+        Ok(bytes) => serde_json::from_slice(&bytes)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut current: BTreeMap<String, String> = BTreeMap::new();
+    let mut stats = S3SyncStats::default();
+    for object in client.list_objects().await? {
+        if object.key.ends_with('/') {
+            continue; // "folder" placeholder objects
+        }
+        if !filter.keeps(&object.key) {
+            debug!(key = %object.key, "Skipping S3 object excluded by patterns");
+            continue;
+        }
+        let Some(local) = local_path(dest, &object.key) else {
+            warn!(key = %object.key, "Skipping S3 object whose key has no local path");
+            continue;
+        };
+        if previous.get(&object.key) == Some(&object.etag) && local.exists() {
+            stats.unchanged += 1;
+        } else {
+            let body = client.get_object(&object.key).await?;
+            if let Some(parent) = local.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&local, body)?;
+            debug!(key = %object.key, etag = %object.etag, "Downloaded S3 object");
+            stats.downloaded += 1;
+        }
+        current.insert(object.key, object.etag);
+    }
+
+    for key in previous.keys().filter(|k| !current.contains_key(*k)) {
+        let Some(local) = local_path(dest, key) else {
+            continue;
+        };
+        match fs::remove_file(&local) {
+            Ok(()) => stats.removed += 1,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        // Prune directories left empty; `remove_dir` fails harmlessly on non-empty ones.
+        let mut dir = local.parent();
+        while let Some(d) = dir.filter(|d| *d != dest) {
+            if fs::remove_dir(d).is_err() {
+                break;
+            }
+            dir = d.parent();
+        }
+    }
+
+    fs::write(&state_file, serde_json::to_vec_pretty(&current)?)?;
+    info!(
+        bucket = %source.bucket,
+        prefix = %source.prefix,
+        downloaded = stats.downloaded,
+        unchanged = stats.unchanged,
+        removed = stats.removed,
+        "Synchronised S3 objects"
+    );
+    Ok(stats)
+}
+
+/// Map an object key to a path under `dest`; `None` for keys with empty, `.` or `..`
+/// segments, which are valid in S3 but have no file of their own (or would escape `dest`).
+fn local_path(dest: &Path, key: &str) -> Option<PathBuf> {
+    let mut path = dest.to_path_buf();
+    for segment in key.split('/') {
+        if segment.is_empty() || segment == "." || segment == ".." {
+            return None;
+        }
+        path.push(segment);
+    }
+    Some(path)
+}
+
+/// Minimal path-style S3 client signing requests with AWS Signature V4.
+struct S3Client {
+    http: reqwest::Client,
+    endpoint: String,
+    bucket: String,
+    prefix: String,
+    region: String,
+    credentials: Option<Credentials>,
+}
+
+impl S3Client {
+    fn new(source: &S3Source) -> Self {
+        let access_key_id = source
+            .access_key_id
+            .clone()
+            .or_else(|| std::env::var("AWS_ACCESS_KEY_ID").ok());
+        let secret_access_key = source
+            .secret_access_key
+            .clone()
+            .or_else(|| std::env::var("AWS_SECRET_ACCESS_KEY").ok());
+        let credentials = match (access_key_id, secret_access_key) {
+            (Some(access_key_id), Some(secret_access_key)) => Some(Credentials {
+                access_key_id,
+                secret_access_key,
+            }),
+            _ => None,
+        };
+        Self {
+            http: reqwest::Client::new(),
+            endpoint: source.endpoint.trim_end_matches('/').to_string(),
+            bucket: source.bucket.clone(),
+            prefix: source.prefix.clone(),
+            region: source
+                .region
+                .clone()
+                .unwrap_or_else(|| "us-east-1".to_string()),
+            credentials,
+        }
+    }
+
+    async fn list_objects(&self) -> Result<Vec<ListedObject>, S3Error> {
+        let mut objects = Vec::new();
+        let mut continuation: Option<String> = None;
+        loop {
+            let mut query = vec![
+                ("list-type".to_string(), "2".to_string()),
+                ("prefix".to_string(), self.prefix.clone()),
+            ];
+            if let Some(token) = &continuation {
+                query.push(("continuation-token".to_string(), token.clone()));
+            }
+            let body = self.get(&format!("/{}", self.bucket), &query).await?;
+            let page = parse_list_response(&body)?;
+            objects.extend(page.objects);
+            match page.next_token {
+                Some(token) if page.truncated => continuation = Some(token),
+                _ => break,
+            }
+        }
+        Ok(objects)
+    }
+
+    async fn get_object(&self, key: &str) -> Result<Vec<u8>, S3Error> {
+        let path = format!("/{}/{}", self.bucket, uri_encode(key, false));
+        self.get(&path, &[]).await
+    }
+
+    async fn get(&self, path: &str, query: &[(String, String)]) -> Result<Vec<u8>, S3Error> {
+        let mut sorted: Vec<(String, String)> = query
+            .iter()
+            .map(|(k, v)| (uri_encode(k, true), uri_encode(v, true)))
+            .collect();
+        sorted.sort();
+        let query_string = sorted
+            .iter()
+            .map(|(k, v)| format!("{k}={v}"))
+            .collect::<Vec<_>>()
+            .join("&");
+        let url = if query_string.is_empty() {
+            format!("{}{}", self.endpoint, path)
+        } else {
+            format!("{}{}?{}", self.endpoint, path, query_string)
+        };
+
+        let mut request = self.http.get(&url);
+        if let Some(credentials) = &self.credentials {
+            let parsed = reqwest::Url::parse(&url)
+                .map_err(|e| S3Error::InvalidUrl(format!("{url}: {e}")))?;
+            let host = match parsed.port() {
+                Some(port) => format!("{}:{}", parsed.host_str().unwrap_or_default(), port),
+                None => parsed.host_str().unwrap_or_default().to_string(),
+            };
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or_default();
+            for (name, value) in sign_v4(
+                credentials,
+                &self.region,
+                &host,
+                parsed.path(),
+                &query_string,
+                now,
+            ) {
+                request = request.header(name, value);
+            }
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        let body = response.bytes().await?.to_vec();
+        if !status.is_success() {
+            return Err(S3Error::Status {
+                status: status.as_u16(),
+                url,
+                body: String::from_utf8_lossy(&body).into_owned(),
+            });
+        }
+        Ok(body)
+    }
+}
+
+/// Compute the SigV4 headers for an unsigned-payload GET request.
+fn sign_v4(
+    credentials: &Credentials,
+    region: &str,
+    host: &str,
+    canonical_uri: &str,
+    canonical_query: &str,
+    now: i64,
+) -> Vec<(&'static str, String)> {
+    let (year, month, day, hour, minute, second) = utc_datetime(now);
+    let date = format!("{year:04}{month:02}{day:02}");
+    let amz_date = format!("{date}T{hour:02}{minute:02}{second:02}Z");
+    let payload_hash = "UNSIGNED-PAYLOAD";
+    let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+
+    let canonical_request = format!(
+        "GET\n{canonical_uri}\n{canonical_query}\nhost:{host}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{amz_date}\n\n{signed_headers}\n{payload_hash}"
+    );
+    let scope = format!("{date}/{region}/s3/aws4_request");
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let mut key = hmac_sha256(
+        format!("AWS4{}", credentials.secret_access_key).as_bytes(),
+        date.as_bytes(),
+    );
+    for part in [region, "s3", "aws4_request"] {
+        key = hmac_sha256(&key, part.as_bytes());
+    }
+    let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+
+    vec![
+        ("x-amz-date", amz_date),
+        ("x-amz-content-sha256", payload_hash.to_string()),
+        (
+            "authorization",
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+                credentials.access_key_id
+            ),
+        ),
+    ]
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Percent-encode per the SigV4 rules; `/` is kept in paths and encoded in query values.
+fn uri_encode(value: &str, encode_slash: bool) -> String {
+    let mut out = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(byte as char)
+            }
+            b'/' if !encode_slash => out.push('/'),
+            _ => out.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    out
+}
+
+struct ListPage {
+    objects: Vec<ListedObject>,
+    truncated: bool,
+    next_token: Option<String>,
+}
+
+fn parse_list_response(body: &[u8]) -> Result<ListPage, S3Error> {
+    let mut reader = Reader::from_reader(body);
+    let mut page = ListPage {
+        objects: Vec::new(),
+        truncated: false,
+        next_token: None,
+    };
+    let mut current: Option<ListedObject> = None;
+    let mut element = Vec::new();
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(e) => {
+                element = e.name().as_ref().to_vec();
+                if element == b"Contents" {
+                    current = Some(ListedObject::default());
+                }
+            }
+            Event::Text(t) => {
+                let text = t.unescape()?.into_owned();
+                match (element.as_slice(), current.as_mut()) {
+                    (b"Key", Some(object)) => object.key.push_str(&text),
+                    (b"ETag", Some(object)) => object.etag = text.trim_matches('"').to_string(),
+                    (b"IsTruncated", _) => page.truncated = text.trim() == "true",
+                    (b"NextContinuationToken", _) => page.next_token = Some(text),
+                    _ => {}
+                }
+            }
+            Event::End(e) => {
+                if e.name().as_ref() == b"Contents" {
+                    page.objects.extend(current.take());
+                }
+                element.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(page)
+}
@@ -19,7 +19,7 @@
 //! - [`preprocess`]: Processing/conversion of downloaded repos to uploadable items (PDFs, file flattening, etc).
 //! - [`synchronise`]: High-level pipeline for end-to-end sync (download-process-upload/report).
 //! - [`contract`]: Interface trait for uploading sources/items (mockable for test).
-//! - [`code_to_pdf`]: Minimal stub conversion of code/README to PDF files.
+//! - [`code_to_pdf`]: Paginated PDF rendering of code files and markdown READMEs with bundled DejaVu fonts.
 //!
 //! ## Example
 //! ```rust
@@ -32,6 +32,7 @@
 //!
 
 pub mod code_to_pdf;
+pub mod config;
 pub mod contract;
 pub mod download;
 pub mod preprocess;
@@ -1,11 +1,36 @@
-use crate::code_to_pdf::{code_file_to_pdf, CodeToPdfError};
+//! Processing: turns a downloaded source into items for upload by running a
+//! [`pipeline::Pipeline`] of stages, configured explicitly or by a
+//! [`ProcessorKind`](crate::contract::ProcessorKind) preset. [`Processors`] picks the
+//! config per source.
+
+use std::path::Path;
+
+use crate::code_to_pdf::CodeToPdfError;
 use crate::contract::{
-    ExternalItemInput, ExternalSourceInput, ProcessConfig, ProcessError, ProcessInput,
-    ProcessorKind,
+    DownloadedSource, ExternalSourceInput, Preprocessor, PreprocessorSelector, ProcessConfig,
+    ProcessError, ProcessInput,
 };
-use tempfile;
 use tracing::{debug, error, info};
 
+pub mod api_schema;
+pub mod chunk;
+pub mod content;
+pub mod dedup;
+pub mod header;
+pub mod history;
+pub mod metadata;
+pub mod notebook;
+pub mod overview;
+pub mod pii;
+pub mod pipeline;
+pub mod redact;
+pub mod stages;
+pub mod symbols;
+pub mod walk;
+
+use metadata::{external_items, SourceInfo};
+use pipeline::{Pipeline, StageContext};
+
 /// Main processor struct for CLI usage: implements Preprocessor.
 pub struct Processor {
     config: ProcessConfig,
@@ -17,8 +42,34 @@ impl Processor {
     }
 }
 
+/// Selects a [`Processor`] per source: the source's own `process` config when it sets
+/// one, otherwise the top-level config.
+pub struct Processors {
+    default: Processor,
+}
+
+impl Processors {
+    pub fn new(default: ProcessConfig) -> Self {
+        Self {
+            default: Processor::new(default),
+        }
+    }
+}
+
+impl PreprocessorSelector for Processors {
+    fn select<'a>(&'a self, source: &DownloadedSource) -> Box<dyn Preprocessor + 'a> {
+        match &source.original_source.options().process {
+            Some(config) => {
+                debug!(source = %source.logical_name, processor = ?config.kind, "Using the source's own process config");
+                Box::new(Processor::new(config.clone()))
+            }
+            None => Box::new(&self.default),
+        }
+    }
+}
+
 #[async_trait::async_trait]
-impl crate::contract::Preprocessor for Processor {
+impl Preprocessor for Processor {
     async fn process(&self, input: ProcessInput) -> Result<ExternalSourceInput, ProcessError> {
         self.process_sync(input)
     }
@@ -27,158 +78,85 @@ impl crate::contract::Preprocessor for Processor {
 impl Processor {
     /// Synchronous process logic for unit tests and internal use.
     pub fn process_sync(&self, input: ProcessInput) -> Result<ExternalSourceInput, ProcessError> {
-        let config = &self.config;
-        info!(processor = ?config.kind, name = input.name, "Starting processing for source");
-        let result = match config.kind {
-            ProcessorKind::ReadmeToPDF => process_readme_to_pdf(input),
-            ProcessorKind::FlattenFiles => process_flatten_files(input),
-            // Add more processor kinds as needed
-        };
+        let pipeline = Pipeline::from_config(&self.config.pipeline());
+        info!(
+            processor = ?self.config.kind,
+            stages = ?pipeline.stage_names(),
+            name = input.name,
+            "Starting processing for source"
+        );
+        let context = StageContext::from(&input);
+        let result = pipeline.run(&context);
         match &result {
-            Ok(ext) => info!(
-                items = ext.external_items.len(),
+            Ok(batch) => info!(
+                items = batch.items.len(),
+                skipped = batch.skipped.len(),
+                secrets = batch.findings.len(),
+                pii = ?batch.pii,
                 "Processing completed successfully"
             ),
             Err(e) => error!(error = ?e, "Processing failed"),
         };
-        result
+        let batch = result?;
+        let info = SourceInfo::load(&context);
+        Ok(ExternalSourceInput {
+            name: input.name,
+            external_items: external_items(&info, batch.items),
+            skipped: batch.skipped,
+            findings: batch.findings,
+            pii: batch.pii,
+        })
     }
 }
 
-/// (No longer needed free function for process -- use Processor::process_sync or the trait.)
-
-fn process_readme_to_pdf(input: ProcessInput) -> Result<ExternalSourceInput, ProcessError> {
-    let readme_path = input.repo_path.join("README.md");
-    debug!(repo_path = %input.repo_path.display(), "Looking for README.md in repo path");
-
-    if !readme_path.exists() {
-        error!(path = %readme_path.display(), "No README.md found in repository");
-        return Err(ProcessError::NoReadme);
+/// Flat item name for a relative path: components joined with "__", dropping leading
+/// directories until the name fits in 180 bytes.
+fn flat_name(rel_path: &Path) -> String {
+    let mut segments: Vec<String> = rel_path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    let basename = segments.pop().unwrap_or_default();
+    let max_len = 180;
+    // Try to include as many trailing segments as possible
+    let mut from = 0;
+    loop {
+        let joined = if segments.len() > from {
+            segments[from..].join("__") + "__" + &basename
+        } else {
+            basename.clone()
+        };
+        if joined.len() <= max_len || from >= segments.len() {
+            return joined;
+        }
+        from += 1;
     }
+}
 
-    // Prepare a temp output file path for pdf generation
-    let tmp_pdf = tempfile::NamedTempFile::new().map_err(|e| {
-        error!(error = ?e, "Failed to create temp file for PDF output");
-        ProcessError::Io(e)
-    })?;
-    let tmp_pdf_path = tmp_pdf.path();
-
-    // Call the code_to_pdf module (on-disk)
-    code_file_to_pdf(&readme_path, tmp_pdf_path)
-        .map_err(|e| {
-            match &e {
-                CodeToPdfError::Io(err) => error!(path = %readme_path.display(), error = ?err, "IO error during PDF generation"),
-                CodeToPdfError::EmptyInput => error!("Attempted PDF generation with empty input"),
-                CodeToPdfError::Font(desc) => error!(desc = *desc, "Font error during PDF generation"),
-            }
-            match e {
-                CodeToPdfError::Io(e) => ProcessError::Io(e),
-                CodeToPdfError::EmptyInput => ProcessError::Other("PDF: Empty input".into()),
-                CodeToPdfError::Font(_) => ProcessError::Other("PDF: font error".into()),
-            }
-        })?;
-
-    // Read PDF as bytes
-    let content = std::fs::read(tmp_pdf_path).map_err(|e| {
-        error!(error = ?e, path = %tmp_pdf_path.display(), "Failed to read generated PDF from disk");
-        ProcessError::Io(e)
-    })?;
-
-    // Prepare the result structures
-    let ext_item = ExternalItemInput {
-        filename: "README.pdf".to_string(),
-        content,
-    };
-
-    info!(
-        filename = "README.pdf",
-        size = ext_item.content.len(),
-        "Generated README.pdf from README.md"
-    );
-    Ok(ExternalSourceInput {
-        name: input.name,
-        external_items: vec![ext_item],
-    })
+/// Relative path with `/` separators on every platform.
+fn slash_path(rel_path: &Path) -> String {
+    rel_path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
 }
 
-/// Recursively flatten all files and output as items with "__" as directory separator.
-fn process_flatten_files(input: ProcessInput) -> Result<ExternalSourceInput, ProcessError> {
-    info!(path = %input.repo_path.display(), "Flattening files in repository");
-    let mut external_items = Vec::new();
-    let repo_path = &input.repo_path;
-    let _base_len = repo_path.components().count();
+/// A backtick fence longer than any backtick run in `text`, so it can hold it verbatim.
+fn code_fence(text: &str) -> String {
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
 
-    fn visit_dir(
-        dir: &std::path::Path,
-        repo_path: &std::path::Path,
-        results: &mut Vec<ExternalItemInput>,
-    ) -> Result<(), ProcessError> {
-        for entry_res in std::fs::read_dir(dir)? {
-            let entry = entry_res?;
-            let path = entry.path();
-            if path.is_dir() {
-                // Skip .git and target directories
-                let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
-                if file_name == ".git" || file_name == "target" {
-                    debug!(path = %path.display(), "Skipping directory");
-                    continue;
-                }
-                visit_dir(&path, repo_path, results)?;
-            } else if path.is_file() {
-                // compute a flat filename with "__" as a separator, with truncation logic
-                let rel_path = path.strip_prefix(repo_path).unwrap();
-                let mut segments: Vec<String> = Vec::new();
-                for comp in rel_path.components() {
-                    segments.push(comp.as_os_str().to_string_lossy().into_owned());
-                }
-                if segments.is_empty() {
-                    continue;
-                }
-                let basename = segments.pop().unwrap();
-                let mut joined: String;
-                let max_len = 180;
-                // Try to include as many trailing segments as possible
-                let mut from = 0;
-                loop {
-                    joined = if segments.len() > from {
-                        segments[from..].join("__") + "__" + &basename
-                    } else {
-                        basename.clone()
-                    };
-                    if joined.len() <= max_len || from >= segments.len() {
-                        break;
-                    }
-                    from += 1;
-                }
-                let flat_name = joined;
-                match std::fs::read(&path) {
-                    Ok(content) => {
-                        debug!(filename = %flat_name, size = content.len(), "Flattened file");
-                        results.push(ExternalItemInput {
-                            filename: flat_name,
-                            content,
-                        });
-                    }
-                    Err(e) => {
-                        error!(error = ?e, path = %path.display(), "Failed to read file while flattening");
-                        return Err(ProcessError::Io(e));
-                    }
-                }
-            }
-        }
-        Ok(())
+fn pdf_error(e: CodeToPdfError) -> ProcessError {
+    match &e {
+        CodeToPdfError::Io(err) => error!(error = ?err, "IO error during PDF generation"),
+        CodeToPdfError::EmptyInput => error!("Attempted PDF generation with empty input"),
+        CodeToPdfError::Font(desc) => error!(desc = *desc, "Font error during PDF generation"),
     }
-    if let Err(e) = visit_dir(repo_path, repo_path, &mut external_items) {
-        error!(error = ?e, "Error occurred during directory flattening");
-        return Err(e);
+    match e {
+        CodeToPdfError::Io(e) => ProcessError::Io(e),
+        CodeToPdfError::EmptyInput => ProcessError::Other("PDF: Empty input".into()),
+        CodeToPdfError::Font(_) => ProcessError::Other("PDF: font error".into()),
     }
-
-    info!(
-        count = external_items.len(),
-        "Completed flattening files in repository"
-    );
-    Ok(ExternalSourceInput {
-        name: input.name,
-        external_items,
-    })
 }
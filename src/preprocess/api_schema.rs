@@ -0,0 +1,156 @@
+//! API schemas stage: renders API contracts into readable markdown, so questions about
+//! endpoints, RPCs and types retrieve the part of the contract that answers them.
+//!
+//! Three formats are recognised:
+//! - OpenAPI 3 and Swagger 2 documents (YAML or JSON with a top-level `openapi` or
+//!   `swagger` key): one item per operation with its parameters, request body and
+//!   responses, `$ref`s resolved within the document;
+//! - protobuf (`.proto`): one item per RPC with its request and response messages, and
+//!   one per message and enum, with the comments written next to them;
+//! - GraphQL SDL (`.graphql`, `.graphqls`, `.gql`): one item per query, mutation and
+//!   subscription field with its arguments and result type, and one per named type.
+//!
+//! Each file also gets an index item (`api/openapi.yaml.md`) listing what it defines;
+//! the others are named after it (`api/openapi.yaml__GET_pets.md`). Nested schemas,
+//! messages and types are listed inline down to [`ApiSchemaOptions::depth`]. Files that
+//! fail to parse, and GraphQL files holding queries rather than a schema, pass through.
+
+mod graphql;
+mod openapi;
+mod protobuf;
+
+use std::collections::HashSet;
+
+use tracing::{debug, info, warn};
+
+use super::chunk::suffixed_name;
+use super::pipeline::{Batch, Item, Stage, StageContext};
+use super::symbols::slug;
+use crate::contract::{ApiSchemaOptions, ProcessError};
+
+/// Replace schema files with their markdown renderings.
+#[derive(Debug, Clone, Default)]
+pub struct ApiSchemas {
+    pub options: ApiSchemaOptions,
+}
+
+impl Stage for ApiSchemas {
+    fn name(&self) -> &'static str {
+        "api_schemas"
+    }
+
+    fn run(&self, _context: &StageContext, batch: Batch) -> Result<Batch, ProcessError> {
+        let mut items = Vec::with_capacity(batch.items.len());
+        let (mut files, mut documents) = (0, 0);
+        for item in batch.items {
+            let rendered = item
+                .text()
+                .and_then(|text| render(&item.path, text, self.options.depth));
+            let rendered = match rendered {
+                Some(Ok(rendered)) => rendered,
+                Some(Err(reason)) => {
+                    warn!(path = %item.path, reason = %reason, "Could not render API schema; keeping it as is");
+                    items.push(item);
+                    continue;
+                }
+                None => {
+                    items.push(item);
+                    continue;
+                }
+            };
+            debug!(path = %item.path, items = rendered.len(), "Rendered API schema");
+            files += 1;
+            documents += rendered.len();
+            let index = format!("{}.md", item.path);
+            let mut names = HashSet::new();
+            let rendered: Vec<Item> = rendered
+                .into_iter()
+                .map(|document| {
+                    let path = match &document.name {
+                        None => index.clone(),
+                        Some(name) => {
+                            let base = slug(name);
+                            let mut name = base.clone();
+                            for n in 2.. {
+                                if names.insert(name.clone()) {
+                                    break;
+                                }
+                                name = format!("{base}_{n}");
+                            }
+                            suffixed_name(&index, &name)
+                        }
+                    };
+                    Item {
+                        path,
+                        origin: item.origin.clone(),
+                        content: document.markdown.into_bytes(),
+                        mime_type: Some("text/markdown".to_string()),
+                        breadcrumb: document.breadcrumb,
+                        fingerprint: None,
+                        symbol: None,
+                    }
+                })
+                .collect();
+            if self.options.keep_files {
+                items.push(item);
+            }
+            items.extend(rendered);
+        }
+        info!(files, items = documents, "Rendered API schemas");
+        Ok(Batch { items, ..batch })
+    }
+}
+
+/// One markdown rendering of part of a schema file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Document {
+    /// What it describes (`GET /pets`, `Billing.CreateInvoice`); `None` for the file's
+    /// index.
+    name: Option<String>,
+    breadcrumb: Vec<String>,
+    markdown: String,
+}
+
+/// The renderings of a schema file, an error when it looks like a schema but cannot be
+/// read, or `None` when it is not one.
+fn render(path: &str, text: &str, depth: usize) -> Option<Result<Vec<Document>, String>> {
+    let extension = path
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase());
+    match extension.as_deref()? {
+        "yaml" | "yml" | "json" => openapi::render(text, depth),
+        "proto" => Some(protobuf::render(path, text, depth)),
+        "graphql" | "graphqls" | "gql" => graphql::render(path, text, depth),
+        _ => None,
+    }
+}
+
+/// Append a list item at nesting `level`: `head`, then `doc` on the same line.
+fn bullet(out: &mut String, level: usize, head: &str, doc: Option<&str>) {
+    out.push_str(&"  ".repeat(level));
+    out.push_str("- ");
+    out.push_str(head);
+    if let Some(doc) = doc.map(one_line).filter(|doc| !doc.is_empty()) {
+        out.push_str(": ");
+        out.push_str(&doc);
+    }
+    out.push('\n');
+}
+
+/// `text` with its whitespace, line breaks included, collapsed to single spaces.
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The document with a single trailing newline.
+fn finish(out: String) -> String {
+    format!("{}\n", out.trim_end())
+}
+
+/// Append `text` as a paragraph when it is not empty.
+fn paragraph(out: &mut String, text: Option<&str>) {
+    if let Some(text) = text.map(str::trim).filter(|text| !text.is_empty()) {
+        out.push_str(text);
+        out.push_str("\n\n");
+    }
+}
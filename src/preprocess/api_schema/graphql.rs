@@ -0,0 +1,737 @@
+//! GraphQL schema definition language.
+//!
+//! Type system definitions and extensions are read; files holding operations or
+//! fragments are queries written against a schema, not a schema, and are left alone.
+//! Descriptions document definitions, falling back to `#` comments right above them.
+
+use std::fmt::Write as _;
+
+use super::{bullet, finish, paragraph, Document};
+
+/// Renderings of an SDL file: its index, then one per root operation field and one per
+/// other named type; `None` when the file holds operations instead.
+pub(super) fn render(
+    path: &str,
+    text: &str,
+    depth: usize,
+) -> Option<Result<Vec<Document>, String>> {
+    let tokens = match tokenize(text) {
+        Ok(tokens) => tokens,
+        Err(e) => return Some(Err(e)),
+    };
+    let schema = match (Parser { tokens, pos: 0 }).schema() {
+        Ok(Some(schema)) => schema,
+        Ok(None) => return None,
+        Err(e) => return Some(Err(e)),
+    };
+
+    let mut documents = vec![Document {
+        name: None,
+        breadcrumb: vec![path.to_string()],
+        markdown: schema.index(path),
+    }];
+    for (operation, root) in schema.roots() {
+        for field in &root.fields {
+            let name = format!("{operation} {}", field.name);
+            documents.push(Document {
+                markdown: schema.operation(&name, field, depth),
+                breadcrumb: vec![path.to_string(), root.name.clone(), field.name.clone()],
+                name: Some(name),
+            });
+        }
+    }
+    let roots: Vec<&str> = schema
+        .roots()
+        .iter()
+        .map(|(_, root)| root.name.as_str())
+        .collect();
+    for definition in schema
+        .types
+        .iter()
+        .filter(|t| !roots.contains(&t.name.as_str()))
+    {
+        documents.push(Document {
+            name: Some(definition.name.clone()),
+            breadcrumb: vec![path.to_string(), definition.name.clone()],
+            markdown: schema.definition(definition, depth),
+        });
+    }
+    Some(Ok(documents))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenKind {
+    Name,
+    /// Numbers and other values that are neither names nor strings.
+    Value,
+    Str,
+    Punct,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    kind: TokenKind,
+    line: usize,
+    /// `#` comments on the lines right above the token.
+    comment: Option<String>,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    // Comment lines since the last token, with the line each is on.
+    let mut comments: Vec<(usize, String)> = Vec::new();
+    let (mut i, mut line) = (0, 1);
+    while i < chars.len() {
+        let c = chars[i];
+        let start_line = line;
+        let (text, kind) = if c == '\n' {
+            line += 1;
+            i += 1;
+            continue;
+        } else if c.is_whitespace() || c == ',' || c == '\u{feff}' {
+            i += 1;
+            continue;
+        } else if c == '#' {
+            let start = i + 1;
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            let comment: String = chars[start..i].iter().collect();
+            comments.push((line, comment.trim().to_string()));
+            continue;
+        } else if chars[i..].starts_with(&['"', '"', '"']) {
+            i += 3;
+            let start = i;
+            while i < chars.len() && !chars[i..].starts_with(&['"', '"', '"']) {
+                if chars[i] == '\n' {
+                    line += 1;
+                }
+                if chars[i] == '\\' && chars[i + 1..].starts_with(&['"', '"', '"']) {
+                    i += 3;
+                    continue;
+                }
+                i += 1;
+            }
+            if i >= chars.len() {
+                return Err(format!("line {start_line}: unterminated block string"));
+            }
+            let raw: String = chars[start..i].iter().collect();
+            i += 3;
+            (
+                block_string(&raw.replace("\\\"\"\"", "\"\"\"")),
+                TokenKind::Str,
+            )
+        } else if c == '"' {
+            i += 1;
+            let mut value = String::new();
+            while i < chars.len() && chars[i] != '"' {
+                if chars[i] == '\n' {
+                    return Err(format!("line {line}: unterminated string"));
+                }
+                if chars[i] == '\\' && i + 1 < chars.len() {
+                    i += 1;
+                    value.push(match chars[i] {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        other => other,
+                    });
+                } else {
+                    value.push(chars[i]);
+                }
+                i += 1;
+            }
+            i += 1;
+            (value, TokenKind::Str)
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            (chars[start..i].iter().collect(), TokenKind::Name)
+        } else if c.is_ascii_digit() || c == '-' {
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].is_alphanumeric() || "._+-".contains(chars[i])) {
+                i += 1;
+            }
+            (chars[start..i].iter().collect(), TokenKind::Value)
+        } else if chars[i..].starts_with(&['.', '.', '.']) {
+            i += 3;
+            ("...".to_string(), TokenKind::Punct)
+        } else {
+            i += 1;
+            (c.to_string(), TokenKind::Punct)
+        };
+        // Only the comment block directly above the token documents it.
+        let mut block: Vec<&str> = Vec::new();
+        let mut above = start_line;
+        for (comment_line, comment) in comments.iter().rev() {
+            if *comment_line + 1 != above {
+                break;
+            }
+            block.push(comment);
+            above = *comment_line;
+        }
+        block.reverse();
+        let comment = block.join("\n").trim().to_string();
+        tokens.push(Token {
+            text,
+            kind,
+            line: start_line,
+            comment: (!comment.is_empty()).then_some(comment),
+        });
+        comments.clear();
+    }
+    Ok(tokens)
+}
+
+/// The value of a `"""` block string: common indentation and blank first and last lines
+/// removed.
+fn block_string(raw: &str) -> String {
+    let lines: Vec<&str> = raw.lines().collect();
+    let indent = lines
+        .iter()
+        .skip(1)
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let lines: Vec<&str> = lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| match i {
+            0 => line,
+            _ => line.get(indent..).unwrap_or(line.trim_start()),
+        })
+        .collect();
+    lines.join("\n").trim_matches('\n').trim_end().to_string()
+}
+
+#[derive(Debug, Default)]
+struct Schema {
+    types: Vec<TypeDefinition>,
+    /// Root operation types from a `schema` definition.
+    schema_roots: Vec<(String, String)>,
+}
+
+#[derive(Debug, Default)]
+struct TypeDefinition {
+    /// `type`, `interface`, `input`, `enum`, `union` or `scalar`.
+    kind: String,
+    name: String,
+    description: Option<String>,
+    interfaces: Vec<String>,
+    fields: Vec<Field>,
+    /// Enum values as fields without a type.
+    values: Vec<Field>,
+    members: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Field {
+    name: String,
+    description: Option<String>,
+    arguments: Vec<Field>,
+    /// The type as written (`[Post!]!`); empty for enum values.
+    kind: String,
+    default: Option<String>,
+    /// The reason given by `@deprecated`, empty when none is.
+    deprecated: Option<String>,
+}
+
+impl Field {
+    /// `posts(first: Int = 10): [Post!]!`.
+    fn signature(&self) -> String {
+        let mut signature = self.name.clone();
+        if !self.arguments.is_empty() {
+            let arguments: Vec<String> = self.arguments.iter().map(Field::signature).collect();
+            let _ = write!(signature, "({})", arguments.join(", "));
+        }
+        if !self.kind.is_empty() {
+            let _ = write!(signature, ": {}", self.kind);
+        }
+        if let Some(default) = &self.default {
+            let _ = write!(signature, " = {default}");
+        }
+        signature
+    }
+
+    /// The named type under any list and non-null wrappers.
+    fn type_name(&self) -> &str {
+        self.kind.trim_matches(|c| "[]!".contains(c))
+    }
+
+    fn head(&self) -> String {
+        let mut head = format!("`{}`", self.signature());
+        match self.deprecated.as_deref() {
+            Some("") => head.push_str(" (deprecated)"),
+            Some(reason) => {
+                let _ = write!(head, " (deprecated: {reason})");
+            }
+            None => {}
+        }
+        head
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_is(&self, text: &str) -> bool {
+        self.peek()
+            .is_some_and(|t| t.text == text && t.kind == TokenKind::Punct)
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self.tokens.get(self.pos).cloned().ok_or_else(|| {
+            let line = self.tokens.last().map_or(1, |t| t.line);
+            format!("line {line}: unexpected end of file")
+        })?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, text: &str) -> Result<Token, String> {
+        let token = self.next()?;
+        match token.text == text && token.kind != TokenKind::Str {
+            true => Ok(token),
+            false => Err(format!(
+                "line {}: expected `{text}`, found `{}`",
+                token.line, token.text
+            )),
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let token = self.next()?;
+        match token.kind {
+            TokenKind::Name => Ok(token.text),
+            _ => Err(format!(
+                "line {}: expected a name, found `{}`",
+                token.line, token.text
+            )),
+        }
+    }
+
+    /// A description string, else the comment above the next token.
+    fn description(&mut self) -> Option<String> {
+        let token = self.peek()?;
+        if token.kind == TokenKind::Str {
+            let description = token.text.clone();
+            self.pos += 1;
+            return Some(description);
+        }
+        token.comment.clone()
+    }
+
+    /// The whole schema, or `None` for an executable document.
+    fn schema(mut self) -> Result<Option<Schema>, String> {
+        let mut schema = Schema::default();
+        while self.peek().is_some() {
+            let description = self.description();
+            let keyword = self.next()?;
+            if keyword.kind == TokenKind::Punct && keyword.text == "{" {
+                return Ok(None);
+            }
+            let extend = keyword.text == "extend";
+            let keyword = match extend {
+                true => self.name()?,
+                false => keyword.text,
+            };
+            let definition = match keyword.as_str() {
+                "query" | "mutation" | "subscription" | "fragment" => return Ok(None),
+                "schema" => {
+                    self.directives()?;
+                    self.expect("{")?;
+                    while !self.peek_is("}") {
+                        let operation = self.name()?;
+                        self.expect(":")?;
+                        schema.schema_roots.push((operation, self.name()?));
+                    }
+                    self.expect("}")?;
+                    continue;
+                }
+                "directive" => {
+                    self.expect("@")?;
+                    self.name()?;
+                    if self.peek_is("(") {
+                        self.arguments()?;
+                    }
+                    if self.peek().is_some_and(|t| t.text == "repeatable") {
+                        self.next()?;
+                    }
+                    self.expect("on")?;
+                    if self.peek_is("|") {
+                        self.next()?;
+                    }
+                    self.name()?;
+                    while self.peek_is("|") {
+                        self.next()?;
+                        self.name()?;
+                    }
+                    continue;
+                }
+                "scalar" | "type" | "interface" | "input" | "enum" | "union" => {
+                    self.definition(&keyword, description)?
+                }
+                other => {
+                    return Err(format!(
+                        "line {}: unexpected `{other}`",
+                        self.tokens[self.pos - 1].line
+                    ))
+                }
+            };
+            match schema
+                .types
+                .iter_mut()
+                .find(|t| extend && t.name == definition.name)
+            {
+                Some(existing) => {
+                    existing.interfaces.extend(definition.interfaces);
+                    existing.fields.extend(definition.fields);
+                    existing.values.extend(definition.values);
+                    existing.members.extend(definition.members);
+                }
+                None => schema.types.push(definition),
+            }
+        }
+        Ok(Some(schema))
+    }
+
+    fn definition(
+        &mut self,
+        kind: &str,
+        description: Option<String>,
+    ) -> Result<TypeDefinition, String> {
+        let mut definition = TypeDefinition {
+            kind: kind.to_string(),
+            name: self.name()?,
+            description,
+            ..TypeDefinition::default()
+        };
+        if self.peek().is_some_and(|t| t.text == "implements") {
+            self.next()?;
+            if self.peek_is("&") {
+                self.next()?;
+            }
+            definition.interfaces.push(self.name()?);
+            while self.peek_is("&") {
+                self.next()?;
+                definition.interfaces.push(self.name()?);
+            }
+        }
+        self.directives()?;
+        match kind {
+            "union" if self.peek_is("=") => {
+                self.next()?;
+                if self.peek_is("|") {
+                    self.next()?;
+                }
+                definition.members.push(self.name()?);
+                while self.peek_is("|") {
+                    self.next()?;
+                    definition.members.push(self.name()?);
+                }
+            }
+            "enum" if self.peek_is("{") => {
+                self.next()?;
+                while !self.peek_is("}") {
+                    let description = self.description();
+                    let name = self.name()?;
+                    definition.values.push(Field {
+                        name,
+                        description,
+                        deprecated: self.directives()?,
+                        ..Field::default()
+                    });
+                }
+                self.expect("}")?;
+            }
+            "type" | "interface" | "input" if self.peek_is("{") => {
+                self.next()?;
+                while !self.peek_is("}") {
+                    definition.fields.push(self.field()?);
+                }
+                self.expect("}")?;
+            }
+            _ => {}
+        }
+        Ok(definition)
+    }
+
+    /// A field or argument definition.
+    fn field(&mut self) -> Result<Field, String> {
+        let description = self.description();
+        let name = self.name()?;
+        let arguments = match self.peek_is("(") {
+            true => self.arguments()?,
+            false => Vec::new(),
+        };
+        self.expect(":")?;
+        let kind = self.type_reference()?;
+        let default = match self.peek_is("=") {
+            true => {
+                self.next()?;
+                Some(self.value()?)
+            }
+            false => None,
+        };
+        Ok(Field {
+            name,
+            description,
+            arguments,
+            kind,
+            default,
+            deprecated: self.directives()?,
+        })
+    }
+
+    fn arguments(&mut self) -> Result<Vec<Field>, String> {
+        self.expect("(")?;
+        let mut arguments = Vec::new();
+        while !self.peek_is(")") {
+            arguments.push(self.field()?);
+        }
+        self.expect(")")?;
+        Ok(arguments)
+    }
+
+    fn type_reference(&mut self) -> Result<String, String> {
+        let mut kind = match self.peek_is("[") {
+            true => {
+                self.next()?;
+                let inner = self.type_reference()?;
+                self.expect("]")?;
+                format!("[{inner}]")
+            }
+            false => self.name()?,
+        };
+        if self.peek_is("!") {
+            self.next()?;
+            kind.push('!');
+        }
+        Ok(kind)
+    }
+
+    /// A default or directive argument value, as written.
+    fn value(&mut self) -> Result<String, String> {
+        let token = self.next()?;
+        match (token.kind, token.text.as_str()) {
+            (TokenKind::Str, text) => Ok(format!("{text:?}")),
+            (TokenKind::Punct, "$") => Ok(format!("${}", self.name()?)),
+            (TokenKind::Punct, open @ ("[" | "{")) => {
+                let close = if open == "[" { "]" } else { "}" };
+                let mut parts = Vec::new();
+                while !self.peek_is(close) {
+                    if open == "{" {
+                        parts.push(format!("{}: ", self.name()?));
+                        self.expect(":")?;
+                        let value = self.value()?;
+                        parts.last_mut().expect("pushed").push_str(&value);
+                    } else {
+                        parts.push(self.value()?);
+                    }
+                }
+                self.next()?;
+                Ok(format!("{open}{}{close}", parts.join(", ")))
+            }
+            (TokenKind::Punct, text) => Err(format!(
+                "line {}: unexpected `{text}` in a value",
+                token.line
+            )),
+            (_, text) => Ok(text.to_string()),
+        }
+    }
+
+    /// Skip directives, returning the `@deprecated` reason if one is among them.
+    fn directives(&mut self) -> Result<Option<String>, String> {
+        let mut deprecated = None;
+        while self.peek_is("@") {
+            self.next()?;
+            let name = self.name()?;
+            let mut reason = String::new();
+            if self.peek_is("(") {
+                self.next()?;
+                while !self.peek_is(")") {
+                    let argument = self.name()?;
+                    self.expect(":")?;
+                    let value = self.value()?;
+                    if argument == "reason" {
+                        reason = value.trim_matches('"').to_string();
+                    }
+                }
+                self.next()?;
+            }
+            if name == "deprecated" {
+                deprecated = Some(reason);
+            }
+        }
+        Ok(deprecated)
+    }
+}
+
+impl Schema {
+    /// Root operation types that are defined, with the operation they serve.
+    fn roots(&self) -> Vec<(&'static str, &TypeDefinition)> {
+        ["query", "mutation", "subscription"]
+            .into_iter()
+            .filter_map(|operation| {
+                let name = self
+                    .schema_roots
+                    .iter()
+                    .find(|(op, _)| op == operation)
+                    .map(|(_, name)| name.clone())
+                    .unwrap_or_else(|| {
+                        let mut name = operation.to_string();
+                        name[..1].make_ascii_uppercase();
+                        name
+                    });
+                Some((operation, self.find(&name)?))
+            })
+            .collect()
+    }
+
+    fn find(&self, name: &str) -> Option<&TypeDefinition> {
+        self.types.iter().find(|t| t.name == name)
+    }
+
+    fn index(&self, path: &str) -> String {
+        let mut out = format!("# {path}\n");
+        for (operation, root) in self.roots() {
+            let heading = match operation {
+                "query" => "Queries",
+                "mutation" => "Mutations",
+                _ => "Subscriptions",
+            };
+            let _ = writeln!(out, "\n## {heading}\n");
+            for field in &root.fields {
+                bullet(&mut out, 0, &field.head(), field.description.as_deref());
+            }
+        }
+        let roots: Vec<&str> = self.roots().iter().map(|(_, r)| r.name.as_str()).collect();
+        let types: Vec<&TypeDefinition> = self
+            .types
+            .iter()
+            .filter(|t| !roots.contains(&t.name.as_str()))
+            .collect();
+        if !types.is_empty() {
+            out.push_str("\n## Types\n\n");
+            for definition in types {
+                bullet(
+                    &mut out,
+                    0,
+                    &format!("{} `{}`", definition.kind, definition.name),
+                    definition.description.as_deref(),
+                );
+            }
+        }
+        finish(out)
+    }
+
+    fn operation(&self, name: &str, field: &Field, depth: usize) -> String {
+        let mut out = format!("# {name}\n\n");
+        paragraph(&mut out, field.description.as_deref());
+        let _ = writeln!(out, "- Signature: `{}`", field.signature());
+        match field.deprecated.as_deref() {
+            Some("") => out.push_str("- Deprecated\n"),
+            Some(reason) => {
+                let _ = writeln!(out, "- Deprecated: {reason}");
+            }
+            None => {}
+        }
+        if !field.arguments.is_empty() {
+            out.push_str("\n## Arguments\n\n");
+            self.fields(&mut out, &field.arguments, 0, depth.max(1), &mut Vec::new());
+        }
+        if let Some(result) = self.find(field.type_name()) {
+            let _ = writeln!(out, "\n## Returns {} `{}`\n", result.kind, result.name);
+            paragraph(&mut out, result.description.as_deref());
+            if depth > 0 {
+                self.members(&mut out, result, 0, depth, &mut vec![result.name.as_str()]);
+            }
+        }
+        finish(out)
+    }
+
+    fn definition(&self, definition: &TypeDefinition, depth: usize) -> String {
+        let mut out = format!("# {} {}\n\n", definition.kind, definition.name);
+        paragraph(&mut out, definition.description.as_deref());
+        if !definition.interfaces.is_empty() {
+            let interfaces: Vec<String> = definition
+                .interfaces
+                .iter()
+                .map(|i| format!("`{i}`"))
+                .collect();
+            let _ = writeln!(out, "- Implements: {}\n", interfaces.join(", "));
+        }
+        let heading = match definition.kind.as_str() {
+            "enum" => "Values",
+            "union" => "Members",
+            _ => "Fields",
+        };
+        let mut members = String::new();
+        self.members(
+            &mut members,
+            definition,
+            0,
+            depth.max(1),
+            &mut vec![definition.name.as_str()],
+        );
+        if !members.is_empty() {
+            let _ = write!(out, "## {heading}\n\n{members}");
+        }
+        finish(out)
+    }
+
+    /// List what a type is made of at `level`: its fields, enum values or union members.
+    fn members<'a>(
+        &'a self,
+        out: &mut String,
+        definition: &'a TypeDefinition,
+        level: usize,
+        depth: usize,
+        seen: &mut Vec<&'a str>,
+    ) {
+        self.fields(out, &definition.fields, level, depth, seen);
+        for value in &definition.values {
+            bullet(out, level, &value.head(), value.description.as_deref());
+        }
+        for member in &definition.members {
+            let description = self.find(member).and_then(|m| m.description.as_deref());
+            bullet(out, level, &format!("`{member}`"), description);
+        }
+    }
+
+    /// List `fields` at `level`, and what their types are made of while `depth` allows;
+    /// `seen` stops recursive types.
+    fn fields<'a>(
+        &'a self,
+        out: &mut String,
+        fields: &'a [Field],
+        level: usize,
+        depth: usize,
+        seen: &mut Vec<&'a str>,
+    ) {
+        for field in fields {
+            bullet(out, level, &field.head(), field.description.as_deref());
+            if depth <= 1 || seen.contains(&field.type_name()) {
+                continue;
+            }
+            if let Some(nested) = self.find(field.type_name()) {
+                seen.push(&nested.name);
+                self.members(out, nested, level + 1, depth - 1, seen);
+                seen.pop();
+            }
+        }
+    }
+}
@@ -0,0 +1,435 @@
+//! OpenAPI 3 and Swagger 2 documents.
+
+use std::fmt::Write as _;
+
+use serde_json::Value;
+
+use super::{bullet, finish, one_line, paragraph, Document};
+
+/// Operation methods in the order they are listed under a path.
+const METHODS: [&str; 8] = [
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+/// Most enum values named in a schema label.
+const MAX_ENUM_VALUES: usize = 10;
+
+/// Renderings of an OpenAPI or Swagger document, or `None` when `text` is not one.
+pub(super) fn render(text: &str, depth: usize) -> Option<Result<Vec<Document>, String>> {
+    if !(text.contains("openapi") || text.contains("swagger")) {
+        return None;
+    }
+    let doc: Value = serde_yaml::from_str(text).ok()?;
+    let version = ["openapi", "swagger"]
+        .iter()
+        .find_map(|key| doc.get(key))
+        .map(|version| match version {
+            Value::String(version) => version.clone(),
+            other => other.to_string(),
+        })?;
+    let Some(paths) = doc.get("paths").and_then(Value::as_object) else {
+        return Some(Err("no `paths` object".to_string()));
+    };
+    let spec = Spec { doc: &doc, depth };
+    let title = format!(
+        "{} {}",
+        doc["info"]["title"].as_str().unwrap_or("API"),
+        doc["info"]["version"].as_str().unwrap_or_default()
+    )
+    .trim()
+    .to_string();
+
+    let mut operations = Vec::new();
+    for (path, item) in paths {
+        let (_, item) = spec.resolve(item);
+        for method in METHODS {
+            if let Some(operation) = item.get(method).filter(|op| op.is_object()) {
+                operations.push((path.as_str(), method, item, operation));
+            }
+        }
+    }
+
+    let mut documents = vec![Document {
+        name: None,
+        breadcrumb: vec![title.clone()],
+        markdown: spec.index(&title, &version, &operations),
+    }];
+    for (path, method, item, operation) in operations {
+        let name = format!("{} {path}", method.to_uppercase());
+        documents.push(Document {
+            markdown: spec.operation(&name, &title, item, operation),
+            breadcrumb: vec![title.clone(), name.clone()],
+            name: Some(name),
+        });
+    }
+    Some(Ok(documents))
+}
+
+/// A parsed document and how deep to list nested schemas.
+struct Spec<'a> {
+    doc: &'a Value,
+    depth: usize,
+}
+
+impl<'a> Spec<'a> {
+    /// Follow local `$ref`s: the name of the last schema referred to, and its definition.
+    fn resolve(&self, mut value: &'a Value) -> (Option<&'a str>, &'a Value) {
+        let mut name = None;
+        for _ in 0..16 {
+            let Some(reference) = value.get("$ref").and_then(Value::as_str) else {
+                break;
+            };
+            let Some(target) = reference
+                .strip_prefix('#')
+                .and_then(|pointer| self.doc.pointer(pointer))
+            else {
+                break;
+            };
+            name = reference.rsplit('/').next();
+            value = target;
+        }
+        (name, value)
+    }
+
+    /// The file's index: description, servers, operations and schemas.
+    fn index(
+        &self,
+        title: &str,
+        version: &str,
+        operations: &[(&str, &str, &Value, &Value)],
+    ) -> String {
+        let doc = self.doc;
+        let mut out = format!("# {title}\n\n");
+        paragraph(&mut out, doc["info"]["description"].as_str());
+        let format = match doc.get("openapi") {
+            Some(_) => "OpenAPI",
+            None => "Swagger",
+        };
+        let _ = writeln!(out, "- {format} {version}");
+        let servers: Vec<String> = match doc.get("servers").and_then(Value::as_array) {
+            Some(servers) => servers
+                .iter()
+                .filter_map(|server| server["url"].as_str())
+                .map(|url| format!("`{url}`"))
+                .collect(),
+            None => doc["host"]
+                .as_str()
+                .map(|host| format!("`{host}{}`", doc["basePath"].as_str().unwrap_or_default()))
+                .into_iter()
+                .collect(),
+        };
+        if !servers.is_empty() {
+            let _ = writeln!(out, "- Servers: {}", servers.join(", "));
+        }
+
+        if !operations.is_empty() {
+            out.push_str("\n## Operations\n\n");
+            for (path, method, _, operation) in operations {
+                let summary = operation["summary"]
+                    .as_str()
+                    .or(operation["description"].as_str());
+                bullet(
+                    &mut out,
+                    0,
+                    &format!("`{} {path}`", method.to_uppercase()),
+                    summary,
+                );
+            }
+        }
+
+        let schemas = doc
+            .pointer("/components/schemas")
+            .or(doc.get("definitions"))
+            .and_then(Value::as_object);
+        if let Some(schemas) = schemas.filter(|schemas| !schemas.is_empty()) {
+            out.push_str("\n## Schemas\n\n");
+            for (name, schema) in schemas {
+                let (_, resolved) = self.resolve(schema);
+                bullet(
+                    &mut out,
+                    0,
+                    &format!("`{name}` ({})", self.shape(resolved)),
+                    resolved["description"].as_str(),
+                );
+            }
+        }
+        finish(out)
+    }
+
+    /// One operation: summary, parameters, request body and responses.
+    fn operation(&self, name: &str, title: &str, item: &'a Value, operation: &'a Value) -> String {
+        let mut out = format!("# {name}\n\n");
+        paragraph(&mut out, operation["summary"].as_str());
+        paragraph(&mut out, operation["description"].as_str());
+        let _ = writeln!(out, "- API: {title}");
+        if let Some(id) = operation["operationId"].as_str() {
+            let _ = writeln!(out, "- Operation ID: `{id}`");
+        }
+        let tags: Vec<&str> = operation["tags"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .collect();
+        if !tags.is_empty() {
+            let _ = writeln!(out, "- Tags: {}", tags.join(", "));
+        }
+        if operation["deprecated"].as_bool() == Some(true) {
+            out.push_str("- Deprecated\n");
+        }
+
+        // Path-level parameters apply unless the operation redefines them.
+        let mut parameters: Vec<&Value> = Vec::new();
+        for parameter in [&operation["parameters"], &item["parameters"]]
+            .into_iter()
+            .filter_map(Value::as_array)
+            .flatten()
+        {
+            let (_, parameter) = self.resolve(parameter);
+            let key = |p: &Value| (p["name"].clone(), p["in"].clone());
+            if !parameters.iter().any(|p| key(p) == key(parameter)) {
+                parameters.push(parameter);
+            }
+        }
+        let (body, parameters): (Vec<&Value>, Vec<&Value>) = parameters
+            .into_iter()
+            .partition(|parameter| parameter["in"] == "body");
+
+        if !parameters.is_empty() {
+            out.push_str("\n## Parameters\n\n");
+            for parameter in parameters {
+                let schema = parameter.get("schema").unwrap_or(parameter);
+                let mut head = format!(
+                    "`{}` ({}, {}",
+                    parameter["name"].as_str().unwrap_or_default(),
+                    parameter["in"].as_str().unwrap_or_default(),
+                    self.label(schema)
+                );
+                if parameter["required"].as_bool() == Some(true) {
+                    head.push_str(", required");
+                }
+                head.push(')');
+                bullet(&mut out, 0, &head, parameter["description"].as_str());
+            }
+        }
+
+        let consumes = media_types(operation, self.doc, "consumes");
+        let request = match body.first() {
+            Some(parameter) => Some((
+                parameter["description"].as_str(),
+                parameter["required"].as_bool() == Some(true),
+                vec![(consumes, &parameter["schema"])],
+            )),
+            None => operation.get("requestBody").map(|body| {
+                let (_, body) = self.resolve(body);
+                (
+                    body["description"].as_str(),
+                    body["required"].as_bool() == Some(true),
+                    content(body),
+                )
+            }),
+        };
+        if let Some((description, required, content)) = request {
+            out.push_str("\n## Request body\n\n");
+            paragraph(&mut out, description);
+            self.content(&mut out, &content, required);
+        }
+
+        if let Some(responses) = operation["responses"].as_object() {
+            out.push_str("\n## Responses\n");
+            let produces = media_types(operation, self.doc, "produces");
+            for (status, response) in responses {
+                let (_, response) = self.resolve(response);
+                let _ = write!(out, "\n### {status}");
+                match response["description"].as_str().map(one_line) {
+                    Some(description) if !description.is_empty() => {
+                        let _ = writeln!(out, ": {description}");
+                    }
+                    _ => out.push('\n'),
+                }
+                let content = match response.get("schema") {
+                    Some(schema) => vec![(produces.clone(), schema)],
+                    None => content(response),
+                };
+                self.content(&mut out, &content, false);
+            }
+        }
+        finish(out)
+    }
+
+    /// The schemas of a body, each under the media types that share it.
+    fn content(&self, out: &mut String, content: &[(Vec<String>, &'a Value)], required: bool) {
+        for (media_types, schema) in content {
+            let media_types: Vec<String> = media_types.iter().map(|t| format!("`{t}`")).collect();
+            let mut head = self.label(schema);
+            if required {
+                head.push_str(", required");
+            }
+            let mut fields = String::new();
+            if self.depth > 0 {
+                self.fields(&mut fields, schema, 0, self.depth, &mut Vec::new());
+            }
+            if !out.ends_with("\n\n") {
+                out.push('\n');
+            }
+            let _ = match media_types.is_empty() {
+                true => writeln!(out, "Body: {head}"),
+                false if fields.is_empty() => writeln!(out, "{} ({head})", media_types.join(", ")),
+                false => writeln!(out, "{} ({head}):", media_types.join(", ")),
+            };
+            if !fields.is_empty() {
+                out.push('\n');
+                out.push_str(&fields);
+            }
+        }
+    }
+
+    /// A short description of a schema: its name when it has one, else its shape.
+    fn label(&self, schema: &'a Value) -> String {
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            return match self.resolve(schema) {
+                (Some(name), _) => format!("`{name}`"),
+                (None, _) => format!("`{reference}`"),
+            };
+        }
+        self.shape(schema)
+    }
+
+    /// What a schema is made of: `array of `Pet``, `string (date-time)`, `one of ...`.
+    fn shape(&self, schema: &'a Value) -> String {
+        let labels = |key: &str| -> Option<Vec<String>> {
+            let variants = schema.get(key)?.as_array()?;
+            Some(variants.iter().map(|s| self.label(s)).collect())
+        };
+        let mut shape = if let Some(items) = schema.get("items") {
+            format!("array of {}", self.label(items))
+        } else if let Some(variants) = labels("oneOf").or_else(|| labels("anyOf")) {
+            format!("one of {}", variants.join(" | "))
+        } else if let Some(parts) = labels("allOf") {
+            format!("all of {}", parts.join(" & "))
+        } else if let Some(values) = schema.get("additionalProperties").filter(|v| v.is_object()) {
+            format!("map of {}", self.label(values))
+        } else {
+            let types: Vec<&str> = match &schema["type"] {
+                Value::String(kind) => vec![kind.as_str()],
+                Value::Array(kinds) => kinds.iter().filter_map(Value::as_str).collect(),
+                _ if schema.get("properties").is_some() => vec!["object"],
+                _ => vec!["any"],
+            };
+            let mut shape = types.join(" or ");
+            if let Some(format) = schema["format"].as_str() {
+                let _ = write!(shape, " ({format})");
+            }
+            shape
+        };
+        if let Some(values) = schema["enum"].as_array() {
+            let mut names: Vec<String> = values
+                .iter()
+                .take(MAX_ENUM_VALUES)
+                .map(|value| match value {
+                    Value::String(value) => format!("`{value}`"),
+                    value => format!("`{value}`"),
+                })
+                .collect();
+            if values.len() > MAX_ENUM_VALUES {
+                names.push("...".to_string());
+            }
+            let _ = write!(shape, ", one of {}", names.join(", "));
+        }
+        if schema["nullable"].as_bool() == Some(true) {
+            shape.push_str(", nullable");
+        }
+        shape
+    }
+
+    /// List the properties of `schema` at `level`, and theirs while `depth` allows.
+    /// `seen` holds the named schemas being listed, so recursive ones stop.
+    fn fields(
+        &self,
+        out: &mut String,
+        schema: &'a Value,
+        level: usize,
+        depth: usize,
+        seen: &mut Vec<&'a str>,
+    ) {
+        let (name, schema) = self.resolve(schema);
+        if let Some(name) = name {
+            if seen.contains(&name) {
+                return;
+            }
+            seen.push(name);
+        }
+        if let Some(items) = schema.get("items") {
+            self.fields(out, items, level, depth, seen);
+        }
+        for part in schema["allOf"].as_array().into_iter().flatten() {
+            self.fields(out, part, level, depth, seen);
+        }
+        for key in ["oneOf", "anyOf"] {
+            for variant in schema[key].as_array().into_iter().flatten() {
+                let (_, resolved) = self.resolve(variant);
+                bullet(
+                    out,
+                    level,
+                    &format!("variant {}", self.label(variant)),
+                    resolved["description"].as_str(),
+                );
+                if depth > 1 {
+                    self.fields(out, variant, level + 1, depth - 1, seen);
+                }
+            }
+        }
+        let required: Vec<&str> = schema["required"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .collect();
+        for (property, definition) in schema["properties"].as_object().into_iter().flatten() {
+            let mut head = format!("`{property}` ({}", self.label(definition));
+            if required.contains(&property.as_str()) {
+                head.push_str(", required");
+            }
+            head.push(')');
+            let description = definition["description"]
+                .as_str()
+                .or_else(|| self.resolve(definition).1["description"].as_str());
+            bullet(out, level, &head, description);
+            if depth > 1 {
+                self.fields(out, definition, level + 1, depth - 1, seen);
+            }
+        }
+        if name.is_some() {
+            seen.pop();
+        }
+    }
+}
+
+/// Media types and schemas of an OpenAPI 3 request body or response, grouping media
+/// types that share a schema.
+fn content(body: &Value) -> Vec<(Vec<String>, &Value)> {
+    let mut content: Vec<(Vec<String>, &Value)> = Vec::new();
+    for (media_type, media) in body["content"].as_object().into_iter().flatten() {
+        let Some(schema) = media.get("schema") else {
+            content.push((vec![media_type.clone()], &Value::Null));
+            continue;
+        };
+        match content.iter_mut().find(|(_, s)| *s == schema) {
+            Some((types, _)) => types.push(media_type.clone()),
+            None => content.push((vec![media_type.clone()], schema)),
+        }
+    }
+    content
+}
+
+/// Swagger 2 `consumes` or `produces` of an operation, or of the document.
+fn media_types(operation: &Value, doc: &Value, key: &str) -> Vec<String> {
+    operation[key]
+        .as_array()
+        .or(doc[key].as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(str::to_string)
+        .collect()
+}
@@ -0,0 +1,755 @@
+//! Protocol buffer definitions (`.proto`), proto2, proto3 and editions.
+//!
+//! A small parser reads the declarations that describe an API (messages, enums, services
+//! and their fields, values and RPCs) and skips options, imports, reservations and
+//! extensions. Comments on the lines right above a declaration, or after it on the same
+//! line, document it.
+
+use std::fmt::Write as _;
+
+use super::{bullet, finish, paragraph, Document};
+
+/// Renderings of a `.proto` file: its index, then one per RPC, message and enum.
+pub(super) fn render(path: &str, text: &str, depth: usize) -> Result<Vec<Document>, String> {
+    let file = Parser {
+        tokens: tokenize(text)?,
+        pos: 0,
+    }
+    .file()?;
+    let title = file.package.clone().unwrap_or_else(|| path.to_string());
+
+    let mut documents = vec![Document {
+        name: None,
+        breadcrumb: vec![title.clone()],
+        markdown: file.index(path),
+    }];
+    for service in &file.services {
+        for rpc in &service.rpcs {
+            let name = format!("{}.{}", service.name, rpc.name);
+            documents.push(Document {
+                markdown: file.rpc(service, rpc, depth),
+                breadcrumb: vec![title.clone(), service.name.clone(), rpc.name.clone()],
+                name: Some(name),
+            });
+        }
+    }
+    for message in &file.messages {
+        documents.push(Document {
+            name: Some(message.name.clone()),
+            breadcrumb: vec![title.clone(), message.name.clone()],
+            markdown: file.message(message, depth),
+        });
+    }
+    for definition in &file.enums {
+        documents.push(Document {
+            name: Some(definition.name.clone()),
+            breadcrumb: vec![title.clone(), definition.name.clone()],
+            markdown: file.enumeration(definition),
+        });
+    }
+    Ok(documents)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenKind {
+    /// Identifiers, dotted names, keywords and numbers.
+    Word,
+    Str,
+    Punct,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    kind: TokenKind,
+    line: usize,
+    /// Comments on the lines right above the token.
+    doc: Option<String>,
+    /// A comment after the token on its line.
+    trailing: Option<String>,
+}
+
+struct Comment {
+    text: String,
+    start_line: usize,
+    end_line: usize,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut comments: Vec<Comment> = Vec::new();
+    let (mut i, mut line) = (0, 1);
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\n' {
+            line += 1;
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && chars.get(i + 1) == Some(&'/') {
+            let start = i;
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            let comment: String = chars[start..i].iter().collect();
+            let comment = comment.trim_start_matches('/');
+            let comment = Comment {
+                text: comment
+                    .strip_prefix(' ')
+                    .unwrap_or(comment)
+                    .trim_end()
+                    .to_string(),
+                start_line: line,
+                end_line: line,
+            };
+            match tokens.last_mut() {
+                Some(token) if token.line == line && comments.is_empty() => {
+                    token.trailing = Some(comment.text);
+                }
+                _ => comments.push(comment),
+            }
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            let (start, start_line) = (i + 2, line);
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                if chars[i] == '\n' {
+                    line += 1;
+                }
+                i += 1;
+            }
+            if i >= chars.len() {
+                return Err(format!("line {start_line}: unterminated comment"));
+            }
+            let body: String = chars[start..i].iter().collect();
+            i += 2;
+            let text = body
+                .lines()
+                .map(|l| {
+                    let l = l.trim();
+                    let l = l.strip_prefix('*').unwrap_or(l);
+                    l.strip_prefix(' ').unwrap_or(l)
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+                .trim()
+                .trim_start_matches('*')
+                .trim()
+                .to_string();
+            comments.push(Comment {
+                text,
+                start_line,
+                end_line: line,
+            });
+        } else {
+            let start = i;
+            let kind = if c == '"' || c == '\'' {
+                i += 1;
+                while i < chars.len() && chars[i] != c {
+                    if chars[i] == '\\' {
+                        i += 1;
+                    }
+                    if chars.get(i) == Some(&'\n') {
+                        return Err(format!("line {line}: unterminated string"));
+                    }
+                    i += 1;
+                }
+                i += 1;
+                TokenKind::Str
+            } else if c.is_alphanumeric() || c == '_' || c == '.' {
+                while i < chars.len() && (chars[i].is_alphanumeric() || "_.".contains(chars[i])) {
+                    i += 1;
+                }
+                TokenKind::Word
+            } else {
+                i += 1;
+                TokenKind::Punct
+            };
+            let text: String = chars[start..i.min(chars.len())].iter().collect();
+            tokens.push(Token {
+                text,
+                kind,
+                line,
+                doc: leading_doc(&comments, line),
+                trailing: None,
+            });
+            comments.clear();
+        }
+    }
+    Ok(tokens)
+}
+
+/// The block of comments ending on the line above `line` (or on it), joined.
+fn leading_doc(comments: &[Comment], line: usize) -> Option<String> {
+    let last = comments.last().filter(|c| c.end_line + 1 >= line)?;
+    let mut start = comments.len() - 1;
+    let mut first_line = last.start_line;
+    while start > 0 && comments[start - 1].end_line + 1 >= first_line {
+        start -= 1;
+        first_line = comments[start].start_line;
+    }
+    let doc = comments[start..]
+        .iter()
+        .map(|c| c.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string();
+    (!doc.is_empty()).then_some(doc)
+}
+
+#[derive(Debug, Default)]
+struct ProtoFile {
+    syntax: Option<String>,
+    package: Option<String>,
+    messages: Vec<Message>,
+    enums: Vec<Enum>,
+    services: Vec<Service>,
+}
+
+#[derive(Debug, Default)]
+struct Message {
+    /// Name within the package, with enclosing messages (`Invoice.Line`).
+    name: String,
+    doc: Option<String>,
+    fields: Vec<Field>,
+}
+
+#[derive(Debug)]
+struct Field {
+    name: String,
+    number: String,
+    /// `repeated`, `optional` or `required`, when given.
+    label: Option<String>,
+    /// The type as written (`string`, `Invoice.Line`, `map<string, Price>`).
+    kind: String,
+    /// The message or enum type to look up: the type, or a map's value type.
+    type_name: String,
+    oneof: Option<String>,
+    doc: Option<String>,
+}
+
+#[derive(Debug)]
+struct Enum {
+    name: String,
+    doc: Option<String>,
+    values: Vec<(String, String, Option<String>)>,
+}
+
+#[derive(Debug)]
+struct Service {
+    name: String,
+    doc: Option<String>,
+    rpcs: Vec<Rpc>,
+}
+
+#[derive(Debug)]
+struct Rpc {
+    name: String,
+    doc: Option<String>,
+    request: String,
+    request_stream: bool,
+    response: String,
+    response_stream: bool,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|t| t.text.as_str())
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self.tokens.get(self.pos).cloned().ok_or_else(|| {
+            let line = self.tokens.last().map_or(1, |t| t.line);
+            format!("line {line}: unexpected end of file")
+        })?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, text: &str) -> Result<Token, String> {
+        let token = self.next()?;
+        match token.text == text {
+            true => Ok(token),
+            false => Err(format!(
+                "line {}: expected `{text}`, found `{}`",
+                token.line, token.text
+            )),
+        }
+    }
+
+    fn name(&mut self) -> Result<Token, String> {
+        let token = self.next()?;
+        match token.kind {
+            TokenKind::Word => Ok(token),
+            _ => Err(format!(
+                "line {}: expected a name, found `{}`",
+                token.line, token.text
+            )),
+        }
+    }
+
+    /// Skip to the end of a statement, past nested brackets and braces.
+    fn skip_statement(&mut self) -> Result<(), String> {
+        let mut depth = 0usize;
+        loop {
+            let token = self.next()?;
+            match token.text.as_str() {
+                "{" | "[" | "(" | "<" => depth += 1,
+                "}" | "]" | ")" | ">" => depth = depth.saturating_sub(1),
+                ";" if depth == 0 => return Ok(()),
+                _ => {}
+            }
+        }
+    }
+
+    /// Skip to the end of the next `{ ... }` block.
+    fn skip_block(&mut self) -> Result<(), String> {
+        while self.next()?.text != "{" {}
+        let mut depth = 1;
+        while depth > 0 {
+            match self.next()?.text.as_str() {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Skip `[ ... ]` field options, if any.
+    fn skip_options(&mut self) -> Result<(), String> {
+        if self.peek() == Some("[") {
+            let mut depth = 0;
+            loop {
+                match self.next()?.text.as_str() {
+                    "[" => depth += 1,
+                    "]" if depth == 1 => return Ok(()),
+                    "]" => depth -= 1,
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn file(mut self) -> Result<ProtoFile, String> {
+        let mut file = ProtoFile::default();
+        while let Some(keyword) = self.peek() {
+            match keyword {
+                "syntax" | "edition" => {
+                    let keyword = self.next()?.text;
+                    self.expect("=")?;
+                    let value = self.next()?.text;
+                    let value = value.trim_matches(|c| c == '"' || c == '\'');
+                    file.syntax = Some(match keyword.as_str() {
+                        "edition" => format!("edition {value}"),
+                        _ => value.to_string(),
+                    });
+                    self.expect(";")?;
+                }
+                "package" => {
+                    self.next()?;
+                    file.package = Some(self.name()?.text);
+                    self.expect(";")?;
+                }
+                "import" | "option" => self.skip_statement()?,
+                "message" => self.message("", &mut file)?,
+                "enum" => self.enumeration("", &mut file)?,
+                "service" => self.service(&mut file)?,
+                "extend" => self.skip_block()?,
+                ";" => {
+                    self.next()?;
+                }
+                _ => {
+                    let token = self.next()?;
+                    return Err(format!("line {}: unexpected `{}`", token.line, token.text));
+                }
+            }
+        }
+        Ok(file)
+    }
+
+    fn message(&mut self, scope: &str, file: &mut ProtoFile) -> Result<(), String> {
+        let doc = self.expect("message")?.doc;
+        let name = scoped(scope, &self.name()?.text);
+        self.expect("{")?;
+        // Listed before the messages nested in it.
+        let index = file.messages.len();
+        file.messages.push(Message::default());
+        let mut fields = Vec::new();
+        loop {
+            match self.peek() {
+                Some("}") => {
+                    self.next()?;
+                    break;
+                }
+                Some("message") => self.message(&name, file)?,
+                Some("enum") => self.enumeration(&name, file)?,
+                Some("oneof") => {
+                    self.next()?;
+                    let oneof = self.name()?.text;
+                    self.expect("{")?;
+                    loop {
+                        match self.peek() {
+                            Some("}") => {
+                                self.next()?;
+                                break;
+                            }
+                            Some("option") => self.skip_statement()?,
+                            Some(";") => {
+                                self.next()?;
+                            }
+                            _ => fields.extend(self.field(Some(&oneof))?),
+                        }
+                    }
+                }
+                Some("option" | "reserved" | "extensions") => self.skip_statement()?,
+                Some("extend") => self.skip_block()?,
+                Some(";") => {
+                    self.next()?;
+                }
+                _ => fields.extend(self.field(None)?),
+            }
+        }
+        file.messages[index] = Message { name, doc, fields };
+        Ok(())
+    }
+
+    /// A field, or `None` for a proto2 group, which is skipped.
+    fn field(&mut self, oneof: Option<&str>) -> Result<Option<Field>, String> {
+        let first = self.name()?;
+        let doc = first.doc.clone();
+        let (label, mut kind) = match first.text.as_str() {
+            "repeated" | "optional" | "required" => (Some(first.text), self.name()?),
+            _ => (None, first),
+        };
+        if kind.text == "group" {
+            self.skip_block()?;
+            return Ok(None);
+        }
+        let mut type_name = kind.text.clone();
+        if kind.text == "map" {
+            self.expect("<")?;
+            let key = self.name()?.text;
+            self.expect(",")?;
+            type_name = self.name()?.text;
+            self.expect(">")?;
+            kind.text = format!("map<{key}, {type_name}>");
+        }
+        let name = self.name()?.text;
+        self.expect("=")?;
+        let number = self.next()?.text;
+        self.skip_options()?;
+        let end = self.expect(";")?;
+        Ok(Some(Field {
+            name,
+            number,
+            label,
+            kind: kind.text,
+            type_name,
+            oneof: oneof.map(str::to_string),
+            doc: doc.or(end.trailing),
+        }))
+    }
+
+    fn enumeration(&mut self, scope: &str, file: &mut ProtoFile) -> Result<(), String> {
+        let doc = self.expect("enum")?.doc;
+        let name = scoped(scope, &self.name()?.text);
+        self.expect("{")?;
+        let mut values = Vec::new();
+        loop {
+            match self.peek() {
+                Some("}") => {
+                    self.next()?;
+                    break;
+                }
+                Some("option" | "reserved") => self.skip_statement()?,
+                Some(";") => {
+                    self.next()?;
+                }
+                _ => {
+                    let value = self.name()?;
+                    self.expect("=")?;
+                    let mut number = self.next()?.text;
+                    if number == "-" {
+                        number.push_str(&self.next()?.text);
+                    }
+                    self.skip_options()?;
+                    let end = self.expect(";")?;
+                    values.push((value.text, number, value.doc.or(end.trailing)));
+                }
+            }
+        }
+        file.enums.push(Enum { name, doc, values });
+        Ok(())
+    }
+
+    fn service(&mut self, file: &mut ProtoFile) -> Result<(), String> {
+        let doc = self.expect("service")?.doc;
+        let name = self.name()?.text;
+        self.expect("{")?;
+        let mut rpcs = Vec::new();
+        loop {
+            match self.peek() {
+                Some("}") => {
+                    self.next()?;
+                    break;
+                }
+                Some("option") => self.skip_statement()?,
+                Some(";") => {
+                    self.next()?;
+                }
+                _ => {
+                    let doc = self.expect("rpc")?.doc;
+                    let name = self.name()?.text;
+                    let (request_stream, request) = self.rpc_type()?;
+                    self.expect("returns")?;
+                    let (response_stream, response) = self.rpc_type()?;
+                    let trailing = match self.peek() {
+                        Some("{") => {
+                            let trailing = self.tokens[self.pos].trailing.clone();
+                            self.skip_block()?;
+                            if self.peek() == Some(";") {
+                                self.next()?;
+                            }
+                            trailing
+                        }
+                        _ => self.expect(";")?.trailing,
+                    };
+                    rpcs.push(Rpc {
+                        name,
+                        doc: doc.or(trailing),
+                        request,
+                        request_stream,
+                        response,
+                        response_stream,
+                    });
+                }
+            }
+        }
+        file.services.push(Service { name, doc, rpcs });
+        Ok(())
+    }
+
+    /// `( [stream] Type )`.
+    fn rpc_type(&mut self) -> Result<(bool, String), String> {
+        self.expect("(")?;
+        let mut name = self.name()?.text;
+        let stream = name == "stream" && self.peek() != Some(")");
+        if stream {
+            name = self.name()?.text;
+        }
+        self.expect(")")?;
+        Ok((stream, name))
+    }
+}
+
+fn scoped(scope: &str, name: &str) -> String {
+    match scope.is_empty() {
+        true => name.to_string(),
+        false => format!("{scope}.{name}"),
+    }
+}
+
+impl ProtoFile {
+    /// The fully qualified name of a declaration.
+    fn full_name(&self, name: &str) -> String {
+        match &self.package {
+            Some(package) => format!("{package}.{name}"),
+            None => name.to_string(),
+        }
+    }
+
+    /// Resolve a type name as protobuf does: in the scope it is used from, then in each
+    /// enclosing scope. Types from other files are not found.
+    fn find<'a, T>(
+        &self,
+        items: &'a [T],
+        item_name: impl Fn(&T) -> &str,
+        scope: &str,
+        name: &str,
+    ) -> Option<&'a T> {
+        let mut name = name.trim_start_matches('.');
+        if let Some(package) = &self.package {
+            name = name
+                .strip_prefix(package.as_str())
+                .and_then(|rest| rest.strip_prefix('.'))
+                .unwrap_or(name);
+        }
+        let mut scope = scope;
+        loop {
+            let candidate = scoped(scope, name);
+            if let Some(item) = items.iter().find(|item| item_name(item) == candidate) {
+                return Some(item);
+            }
+            if scope.is_empty() {
+                return None;
+            }
+            scope = scope.rsplit_once('.').map_or("", |(outer, _)| outer);
+        }
+    }
+
+    fn find_message(&self, scope: &str, name: &str) -> Option<&Message> {
+        self.find(&self.messages, |m| m.name.as_str(), scope, name)
+    }
+
+    fn find_enum(&self, scope: &str, name: &str) -> Option<&Enum> {
+        self.find(&self.enums, |e| e.name.as_str(), scope, name)
+    }
+
+    fn index(&self, path: &str) -> String {
+        let mut out = format!("# {path}\n\n");
+        if let Some(package) = &self.package {
+            let _ = writeln!(out, "- Package: `{package}`");
+        }
+        if let Some(syntax) = &self.syntax {
+            let _ = writeln!(out, "- Syntax: {syntax}");
+        }
+        if !self.services.is_empty() {
+            out.push_str("\n## Services\n\n");
+            for service in &self.services {
+                bullet(
+                    &mut out,
+                    0,
+                    &format!("`{}`", service.name),
+                    service.doc.as_deref(),
+                );
+                for rpc in &service.rpcs {
+                    bullet(
+                        &mut out,
+                        1,
+                        &format!("`{}`", signature(rpc)),
+                        rpc.doc.as_deref(),
+                    );
+                }
+            }
+        }
+        for (heading, names) in [
+            (
+                "Messages",
+                self.messages
+                    .iter()
+                    .map(|m| (&m.name, &m.doc))
+                    .collect::<Vec<_>>(),
+            ),
+            (
+                "Enums",
+                self.enums.iter().map(|e| (&e.name, &e.doc)).collect(),
+            ),
+        ] {
+            if !names.is_empty() {
+                let _ = writeln!(out, "\n## {heading}\n");
+                for (name, doc) in names {
+                    bullet(&mut out, 0, &format!("`{name}`"), doc.as_deref());
+                }
+            }
+        }
+        finish(out)
+    }
+
+    fn rpc(&self, service: &Service, rpc: &Rpc, depth: usize) -> String {
+        let mut out = format!("# rpc {}.{}\n\n", self.full_name(&service.name), rpc.name);
+        paragraph(&mut out, rpc.doc.as_deref());
+        let _ = writeln!(out, "- Signature: `{}`", signature(rpc));
+        bullet(
+            &mut out,
+            0,
+            &format!("Service: `{}`", self.full_name(&service.name)),
+            service.doc.as_deref(),
+        );
+        for (heading, name) in [("Request", &rpc.request), ("Response", &rpc.response)] {
+            let Some(message) = self.find_message("", name) else {
+                continue;
+            };
+            if !out.ends_with("\n\n") {
+                out.push('\n');
+            }
+            let _ = writeln!(out, "## {heading} `{}`\n", message.name);
+            paragraph(&mut out, message.doc.as_deref());
+            if depth > 0 {
+                self.fields(&mut out, message, 0, depth, &mut Vec::new());
+            }
+        }
+        finish(out)
+    }
+
+    fn message(&self, message: &Message, depth: usize) -> String {
+        let mut out = format!("# message {}\n\n", self.full_name(&message.name));
+        paragraph(&mut out, message.doc.as_deref());
+        if !message.fields.is_empty() {
+            out.push_str("## Fields\n\n");
+            self.fields(&mut out, message, 0, depth.max(1), &mut Vec::new());
+        }
+        finish(out)
+    }
+
+    fn enumeration(&self, definition: &Enum) -> String {
+        let mut out = format!("# enum {}\n\n", self.full_name(&definition.name));
+        paragraph(&mut out, definition.doc.as_deref());
+        if !definition.values.is_empty() {
+            out.push_str("## Values\n\n");
+            values(&mut out, definition, 0);
+        }
+        finish(out)
+    }
+
+    /// List the fields of `message` at `level`, and those of message and enum fields
+    /// while `depth` allows; `seen` stops recursive messages.
+    fn fields<'a>(
+        &'a self,
+        out: &mut String,
+        message: &'a Message,
+        level: usize,
+        depth: usize,
+        seen: &mut Vec<&'a str>,
+    ) {
+        seen.push(&message.name);
+        for field in &message.fields {
+            let mut head = String::from("`");
+            if let Some(label) = &field.label {
+                let _ = write!(head, "{label} ");
+            }
+            let _ = write!(head, "{} {} = {}`", field.kind, field.name, field.number);
+            if let Some(oneof) = &field.oneof {
+                let _ = write!(head, " (one of `{oneof}`)");
+            }
+            bullet(out, level, &head, field.doc.as_deref());
+            if depth <= 1 {
+                continue;
+            }
+            if let Some(nested) = self.find_message(&message.name, &field.type_name) {
+                if !seen.contains(&nested.name.as_str()) {
+                    self.fields(out, nested, level + 1, depth - 1, seen);
+                }
+            } else if let Some(nested) = self.find_enum(&message.name, &field.type_name) {
+                values(out, nested, level + 1);
+            }
+        }
+        seen.pop();
+    }
+}
+
+fn values(out: &mut String, definition: &Enum, level: usize) {
+    for (name, number, doc) in &definition.values {
+        bullet(out, level, &format!("`{name} = {number}`"), doc.as_deref());
+    }
+}
+
+/// `rpc Name(stream Request) returns (Response)`.
+fn signature(rpc: &Rpc) -> String {
+    let stream = |stream: bool| if stream { "stream " } else { "" };
+    format!(
+        "rpc {}({}{}) returns ({}{})",
+        rpc.name,
+        stream(rpc.request_stream),
+        rpc.request,
+        stream(rpc.response_stream),
+        rpc.response
+    )
+}
@@ -0,0 +1,406 @@
+//! Chunk stage: splits text items into retrieval-sized pieces.
+//!
+//! Text is cut at the most meaningful boundary that keeps a piece within
+//! [`ChunkOptions::size`], trying in order:
+//! 1. markdown headings (outside code fences), or top-level definitions in code whose
+//!    language is detected, with their leading comments and attributes;
+//! 2. nested definitions (code only);
+//! 3. paragraphs (blank lines);
+//! 4. lines;
+//! 5. the size limit itself.
+//!
+//! Consecutive pieces are then packed greedily into chunks; a heading section or
+//! definition that does not fit in the current chunk starts a new one. A chunk's
+//! breadcrumb is the heading path its pieces share. Every chunk after the first starts
+//! with up to [`ChunkOptions::overlap`] units from the end of the previous one, snapped
+//! to a word.
+
+use std::path::Path;
+use std::sync::LazyLock;
+
+use regex::Regex;
+use tracing::debug;
+
+use super::pipeline::{Batch, Item, Stage, StageContext};
+use crate::code_to_pdf::highlight::detect_language;
+use crate::contract::{ChunkOptions, ChunkUnit, ProcessError};
+
+/// A chunk of a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    pub text: String,
+    /// Headings enclosing the chunk, outermost first.
+    pub breadcrumb: Vec<String>,
+}
+
+/// Pipeline stage that splits every text item into chunks. Items that are not text (PDFs,
+/// uploaded binaries) pass through unchanged. An item that splits into more than one
+/// chunk becomes `<path>__chunkNNN<.ext>` items, numbered from 1.
+#[derive(Debug, Clone, Default)]
+pub struct Chunker {
+    pub options: ChunkOptions,
+}
+
+impl Stage for Chunker {
+    fn name(&self) -> &'static str {
+        "chunk"
+    }
+
+    fn run(&self, _context: &StageContext, batch: Batch) -> Result<Batch, ProcessError> {
+        let mut items = Vec::with_capacity(batch.items.len());
+        for item in batch.items {
+            let Some(text) = item.text() else {
+                items.push(item);
+                continue;
+            };
+            let markdown = item.mime_type.as_deref() == Some("text/markdown")
+                || matches!(extension(&item.path), Some("md" | "markdown"));
+            let chunks = chunk_text(Path::new(&item.path), text, markdown, &self.options);
+            debug!(path = %item.path, chunks = chunks.len(), "Chunked item");
+            if chunks.len() == 1 {
+                items.push(Item {
+                    breadcrumb: chunks.into_iter().next().unwrap().breadcrumb,
+                    ..item
+                });
+                continue;
+            }
+            for (i, chunk) in chunks.into_iter().enumerate() {
+                items.push(Item {
+                    path: chunk_name(&item.path, i + 1),
+                    origin: item.origin.clone(),
+                    content: chunk.text.into_bytes(),
+                    mime_type: item.mime_type.clone(),
+                    breadcrumb: chunk.breadcrumb,
+                    fingerprint: None,
+                    symbol: item.symbol.clone(),
+                });
+            }
+        }
+        Ok(Batch {
+            items,
+            skipped: batch.skipped,
+            findings: batch.findings,
+            pii: batch.pii,
+        })
+    }
+}
+
+/// `src/lib.rs`, 2 → `src/lib__chunk002.rs`; names without an extension get the suffix.
+pub fn chunk_name(filename: &str, index: usize) -> String {
+    suffixed_name(filename, &format!("chunk{index:03}"))
+}
+
+/// `src/lib.rs`, `parse` → `src/lib__parse.rs`, keeping the extension last.
+pub(crate) fn suffixed_name(filename: &str, suffix: &str) -> String {
+    match extension(filename) {
+        Some(ext) => format!(
+            "{}__{suffix}.{ext}",
+            &filename[..filename.len() - ext.len() - 1]
+        ),
+        None => format!("{filename}__{suffix}"),
+    }
+}
+
+/// Extension of the last path segment (`/` or `__` separated); dotfiles such as
+/// `.gitignore` have none.
+fn extension(filename: &str) -> Option<&str> {
+    let name = filename.rsplit('/').next().unwrap_or(filename);
+    let name = name.rsplit("__").next().unwrap_or(name);
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() && !ext.is_empty() => Some(ext),
+        _ => None,
+    }
+}
+
+/// Split `text`, read from `path`, into chunks as described in the module docs.
+pub fn chunk_text(path: &Path, text: &str, markdown: bool, options: &ChunkOptions) -> Vec<Chunk> {
+    let measure = Measure::new(options.unit);
+    let size = options.size.max(1);
+    let code = !markdown && detect_language(path, text).is_some();
+
+    let sections = if markdown {
+        markdown_sections(text)
+    } else if code {
+        split_lines(text, definition_starts(text, true))
+            .into_iter()
+            .map(|piece| (piece, Vec::new()))
+            .collect()
+    } else {
+        vec![(text, Vec::new())]
+    };
+
+    let mut chunks: Vec<(String, Vec<String>, usize)> = Vec::new();
+    for (section, breadcrumb) in sections {
+        // A section that does not fit in the current chunk starts a new one rather than
+        // being spread over two.
+        if let Some((_, _, total)) = chunks.last() {
+            if *total + measure.count(section) > size {
+                chunks.push((String::new(), breadcrumb.clone(), 0));
+            }
+        }
+        for piece in fit(section, code, markdown, size, &measure) {
+            pack(&mut chunks, piece, &breadcrumb, size, &measure);
+        }
+    }
+    chunks.retain(|(text, _, _)| !text.is_empty());
+
+    let mut result: Vec<Chunk> = Vec::with_capacity(chunks.len());
+    let mut previous: Option<String> = None;
+    for (text, breadcrumb, _) in chunks {
+        let overlap = previous
+            .as_deref()
+            .map(|prev| measure.tail(prev, options.overlap))
+            .unwrap_or_default();
+        result.push(Chunk {
+            text: format!("{overlap}{text}"),
+            breadcrumb,
+        });
+        previous = Some(text);
+    }
+    if result.is_empty() {
+        result.push(Chunk {
+            text: String::new(),
+            breadcrumb: Vec::new(),
+        });
+    }
+    result
+}
+
+/// Append `piece` to the last chunk if it fits, narrowing the chunk's breadcrumb to the
+/// headings both share; otherwise start a new chunk.
+fn pack(
+    chunks: &mut Vec<(String, Vec<String>, usize)>,
+    piece: &str,
+    breadcrumb: &[String],
+    size: usize,
+    measure: &Measure,
+) {
+    let units = measure.count(piece);
+    match chunks.last_mut() {
+        Some((text, shared, total)) if text.is_empty() || *total + units <= size => {
+            if text.is_empty() {
+                *shared = breadcrumb.to_vec();
+            }
+            text.push_str(piece);
+            *total += units;
+            let common = shared
+                .iter()
+                .zip(breadcrumb)
+                .take_while(|(a, b)| a == b)
+                .count();
+            shared.truncate(common);
+        }
+        _ => chunks.push((piece.to_string(), breadcrumb.to_vec(), units)),
+    }
+}
+
+/// Split a piece that is over `size` at the finest boundary level needed.
+fn fit<'a>(text: &'a str, code: bool, markdown: bool, size: usize, m: &Measure) -> Vec<&'a str> {
+    if m.count(text) <= size {
+        return vec![text];
+    }
+    let levels: [Option<Vec<usize>>; 3] = [
+        code.then(|| definition_starts(text, false)),
+        Some(paragraph_starts(text, markdown)),
+        Some(text.match_indices('\n').map(|(i, _)| i + 1).collect()),
+    ];
+    for starts in levels.into_iter().flatten() {
+        let pieces = split_lines(text, starts);
+        if pieces.len() > 1 {
+            return pieces
+                .into_iter()
+                .flat_map(|piece| fit(piece, code, markdown, size, m))
+                .collect();
+        }
+    }
+    m.hard_split(text, size)
+}
+
+/// Split `text` at the given byte offsets (line starts), dropping empty pieces.
+fn split_lines(text: &str, starts: Vec<usize>) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut from = 0;
+    for start in starts.into_iter().chain([text.len()]) {
+        if start > from && start <= text.len() {
+            pieces.push(&text[from..start]);
+            from = start;
+        }
+    }
+    pieces
+}
+
+/// Lines of `text` with their byte offsets.
+fn lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut offset = 0;
+    text.split_inclusive('\n').map(move |line| {
+        let start = offset;
+        offset += line.len();
+        (start, line)
+    })
+}
+
+static HEADING: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(#{1,6})[ \t]+(.*?)[ \t#]*$").unwrap());
+
+/// Markdown sections, each starting at a heading, with the heading path it sits under.
+fn markdown_sections(text: &str) -> Vec<(&str, Vec<String>)> {
+    let mut sections = Vec::new();
+    let mut stack: Vec<(usize, String)> = Vec::new();
+    let mut breadcrumb = Vec::new();
+    let mut from = 0;
+    let mut fence: Option<&str> = None;
+    for (start, line) in lines(text) {
+        let trimmed = line.trim_end();
+        if let Some(marker) = fence_marker(trimmed) {
+            fence = match fence {
+                Some(open) if marker.starts_with(open) => None,
+                Some(open) => Some(open),
+                None => Some(marker),
+            };
+            continue;
+        }
+        if fence.is_some() {
+            continue;
+        }
+        let Some(caps) = HEADING.captures(trimmed) else {
+            continue;
+        };
+        if start > from {
+            sections.push((&text[from..start], breadcrumb.clone()));
+        }
+        from = start;
+        let level = caps[1].len();
+        stack.retain(|(l, _)| *l < level);
+        stack.push((level, caps[2].to_string()));
+        breadcrumb = stack.iter().map(|(_, title)| title.clone()).collect();
+    }
+    if from < text.len() || sections.is_empty() {
+        sections.push((&text[from..], breadcrumb));
+    }
+    sections
+}
+
+/// The ```` ``` ```` or `~~~` run that opens or closes a fenced code block.
+fn fence_marker(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    let fence = |c: char| {
+        let run = line.len() - line.trim_start_matches(c).len();
+        (run >= 3).then(|| &line[..run])
+    };
+    fence('`').or_else(|| fence('~'))
+}
+
+static DEFINITION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^\s*(?:export\s+)?(?:default\s+)?(?:pub(?:\([^)]*\))?\s+)?(?:(?:async|unsafe|const|static|abstract|final|public|private|protected|extern)\s+)*(?:fn|struct|enum|union|impl|trait|mod|type|class|def|function|interface|func|module|macro_rules!)[\s(<{]",
+    )
+    .unwrap()
+});
+
+/// Line starts of definitions (functions, types, classes), moved up over the comments,
+/// attributes and decorators directly above them. `top_level` only counts unindented ones.
+fn definition_starts(text: &str, top_level: bool) -> Vec<usize> {
+    let lines: Vec<(usize, &str)> = lines(text).collect();
+    let mut starts = Vec::new();
+    for (i, (_, line)) in lines.iter().enumerate() {
+        let indented = line.starts_with([' ', '\t']);
+        if (top_level && indented) || !DEFINITION.is_match(line) {
+            continue;
+        }
+        let mut first = i;
+        while first > 0 && is_preamble(lines[first - 1].1) {
+            first -= 1;
+        }
+        starts.push(lines[first].0);
+    }
+    starts.dedup();
+    starts
+}
+
+/// Comment, attribute or decorator lines that belong to the definition below them.
+fn is_preamble(line: &str) -> bool {
+    let line = line.trim_start();
+    ["//", "/*", "*", "#", "@", "--", "\"\"\""]
+        .iter()
+        .any(|prefix| line.starts_with(prefix))
+}
+
+/// Starts of lines that follow a blank line, ignoring blank lines inside markdown fences.
+fn paragraph_starts(text: &str, markdown: bool) -> Vec<usize> {
+    let mut starts = Vec::new();
+    let mut after_blank = false;
+    let mut in_fence = false;
+    for (start, line) in lines(text) {
+        let blank = line.trim().is_empty();
+        if after_blank && !blank && !in_fence {
+            starts.push(start);
+        }
+        if markdown && fence_marker(line.trim_end()).is_some() {
+            in_fence = !in_fence;
+        }
+        after_blank = blank && !in_fence;
+    }
+    starts
+}
+
+/// Counts [`ChunkUnit`]s in text.
+struct Measure {
+    unit: ChunkUnit,
+}
+
+static TOKEN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\w+|[^\w\s]").unwrap());
+
+impl Measure {
+    fn new(unit: ChunkUnit) -> Self {
+        Measure { unit }
+    }
+
+    fn count(&self, text: &str) -> usize {
+        match self.unit {
+            ChunkUnit::Chars => text.chars().count(),
+            ChunkUnit::Tokens => TOKEN.find_iter(text).count(),
+        }
+    }
+
+    /// Byte offsets at which each unit starts.
+    fn unit_starts(&self, text: &str) -> Vec<usize> {
+        match self.unit {
+            ChunkUnit::Chars => text.char_indices().map(|(i, _)| i).collect(),
+            ChunkUnit::Tokens => TOKEN.find_iter(text).map(|m| m.start()).collect(),
+        }
+    }
+
+    /// Cut `text` into pieces of at most `size` units.
+    fn hard_split<'a>(&self, text: &'a str, size: usize) -> Vec<&'a str> {
+        let cuts = self
+            .unit_starts(text)
+            .into_iter()
+            .skip(size)
+            .step_by(size)
+            .collect();
+        split_lines(text, cuts)
+    }
+
+    /// The end of `text` holding at most `units` units, starting at a word boundary when
+    /// one is available.
+    fn tail<'a>(&self, text: &'a str, units: usize) -> &'a str {
+        if units == 0 {
+            return "";
+        }
+        let starts = self.unit_starts(text);
+        if starts.len() <= units {
+            return text;
+        }
+        let from = starts[starts.len() - units];
+        let tail = &text[from..];
+        match self.unit {
+            ChunkUnit::Tokens => tail,
+            ChunkUnit::Chars => match tail.find(char::is_whitespace) {
+                Some(space) if !text[..from].ends_with(char::is_whitespace) => {
+                    tail[space..].trim_start()
+                }
+                _ => tail,
+            },
+        }
+    }
+}
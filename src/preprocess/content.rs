@@ -0,0 +1,291 @@
+//! File content sniffing and the per-type [`FilePolicy`] applied by every file-based
+//! processor.
+//!
+//! A file is classified from its bytes, not its name: known magic numbers first, then
+//! UTF-16 byte order marks, then NUL bytes and UTF-8 validity. The extension is only used
+//! to pick the MIME type of text files. Binary files are skipped, uploaded as-is or
+//! converted to text according to the policy; files over `max_file_size` are never read.
+
+use std::path::Path;
+
+use tracing::{debug, error, warn};
+
+use crate::contract::{BinaryPolicy, FilePolicy, ProcessError, SkipReason, SkippedPath};
+use crate::download::database::sqlite_file_markdown;
+
+/// Bytes inspected for NUL bytes and UTF-8 validity.
+const SNIFF_LEN: usize = 8192;
+
+/// Magic numbers at offset 0, most specific first.
+const MAGIC: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"SQLite format 3\0", "application/vnd.sqlite3"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"PK\x05\x06", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"BZh", "application/x-bzip2"),
+    (b"\xfd7zXZ\0", "application/x-xz"),
+    (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (b"\x7fELF", "application/x-elf"),
+    (b"\xca\xfe\xba\xbe", "application/java-vm"),
+    (b"\0asm", "application/wasm"),
+    (b"\xcf\xfa\xed\xfe", "application/x-mach-binary"),
+    (b"\xce\xfa\xed\xfe", "application/x-mach-binary"),
+    (b"II*\0", "image/tiff"),
+    (b"MM\0*", "image/tiff"),
+    (b"\0\0\x01\0", "image/x-icon"),
+    (b"wOFF", "font/woff"),
+    (b"wOF2", "font/woff2"),
+];
+
+/// Magic numbers that are also plausible text; they only count when the file has a NUL.
+const WEAK_MAGIC: &[(&[u8], &str)] = &[
+    (b"MZ", "application/vnd.microsoft.portable-executable"),
+    (b"BM", "image/bmp"),
+];
+
+/// MIME types of text files by extension; anything else is `text/plain`.
+const TEXT_TYPES: &[(&str, &str)] = &[
+    ("md", "text/markdown"),
+    ("markdown", "text/markdown"),
+    ("rs", "text/x-rust"),
+    ("py", "text/x-python"),
+    ("js", "text/javascript"),
+    ("mjs", "text/javascript"),
+    ("ts", "text/x-typescript"),
+    ("go", "text/x-go"),
+    ("java", "text/x-java"),
+    ("c", "text/x-c"),
+    ("h", "text/x-c"),
+    ("cpp", "text/x-c++"),
+    ("hpp", "text/x-c++"),
+    ("sh", "application/x-sh"),
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("css", "text/css"),
+    ("csv", "text/csv"),
+    ("xml", "application/xml"),
+    ("svg", "image/svg+xml"),
+    ("json", "application/json"),
+    ("yaml", "application/yaml"),
+    ("yml", "application/yaml"),
+    ("toml", "application/toml"),
+    ("sql", "application/sql"),
+];
+
+/// What a file's bytes turned out to be.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sniffed {
+    pub mime_type: String,
+    pub kind: ContentKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentKind {
+    /// Valid UTF-8 without NUL bytes (a UTF-8 byte order mark is allowed).
+    Text,
+    /// UTF-16 text with a byte order mark.
+    Utf16 {
+        big_endian: bool,
+    },
+    /// Not UTF-8, but nearly all printable in Latin-1.
+    Latin1,
+    Binary,
+}
+
+/// Classify `bytes`, read from `path`, as described in the module docs.
+pub fn sniff(path: &Path, bytes: &[u8]) -> Sniffed {
+    let head = &bytes[..bytes.len().min(SNIFF_LEN)];
+    let has_nul = head.contains(&0);
+    let binary = |mime_type: &str| Sniffed {
+        mime_type: mime_type.to_string(),
+        kind: ContentKind::Binary,
+    };
+
+    if let Some((_, mime_type)) = MAGIC.iter().find(|(magic, _)| bytes.starts_with(magic)) {
+        return binary(archive_type(path, mime_type));
+    }
+    if has_nul {
+        if let Some((_, mime_type)) = WEAK_MAGIC.iter().find(|(m, _)| bytes.starts_with(m)) {
+            return binary(mime_type);
+        }
+    }
+    if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        return binary("image/webp");
+    }
+    for (bom, big_endian) in [(b"\xfe\xff", true), (b"\xff\xfe", false)] {
+        if bytes.starts_with(bom) {
+            return Sniffed {
+                mime_type: format!(
+                    "text/plain; charset=utf-16{}",
+                    if big_endian { "be" } else { "le" }
+                ),
+                kind: ContentKind::Utf16 { big_endian },
+            };
+        }
+    }
+    if has_nul {
+        return binary("application/octet-stream");
+    }
+    if is_utf8_prefix(head, head.len() < bytes.len()) {
+        return Sniffed {
+            mime_type: text_type(path).to_string(),
+            kind: ContentKind::Text,
+        };
+    }
+    let printable = head
+        .iter()
+        .filter(|&&b| matches!(b, b'\t' | b'\n' | b'\r' | 0x20..=0x7e | 0xa0..=0xff))
+        .count();
+    if printable * 100 >= head.len() * 95 {
+        return Sniffed {
+            mime_type: "text/plain; charset=iso-8859-1".to_string(),
+            kind: ContentKind::Latin1,
+        };
+    }
+    binary("application/octet-stream")
+}
+
+/// Whether `head` is UTF-8, allowing a character cut off at the end when `truncated`.
+fn is_utf8_prefix(head: &[u8], truncated: bool) -> bool {
+    match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(e) => truncated && e.error_len().is_none(),
+    }
+}
+
+/// Zip-based formats recognised by extension.
+fn archive_type<'a>(path: &Path, mime_type: &'a str) -> &'a str {
+    if mime_type != "application/zip" {
+        return mime_type;
+    }
+    match extension(path).as_deref() {
+        Some("jar") => "application/java-archive",
+        Some("docx") => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        Some("xlsx") => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        _ => mime_type,
+    }
+}
+
+/// Whether `mime_type` is one that [`sniff`] gives to text files.
+pub fn is_text_mime(mime_type: &str) -> bool {
+    mime_type.starts_with("text/") || TEXT_TYPES.iter().any(|(_, m)| *m == mime_type)
+}
+
+fn text_type(path: &Path) -> &'static str {
+    extension(path)
+        .and_then(|ext| TEXT_TYPES.iter().find(|(e, _)| *e == ext))
+        .map(|(_, mime_type)| *mime_type)
+        .unwrap_or("text/plain")
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+}
+
+/// A file that passed the [`FilePolicy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileContent {
+    /// UTF-8 text: the file itself, or converted from another encoding or format.
+    Text {
+        text: String,
+        mime_type: String,
+        /// Extension to append to the item name when the text is in a different format
+        /// from the file (e.g. `.md` for a SQLite schema).
+        extension: Option<&'static str>,
+    },
+    /// Raw bytes, uploaded under [`BinaryPolicy::Upload`].
+    Binary { bytes: Vec<u8>, mime_type: String },
+}
+
+/// Read `rel_path` under `repo_path` and apply `policy`. Files that are too large or whose
+/// binary type is skipped (or has no converter) are recorded in `skipped` and return `None`.
+pub fn load_file(
+    repo_path: &Path,
+    rel_path: &Path,
+    policy: &FilePolicy,
+    skipped: &mut Vec<SkippedPath>,
+) -> Result<Option<FileContent>, ProcessError> {
+    let path = repo_path.join(rel_path);
+    let mut skip = |reason: SkipReason| {
+        debug!(path = %rel_path.display(), reason = %reason, "Skipping file");
+        skipped.push(SkippedPath {
+            path: super::slash_path(rel_path),
+            reason,
+        });
+        Ok(None)
+    };
+
+    let size = std::fs::metadata(&path)?.len();
+    if size > policy.max_file_size {
+        return skip(SkipReason::TooLarge {
+            size,
+            limit: policy.max_file_size,
+        });
+    }
+    let bytes = std::fs::read(&path).map_err(|e| {
+        error!(error = ?e, path = %rel_path.display(), "Failed to read file");
+        ProcessError::Io(e)
+    })?;
+    let Sniffed { mime_type, kind } = sniff(rel_path, &bytes);
+    let text = |text: String| {
+        Ok(Some(FileContent::Text {
+            text,
+            mime_type: "text/plain".to_string(),
+            extension: None,
+        }))
+    };
+
+    match (kind, policy.policy_for(&mime_type)) {
+        (ContentKind::Text, _) => {
+            // Only the head is sniffed, so a stray byte further on is replaced rather than
+            // failing the source.
+            let text = String::from_utf8(bytes).unwrap_or_else(|e| {
+                warn!(path = %rel_path.display(), error = %e.utf8_error(), "Replacing invalid UTF-8");
+                String::from_utf8_lossy(e.as_bytes()).into_owned()
+            });
+            Ok(Some(FileContent::Text {
+                text: text
+                    .strip_prefix('\u{feff}')
+                    .map(str::to_string)
+                    .unwrap_or(text),
+                mime_type,
+                extension: None,
+            }))
+        }
+        (_, BinaryPolicy::Skip) => skip(SkipReason::Binary(mime_type)),
+        (_, BinaryPolicy::Upload) => Ok(Some(FileContent::Binary { bytes, mime_type })),
+        (ContentKind::Utf16 { big_endian }, BinaryPolicy::Convert) => {
+            let units: Vec<u16> = bytes[2..]
+                .chunks_exact(2)
+                .map(|pair| match big_endian {
+                    true => u16::from_be_bytes([pair[0], pair[1]]),
+                    false => u16::from_le_bytes([pair[0], pair[1]]),
+                })
+                .collect();
+            text(String::from_utf16_lossy(&units))
+        }
+        (ContentKind::Latin1, BinaryPolicy::Convert) => {
+            text(bytes.iter().map(|&b| char::from(b)).collect())
+        }
+        (ContentKind::Binary, BinaryPolicy::Convert) if mime_type == "application/vnd.sqlite3" => {
+            match sqlite_file_markdown(&path, &super::slash_path(rel_path)) {
+                Ok(text) => Ok(Some(FileContent::Text {
+                    text,
+                    mime_type: "text/markdown".to_string(),
+                    extension: Some("md"),
+                })),
+                Err(e) => {
+                    warn!(error = ?e, path = %rel_path.display(), "Could not read SQLite schema");
+                    skip(SkipReason::Binary(mime_type))
+                }
+            }
+        }
+        (ContentKind::Binary, BinaryPolicy::Convert) => skip(SkipReason::Binary(mime_type)),
+    }
+}
@@ -0,0 +1,124 @@
+//! Deduplication across the sources of a run: items whose normalised content another
+//! item already has are left out, so vendored files, licences and copied READMEs are
+//! uploaded once.
+//!
+//! Items are compared on their [fingerprint](crate::contract::ItemMetadata::fingerprint).
+//! The copy in the most preferred source is kept, otherwise the first in source order;
+//! the others are reported as skipped duplicates and can be listed as aliases of the kept
+//! item.
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+use tracing::debug;
+
+use super::metadata::sha256_hex;
+use crate::contract::{DedupOptions, ExternalSourceInput, SkipReason, SkippedPath};
+
+/// What deduplication removed from a run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DedupReport {
+    pub items: usize,
+    pub bytes: u64,
+}
+
+/// SHA-256 of `content` with a leading BOM, `\r\n` line endings, trailing whitespace and
+/// trailing blank lines removed; binary content is hashed as is.
+pub fn fingerprint(content: &[u8]) -> String {
+    match std::str::from_utf8(content) {
+        Ok(text) => {
+            let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+            let normalised = text
+                .lines()
+                .map(str::trim_end)
+                .collect::<Vec<_>>()
+                .join("\n");
+            sha256_hex(normalised.trim_end_matches('\n').as_bytes())
+        }
+        Err(_) => sha256_hex(content),
+    }
+}
+
+/// Remove items whose fingerprint an earlier or preferred item has, recording each as a
+/// skipped [`SkipReason::Duplicate`] in its own source.
+pub fn deduplicate(sources: &mut [ExternalSourceInput], options: &DedupOptions) -> DedupReport {
+    let rank = |name: &str| {
+        options
+            .prefer
+            .iter()
+            .position(|preferred| preferred == name)
+            .unwrap_or(options.prefer.len())
+    };
+    let mut order: Vec<usize> = (0..sources.len()).collect();
+    order.sort_by_key(|&s| rank(&sources[s].name));
+
+    // (source, item) of every duplicate and of the copy kept instead.
+    let mut kept: HashMap<String, (usize, usize)> = HashMap::new();
+    let mut duplicates: Vec<((usize, usize), (usize, usize))> = Vec::new();
+    for s in order {
+        for (i, item) in sources[s].external_items.iter().enumerate() {
+            let key = match item.metadata.fingerprint.as_str() {
+                "" => fingerprint(&item.content),
+                known => known.to_string(),
+            };
+            match kept.entry(key) {
+                Entry::Occupied(original) => duplicates.push(((s, i), *original.get())),
+                Entry::Vacant(slot) => {
+                    slot.insert((s, i));
+                }
+            }
+        }
+    }
+
+    let location = |(s, i): (usize, usize)| {
+        let source: &ExternalSourceInput = &sources[s];
+        let item = &source.external_items[i];
+        item.metadata.url.clone().unwrap_or_else(|| {
+            let path = match item.metadata.path.as_str() {
+                "" => &item.filename,
+                path => path,
+            };
+            format!("{}/{path}", source.name.trim_end_matches('/'))
+        })
+    };
+    let described: Vec<_> = duplicates
+        .iter()
+        .map(|&(duplicate, original)| {
+            (duplicate, original, location(duplicate), location(original))
+        })
+        .collect();
+
+    let mut report = DedupReport::default();
+    let mut removed = HashSet::new();
+    for ((s, i), (ks, ki), duplicate_at, original_at) in described {
+        let item = &sources[s].external_items[i];
+        debug!(duplicate = %duplicate_at, kept = %original_at, "Removing duplicate item");
+        report.items += 1;
+        report.bytes += item.content.len() as u64;
+        let path = match item.metadata.path.as_str() {
+            "" => item.filename.clone(),
+            path => path.to_string(),
+        };
+        sources[s].skipped.push(SkippedPath {
+            path,
+            reason: SkipReason::Duplicate(original_at),
+        });
+        if options.aliases {
+            sources[ks].external_items[ki]
+                .metadata
+                .aliases
+                .push(duplicate_at);
+        }
+        removed.insert((s, i));
+    }
+    for (s, source) in sources.iter_mut().enumerate() {
+        let items = std::mem::take(&mut source.external_items);
+        source.external_items = items
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| !removed.contains(&(s, *i)))
+            .map(|(_, item)| item)
+            .collect();
+    }
+    report
+}
@@ -0,0 +1,253 @@
+//! Header stage: prepends a short provenance header to every text item, so content
+//! indexed on its own still says where it came from.
+//!
+//! The header is rendered from a template per source type, filled in from the source's
+//! [`variables`](super::pipeline::StageContext::variables) and the item's metadata, and
+//! written as YAML front matter, an HTML comment or line comments in the item's language.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use tracing::{debug, info};
+
+use super::metadata::SourceInfo;
+use super::pipeline::{Batch, Item, Stage, StageContext};
+use crate::code_to_pdf::highlight::detect_language;
+use crate::contract::{HeaderFormat, HeaderOptions, ProcessError};
+use crate::download::fill_placeholders;
+
+/// Variables a header template can use.
+pub const VARIABLES: &[&str] = &[
+    "type",
+    "name",
+    "path",
+    "item",
+    "url",
+    "commit",
+    "modified",
+    "language",
+    "breadcrumb",
+    "symbol",
+    "lines",
+    "repository",
+    "branch",
+    "space",
+    "base_url",
+    "dump",
+    "mailbox",
+    "bucket",
+    "prefix",
+    "api",
+];
+
+const GIT_TEMPLATE: &str = "source: {{ repository }}\n\
+                            path: {{ path }}\n\
+                            symbol: {{ symbol }}\n\
+                            lines: {{ lines }}\n\
+                            branch: {{ branch }}\n\
+                            commit: {{ commit }}\n\
+                            modified: {{ modified }}\n\
+                            section: {{ breadcrumb }}";
+
+const CONFLUENCE_TEMPLATE: &str = "source: Confluence space {{ space }}\n\
+                                   page: {{ breadcrumb }}\n\
+                                   url: {{ url }}\n\
+                                   modified: {{ modified }}";
+
+const DEFAULT_TEMPLATE: &str = "source: {{ name }}\n\
+                                path: {{ path }}\n\
+                                symbol: {{ symbol }}\n\
+                                lines: {{ lines }}\n\
+                                url: {{ url }}\n\
+                                modified: {{ modified }}\n\
+                                section: {{ breadcrumb }}";
+
+/// Prepend a header to every text item; binary items pass through.
+#[derive(Debug, Clone, Default)]
+pub struct Header {
+    pub options: HeaderOptions,
+}
+
+impl Stage for Header {
+    fn name(&self) -> &'static str {
+        "header"
+    }
+
+    fn run(&self, context: &StageContext, mut batch: Batch) -> Result<Batch, ProcessError> {
+        let source_type = context.variables.get("type").map_or("", String::as_str);
+        let template = self.template(source_type);
+        let info = SourceInfo::load(context);
+        let mut headed = 0;
+        for item in &mut batch.items {
+            let Some(text) = item.text() else {
+                continue;
+            };
+            let variables = item_variables(context, &info, item);
+            let style = style(self.options.format, item, text);
+            let lines = render(template, &variables, |value| style.escape(value));
+            if lines.is_empty() {
+                continue;
+            }
+            let content = style.wrap(&lines, text);
+            debug!(path = %item.path, lines = lines.len(), "Prepended header");
+            item.fingerprint = Some(item.fingerprint());
+            item.content = content.into_bytes();
+            headed += 1;
+        }
+        info!(items = headed, "Prepended headers to text items");
+        Ok(batch)
+    }
+}
+
+impl Header {
+    /// The configured template for the source type, else the configured `default`, else
+    /// the built-in one.
+    fn template(&self, source_type: &str) -> &str {
+        let templates = &self.options.templates;
+        templates
+            .get(source_type)
+            .or_else(|| templates.get("default"))
+            .map(String::as_str)
+            .unwrap_or(match source_type {
+                "git" => GIT_TEMPLATE,
+                "confluence" => CONFLUENCE_TEMPLATE,
+                _ => DEFAULT_TEMPLATE,
+            })
+    }
+}
+
+/// The source's variables plus the item's own: its path, link, commit, timestamps,
+/// language, breadcrumb (the original's hierarchy, then the chunk's headings) and the
+/// signature and line range of the symbol it holds.
+fn item_variables(
+    context: &StageContext,
+    info: &SourceInfo,
+    item: &Item,
+) -> BTreeMap<String, String> {
+    let metadata = info.metadata(item);
+    let mut breadcrumb = info
+        .indexed(&item.origin)
+        .map(|file| file.breadcrumb.clone())
+        .unwrap_or_default();
+    breadcrumb.extend(item.breadcrumb.iter().cloned());
+
+    let mut variables = context.variables.clone();
+    let mut set = |name: &str, value: Option<String>| {
+        if let Some(value) = value {
+            variables.insert(name.to_string(), value);
+        }
+    };
+    set("name", Some(context.name.clone()));
+    set("path", Some(metadata.path));
+    set("item", Some(item.path.clone()));
+    set("url", metadata.url);
+    set("commit", metadata.commit);
+    set("modified", metadata.modified);
+    set("language", metadata.language);
+    set("breadcrumb", Some(breadcrumb.join(" › ")));
+    if let Some(symbol) = &item.symbol {
+        set("symbol", Some(symbol.signature.clone()));
+        set(
+            "lines",
+            Some(format!("{}-{}", symbol.start_line, symbol.end_line)),
+        );
+    }
+    variables
+}
+
+/// Render each template line; a line whose placeholders all come out empty is dropped.
+fn render(
+    template: &str,
+    variables: &BTreeMap<String, String>,
+    escape: impl Fn(&str) -> String,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    for line in template.lines().filter(|line| !line.trim().is_empty()) {
+        let (mut placeholders, mut filled) = (0, 0);
+        let rendered = fill_placeholders(line, |name| {
+            placeholders += 1;
+            match variables.get(name).filter(|value| !value.is_empty()) {
+                Some(value) => {
+                    filled += 1;
+                    escape(value)
+                }
+                None => String::new(),
+            }
+        });
+        if placeholders == 0 || filled > 0 {
+            lines.push(rendered);
+        }
+    }
+    lines
+}
+
+/// How a header is written into one item.
+enum Style {
+    FrontMatter,
+    MarkdownComment,
+    Comment(&'static str),
+}
+
+fn style(format: HeaderFormat, item: &Item, text: &str) -> Style {
+    let markdown = item.mime_type.as_deref() == Some("text/markdown")
+        || item.path.ends_with(".md")
+        || item.path.ends_with(".markdown");
+    let comment = detect_language(Path::new(&item.path), text).and_then(|l| l.line_comment());
+    match (format, comment) {
+        (HeaderFormat::FrontMatter, _) => Style::FrontMatter,
+        (HeaderFormat::MarkdownComment, _) => Style::MarkdownComment,
+        (HeaderFormat::Auto, _) if markdown => Style::FrontMatter,
+        (HeaderFormat::Auto | HeaderFormat::Comment, Some(marker)) => Style::Comment(marker),
+        (HeaderFormat::Auto | HeaderFormat::Comment, None) => Style::FrontMatter,
+    }
+}
+
+impl Style {
+    /// A variable's value made safe for the header: on one line, and quoted where YAML
+    /// would misread it.
+    fn escape(&self, value: &str) -> String {
+        let value = value.replace(['\r', '\n'], " ");
+        match self {
+            Style::FrontMatter => {
+                let special = value.starts_with([
+                    '-', '?', ':', ',', '[', ']', '{', '}', '#', '&', '*', '!', '|', '>', '\'',
+                    '"', '%', '@', '`',
+                ]) || value.contains(": ")
+                    || value.contains(" #")
+                    || value.ends_with(':');
+                if special {
+                    serde_json::to_string(&value).unwrap_or(value)
+                } else {
+                    value
+                }
+            }
+            Style::MarkdownComment => value.replace("-->", "--&gt;"),
+            Style::Comment(_) => value,
+        }
+    }
+
+    /// `text` with the header in front. Existing front matter is extended rather than
+    /// duplicated, and a `#!` line stays first.
+    fn wrap(&self, lines: &[String], text: &str) -> String {
+        let body = lines.join("\n");
+        match self {
+            Style::FrontMatter => match text.strip_prefix("---\n") {
+                Some(rest) => format!("---\n{body}\n{rest}"),
+                None => format!("---\n{body}\n---\n{text}"),
+            },
+            Style::MarkdownComment => format!("<!--\n{body}\n-->\n{text}"),
+            Style::Comment(marker) => {
+                let comment: String = lines
+                    .iter()
+                    .map(|line| format!("{marker} {line}\n"))
+                    .collect();
+                match text.split_once('\n') {
+                    Some((shebang, rest)) if shebang.starts_with("#!") => {
+                        format!("{shebang}\n{comment}{rest}")
+                    }
+                    _ => format!("{comment}{text}"),
+                }
+            }
+        }
+    }
+}
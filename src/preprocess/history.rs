@@ -0,0 +1,367 @@
+//! History stage: adds markdown documents of a git source's commit history, so questions
+//! about when and why something changed have something to retrieve.
+//!
+//! Commits come from `git log` on the checkout's `HEAD`, newest first and bounded by
+//! [`HistoryOptions`]. Each gets a section with its message, author, date, tags and
+//! changed files, and optionally its diff cut to a few lines per file. Sections are
+//! grouped into one document per month (`_history/2024-03.md`) or per release tag
+//! (`_history/v1.2.0.md`, with untagged commits in `_history/unreleased.md`).
+//! Sources that are not git checkouts get no documents.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::path::Path;
+use std::process::Command;
+
+use tracing::{debug, info, warn};
+
+use super::code_fence;
+use super::metadata::web_root;
+use super::pipeline::{Batch, Item, Stage, StageContext};
+use crate::contract::{HistoryGrouping, HistoryOptions, ProcessError};
+
+/// Directory the generated documents are named under.
+pub const HISTORY_DIR: &str = "_history";
+
+/// Document holding the commits in no release tag when grouping by tag.
+const UNRELEASED: &str = "unreleased";
+
+/// Separates commits and their fields in the `git log` output.
+const RECORD: char = '\u{1e}';
+const FIELD: char = '\u{1f}';
+
+/// Add commit history documents after the other items.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    pub options: HistoryOptions,
+}
+
+impl Stage for History {
+    fn name(&self) -> &'static str {
+        "history"
+    }
+
+    fn run(&self, context: &StageContext, mut batch: Batch) -> Result<Batch, ProcessError> {
+        let repo = &context.repo_path;
+        if !repo.join(".git").exists() {
+            warn!(source = %context.name, "Source is not a git checkout; no history to add");
+            return Ok(batch);
+        }
+        if git(repo, &["rev-parse", "--verify", "--quiet", "HEAD"]).is_err() {
+            warn!(source = %context.name, "Repository has no commits; no history to add");
+            return Ok(batch);
+        }
+        let commits = log(repo, &self.options)?;
+        let tags = tags(repo)?;
+        let releases = match self.options.group_by {
+            HistoryGrouping::Month => HashMap::new(),
+            HistoryGrouping::Tag => releases(repo, &tags)?,
+        };
+        let mut tagged: HashMap<&str, Vec<&str>> = HashMap::new();
+        for tag in &tags {
+            tagged.entry(&tag.commit).or_default().push(&tag.name);
+        }
+
+        // Documents in order of their newest commit, which `git log` lists first.
+        let mut groups: Vec<(String, Vec<&Commit>)> = Vec::new();
+        for commit in &commits {
+            let key = match self.options.group_by {
+                HistoryGrouping::Month => commit.date.chars().take(7).collect(),
+                HistoryGrouping::Tag => releases
+                    .get(commit.hash.as_str())
+                    .map_or(UNRELEASED.to_string(), |tag| tag.to_string()),
+            };
+            match groups.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, group)) => group.push(commit),
+                None => groups.push((key, vec![commit])),
+            }
+        }
+
+        let root = context.base_url.as_deref().map(|url| web_root(url, true));
+        let tag_dates: HashMap<&str, &str> = tags
+            .iter()
+            .map(|tag| (tag.name.as_str(), tag.date.as_str()))
+            .collect();
+        for (key, group) in &groups {
+            let intro = match self.options.group_by {
+                HistoryGrouping::Month => format!("Commits authored in {key}"),
+                HistoryGrouping::Tag if key == UNRELEASED => {
+                    "Commits not yet in a release tag".to_string()
+                }
+                HistoryGrouping::Tag => format!(
+                    "Commits first released in `{key}`, tagged {}",
+                    tag_dates.get(key.as_str()).copied().unwrap_or_default()
+                ),
+            };
+            let mut content = format!(
+                "# History of {}: {key}\n\n{intro}; {} commit{}, newest first.\n",
+                context.name,
+                group.len(),
+                if group.len() == 1 { "" } else { "s" }
+            );
+            for commit in group {
+                let tags = tagged
+                    .get(commit.hash.as_str())
+                    .map_or(&[][..], Vec::as_slice);
+                content.push('\n');
+                content.push_str(&section(commit, tags, root.as_deref()));
+            }
+            debug!(document = %key, commits = group.len(), "Generated history document");
+            batch.items.push(Item {
+                path: format!("{HISTORY_DIR}/{key}.md"),
+                origin: String::new(),
+                content: content.into_bytes(),
+                mime_type: Some("text/markdown".to_string()),
+                breadcrumb: Vec::new(),
+                fingerprint: None,
+                symbol: None,
+            });
+        }
+        info!(
+            commits = commits.len(),
+            documents = groups.len(),
+            "Generated commit history"
+        );
+        Ok(batch)
+    }
+}
+
+/// One commit from `git log`.
+#[derive(Debug, Clone, Default)]
+struct Commit {
+    hash: String,
+    author: String,
+    /// Author date, RFC 3339.
+    date: String,
+    message: String,
+    files: Vec<ChangedFile>,
+}
+
+/// A file a commit changed, with its trimmed diff when diffs are included.
+#[derive(Debug, Clone, Default)]
+struct ChangedFile {
+    /// `A`, `M`, `D`, `R`, ... as in `git log --raw`.
+    status: String,
+    path: String,
+    /// The path before a rename or copy.
+    from: Option<String>,
+    diff: Vec<String>,
+    /// Diff lines cut from `diff`.
+    omitted: usize,
+}
+
+/// A release tag and the commit it points at.
+#[derive(Debug, Clone)]
+struct Tag {
+    name: String,
+    commit: String,
+    /// Tagging date (commit date for lightweight tags), `YYYY-MM-DD`.
+    date: String,
+}
+
+fn git(repo: &Path, args: &[&str]) -> Result<String, ProcessError> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["-c", "core.quotePath=false"])
+        .args(args)
+        .output()
+        .map_err(|e| ProcessError::Other(format!("could not run git: {e}")))?;
+    if !output.status.success() {
+        return Err(ProcessError::Other(format!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Commits on `HEAD` within the bounds of `options`, newest first.
+fn log(repo: &Path, options: &HistoryOptions) -> Result<Vec<Commit>, ProcessError> {
+    let mut args = vec![
+        "log".to_string(),
+        "--no-color".to_string(),
+        "-M".to_string(),
+        format!("--format={RECORD}%H{FIELD}%an{FIELD}%aI{FIELD}%B{FIELD}"),
+        format!("--max-count={}", options.max_commits),
+        match options.diff_lines {
+            0 => "--raw".to_string(),
+            _ => "--patch-with-raw".to_string(),
+        },
+    ];
+    if let Some(since) = &options.since {
+        args.push(format!("--since={since}"));
+    }
+    if let Some(until) = &options.until {
+        args.push(format!("--until={until}"));
+    }
+    args.extend(["HEAD".to_string(), "--".to_string()]);
+    args.extend(options.paths.iter().cloned());
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let output = git(repo, &args)?;
+    Ok(output
+        .split(RECORD)
+        .filter(|record| !record.is_empty())
+        .filter_map(|record| parse_commit(record, options.diff_lines))
+        .collect())
+}
+
+/// Parse one `git log` record: the formatted fields, then `--raw` lines and patches.
+fn parse_commit(record: &str, diff_lines: usize) -> Option<Commit> {
+    let mut fields = record.splitn(5, FIELD);
+    let mut commit = Commit {
+        hash: fields.next()?.to_string(),
+        author: fields.next()?.to_string(),
+        date: fields.next()?.to_string(),
+        message: fields.next()?.trim().to_string(),
+        files: Vec::new(),
+    };
+    // The file whose patch is being read.
+    let mut patch: Option<usize> = None;
+    for line in fields.next()?.lines() {
+        if let Some(raw) = line.strip_prefix(':') {
+            let Some((modes, paths)) = raw.split_once('\t') else {
+                continue;
+            };
+            let status = modes.rsplit(' ').next().unwrap_or_default();
+            let mut paths = paths.split('\t');
+            let first = paths.next().unwrap_or_default().to_string();
+            let (path, from) = match paths.next() {
+                Some(to) => (to.to_string(), Some(first)),
+                None => (first, None),
+            };
+            commit.files.push(ChangedFile {
+                status: status.chars().take(1).collect(),
+                path,
+                from,
+                diff: Vec::new(),
+                omitted: 0,
+            });
+        } else if line.starts_with("diff --git ") {
+            // Patches follow the raw lines in the same order.
+            let next = patch.map_or(0, |file| file + 1);
+            patch = (next < commit.files.len()).then_some(next);
+        } else if let Some(file) = patch {
+            let file = &mut commit.files[file];
+            // Skip the `index`, `---` and `+++` lines before the first hunk.
+            if file.diff.is_empty() && !(line.starts_with("@@") || line.starts_with("Binary files"))
+            {
+                continue;
+            }
+            if file.diff.len() < diff_lines {
+                file.diff.push(line.to_string());
+            } else {
+                file.omitted += 1;
+            }
+        }
+    }
+    Some(commit)
+}
+
+/// Tags pointing into the history, oldest first.
+fn tags(repo: &Path) -> Result<Vec<Tag>, ProcessError> {
+    let output = git(
+        repo,
+        &[
+            "for-each-ref",
+            "--sort=refname",
+            "--sort=creatordate",
+            &format!(
+                "--format=%(refname:short){FIELD}%(objectname){FIELD}%(*objectname){FIELD}%(creatordate:short)"
+            ),
+            "refs/tags",
+        ],
+    )?;
+    Ok(output
+        .lines()
+        .filter_map(|line| {
+            let [name, object, peeled, date] = line.split(FIELD).collect::<Vec<_>>()[..] else {
+                return None;
+            };
+            Some(Tag {
+                name: name.to_string(),
+                commit: if peeled.is_empty() { object } else { peeled }.to_string(),
+                date: date.to_string(),
+            })
+        })
+        .collect())
+}
+
+/// The tag each commit was first released in: the oldest tag it is reachable from.
+fn releases(repo: &Path, tags: &[Tag]) -> Result<HashMap<String, String>, ProcessError> {
+    let mut releases = HashMap::new();
+    let mut previous: Option<&Tag> = None;
+    let mut seen = HashSet::new();
+    for tag in tags {
+        if !seen.insert(&tag.commit) {
+            // A second tag on an already released commit adds nothing.
+            continue;
+        }
+        let exclude = previous.map(|tag| format!("^{}", tag.commit));
+        let mut args = vec!["rev-list", tag.commit.as_str()];
+        args.extend(exclude.as_deref());
+        for commit in git(repo, &args)?.lines() {
+            releases
+                .entry(commit.to_string())
+                .or_insert_with(|| tag.name.clone());
+        }
+        previous = Some(tag);
+    }
+    Ok(releases)
+}
+
+/// The markdown section for one commit.
+fn section(commit: &Commit, tags: &[&str], root: Option<&str>) -> String {
+    let (subject, body) = commit
+        .message
+        .split_once('\n')
+        .map_or((commit.message.as_str(), ""), |(subject, body)| {
+            (subject, body.trim())
+        });
+    let short = &commit.hash[..commit.hash.len().min(10)];
+    let mut out = format!("## {subject}\n\n");
+    match root {
+        Some(root) => {
+            let _ = writeln!(out, "- Commit: [`{short}`]({root}/commit/{})", commit.hash);
+        }
+        None => {
+            let _ = writeln!(out, "- Commit: `{short}`");
+        }
+    }
+    let _ = writeln!(out, "- Author: {}", commit.author);
+    let _ = writeln!(out, "- Date: {}", commit.date);
+    if !tags.is_empty() {
+        let tags: Vec<String> = tags.iter().map(|tag| format!("`{tag}`")).collect();
+        let _ = writeln!(out, "- Tags: {}", tags.join(", "));
+    }
+    if !body.is_empty() {
+        let _ = write!(out, "\n{body}\n");
+    }
+    if !commit.files.is_empty() {
+        out.push_str("\nChanged files:\n\n");
+        for file in &commit.files {
+            match &file.from {
+                Some(from) => {
+                    let _ = writeln!(out, "- {} `{from}` → `{}`", file.status, file.path);
+                }
+                None => {
+                    let _ = writeln!(out, "- {} `{}`", file.status, file.path);
+                }
+            }
+        }
+    }
+    for file in commit.files.iter().filter(|file| !file.diff.is_empty()) {
+        let fence = code_fence(&file.diff.join("\n"));
+        let _ = write!(out, "\n`{}`:\n\n{fence}diff\n", file.path);
+        for line in &file.diff {
+            out.push_str(line);
+            out.push('\n');
+        }
+        if file.omitted > 0 {
+            let _ = writeln!(out, "… {} more lines", file.omitted);
+        }
+        let _ = writeln!(out, "{fence}");
+    }
+    out
+}
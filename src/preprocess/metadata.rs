@@ -0,0 +1,216 @@
+//! Provenance of processed items: where each original lives, when it last changed and
+//! what the uploaded content hashes to.
+//!
+//! Git checkouts are asked for their `HEAD` commit and the last commit touching each
+//! file, and links point at that commit so they keep resolving after the branch moves;
+//! an item holding one symbol links to its lines.
+//! Other sources use the [`SourceIndex`] their downloader wrote, falling back to file
+//! modification times.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::process::Command;
+use std::time::UNIX_EPOCH;
+
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
+
+use super::pipeline::{Item, StageContext};
+use crate::code_to_pdf::highlight::detect_language;
+use crate::contract::{ExternalItemInput, ItemMetadata};
+use crate::download::{utc_datetime, IndexedFile, SourceIndex};
+
+/// What is known about a source as a whole, loaded once before its items are described.
+#[derive(Debug, Clone, Default)]
+pub struct SourceInfo {
+    /// `HEAD` of a git checkout.
+    pub commit: Option<String>,
+    /// The repository's web root (`https://host/org/repo`), or the base URL of another
+    /// source with one.
+    web_root: Option<String>,
+    /// Last commit time per path, RFC 3339.
+    git_modified: HashMap<String, String>,
+    index: SourceIndex,
+    repo_path: std::path::PathBuf,
+}
+
+impl SourceInfo {
+    pub fn load(context: &StageContext) -> SourceInfo {
+        let repo = &context.repo_path;
+        let commit = repo
+            .join(".git")
+            .exists()
+            .then(|| git(repo, &["rev-parse", "HEAD"]))
+            .flatten()
+            .map(|out| out.trim().to_string());
+        let git_modified = match &commit {
+            Some(_) => git(repo, &["log", "--format=%x00%cI", "--name-only", "HEAD"])
+                .map(|log| last_commit_times(&log))
+                .unwrap_or_default(),
+            None => HashMap::new(),
+        };
+        debug!(commit = ?commit, files = git_modified.len(), "Loaded source provenance");
+        SourceInfo {
+            web_root: context
+                .base_url
+                .as_deref()
+                .map(|url| web_root(url, commit.is_some())),
+            commit,
+            git_modified,
+            index: SourceIndex::load(repo),
+            repo_path: repo.clone(),
+        }
+    }
+
+    /// What the downloader recorded about an original, by its relative path.
+    pub fn indexed(&self, origin: &str) -> Option<&IndexedFile> {
+        self.index.files.get(origin)
+    }
+
+    /// Metadata for an item produced from this source.
+    pub fn metadata(&self, item: &Item) -> ItemMetadata {
+        let indexed = self.index.files.get(&item.origin);
+        let url = match (&self.web_root, &self.commit) {
+            (Some(root), Some(commit)) if item.origin.is_empty() => {
+                Some(format!("{root}/tree/{commit}"))
+            }
+            (Some(root), Some(commit)) => Some(format!(
+                "{root}/blob/{commit}/{}{}",
+                encode_path(&item.origin),
+                item.symbol.as_ref().map_or(String::new(), |symbol| format!(
+                    "#L{}-L{}",
+                    symbol.start_line, symbol.end_line
+                ))
+            )),
+            (Some(base), None) if indexed.is_none() => {
+                Some(format!("{base}/{}", encode_path(&item.origin)))
+            }
+            _ => indexed.and_then(|file| file.url.clone()),
+        };
+        let modified = indexed
+            .and_then(|file| file.modified.clone())
+            .or_else(|| self.git_modified.get(&item.origin).cloned())
+            .or_else(|| file_modified(&self.repo_path.join(&item.origin)));
+        let language = detect_language(Path::new(&item.origin), item.text().unwrap_or(""))
+            .map(|language| language.name.to_string());
+        ItemMetadata {
+            path: item.origin.clone(),
+            url,
+            language,
+            size: item.content.len() as u64,
+            content_hash: sha256_hex(&item.content),
+            modified,
+            commit: self.commit.clone(),
+            breadcrumb: item.breadcrumb.clone(),
+            fingerprint: item.fingerprint(),
+            aliases: Vec::new(),
+            symbol: item.symbol.clone(),
+        }
+    }
+}
+
+/// The web root of a base URL: the repository root of a git checkout's blob URL
+/// (`https://host/org/repo/blob/main` → `https://host/org/repo`), otherwise the URL itself.
+pub(crate) fn web_root(base_url: &str, git: bool) -> String {
+    match git {
+        true => base_url
+            .split_once("/blob/")
+            .map_or(base_url, |(root, _)| root)
+            .to_string(),
+        false => base_url.trim_end_matches('/').to_string(),
+    }
+}
+
+/// Upload items for a batch, each with its metadata. Items derived from the same
+/// original (chunks) share its link, so theirs get the item filename as a fragment to
+/// stay unique.
+pub fn external_items(info: &SourceInfo, items: Vec<Item>) -> Vec<ExternalItemInput> {
+    let mut external: Vec<ExternalItemInput> = items
+        .into_iter()
+        .map(|item| {
+            let metadata = info.metadata(&item);
+            item.into_external(metadata)
+        })
+        .collect();
+    let mut shared: BTreeMap<String, usize> = BTreeMap::new();
+    for item in &external {
+        if let Some(url) = &item.metadata.url {
+            *shared.entry(url.clone()).or_default() += 1;
+        }
+    }
+    for item in &mut external {
+        if let Some(url) = &mut item.metadata.url {
+            if shared[url.as_str()] > 1 {
+                *url = format!("{url}#{}", item.filename);
+            }
+        }
+    }
+    external
+}
+
+fn git(repo: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git").arg("-C").arg(repo).args(args).output();
+    match output {
+        Ok(output) if output.status.success() => String::from_utf8(output.stdout).ok(),
+        Ok(output) => {
+            warn!(
+                args = ?args,
+                stderr = %String::from_utf8_lossy(&output.stderr).trim(),
+                "git failed while reading provenance"
+            );
+            None
+        }
+        Err(e) => {
+            warn!(error = %e, "Could not run git to read provenance");
+            None
+        }
+    }
+}
+
+/// Parse `git log --format=%x00%cI --name-only`, newest first, into the time each path
+/// last changed.
+fn last_commit_times(log: &str) -> HashMap<String, String> {
+    let mut times = HashMap::new();
+    let mut current = "";
+    for line in log.lines() {
+        if let Some(time) = line.strip_prefix('\0') {
+            current = time;
+        } else if !line.is_empty() {
+            times
+                .entry(line.to_string())
+                .or_insert_with(|| current.to_string());
+        }
+    }
+    times
+}
+
+fn file_modified(path: &Path) -> Option<String> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    let secs = modified.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+    let (year, month, day, hour, minute, second) = utc_datetime(secs);
+    Some(format!(
+        "{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}Z"
+    ))
+}
+
+/// Percent-encode the characters of a relative path that cannot appear in a URL path.
+fn encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+/// SHA-256 of `bytes`, lowercase hex.
+pub(crate) fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
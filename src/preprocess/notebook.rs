@@ -0,0 +1,208 @@
+//! Notebooks stage: converts Jupyter notebooks to markdown, so their prose, code and
+//! results are uploaded instead of raw JSON with base64-encoded images.
+//!
+//! Markdown cells are kept as they are and code cells become fenced blocks in the
+//! notebook's language, each followed by its text outputs (streams, results and error
+//! tracebacks without terminal colours), cut to [`NotebookOptions::output_chars`].
+//! Images, widgets and other outputs without a text form are summarised or dropped, and
+//! notebook metadata, widget state and cell attachments are left out.
+
+use std::fmt::Write as _;
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde_json::Value;
+use tracing::{debug, info, warn};
+
+use super::code_fence;
+use super::pipeline::{Batch, Item, Stage, StageContext};
+use crate::contract::{NotebookOptions, ProcessError, RichOutputs};
+
+/// Output MIME types used as an output's text, most preferred first.
+const TEXT_OUTPUTS: &[&str] = &["text/markdown", "text/plain"];
+
+/// Output MIME type of Jupyter widgets.
+const WIDGET: &str = "application/vnd.jupyter.widget-view+json";
+
+/// Terminal colour and cursor sequences in error tracebacks.
+static ANSI: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]").unwrap());
+
+/// Replace each `.ipynb` item with a markdown rendering named `<notebook>.md`.
+#[derive(Debug, Clone, Default)]
+pub struct Notebooks {
+    pub options: NotebookOptions,
+}
+
+impl Stage for Notebooks {
+    fn name(&self) -> &'static str {
+        "notebooks"
+    }
+
+    fn run(&self, _context: &StageContext, batch: Batch) -> Result<Batch, ProcessError> {
+        let mut converted = 0;
+        let items = batch
+            .items
+            .into_iter()
+            .map(|item| {
+                if !item.path.to_ascii_lowercase().ends_with(".ipynb") {
+                    return item;
+                }
+                let Some(text) = item.text() else {
+                    return item;
+                };
+                match notebook_markdown(text, &self.options) {
+                    Ok(markdown) => {
+                        debug!(path = %item.path, "Converted notebook");
+                        converted += 1;
+                        Item {
+                            path: format!("{}.md", item.path),
+                            content: markdown.into_bytes(),
+                            mime_type: Some("text/markdown".to_string()),
+                            fingerprint: None,
+                            ..item
+                        }
+                    }
+                    Err(reason) => {
+                        warn!(
+                            path = %item.path,
+                            reason = %reason,
+                            "Could not convert notebook; keeping it as is"
+                        );
+                        item
+                    }
+                }
+            })
+            .collect();
+        info!(notebooks = converted, "Converted notebooks to markdown");
+        Ok(Batch { items, ..batch })
+    }
+}
+
+/// Render a notebook (nbformat 4) as markdown, or say why it could not be read.
+pub fn notebook_markdown(json: &str, options: &NotebookOptions) -> Result<String, String> {
+    let notebook: Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    let cells = notebook
+        .get("cells")
+        .and_then(Value::as_array)
+        .ok_or("no `cells` list; only nbformat 4 notebooks are supported")?;
+    let metadata = &notebook["metadata"];
+    let language = metadata["kernelspec"]["language"]
+        .as_str()
+        .or(metadata["language_info"]["name"].as_str())
+        .unwrap_or_default();
+
+    let mut blocks: Vec<String> = Vec::new();
+    for cell in cells {
+        let source = joined(&cell["source"]);
+        let source = source.trim_end();
+        match cell["cell_type"].as_str() {
+            Some("markdown") if !source.trim().is_empty() => blocks.push(source.to_string()),
+            Some("code") => {
+                if !source.trim().is_empty() {
+                    let fence = code_fence(source);
+                    blocks.push(format!("{fence}{language}\n{source}\n{fence}"));
+                }
+                if options.output_chars > 0 {
+                    blocks.extend(outputs(&cell["outputs"], options));
+                }
+            }
+            Some("raw") if !source.trim().is_empty() => {
+                let fence = code_fence(source);
+                blocks.push(format!("{fence}\n{source}\n{fence}"));
+            }
+            _ => {}
+        }
+    }
+    let mut markdown = blocks.join("\n\n");
+    markdown.push('\n');
+    Ok(markdown)
+}
+
+/// The blocks for a code cell's outputs: one text block, cut to the limit, then one per
+/// summarised rich output.
+fn outputs(outputs: &Value, options: &NotebookOptions) -> Vec<String> {
+    let mut text = String::new();
+    let mut summaries = Vec::new();
+    for output in outputs.as_array().into_iter().flatten() {
+        match output["output_type"].as_str() {
+            Some("stream") => text.push_str(&joined(&output["text"])),
+            Some("execute_result" | "display_data") => {
+                let Some(data) = output["data"].as_object() else {
+                    continue;
+                };
+                let shown = TEXT_OUTPUTS
+                    .iter()
+                    .find_map(|mime_type| data.get(*mime_type).map(joined));
+                for mime_type in data.keys() {
+                    let rich = mime_type.starts_with("image/") || mime_type == WIDGET;
+                    if rich || (shown.is_none() && !TEXT_OUTPUTS.contains(&mime_type.as_str())) {
+                        summaries.push(match mime_type.as_str() {
+                            WIDGET => "[interactive widget]".to_string(),
+                            _ => format!("[{mime_type} output]"),
+                        });
+                    }
+                }
+                if let Some(shown) = shown {
+                    push_line(&mut text, &shown);
+                }
+            }
+            Some("error") => {
+                let traceback: Vec<String> = output["traceback"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str)
+                    .map(|line| ANSI.replace_all(line, "").into_owned())
+                    .collect();
+                let error = match traceback.is_empty() {
+                    true => format!(
+                        "{}: {}",
+                        output["ename"].as_str().unwrap_or("Error"),
+                        output["evalue"].as_str().unwrap_or_default()
+                    ),
+                    false => traceback.join("\n"),
+                };
+                push_line(&mut text, &error);
+            }
+            _ => {}
+        }
+    }
+
+    let mut blocks = Vec::new();
+    let text = text.trim_end();
+    if !text.trim().is_empty() {
+        let total = text.chars().count();
+        let mut shown: String = text.chars().take(options.output_chars).collect();
+        if total > options.output_chars {
+            let _ = write!(
+                shown,
+                "\n… {} more characters",
+                total - options.output_chars
+            );
+        }
+        let fence = code_fence(&shown);
+        blocks.push(format!("Output:\n\n{fence}text\n{shown}\n{fence}"));
+    }
+    if options.rich_outputs == RichOutputs::Summarise && !summaries.is_empty() {
+        blocks.extend(summaries);
+    }
+    blocks
+}
+
+/// A notebook string field, stored either as one string or as a list of lines.
+fn joined(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Array(lines) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+/// Append `line` to `text`, starting a new line if `text` does not end with one.
+fn push_line(text: &mut String, line: &str) {
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text.push_str(line);
+    text.push('\n');
+}
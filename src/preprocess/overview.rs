@@ -0,0 +1,587 @@
+//! Overview stage: adds one generated markdown item that says what a source is, so
+//! questions about a repository as a whole have something to retrieve.
+//!
+//! The overview is computed from the checkout alone, with the same ignore rules and
+//! include/exclude globs as [`Collect`](super::stages::Collect), and is the same for the
+//! same files. It holds, in order and leaving out empty sections:
+//! - the introduction of the root README: the paragraphs before its second heading;
+//! - languages by lines of code;
+//! - build manifests (`Cargo.toml`, `package.json`, `go.mod`, `pom.xml`) with the
+//!   dependencies they list;
+//! - entry points: Rust binaries and library roots, npm `main`/`bin`, Go `main` packages,
+//!   Java `main` methods and Python `__main__` modules;
+//! - the directory tree down to [`OverviewOptions::depth`].
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use regex::Regex;
+use tracing::{debug, info, warn};
+
+use super::pipeline::{Batch, Item, Stage, StageContext};
+use super::slash_path;
+use super::walk::{walk, Walk};
+use crate::code_to_pdf::highlight::detect_language;
+use crate::contract::{OverviewOptions, ProcessError};
+
+/// Path of the generated item.
+pub const OVERVIEW_FILE: &str = "_overview.md";
+
+/// Most README characters quoted in the introduction.
+const INTRO_CHARS: usize = 1500;
+/// Most entries listed per directory in the tree.
+const MAX_ENTRIES: usize = 30;
+
+/// Add an [`OVERVIEW_FILE`] item before the other items.
+#[derive(Debug, Clone, Default)]
+pub struct Overview {
+    pub options: OverviewOptions,
+}
+
+impl Stage for Overview {
+    fn name(&self) -> &'static str {
+        "overview"
+    }
+
+    fn run(&self, context: &StageContext, mut batch: Batch) -> Result<Batch, ProcessError> {
+        let Walk { files, .. } = walk(&context.repo_path, &context.options)?;
+        let files: Vec<String> = files.iter().map(|path| slash_path(path)).collect();
+        let content = overview(context, &files, &self.options);
+        info!(
+            files = files.len(),
+            size = content.len(),
+            "Generated source overview"
+        );
+        batch.items.insert(
+            0,
+            Item {
+                path: OVERVIEW_FILE.to_string(),
+                origin: String::new(),
+                content: content.into_bytes(),
+                mime_type: Some("text/markdown".to_string()),
+                breadcrumb: Vec::new(),
+                fingerprint: None,
+                symbol: None,
+            },
+        );
+        Ok(batch)
+    }
+}
+
+fn overview(context: &StageContext, files: &[String], options: &OverviewOptions) -> String {
+    let root = &context.repo_path;
+    let mut languages: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    let mut entry_points: BTreeMap<String, String> = BTreeMap::new();
+    let mut manifests = Vec::new();
+    let mut readme = None;
+    for path in files {
+        let Ok(text) = fs::read_to_string(root.join(path)) else {
+            continue;
+        };
+        let name = path.rsplit('/').next().unwrap_or(path);
+        // Configuration formats (TOML, YAML, INI) share one highlighting language; they
+        // are not code.
+        let language = detect_language(Path::new(path), &text).filter(|l| l.name != "yaml");
+        if let Some(language) = language {
+            let counts = languages.entry(language.name).or_default();
+            counts.0 += 1;
+            counts.1 += text.lines().count();
+        }
+        if let Some(kind) = entry_point(path, &text) {
+            entry_points.entry(path.clone()).or_insert(kind);
+        }
+        if let Some(manifest) = Manifest::parse(path, &text, files) {
+            manifests.push(manifest);
+        }
+        if !path.contains('/') && name.to_ascii_lowercase().starts_with("readme") {
+            // `README.md` sorts before `README.txt`; keep the first.
+            readme.get_or_insert(text);
+        }
+    }
+    for manifest in &manifests {
+        for (path, kind) in &manifest.entry_points {
+            entry_points
+                .entry(path.clone())
+                .or_insert_with(|| kind.clone());
+        }
+    }
+    debug!(
+        languages = languages.len(),
+        manifests = manifests.len(),
+        entry_points = entry_points.len(),
+        "Summarised source"
+    );
+
+    let mut out = format!("# {}\n\n", context.name.trim_end_matches('/'));
+    out.push_str(
+        "Overview generated from the source: README introduction, languages, build \
+         manifests, dependencies, entry points and directory tree.\n",
+    );
+    if let Some(intro) = readme.as_deref().map(intro).filter(|i| !i.is_empty()) {
+        let _ = write!(out, "\n## Introduction\n\n{intro}\n");
+    }
+    if !languages.is_empty() {
+        let mut by_lines: Vec<_> = languages.into_iter().collect();
+        by_lines.sort_by(|a, b| b.1 .1.cmp(&a.1 .1).then(a.0.cmp(b.0)));
+        out.push_str("\n## Languages\n\n| Language | Files | Lines |\n| --- | ---: | ---: |\n");
+        for (language, (files, lines)) in by_lines {
+            let _ = writeln!(out, "| {language} | {files} | {lines} |");
+        }
+    }
+    if !manifests.is_empty() {
+        out.push_str("\n## Build manifests\n");
+        for manifest in &manifests {
+            manifest.write(&mut out);
+        }
+    }
+    if !entry_points.is_empty() {
+        out.push_str("\n## Entry points\n\n");
+        for (path, kind) in &entry_points {
+            let _ = writeln!(out, "- `{path}`: {kind}");
+        }
+    }
+    if !files.is_empty() {
+        let _ = write!(
+            out,
+            "\n## Directory tree\n\n```text\n{}```\n",
+            tree(files, options.depth)
+        );
+    }
+    out
+}
+
+/// The README's paragraphs before its second heading, without headings, badges and HTML,
+/// cut at a paragraph once past [`INTRO_CHARS`].
+fn intro(readme: &str) -> String {
+    let mut paragraphs: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut headings = 0;
+    let mut length = 0;
+    for line in readme.lines().chain(std::iter::once("")) {
+        let trimmed = line.trim();
+        let underline = trimmed.starts_with("===") || trimmed.starts_with("---");
+        if trimmed.starts_with('#') || underline {
+            if underline && !current.is_empty() && !current.contains('\n') {
+                // The line above was a setext heading, not a paragraph.
+                current.clear();
+            }
+            headings += 1;
+            if headings > 1 && !(paragraphs.is_empty() && current.is_empty()) {
+                break;
+            }
+            continue;
+        }
+        if trimmed.starts_with("[![") || trimmed.starts_with("![") || trimmed.starts_with('<') {
+            continue;
+        }
+        if !trimmed.is_empty() {
+            if !current.is_empty() {
+                current.push('\n');
+            }
+            current.push_str(line.trim_end());
+            continue;
+        }
+        if current.is_empty() {
+            continue;
+        }
+        length += current.len();
+        paragraphs.push(std::mem::take(&mut current));
+        if length >= INTRO_CHARS {
+            break;
+        }
+    }
+    paragraphs.join("\n\n")
+}
+
+/// What kind of entry point a file is, judged from its path and content.
+fn entry_point(path: &str, text: &str) -> Option<String> {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let dir = path.rsplit_once('/').map_or("", |(dir, _)| dir);
+    let kind = match name.rsplit_once('.').map(|(_, ext)| ext) {
+        Some("rs") if name == "main.rs" && (dir == "src" || dir.ends_with("/src")) => "Rust binary",
+        Some("rs") if dir == "src/bin" || dir.ends_with("/src/bin") => "Rust binary",
+        Some("rs") if name == "lib.rs" && (dir == "src" || dir.ends_with("/src")) => "Rust library",
+        Some("go")
+            if text.lines().any(|line| line.trim() == "package main")
+                && text.contains("func main()") =>
+        {
+            "Go `main` package"
+        }
+        Some("java") if text.contains("static void main(") => "Java `main` method",
+        Some("py") if name == "__main__.py" => "Python `__main__` module",
+        Some("py")
+            if text.contains("if __name__ == \"__main__\"")
+                || text.contains("if __name__ == '__main__'") =>
+        {
+            "Python script"
+        }
+        _ => return None,
+    };
+    Some(kind.to_string())
+}
+
+/// A leaf element `<name>text</name>`; the open and close names are checked by the caller.
+static MAVEN_ELEMENT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<(\w+)>\s*([^<]*?)\s*</(\w+)>").unwrap());
+static MAVEN_NESTED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?s)<(parent|dependencies|dependencyManagement|build|profiles)>.*?</(parent|dependencies|dependencyManagement|build|profiles)>",
+    )
+    .unwrap()
+});
+static MAVEN_DEPENDENCY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<dependency>(.*?)</dependency>").unwrap());
+static MAVEN_MODULE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<module>\s*([^<]*?)\s*</module>").unwrap());
+static XML_COMMENT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<!--.*?-->").unwrap());
+
+/// A build manifest and what it declares.
+struct Manifest {
+    path: String,
+    /// Build tool, e.g. `Cargo`.
+    tool: &'static str,
+    /// Package, module or artifact name.
+    name: Option<String>,
+    /// Dependency lists by section, e.g. `dev-dependencies`.
+    dependencies: Vec<(String, Vec<String>)>,
+    /// Workspace or module members.
+    members: Vec<String>,
+    /// Entry points named by the manifest, relative to the source root.
+    entry_points: Vec<(String, String)>,
+}
+
+impl Manifest {
+    fn parse(path: &str, text: &str, files: &[String]) -> Option<Manifest> {
+        let (dir, name) = match path.rsplit_once('/') {
+            Some((dir, name)) => (format!("{dir}/"), name),
+            None => (String::new(), path),
+        };
+        let tool = match name {
+            "Cargo.toml" => "Cargo",
+            "package.json" => "npm",
+            "go.mod" => "Go modules",
+            "pom.xml" => "Maven",
+            _ => return None,
+        };
+        let mut manifest = Manifest {
+            path: path.to_string(),
+            tool,
+            name: None,
+            dependencies: Vec::new(),
+            members: Vec::new(),
+            entry_points: Vec::new(),
+        };
+        let parsed = match name {
+            "Cargo.toml" => manifest.cargo(text, &dir, files),
+            "package.json" => manifest.npm(text, &dir),
+            "go.mod" => manifest.go(text),
+            _ => manifest.maven(text),
+        };
+        if let Err(e) = parsed {
+            warn!(path, error = %e, "Could not read build manifest");
+        }
+        Some(manifest)
+    }
+
+    fn cargo(&mut self, text: &str, dir: &str, files: &[String]) -> Result<(), String> {
+        let table: toml::Table = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+        self.name = table
+            .get("package")
+            .and_then(|package| package.get("name"))
+            .and_then(toml::Value::as_str)
+            .map(str::to_string);
+        let mut sections = vec![
+            ("dependencies", table.get("dependencies")),
+            ("dev-dependencies", table.get("dev-dependencies")),
+            ("build-dependencies", table.get("build-dependencies")),
+        ];
+        let workspace = table.get("workspace");
+        sections.push((
+            "workspace dependencies",
+            workspace.and_then(|w| w.get("dependencies")),
+        ));
+        for (section, deps) in sections {
+            let Some(deps) = deps.and_then(toml::Value::as_table) else {
+                continue;
+            };
+            let list = deps
+                .iter()
+                .map(|(name, spec)| {
+                    let version = match spec {
+                        toml::Value::String(version) => Some(version.as_str()),
+                        spec => spec.get("version").and_then(toml::Value::as_str),
+                    };
+                    match version {
+                        Some(version) => format!("{name} {version}"),
+                        None => name.clone(),
+                    }
+                })
+                .collect::<Vec<_>>();
+            if !list.is_empty() {
+                self.dependencies.push((section.to_string(), list));
+            }
+        }
+        self.members = workspace
+            .and_then(|w| w.get("members"))
+            .and_then(toml::Value::as_array)
+            .map(|members| {
+                members
+                    .iter()
+                    .filter_map(|m| m.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let bins = table.get("bin").and_then(toml::Value::as_array);
+        for bin in bins.into_iter().flatten() {
+            let path = bin
+                .get("path")
+                .and_then(toml::Value::as_str)
+                .map(str::to_string)
+                .or_else(|| {
+                    let name = bin.get("name")?.as_str()?;
+                    Some(format!("src/bin/{name}.rs"))
+                });
+            if let Some(path) = path {
+                let path = format!("{dir}{path}");
+                if files.contains(&path) {
+                    self.entry_points.push((path, "Rust binary".to_string()));
+                }
+            }
+        }
+        let lib = table
+            .get("lib")
+            .and_then(|lib| lib.get("path"))
+            .and_then(toml::Value::as_str);
+        if let Some(lib) = lib {
+            self.entry_points
+                .push((format!("{dir}{lib}"), "Rust library".to_string()));
+        }
+        Ok(())
+    }
+
+    fn npm(&mut self, text: &str, dir: &str) -> Result<(), String> {
+        let json: serde_json::Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        self.name = json["name"].as_str().map(str::to_string);
+        for section in [
+            "dependencies",
+            "devDependencies",
+            "peerDependencies",
+            "optionalDependencies",
+        ] {
+            let Some(deps) = json[section].as_object() else {
+                continue;
+            };
+            let list: Vec<String> = deps
+                .iter()
+                .map(|(name, version)| match version.as_str() {
+                    Some(version) => format!("{name} {version}"),
+                    None => name.clone(),
+                })
+                .collect();
+            if !list.is_empty() {
+                self.dependencies.push((section.to_string(), list));
+            }
+        }
+        if let Some(workspaces) = json["workspaces"].as_array() {
+            self.members = workspaces
+                .iter()
+                .filter_map(|w| w.as_str().map(str::to_string))
+                .collect();
+        }
+        let mut entry = |path: &str, kind: String| {
+            let path = path.trim_start_matches("./");
+            self.entry_points.push((format!("{dir}{path}"), kind));
+        };
+        for field in ["main", "module"] {
+            if let Some(path) = json[field].as_str() {
+                entry(path, format!("npm `{field}`"));
+            }
+        }
+        match &json["bin"] {
+            serde_json::Value::String(path) => entry(path, "npm `bin`".to_string()),
+            serde_json::Value::Object(bins) => {
+                for (name, path) in bins {
+                    if let Some(path) = path.as_str() {
+                        entry(path, format!("npm `bin` `{name}`"));
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// `go.mod` is read line by line; it cannot fail.
+    fn go(&mut self, text: &str) -> Result<(), String> {
+        let mut required = Vec::new();
+        let mut in_require = false;
+        for line in text.lines() {
+            let line = line.trim();
+            if let Some(module) = line.strip_prefix("module ") {
+                self.name = Some(module.trim().trim_matches('"').to_string());
+            } else if line == "require (" {
+                in_require = true;
+            } else if in_require && line == ")" {
+                in_require = false;
+            } else if let Some(requirement) = line.strip_prefix("require ") {
+                required.push(go_requirement(requirement));
+            } else if in_require && !line.is_empty() && !line.starts_with("//") {
+                required.push(go_requirement(line));
+            }
+        }
+        if !required.is_empty() {
+            self.dependencies.push(("require".to_string(), required));
+        }
+        Ok(())
+    }
+
+    /// `pom.xml` is searched for elements rather than parsed; it cannot fail.
+    fn maven(&mut self, text: &str) -> Result<(), String> {
+        let element = |name: &str, text: &str| {
+            MAVEN_ELEMENT
+                .captures_iter(text)
+                .find(|c| &c[1] == name && &c[3] == name)
+                .map(|c| c[2].to_string())
+        };
+        let text = XML_COMMENT.replace_all(text, "");
+        let project = MAVEN_NESTED.replace_all(&text, "");
+        self.name = match (
+            element("groupId", &project),
+            element("artifactId", &project),
+        ) {
+            (Some(group), Some(artifact)) => Some(format!("{group}:{artifact}")),
+            (None, artifact) => artifact,
+            (group, None) => group,
+        };
+        let mut by_scope: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for dependency in MAVEN_DEPENDENCY.captures_iter(&text) {
+            let body = &dependency[1];
+            let (Some(group), Some(artifact)) =
+                (element("groupId", body), element("artifactId", body))
+            else {
+                continue;
+            };
+            let scope = element("scope", body).unwrap_or_else(|| "compile".to_string());
+            let entry = match element("version", body) {
+                Some(version) => format!("{group}:{artifact} {version}"),
+                None => format!("{group}:{artifact}"),
+            };
+            by_scope
+                .entry(format!("{scope} dependencies"))
+                .or_default()
+                .push(entry);
+        }
+        self.dependencies.extend(by_scope);
+        self.members = MAVEN_MODULE
+            .captures_iter(&text)
+            .map(|c| c[1].to_string())
+            .collect();
+        Ok(())
+    }
+
+    fn write(&self, out: &mut String) {
+        let _ = write!(out, "\n### `{}` ({})\n\n", self.path, self.tool);
+        if let Some(name) = &self.name {
+            let _ = writeln!(out, "- Name: `{name}`");
+        }
+        if !self.members.is_empty() {
+            let _ = writeln!(out, "- Members: {}", code_list(&self.members));
+        }
+        for (section, dependencies) in &self.dependencies {
+            let _ = writeln!(out, "- {section}: {}", code_list(dependencies));
+        }
+        if self.name.is_none() && self.members.is_empty() && self.dependencies.is_empty() {
+            out.push_str("- No name or dependencies found\n");
+        }
+    }
+}
+
+/// `github.com/pkg/errors v0.9.1 // indirect` → `github.com/pkg/errors v0.9.1 (indirect)`.
+fn go_requirement(line: &str) -> String {
+    match line.split_once("//") {
+        Some((requirement, comment)) if comment.trim() == "indirect" => {
+            format!("{} (indirect)", requirement.trim())
+        }
+        Some((requirement, _)) => requirement.trim().to_string(),
+        None => line.trim().to_string(),
+    }
+}
+
+fn code_list(items: &[String]) -> String {
+    items
+        .iter()
+        .map(|item| format!("`{item}`"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A directory in the tree: its subdirectories and files.
+#[derive(Default)]
+struct Dir<'a> {
+    dirs: BTreeMap<&'a str, Dir<'a>>,
+    files: Vec<&'a str>,
+}
+
+impl<'a> Dir<'a> {
+    fn file_count(&self) -> usize {
+        self.files.len() + self.dirs.values().map(Dir::file_count).sum::<usize>()
+    }
+}
+
+/// Box-drawn tree of `files`, directories first, listing `depth` levels; a directory at
+/// the limit shows its file count instead.
+fn tree(files: &[String], depth: usize) -> String {
+    let mut root = Dir::default();
+    for path in files {
+        let mut dir = &mut root;
+        let mut parts = path.split('/').peekable();
+        while let Some(part) = parts.next() {
+            if parts.peek().is_none() {
+                dir.files.push(part);
+            } else {
+                dir = dir.dirs.entry(part).or_default();
+            }
+        }
+    }
+    let mut out = String::from("./\n");
+    write_tree(&root, "", depth, &mut out);
+    out
+}
+
+fn write_tree(dir: &Dir, indent: &str, depth: usize, out: &mut String) {
+    let entries: Vec<(&str, Option<&Dir>)> = dir
+        .dirs
+        .iter()
+        .map(|(name, sub)| (*name, Some(sub)))
+        .chain(dir.files.iter().map(|name| (*name, None)))
+        .collect();
+    let shown = entries.len().min(MAX_ENTRIES);
+    for (i, (name, sub)) in entries.iter().take(shown).enumerate() {
+        let last = i + 1 == entries.len();
+        let (branch, nested) = match last {
+            true => ("└── ", "    "),
+            false => ("├── ", "│   "),
+        };
+        match sub {
+            Some(sub) if depth > 1 => {
+                let _ = writeln!(out, "{indent}{branch}{name}/");
+                write_tree(sub, &format!("{indent}{nested}"), depth - 1, out);
+            }
+            Some(sub) => {
+                let count = sub.file_count();
+                let files = if count == 1 { "file" } else { "files" };
+                let _ = writeln!(out, "{indent}{branch}{name}/ ({count} {files})");
+            }
+            None => {
+                let _ = writeln!(out, "{indent}{branch}{name}");
+            }
+        }
+    }
+    if entries.len() > shown {
+        let _ = writeln!(out, "{indent}└── … {} more", entries.len() - shown);
+    }
+}
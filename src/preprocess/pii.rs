@@ -0,0 +1,213 @@
+//! PII scrubbing stage: masks or pseudonymises email addresses, phone numbers, IBANs and
+//! personal names in text items.
+//!
+//! Pseudonyms are 64 bits of a keyed hash whose key is drawn once per process, so the
+//! same value gets the same token in every item and source of a run but cannot be linked
+//! across runs. Names are the configured ones plus display names written next to an
+//! email address, collected from the whole batch, i.e. one source, before anything is
+//! replaced. A name found next to an address in one source is not looked for in other
+//! sources; list names that must be scrubbed everywhere in `names`.
+
+use std::collections::BTreeMap;
+use std::hash::{BuildHasher, RandomState};
+use std::sync::{LazyLock, OnceLock};
+
+use regex::Regex;
+use tracing::debug;
+
+use super::pipeline::{Batch, Stage, StageContext};
+use crate::contract::{PiiCategory, PiiMode, PiiOptions, ProcessError};
+
+const EMAIL: &str = r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}";
+
+static EMAIL_ADDRESS: LazyLock<Regex> = LazyLock::new(|| Regex::new(EMAIL).unwrap());
+/// A display name of two or more capitalised words before an address: `Jane Doe <jane@…>`.
+static DISPLAY_NAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r#""?(\p{{Lu}}[\p{{L}}'-]+(?: \p{{Lu}}[\p{{L}}'-]+)+)"?\s*<{EMAIL}>"#
+    ))
+    .unwrap()
+});
+static IBAN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b[A-Z]{2}\d{2}(?: ?[A-Z0-9]{4}){2,7}(?: ?[A-Z0-9]{1,3})?\b").unwrap()
+});
+/// International (`+31 6 1234 5678`), trunk-prefixed (`020-123 4567`), parenthesised area
+/// codes (`(555) 123-4567`) and North American `555-123-4567`.
+static PHONE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?:\+|\b0)\d[\d ().-]{6,}\d|\(\d{2,4}\) ?\d[\d .-]{5,}\d|\b\d{3}[-.]\d{3}[-.]\d{4}\b",
+    )
+    .unwrap()
+});
+
+/// Scrub personal data from every text item; binary items pass through.
+#[derive(Debug, Clone, Default)]
+pub struct PiiScrubber {
+    pub options: PiiOptions,
+}
+
+impl Stage for PiiScrubber {
+    fn name(&self) -> &'static str {
+        "pii"
+    }
+
+    fn run(&self, _context: &StageContext, mut batch: Batch) -> Result<Batch, ProcessError> {
+        let enabled = |category| self.options.categories.contains(&category);
+        let mut names: Vec<String> = self
+            .options
+            .names
+            .iter()
+            .map(|name| normalise_name(name))
+            .filter(|name| !name.is_empty())
+            .collect();
+        if enabled(PiiCategory::Name) {
+            for item in &batch.items {
+                if let Some(text) = item.text() {
+                    names.extend(DISPLAY_NAME.captures_iter(text).map(|c| c[1].to_string()));
+                }
+            }
+        }
+        let detectors = detectors(&self.options.categories, names);
+
+        for item in &mut batch.items {
+            let Some(text) = item.text() else {
+                continue;
+            };
+            let (scrubbed, counts) = scrub(text, &detectors, self.options.mode);
+            if counts.is_empty() {
+                continue;
+            }
+            debug!(path = %item.path, counts = ?counts, "Scrubbed personal data");
+            for (category, count) in counts {
+                *batch.pii.entry(category).or_default() += count;
+            }
+            item.content = scrubbed.into_bytes();
+        }
+        Ok(batch)
+    }
+}
+
+struct Detector {
+    category: PiiCategory,
+    regex: Regex,
+}
+
+/// Detectors for the enabled categories, most specific first.
+fn detectors(categories: &[PiiCategory], mut names: Vec<String>) -> Vec<Detector> {
+    let mut detectors = Vec::new();
+    let mut add = |category, regex: &Regex| {
+        if categories.contains(&category) {
+            detectors.push(Detector {
+                category,
+                regex: regex.clone(),
+            });
+        }
+    };
+    add(PiiCategory::Email, &EMAIL_ADDRESS);
+    add(PiiCategory::Iban, &IBAN);
+    add(PiiCategory::Phone, &PHONE);
+    // Longest first, so `Jane Doe` wins over `Jane`.
+    names.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+    names.dedup();
+    if !names.is_empty() {
+        let alternatives: Vec<String> = names
+            .iter()
+            .map(|name| regex::escape(name).replace(' ', r"\s+"))
+            .collect();
+        let pattern = format!(r"\b(?:{})\b", alternatives.join("|"));
+        add(PiiCategory::Name, &Regex::new(&pattern).unwrap());
+    }
+    detectors
+}
+
+/// The text with every detected value replaced, and how many were replaced per category.
+fn scrub(
+    text: &str,
+    detectors: &[Detector],
+    mode: PiiMode,
+) -> (String, BTreeMap<PiiCategory, usize>) {
+    // Byte ranges of accepted values; earlier detectors win overlaps.
+    let mut spans: Vec<(usize, usize, PiiCategory)> = Vec::new();
+    for detector in detectors {
+        for found in detector.regex.find_iter(text) {
+            let valid = match detector.category {
+                PiiCategory::Iban => iban_checksum_ok(found.as_str()),
+                PiiCategory::Phone => {
+                    (8..=15).contains(&found.as_str().chars().filter(char::is_ascii_digit).count())
+                }
+                _ => true,
+            };
+            let (start, end) = (found.start(), found.end());
+            if valid && !spans.iter().any(|&(s, e, _)| start < e && s < end) {
+                spans.push((start, end, detector.category));
+            }
+        }
+    }
+    spans.sort_unstable();
+
+    let mut scrubbed = String::with_capacity(text.len());
+    let mut counts = BTreeMap::new();
+    let mut last = 0;
+    for (start, end, category) in spans {
+        scrubbed.push_str(&text[last..start]);
+        scrubbed.push_str(&replacement(category, &text[start..end], mode));
+        *counts.entry(category).or_default() += 1;
+        last = end;
+    }
+    scrubbed.push_str(&text[last..]);
+    (scrubbed, counts)
+}
+
+fn replacement(category: PiiCategory, value: &str, mode: PiiMode) -> String {
+    let label = category.to_string().to_ascii_uppercase();
+    match mode {
+        PiiMode::Mask => format!("[{label}]"),
+        PiiMode::Pseudonymise => {
+            static KEY: OnceLock<RandomState> = OnceLock::new();
+            let hash = KEY
+                .get_or_init(RandomState::new)
+                .hash_one((category, canonical(category, value)));
+            format!("[{label}:{hash:016x}]")
+        }
+    }
+}
+
+/// The form two spellings of the same value share: lowercase emails, phone digits,
+/// IBANs without spaces and names with single spaces.
+fn canonical(category: PiiCategory, value: &str) -> String {
+    match category {
+        PiiCategory::Email => value.to_lowercase(),
+        PiiCategory::Phone => value
+            .chars()
+            .filter(|c| c.is_ascii_digit() || *c == '+')
+            .collect(),
+        PiiCategory::Iban => value.chars().filter(|c| !c.is_whitespace()).collect(),
+        PiiCategory::Name => normalise_name(value),
+    }
+}
+
+fn normalise_name(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// ISO 13616 check: move the first four characters to the end, map letters to 10..35
+/// and require the number to be 1 modulo 97.
+fn iban_checksum_ok(iban: &str) -> bool {
+    let compact: Vec<char> = iban.chars().filter(|c| !c.is_whitespace()).collect();
+    if !(15..=34).contains(&compact.len()) {
+        return false;
+    }
+    let mut remainder = 0u32;
+    for c in compact[4..].iter().chain(&compact[..4]) {
+        let value = match c.to_digit(36) {
+            Some(value) => value,
+            None => return false,
+        };
+        remainder = if value < 10 {
+            (remainder * 10 + value) % 97
+        } else {
+            (remainder * 100 + value) % 97
+        };
+    }
+    remainder == 1
+}
@@ -0,0 +1,218 @@
+//! Processing pipelines: an ordered list of [`Stage`]s that each transform a [`Batch`] of
+//! items.
+//!
+//! The first stage is a source stage that reads items from the downloaded source
+//! ([`Collect`](super::stages::Collect), [`Readme`](super::stages::Readme) or
+//! [`History`](super::history::History), which may also follow another); later stages
+//! convert, split or drop items and record what they left out in [`Batch::skipped`].
+//! Every [`ProcessorKind`](crate::contract::ProcessorKind) is a preset pipeline.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use tracing::{debug, info};
+
+use super::api_schema::ApiSchemas;
+use super::chunk::Chunker;
+use super::content::is_text_mime;
+use super::header::Header;
+use super::history::History;
+use super::notebook::Notebooks;
+use super::overview::Overview;
+use super::pii::PiiScrubber;
+use super::redact::Redactor;
+use super::stages::{CodeToPdf, Collect, DirectoryToPdf, MarkdownToPdf, Readme};
+use super::symbols::Symbols;
+use crate::contract::{
+    ExternalItemInput, ItemMetadata, PiiCategory, ProcessError, ProcessInput, SecretFinding,
+    SkippedPath, StageConfig, Symbol,
+};
+use crate::download::SourceOptions;
+
+/// A document flowing through a pipeline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Item {
+    /// Path relative to the source root, `/`-separated. Stages that derive new documents
+    /// name them after their input (`src/main.rs` → `src/main.rs.pdf`).
+    pub path: String,
+    /// Path of the original document the item came from, relative to the source root;
+    /// kept by stages that derive new items (a directory for `directory_to_pdf`).
+    pub origin: String,
+    pub content: Vec<u8>,
+    /// MIME type of `content`, when known.
+    pub mime_type: Option<String>,
+    /// Headings enclosing the item, outermost first; set by the chunk stage.
+    pub breadcrumb: Vec<String>,
+    /// Normalised content hash, kept when a stage renders or rewraps the content so
+    /// copies still match; computed from `content` when `None`.
+    pub fingerprint: Option<String>,
+    /// The declaration the item holds; set by the symbols stage.
+    pub symbol: Option<Symbol>,
+}
+
+impl Item {
+    /// The upload item, named by flattening `path` with `__` separators.
+    pub fn into_external(self, metadata: ItemMetadata) -> ExternalItemInput {
+        ExternalItemInput {
+            filename: super::flat_name(std::path::Path::new(&self.path)),
+            content: self.content,
+            mime_type: self.mime_type,
+            metadata,
+        }
+    }
+
+    /// The recorded fingerprint, or one computed from the current content.
+    pub fn fingerprint(&self) -> String {
+        self.fingerprint
+            .clone()
+            .unwrap_or_else(|| super::dedup::fingerprint(&self.content))
+    }
+
+    /// The content as text, unless its MIME type is not a text type or it is not UTF-8.
+    pub fn text(&self) -> Option<&str> {
+        match self.mime_type.as_deref() {
+            Some(mime_type) if !is_text_mime(mime_type) => None,
+            _ => std::str::from_utf8(&self.content).ok(),
+        }
+    }
+}
+
+/// Items passed between stages, with the paths left out, secrets found and personal
+/// data scrubbed so far.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Batch {
+    pub items: Vec<Item>,
+    pub skipped: Vec<SkippedPath>,
+    pub findings: Vec<SecretFinding>,
+    pub pii: BTreeMap<PiiCategory, usize>,
+}
+
+/// The source being processed, shared by every stage.
+#[derive(Debug, Clone)]
+pub struct StageContext {
+    /// Logical source name, used in PDF page headers.
+    pub name: String,
+    pub repo_path: PathBuf,
+    /// Web URL relative links resolve against, when the source has one.
+    pub base_url: Option<String>,
+    /// Per-source include/exclude globs.
+    pub options: SourceOptions,
+    /// Values describing the source for header templates (`type`, `repository`, ...).
+    pub variables: BTreeMap<String, String>,
+}
+
+impl From<&ProcessInput> for StageContext {
+    fn from(input: &ProcessInput) -> Self {
+        StageContext {
+            name: input.name.clone(),
+            repo_path: input.repo_path.clone(),
+            base_url: input.base_url.clone(),
+            options: input.options.clone(),
+            variables: input.variables.clone(),
+        }
+    }
+}
+
+/// One step of a pipeline.
+pub trait Stage: Send + Sync {
+    /// Name used in logs, matching the `stage` key in config.
+    fn name(&self) -> &'static str;
+
+    /// Transform `batch`; an error aborts the whole pipeline.
+    fn run(&self, context: &StageContext, batch: Batch) -> Result<Batch, ProcessError>;
+}
+
+/// An ordered list of stages.
+pub struct Pipeline {
+    stages: Vec<Box<dyn Stage>>,
+}
+
+impl Pipeline {
+    pub fn new(stages: Vec<Box<dyn Stage>>) -> Self {
+        Pipeline { stages }
+    }
+
+    /// Build the stages described by config.
+    pub fn from_config(stages: &[StageConfig]) -> Self {
+        let notebooks = stages
+            .iter()
+            .any(|stage| matches!(stage, StageConfig::Notebooks(_)));
+        Pipeline::new(
+            stages
+                .iter()
+                .map(|stage| build_stage(stage, notebooks))
+                .collect(),
+        )
+    }
+
+    /// Names of the stages, in order.
+    pub fn stage_names(&self) -> Vec<&'static str> {
+        self.stages.iter().map(|s| s.name()).collect()
+    }
+
+    /// Run every stage in order, starting from an empty batch.
+    pub fn run(&self, context: &StageContext) -> Result<Batch, ProcessError> {
+        let mut batch = Batch::default();
+        for stage in &self.stages {
+            batch = stage.run(context, batch)?;
+            debug!(
+                stage = stage.name(),
+                items = batch.items.len(),
+                skipped = batch.skipped.len(),
+                "Stage completed"
+            );
+        }
+        info!(
+            stages = self.stages.len(),
+            items = batch.items.len(),
+            "Pipeline completed"
+        );
+        Ok(batch)
+    }
+}
+
+/// `notebooks` is whether the pipeline converts notebooks, so collect reads them whatever
+/// their size.
+fn build_stage(config: &StageConfig, notebooks: bool) -> Box<dyn Stage> {
+    match config {
+        StageConfig::Collect(policy) => Box::new(Collect {
+            policy: policy.clone(),
+            notebooks,
+        }),
+        StageConfig::Readme => Box::new(Readme),
+        StageConfig::MarkdownToPdf => Box::new(MarkdownToPdf),
+        StageConfig::CodeToPdf(options) => Box::new(CodeToPdf {
+            options: options.clone(),
+        }),
+        StageConfig::DirectoryToPdf(options) => Box::new(DirectoryToPdf {
+            options: options.clone(),
+        }),
+        StageConfig::Chunk(options) => Box::new(Chunker {
+            options: options.clone(),
+        }),
+        StageConfig::Redact(options) => Box::new(Redactor {
+            options: options.clone(),
+        }),
+        StageConfig::Pii(options) => Box::new(PiiScrubber {
+            options: options.clone(),
+        }),
+        StageConfig::Header(options) => Box::new(Header {
+            options: options.clone(),
+        }),
+        StageConfig::Symbols(options) => Box::new(Symbols {
+            options: options.clone(),
+        }),
+        StageConfig::Overview(options) => Box::new(Overview {
+            options: options.clone(),
+        }),
+        StageConfig::History(options) => Box::new(History {
+            options: options.clone(),
+        }),
+        StageConfig::Notebooks(options) => Box::new(Notebooks {
+            options: options.clone(),
+        }),
+        StageConfig::ApiSchemas(options) => Box::new(ApiSchemas {
+            options: options.clone(),
+        }),
+    }
+}
@@ -0,0 +1,299 @@
+//! Secret redaction stage: finds credentials in text items and replaces each one with
+//! `[REDACTED:<rule>]`, or drops the whole file.
+//!
+//! Known token formats (AWS keys, GitHub tokens, JWTs, private key blocks) are matched
+//! first, then assignments to password-like names, then long high-entropy strings. Lockfiles
+//! and checksum-shaped values (`h1:…`, `sha512-…`) are not scanned for entropy, since
+//! dependency hashes look as random as any key. Every finding is recorded with its path,
+//! line and rule, never the secret itself.
+
+use std::sync::LazyLock;
+
+use globset::GlobMatcher;
+use regex::{Captures, Regex};
+use tracing::{debug, error, warn};
+
+use super::pipeline::{Batch, Stage, StageContext};
+use super::walk::PathFilter;
+use crate::contract::{ProcessError, RedactOptions, SecretFinding, SkipReason, SkippedPath};
+
+/// Strings shorter than this are never flagged for entropy alone.
+const ENTROPY_MIN_LEN: usize = 20;
+/// Shannon entropy, in bits per character, above which a string counts as random.
+const ENTROPY_THRESHOLD: f64 = 4.5;
+/// Dependency lockfiles, full of package hashes; not scanned for entropy.
+const LOCKFILES: &[&str] = &[
+    "go.sum",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "Cargo.lock",
+    "poetry.lock",
+    "Pipfile.lock",
+    "composer.lock",
+    "Gemfile.lock",
+];
+/// Prefixes of checksums in lockfiles and subresource integrity values.
+const CHECKSUM_PREFIXES: &[&str] = &["h1:", "sha1-", "sha256-", "sha384-", "sha512-"];
+
+/// Redact secrets in every text item; binary items pass through unscanned.
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    pub options: RedactOptions,
+}
+
+impl Stage for Redactor {
+    fn name(&self) -> &'static str {
+        "redact"
+    }
+
+    fn run(&self, _context: &StageContext, batch: Batch) -> Result<Batch, ProcessError> {
+        let scanner = Scanner::new(&self.options)?;
+        let mut output = Batch {
+            items: Vec::with_capacity(batch.items.len()),
+            skipped: batch.skipped,
+            findings: batch.findings,
+            pii: batch.pii,
+        };
+        for mut item in batch.items {
+            if scanner.allows_path(&item.path) {
+                debug!(path = %item.path, "Not scanning allowlisted file for secrets");
+                output.items.push(item);
+                continue;
+            }
+            let Some(text) = item.text() else {
+                output.items.push(item);
+                continue;
+            };
+            let lockfile = LOCKFILES.contains(&item.path.rsplit('/').next().unwrap_or_default());
+            let (redacted, findings) = scanner.redact(text, !lockfile);
+            if findings.is_empty() {
+                output.items.push(item);
+                continue;
+            }
+            for (line, rule) in &findings {
+                warn!(path = %item.path, line, rule, "Secret found");
+                output.findings.push(SecretFinding {
+                    path: item.path.clone(),
+                    line: *line,
+                    rule,
+                });
+            }
+            if self.options.drop_files {
+                debug!(path = %item.path, "Dropping file with secrets");
+                output.skipped.push(SkippedPath {
+                    path: item.path,
+                    reason: SkipReason::Secrets(findings.len()),
+                });
+            } else {
+                item.content = redacted.into_bytes();
+                output.items.push(item);
+            }
+        }
+        if self.options.strict && !output.findings.is_empty() {
+            error!(
+                findings = output.findings.len(),
+                "Secrets found in strict mode"
+            );
+            return Err(ProcessError::Secrets(output.findings));
+        }
+        Ok(output)
+    }
+}
+
+struct Rule {
+    name: &'static str,
+    /// The secret is the first capture group that took part in the match, or the whole
+    /// match when the regex has no groups.
+    regex: Regex,
+}
+
+/// Strings that are checked for entropy.
+static ENTROPY_CANDIDATE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[A-Za-z0-9+/=_-]{20,}").unwrap());
+
+/// Compiled allowlists for one run of the stage.
+struct Scanner {
+    entropy: bool,
+    allow: Vec<Regex>,
+    allow_paths: Vec<(GlobMatcher, bool)>,
+}
+
+impl Scanner {
+    fn new(options: &RedactOptions) -> Result<Self, ProcessError> {
+        let allow = options
+            .allow
+            .iter()
+            .map(|pattern| Regex::new(pattern))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| ProcessError::Other(format!("redact.allow: {e}")))?;
+        let allow_paths = options
+            .allow_paths
+            .iter()
+            .map(|pattern| {
+                let trimmed = pattern.trim_start_matches('/');
+                PathFilter::glob(trimmed).map(|g| (g.compile_matcher(), !trimmed.contains('/')))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| ProcessError::Other(format!("redact.allow_paths: {e}")))?;
+        Ok(Scanner {
+            entropy: options.entropy,
+            allow,
+            allow_paths,
+        })
+    }
+
+    fn allows_path(&self, path: &str) -> bool {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        self.allow_paths
+            .iter()
+            .any(|(glob, name_only)| glob.is_match(if *name_only { name } else { path }))
+    }
+
+    fn allowed(&self, secret: &str) -> bool {
+        self.allow.iter().any(|allow| allow.is_match(secret))
+    }
+
+    /// The text with every secret replaced, and the line and rule of each finding.
+    /// `entropy` is false for files whose random-looking strings are not secrets.
+    fn redact(&self, text: &str, entropy: bool) -> (String, Vec<(usize, &'static str)>) {
+        // Byte ranges of accepted secrets; earlier rules win overlaps.
+        let mut spans: Vec<(usize, usize, &'static str)> = Vec::new();
+        let mut accept = |start: usize, end: usize, rule: &'static str| {
+            if !spans.iter().any(|&(s, e, _)| start < e && s < end) {
+                spans.push((start, end, rule));
+            }
+        };
+        for rule in RULES.iter() {
+            for caps in rule.regex.captures_iter(text) {
+                let secret = secret_of(&caps);
+                if rule.name == "password_assignment" && !looks_like_password(secret.as_str()) {
+                    continue;
+                }
+                if !self.allowed(secret.as_str()) {
+                    accept(secret.start(), secret.end(), rule.name);
+                }
+            }
+        }
+        if self.entropy && entropy {
+            for candidate in ENTROPY_CANDIDATE.find_iter(text) {
+                if is_random(candidate.as_str())
+                    && !is_checksum(&text[..candidate.start()], candidate.as_str())
+                    && !self.allowed(candidate.as_str())
+                {
+                    accept(candidate.start(), candidate.end(), "high_entropy");
+                }
+            }
+        }
+        spans.sort_unstable();
+
+        let mut redacted = String::with_capacity(text.len());
+        let mut findings = Vec::with_capacity(spans.len());
+        let mut last = 0;
+        for (start, end, rule) in spans {
+            redacted.push_str(&text[last..start]);
+            redacted.push_str(&format!("[REDACTED:{rule}]"));
+            findings.push((text[..start].matches('\n').count() + 1, rule));
+            last = end;
+        }
+        redacted.push_str(&text[last..]);
+        (redacted, findings)
+    }
+}
+
+/// Known token formats, then password-like assignments, in the order they are applied.
+static RULES: LazyLock<Vec<Rule>> = LazyLock::new(|| {
+    let rule = |name, pattern: &str| Rule {
+        name,
+        regex: Regex::new(pattern).unwrap(),
+    };
+    vec![
+        rule(
+            "private_key",
+            r"-----BEGIN [A-Z0-9 ]*PRIVATE KEY( BLOCK)?-----[\s\S]*?-----END [A-Z0-9 ]*PRIVATE KEY( BLOCK)?-----",
+        ),
+        rule("aws_access_key", r"\b(?:AKIA|ASIA|ABIA|ACCA)[0-9A-Z]{16}\b"),
+        rule(
+            "aws_secret_key",
+            r#"(?i)aws_?secret_?(?:access_?)?key["']?\s*(?::=|=>|[:=])\s*["']?([A-Za-z0-9/+=]{40})\b"#,
+        ),
+        rule(
+            "github_token",
+            r"\b(?:gh[pousr]_[A-Za-z0-9]{36,255}|github_pat_[A-Za-z0-9_]{22,255})\b",
+        ),
+        rule(
+            "jwt",
+            r"\beyJ[A-Za-z0-9_-]{10,}\.eyJ[A-Za-z0-9_-]{10,}\.[A-Za-z0-9_-]{10,}",
+        ),
+        // A quoted value, or an unquoted one running to the end of the line as in `.env`
+        // and YAML files, so `token: String,` in code does not count.
+        rule(
+            "password_assignment",
+            r#"(?im)[\w.-]*(?:password|passwd|pwd|secret|token|api[_-]?key|access[_-]?key)[\w.-]*["']?[ \t]*(?::=|=>|[:=])[ \t]*(?:"([^"\r\n]{4,})"|'([^'\r\n]{4,})'|([^\s"'#,;]{4,})[ \t]*\r?$)"#,
+        ),
+    ]
+});
+
+fn secret_of<'t>(caps: &Captures<'t>) -> regex::Match<'t> {
+    caps.iter()
+        .skip(1)
+        .flatten()
+        .next()
+        .unwrap_or_else(|| caps.get(0).unwrap())
+}
+
+/// Whether an assigned value could be a password rather than a reference, placeholder,
+/// number, call expression or prose.
+fn looks_like_password(value: &str) -> bool {
+    let lower = value.to_ascii_lowercase();
+    let call = value.split_once('(').is_some_and(|(callee, _)| {
+        !callee.is_empty()
+            && callee
+                .chars()
+                .all(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | ':'))
+    });
+    !(value.starts_with(['$', '{', '<', '%', '['])
+        || call
+        || value.chars().any(char::is_whitespace)
+        || value.chars().all(|c| c.is_ascii_digit())
+        || ["true", "false", "null", "none", "nil", "undefined"].contains(&lower.as_str()))
+}
+
+/// Whether a candidate is a package checksum: `h1:` (`go.sum`), `sha256:` and the like
+/// right before it, or a subresource integrity prefix such as `sha512-` at its start.
+fn is_checksum(before: &str, candidate: &str) -> bool {
+    let prefix = before
+        .rsplit([' ', '\t', '"', '\'', '='])
+        .next()
+        .unwrap_or_default();
+    CHECKSUM_PREFIXES
+        .iter()
+        .any(|p| prefix.ends_with(p) || candidate.starts_with(p))
+        || (prefix.starts_with("sha") && prefix.ends_with(':'))
+}
+
+/// Whether a candidate looks random: mixed case and digits with high entropy.
+fn is_random(candidate: &str) -> bool {
+    candidate.len() >= ENTROPY_MIN_LEN
+        && candidate.chars().any(|c| c.is_ascii_lowercase())
+        && candidate.chars().any(|c| c.is_ascii_uppercase())
+        && candidate.chars().any(|c| c.is_ascii_digit())
+        && shannon_entropy(candidate) > ENTROPY_THRESHOLD
+}
+
+fn shannon_entropy(s: &str) -> f64 {
+    let mut counts = [0usize; 256];
+    for b in s.bytes() {
+        counts[b as usize] += 1;
+    }
+    let len = s.len() as f64;
+    counts
+        .iter()
+        .filter(|&&n| n > 0)
+        .map(|&n| {
+            let p = n as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
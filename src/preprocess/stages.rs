@@ -0,0 +1,353 @@
+//! Built-in pipeline stages: sources that read the downloaded files, and PDF renderers.
+//!
+//! Renderers only convert text items. Other items are skipped with a reason, except in
+//! [`MarkdownToPdf`], which passes non-markdown items through unchanged.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use tracing::{debug, error, info};
+
+use super::content::{load_file, FileContent};
+use super::pipeline::{Batch, Item, Stage, StageContext};
+use super::walk::{walk, Walk};
+use super::{pdf_error, slash_path};
+use crate::code_to_pdf::highlight::detect_language;
+use crate::code_to_pdf::markdown::{markdown_to_pdf, MarkdownPdfOptions};
+use crate::code_to_pdf::{files_to_pdf, text_to_pdf, PdfOptions, SourceFile};
+use crate::contract::{
+    CodePdfOptions, DirectoryPdfOptions, FilePolicy, PdfScope, ProcessError, SkipReason,
+    SkippedPath,
+};
+
+const PDF: &str = "application/pdf";
+
+/// Source stage: walk the source and read every file the ignore rules, include/exclude
+/// globs and file policy let through.
+#[derive(Debug, Clone, Default)]
+pub struct Collect {
+    pub policy: FilePolicy,
+    /// Read `.ipynb` files whatever their size, for a notebooks stage to convert. Their
+    /// size is mostly base64 images, which that stage leaves out.
+    pub notebooks: bool,
+}
+
+impl Stage for Collect {
+    fn name(&self) -> &'static str {
+        "collect"
+    }
+
+    fn run(&self, context: &StageContext, mut batch: Batch) -> Result<Batch, ProcessError> {
+        info!(path = %context.repo_path.display(), "Collecting files from source");
+        let Walk { files, skipped } = walk(&context.repo_path, &context.options)?;
+        batch.skipped.extend(skipped);
+        let notebook_policy = FilePolicy {
+            max_file_size: u64::MAX,
+            ..self.policy.clone()
+        };
+        for rel_path in files {
+            let notebook = self.notebooks
+                && rel_path
+                    .extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("ipynb"));
+            let policy = if notebook {
+                &notebook_policy
+            } else {
+                &self.policy
+            };
+            let file = load_file(&context.repo_path, &rel_path, policy, &mut batch.skipped)?;
+            let item = match file {
+                None => continue,
+                Some(FileContent::Text {
+                    text,
+                    mime_type,
+                    extension,
+                }) => Item {
+                    path: match extension {
+                        Some(ext) => format!("{}.{ext}", slash_path(&rel_path)),
+                        None => slash_path(&rel_path),
+                    },
+                    origin: slash_path(&rel_path),
+                    content: text.into_bytes(),
+                    mime_type: Some(mime_type),
+                    breadcrumb: Vec::new(),
+                    fingerprint: None,
+                    symbol: None,
+                },
+                Some(FileContent::Binary { bytes, mime_type }) => Item {
+                    path: slash_path(&rel_path),
+                    origin: slash_path(&rel_path),
+                    content: bytes,
+                    mime_type: Some(mime_type),
+                    breadcrumb: Vec::new(),
+                    fingerprint: None,
+                    symbol: None,
+                },
+            };
+            debug!(path = %item.path, size = item.content.len(), "Collected file");
+            batch.items.push(item);
+        }
+        Ok(batch)
+    }
+}
+
+/// Source stage: the `README.md` at the source root.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Readme;
+
+impl Stage for Readme {
+    fn name(&self) -> &'static str {
+        "readme"
+    }
+
+    fn run(&self, context: &StageContext, mut batch: Batch) -> Result<Batch, ProcessError> {
+        let readme_path = context.repo_path.join("README.md");
+        debug!(repo_path = %context.repo_path.display(), "Looking for README.md in repo path");
+        if !readme_path.exists() {
+            error!(path = %readme_path.display(), "No README.md found in repository");
+            return Err(ProcessError::NoReadme);
+        }
+        let content = std::fs::read(&readme_path).map_err(|e| {
+            error!(error = ?e, path = %readme_path.display(), "Failed to read README.md");
+            ProcessError::Io(e)
+        })?;
+        batch.items.push(Item {
+            path: "README.md".to_string(),
+            origin: "README.md".to_string(),
+            content,
+            mime_type: Some("text/markdown".to_string()),
+            breadcrumb: Vec::new(),
+            fingerprint: None,
+            symbol: None,
+        });
+        Ok(batch)
+    }
+}
+
+/// Render markdown items to PDF, replacing their extension with `.pdf`. Relative images
+/// and links resolve against the item's directory.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MarkdownToPdf;
+
+impl Stage for MarkdownToPdf {
+    fn name(&self) -> &'static str {
+        "markdown_to_pdf"
+    }
+
+    fn run(&self, context: &StageContext, batch: Batch) -> Result<Batch, ProcessError> {
+        let mut output = Batch {
+            items: Vec::with_capacity(batch.items.len()),
+            skipped: batch.skipped,
+            findings: batch.findings,
+            pii: batch.pii,
+        };
+        for item in batch.items {
+            let is_markdown = item.mime_type.as_deref() == Some("text/markdown")
+                || item.path.ends_with(".md")
+                || item.path.ends_with(".markdown");
+            let text = match item.text() {
+                Some(text) if is_markdown => text,
+                _ => {
+                    output.items.push(item);
+                    continue;
+                }
+            };
+            if text.trim().is_empty() {
+                skip(&mut output.skipped, &item.path, SkipReason::Empty);
+                continue;
+            }
+            let dir = Path::new(&item.path)
+                .parent()
+                .map(slash_path)
+                .unwrap_or_default();
+            let options = MarkdownPdfOptions {
+                header: Some(header(context, &item.path)),
+                base_dir: Some(context.repo_path.join(&dir)),
+                base_url: context.base_url.as_ref().map(|url| match dir.as_str() {
+                    "" => url.clone(),
+                    dir => format!("{}/{dir}", url.trim_end_matches('/')),
+                }),
+                ..MarkdownPdfOptions::default()
+            };
+            // Copies rendered under different headers still match on their source text.
+            let fingerprint = item.fingerprint();
+            let content = markdown_to_pdf(text, &options).map_err(|e| {
+                error!(path = %item.path, "Failed to render markdown");
+                pdf_error(e)
+            })?;
+            let stem = item
+                .path
+                .rsplit_once('.')
+                .map(|(stem, _)| stem)
+                .unwrap_or(&item.path);
+            let path = format!("{stem}.pdf");
+            info!(path = %path, size = content.len(), "Rendered markdown to PDF");
+            output.items.push(Item {
+                path,
+                origin: item.origin,
+                content,
+                mime_type: Some(PDF.to_string()),
+                breadcrumb: item.breadcrumb,
+                fingerprint: Some(fingerprint),
+                symbol: item.symbol,
+            });
+        }
+        Ok(output)
+    }
+}
+
+/// Render every text item to its own highlighted PDF, named after the item plus `.pdf`.
+#[derive(Debug, Clone, Default)]
+pub struct CodeToPdf {
+    pub options: CodePdfOptions,
+}
+
+impl Stage for CodeToPdf {
+    fn name(&self) -> &'static str {
+        "code_to_pdf"
+    }
+
+    fn run(&self, context: &StageContext, batch: Batch) -> Result<Batch, ProcessError> {
+        info!(items = batch.items.len(), "Rendering source files to PDF");
+        let mut output = Batch {
+            items: Vec::with_capacity(batch.items.len()),
+            skipped: batch.skipped,
+            findings: batch.findings,
+            pii: batch.pii,
+        };
+        for item in batch.items {
+            let Some(text) = text_or_skip(&item, &mut output.skipped) else {
+                continue;
+            };
+            let options = PdfOptions {
+                header: Some(header(context, &item.path)),
+                language: Some(
+                    detect_language(Path::new(&item.path), text)
+                        .map(|l| l.name)
+                        .unwrap_or("text")
+                        .to_string(),
+                ),
+                theme: self.options.theme,
+                line_numbers: self.options.line_numbers,
+            };
+            let fingerprint = item.fingerprint();
+            let content = text_to_pdf(text, &options).map_err(pdf_error)?;
+            let path = format!("{}.pdf", item.path);
+            debug!(path = %path, size = content.len(), "Rendered file to PDF");
+            output.items.push(Item {
+                path,
+                origin: item.origin,
+                content,
+                mime_type: Some(PDF.to_string()),
+                breadcrumb: item.breadcrumb,
+                fingerprint: Some(fingerprint),
+                symbol: item.symbol,
+            });
+        }
+        Ok(output)
+    }
+}
+
+/// Concatenate the text items of each directory (not its subdirectories) into one PDF
+/// with a table of contents and per-file bookmarks. Items at the root go to `_root.pdf`;
+/// other directories become `<dir>.pdf`. With [`PdfScope::Repository`] every item goes
+/// into a single `_repository.pdf`.
+#[derive(Debug, Clone, Default)]
+pub struct DirectoryToPdf {
+    pub options: DirectoryPdfOptions,
+}
+
+impl Stage for DirectoryToPdf {
+    fn name(&self) -> &'static str {
+        "directory_to_pdf"
+    }
+
+    fn run(&self, context: &StageContext, batch: Batch) -> Result<Batch, ProcessError> {
+        info!(items = batch.items.len(), "Rendering directories to PDF");
+        let mut skipped = batch.skipped;
+        let mut directories: BTreeMap<String, Vec<SourceFile>> = BTreeMap::new();
+        for item in &batch.items {
+            let Some(text) = text_or_skip(item, &mut skipped) else {
+                continue;
+            };
+            let dir = match self.options.scope {
+                PdfScope::Directory => item
+                    .path
+                    .rsplit_once('/')
+                    .map(|(dir, _)| dir.to_string())
+                    .unwrap_or_default(),
+                PdfScope::Repository => String::new(),
+            };
+            directories.entry(dir).or_default().push(SourceFile {
+                path: item.path.clone(),
+                text: text.to_string(),
+            });
+        }
+
+        let name = context.name.trim_end_matches('/');
+        let mut items = Vec::with_capacity(directories.len());
+        for (dir, files) in directories {
+            let (title, path) = match dir.as_str() {
+                "" if self.options.scope == PdfScope::Repository => {
+                    (name.to_string(), "_repository.pdf".to_string())
+                }
+                "" => (name.to_string(), "_root.pdf".to_string()),
+                dir => (format!("{name}/{dir}"), format!("{dir}.pdf")),
+            };
+            let options = PdfOptions {
+                theme: self.options.theme,
+                line_numbers: self.options.line_numbers,
+                ..PdfOptions::default()
+            };
+            let content = files_to_pdf(&title, &files, &options).map_err(pdf_error)?;
+            debug!(path = %path, files = files.len(), size = content.len(), "Rendered directory to PDF");
+            items.push(Item {
+                path,
+                origin: dir,
+                content,
+                mime_type: Some(PDF.to_string()),
+                breadcrumb: Vec::new(),
+                fingerprint: None,
+                symbol: None,
+            });
+        }
+        Ok(Batch {
+            items,
+            skipped,
+            findings: batch.findings,
+            pii: batch.pii,
+        })
+    }
+}
+
+/// `<source name>/<path>`, the page header of a rendered item.
+fn header(context: &StageContext, path: &str) -> String {
+    format!("{}/{path}", context.name.trim_end_matches('/'))
+}
+
+/// The item's text, or `None` after recording why a renderer cannot use it.
+fn text_or_skip<'a>(item: &'a Item, skipped: &mut Vec<SkippedPath>) -> Option<&'a str> {
+    match item.text() {
+        None => {
+            let mime_type = item
+                .mime_type
+                .clone()
+                .unwrap_or_else(|| "application/octet-stream".to_string());
+            skip(skipped, &item.path, SkipReason::Binary(mime_type));
+            None
+        }
+        Some(text) if text.trim().is_empty() => {
+            skip(skipped, &item.path, SkipReason::Empty);
+            None
+        }
+        Some(text) => Some(text),
+    }
+}
+
+fn skip(skipped: &mut Vec<SkippedPath>, path: &str, reason: SkipReason) {
+    debug!(path = %path, reason = %reason, "Skipping item");
+    skipped.push(SkippedPath {
+        path: path.to_string(),
+        reason,
+    });
+}
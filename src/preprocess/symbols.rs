@@ -0,0 +1,576 @@
+//! Symbols stage: splits source files into one item per top-level declaration, so a
+//! question about one function retrieves that function rather than the whole file.
+//!
+//! Rust, Python, TypeScript, Go and Java files are parsed with tree-sitter. Functions,
+//! methods, structs, classes, interfaces, enums, traits, impls, modules and type aliases
+//! at the top level of a file each become an item holding their source lines, including
+//! leading comments and attributes, with the declaration's [`Symbol`] (signature, doc
+//! comment and line range) attached. Each file also gets a markdown outline listing its
+//! symbols, and, unless the whole file is kept, a `remainder` item with the top-level
+//! lines no symbol covers (imports, constants, macros, scripts), so nothing is lost.
+//! Other files, and files without any symbols, pass through unchanged.
+
+use std::collections::HashSet;
+
+use tracing::{debug, info, warn};
+use tree_sitter::{Node, Parser};
+
+use super::chunk::suffixed_name;
+use super::pipeline::{Batch, Item, Stage, StageContext};
+use crate::contract::{ProcessError, Symbol, SymbolKind, SymbolOptions};
+
+/// Replace every supported source file with its symbols and outline.
+#[derive(Debug, Clone, Default)]
+pub struct Symbols {
+    pub options: SymbolOptions,
+}
+
+impl Stage for Symbols {
+    fn name(&self) -> &'static str {
+        "symbols"
+    }
+
+    fn run(&self, _context: &StageContext, batch: Batch) -> Result<Batch, ProcessError> {
+        let mut items = Vec::with_capacity(batch.items.len());
+        let (mut files, mut found) = (0, 0);
+        for item in batch.items {
+            let split = item.text().and_then(|text| {
+                let symbols = extract_symbols(&item.path, text)?;
+                (!symbols.is_empty()).then(|| self.split(&item, text, symbols))
+            });
+            let Some(split) = split else {
+                items.push(item);
+                continue;
+            };
+            debug!(path = %item.path, items = split.len(), "Split file into symbols");
+            files += 1;
+            found += split.iter().filter(|i| i.symbol.is_some()).count();
+            if self.options.keep_files {
+                items.push(item);
+            }
+            items.extend(split);
+        }
+        info!(files, symbols = found, "Split source files into symbols");
+        Ok(Batch { items, ..batch })
+    }
+}
+
+impl Symbols {
+    /// The outline item, when enabled, then one item per symbol named
+    /// `<stem>__<symbol>.<ext>`, then the remainder item `<stem>__remainder.<ext>`.
+    fn split(&self, item: &Item, text: &str, symbols: Vec<Symbol>) -> Vec<Item> {
+        let mut items = Vec::with_capacity(symbols.len() + 1);
+        if self.options.outline {
+            items.push(Item {
+                path: format!("{}.outline.md", item.path),
+                origin: item.origin.clone(),
+                content: outline(&item.origin, &symbols).into_bytes(),
+                mime_type: Some("text/markdown".to_string()),
+                breadcrumb: item.breadcrumb.clone(),
+                fingerprint: None,
+                symbol: None,
+            });
+        }
+        let lines: Vec<&str> = text.split_inclusive('\n').collect();
+        let mut covered = vec![false; lines.len()];
+        let mut names = HashSet::new();
+        for symbol in symbols {
+            covered[symbol.start_line - 1..symbol.end_line].fill(true);
+            let base = slug(&symbol.name);
+            let mut name = base.clone();
+            for n in 2.. {
+                if names.insert(name.clone()) {
+                    break;
+                }
+                name = format!("{base}_{n}");
+            }
+            let mut breadcrumb = item.breadcrumb.clone();
+            breadcrumb.push(symbol.name.clone());
+            items.push(Item {
+                path: suffixed_name(&item.path, &name),
+                origin: item.origin.clone(),
+                content: lines[symbol.start_line - 1..symbol.end_line]
+                    .concat()
+                    .into_bytes(),
+                mime_type: item.mime_type.clone(),
+                breadcrumb,
+                fingerprint: None,
+                symbol: Some(symbol),
+            });
+        }
+        if let Some(remainder) = (!self.options.keep_files)
+            .then(|| remainder(&lines, &covered))
+            .flatten()
+        {
+            let mut name = "remainder".to_string();
+            for n in 2.. {
+                if names.insert(name.clone()) {
+                    break;
+                }
+                name = format!("remainder_{n}");
+            }
+            items.push(Item {
+                path: suffixed_name(&item.path, &name),
+                origin: item.origin.clone(),
+                content: remainder.into_bytes(),
+                mime_type: item.mime_type.clone(),
+                breadcrumb: item.breadcrumb.clone(),
+                fingerprint: None,
+                symbol: None,
+            });
+        }
+        items
+    }
+}
+
+/// The runs of `lines` not `covered` by a symbol, without blank lines at their ends and
+/// separated by one blank line; `None` when only blank lines are left.
+fn remainder(lines: &[&str], covered: &[bool]) -> Option<String> {
+    let mut runs: Vec<String> = Vec::new();
+    let mut run: Vec<&str> = Vec::new();
+    for (line, &covered) in lines.iter().zip(covered).chain([(&"", &true)]) {
+        if !covered {
+            run.push(line);
+            continue;
+        }
+        let start = run.iter().position(|l| !l.trim().is_empty());
+        let end = run.iter().rposition(|l| !l.trim().is_empty());
+        if let (Some(start), Some(end)) = (start, end) {
+            let mut text = run[start..=end].concat();
+            if !text.ends_with('\n') {
+                text.push('\n');
+            }
+            runs.push(text);
+        }
+        run.clear();
+    }
+    (!runs.is_empty()).then(|| runs.join("\n"))
+}
+
+/// The top-level symbols of `text`, read from `path`, in source order; `None` when the
+/// file's language is not supported or it cannot be parsed.
+pub fn extract_symbols(path: &str, text: &str) -> Option<Vec<Symbol>> {
+    let grammar = Grammar::for_path(path)?;
+    let mut parser = Parser::new();
+    if let Err(e) = parser.set_language(&grammar.language()) {
+        warn!(path, error = %e, "Could not load tree-sitter grammar");
+        return None;
+    }
+    let Some(tree) = parser.parse(text, None) else {
+        warn!(path, "tree-sitter could not parse file");
+        return None;
+    };
+    let root = tree.root_node();
+    let children: Vec<Node> = root.named_children(&mut root.walk()).collect();
+    let mut symbols = Vec::new();
+    collect(grammar, &children, text, &mut symbols);
+    Some(symbols)
+}
+
+/// A markdown list of the symbols of the file at `path`, with the first line of each
+/// doc comment.
+fn outline(path: &str, symbols: &[Symbol]) -> String {
+    let mut out = format!("# Outline of {path}\n\n");
+    for symbol in symbols {
+        out.push_str(&format!(
+            "- {} `{}`, lines {}-{}: `{}`\n",
+            symbol.kind, symbol.name, symbol.start_line, symbol.end_line, symbol.signature
+        ));
+        if let Some(summary) = symbol.doc.as_deref().and_then(|doc| doc.lines().next()) {
+            out.push_str(&format!("  {summary}\n"));
+        }
+    }
+    out
+}
+
+/// The alphanumeric runs of `name` joined by `_`: `impl Display for Config` →
+/// `impl_Display_for_Config`.
+pub(crate) fn slug(name: &str) -> String {
+    let slug = name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("_");
+    match slug.is_empty() {
+        true => "symbol".to_string(),
+        false => slug,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Grammar {
+    Rust,
+    Python,
+    TypeScript,
+    Tsx,
+    Go,
+    Java,
+}
+
+impl Grammar {
+    fn for_path(path: &str) -> Option<Grammar> {
+        let (_, ext) = path.rsplit('/').next()?.rsplit_once('.')?;
+        match ext.to_ascii_lowercase().as_str() {
+            "rs" => Some(Grammar::Rust),
+            "py" | "pyi" => Some(Grammar::Python),
+            "ts" | "mts" | "cts" => Some(Grammar::TypeScript),
+            "tsx" => Some(Grammar::Tsx),
+            "go" => Some(Grammar::Go),
+            "java" => Some(Grammar::Java),
+            _ => None,
+        }
+    }
+
+    fn language(self) -> tree_sitter::Language {
+        match self {
+            Grammar::Rust => tree_sitter_rust::LANGUAGE.into(),
+            Grammar::Python => tree_sitter_python::LANGUAGE.into(),
+            Grammar::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            Grammar::Tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
+            Grammar::Go => tree_sitter_go::LANGUAGE.into(),
+            Grammar::Java => tree_sitter_java::LANGUAGE.into(),
+        }
+    }
+
+    /// Whether a node directly above a declaration belongs to it (comments, attributes).
+    fn is_leading(self, node: Node, text: &str) -> bool {
+        match (self, node.kind()) {
+            (Grammar::Rust, "attribute_item") => true,
+            (Grammar::Rust, "line_comment" | "block_comment") => {
+                let comment = source(node, text);
+                !comment.starts_with("//!") && !comment.starts_with("/*!")
+            }
+            (Grammar::Java, "line_comment" | "block_comment") => true,
+            (_, "comment") => true,
+            _ => false,
+        }
+    }
+
+    /// Whether a leading comment is documentation rather than a plain comment.
+    fn is_doc_comment(self, comment: &str) -> bool {
+        match self {
+            Grammar::Rust => {
+                (comment.starts_with("///") && !comment.starts_with("////"))
+                    || (comment.starts_with("/**") && !comment.starts_with("/***"))
+            }
+            Grammar::Go => true,
+            Grammar::Python => false,
+            Grammar::TypeScript | Grammar::Tsx | Grammar::Java => comment.starts_with("/**"),
+        }
+    }
+}
+
+/// A declaration found in the tree.
+struct Declaration<'t> {
+    kind: SymbolKind,
+    name: String,
+    /// Where the signature starts: the declaration, or an `export` wrapping it.
+    head: Node<'t>,
+    /// Where the signature ends, when the declaration has a body.
+    body: Option<Node<'t>>,
+    /// The whole declaration, including decorators and wrappers.
+    span: Node<'t>,
+    /// The node holding a Python docstring.
+    definition: Node<'t>,
+}
+
+fn collect(grammar: Grammar, siblings: &[Node], text: &str, symbols: &mut Vec<Symbol>) {
+    for (i, &node) in siblings.iter().enumerate() {
+        if grammar == Grammar::Go && node.kind() == "type_declaration" {
+            let specs: Vec<Node> = node.named_children(&mut node.walk()).collect();
+            if specs
+                .iter()
+                .filter(|spec| !grammar.is_leading(**spec, text))
+                .count()
+                > 1
+            {
+                collect(grammar, &specs, text, symbols);
+                continue;
+            }
+        }
+        let Some(declaration) = declaration(grammar, node, text) else {
+            continue;
+        };
+        let leading = leading(grammar, &siblings[..i], node, text);
+        let start = leading.first().unwrap_or(&declaration.span);
+        let doc = match grammar {
+            Grammar::Python => docstring(declaration.definition, text),
+            _ => {
+                let comments: Vec<String> = leading
+                    .iter()
+                    .map(|comment| source(*comment, text))
+                    .filter(|comment| grammar.is_doc_comment(comment))
+                    .map(comment_text)
+                    .collect();
+                Some(comments.join("\n"))
+            }
+        };
+        symbols.push(Symbol {
+            kind: declaration.kind,
+            name: declaration.name,
+            signature: signature(declaration.head, declaration.body, text),
+            doc: doc
+                .map(|doc| doc.trim_matches('\n').to_string())
+                .filter(|doc| !doc.is_empty()),
+            start_line: start.start_position().row + 1,
+            end_line: last_row(declaration.span) + 1,
+        });
+    }
+}
+
+/// The comments and attributes directly above `node`, without blank lines between them,
+/// in source order.
+fn leading<'t>(grammar: Grammar, before: &[Node<'t>], node: Node<'t>, text: &str) -> Vec<Node<'t>> {
+    let mut leading = Vec::new();
+    let mut next_row = node.start_position().row;
+    for (j, &sibling) in before.iter().enumerate().rev() {
+        if !grammar.is_leading(sibling, text) || last_row(sibling) + 1 < next_row {
+            break;
+        }
+        // A trailing comment on the line of the previous declaration belongs to that one.
+        if j > 0 && last_row(before[j - 1]) == sibling.start_position().row {
+            break;
+        }
+        leading.push(sibling);
+        next_row = sibling.start_position().row;
+    }
+    leading.reverse();
+    leading
+}
+
+fn declaration<'t>(grammar: Grammar, node: Node<'t>, text: &str) -> Option<Declaration<'t>> {
+    let name = |node: Node| node.child_by_field_name("name").map(|n| source(n, text));
+    let simple = |kind: SymbolKind| {
+        Some(Declaration {
+            kind,
+            name: name(node)?.to_string(),
+            head: node,
+            body: node.child_by_field_name("body"),
+            span: node,
+            definition: node,
+        })
+    };
+    // A declaration inside a wrapper (export, decorators), spanning the wrapper.
+    let wrapped = |inner: Option<Node<'t>>, signature_from_wrapper: bool| {
+        let inner = declaration(grammar, inner?, text)?;
+        Some(Declaration {
+            head: if signature_from_wrapper {
+                node
+            } else {
+                inner.head
+            },
+            span: node,
+            ..inner
+        })
+    };
+    match (grammar, node.kind()) {
+        (Grammar::Rust, "function_item" | "function_signature_item") => {
+            simple(SymbolKind::Function)
+        }
+        (Grammar::Rust, "struct_item" | "union_item") => simple(SymbolKind::Struct),
+        (Grammar::Rust, "enum_item") => simple(SymbolKind::Enum),
+        (Grammar::Rust, "trait_item") => simple(SymbolKind::Trait),
+        (Grammar::Rust, "type_item") => simple(SymbolKind::Type),
+        (Grammar::Rust, "mod_item") => simple(SymbolKind::Module),
+        (Grammar::Rust, "impl_item") => {
+            let target = source(node.child_by_field_name("type")?, text);
+            let name = match node.child_by_field_name("trait") {
+                Some(trait_) => format!("impl {} for {target}", source(trait_, text)),
+                None => format!("impl {target}"),
+            };
+            Some(Declaration {
+                kind: SymbolKind::Impl,
+                name,
+                head: node,
+                body: node.child_by_field_name("body"),
+                span: node,
+                definition: node,
+            })
+        }
+
+        (Grammar::Python, "function_definition") => simple(SymbolKind::Function),
+        (Grammar::Python, "class_definition") => simple(SymbolKind::Class),
+        (Grammar::Python, "decorated_definition") => {
+            wrapped(node.child_by_field_name("definition"), false)
+        }
+
+        (
+            Grammar::TypeScript | Grammar::Tsx,
+            "function_declaration" | "generator_function_declaration" | "function_signature",
+        ) => simple(SymbolKind::Function),
+        (
+            Grammar::TypeScript | Grammar::Tsx,
+            "class_declaration" | "abstract_class_declaration",
+        ) => simple(SymbolKind::Class),
+        (Grammar::TypeScript | Grammar::Tsx, "interface_declaration") => {
+            simple(SymbolKind::Interface)
+        }
+        (Grammar::TypeScript | Grammar::Tsx, "enum_declaration") => simple(SymbolKind::Enum),
+        (Grammar::TypeScript | Grammar::Tsx, "type_alias_declaration") => simple(SymbolKind::Type),
+        (Grammar::TypeScript | Grammar::Tsx, "internal_module" | "module") => {
+            simple(SymbolKind::Module)
+        }
+        (Grammar::TypeScript | Grammar::Tsx, "export_statement") => {
+            wrapped(node.child_by_field_name("declaration"), true)
+        }
+        (Grammar::TypeScript | Grammar::Tsx, "expression_statement") => wrapped(
+            node.named_child(0)
+                .filter(|inner| inner.kind() == "internal_module"),
+            false,
+        ),
+        (Grammar::TypeScript | Grammar::Tsx, "lexical_declaration" | "variable_declaration") => {
+            // `const handler = (req) => { ... }`
+            let mut declarators = node
+                .named_children(&mut node.walk())
+                .filter(|child| child.kind() == "variable_declarator")
+                .collect::<Vec<_>>();
+            let declarator = declarators.pop().filter(|_| declarators.is_empty())?;
+            let value = declarator.child_by_field_name("value")?;
+            if !matches!(
+                value.kind(),
+                "arrow_function" | "function_expression" | "function"
+            ) {
+                return None;
+            }
+            Some(Declaration {
+                kind: SymbolKind::Function,
+                name: name(declarator)?.to_string(),
+                head: node,
+                body: value.child_by_field_name("body"),
+                span: node,
+                definition: node,
+            })
+        }
+
+        (Grammar::Go, "function_declaration") => simple(SymbolKind::Function),
+        (Grammar::Go, "method_declaration") => {
+            let receiver = node
+                .child_by_field_name("receiver")?
+                .named_children(&mut node.walk())
+                .find(|p| p.kind() == "parameter_declaration")?
+                .child_by_field_name("type")?;
+            let receiver = source(receiver, text).trim_start_matches('*');
+            let receiver = receiver.split('[').next().unwrap_or(receiver);
+            Some(Declaration {
+                name: format!("{receiver}.{}", name(node)?),
+                ..simple(SymbolKind::Method)?
+            })
+        }
+        (Grammar::Go, "type_declaration") => {
+            let spec = node
+                .named_children(&mut node.walk())
+                .find(|spec| !grammar.is_leading(*spec, text))?;
+            wrapped(Some(spec), true)
+        }
+        (Grammar::Go, "type_spec" | "type_alias") => {
+            let kind = match node.child_by_field_name("type").map(|t| t.kind()) {
+                Some("struct_type") => SymbolKind::Struct,
+                Some("interface_type") => SymbolKind::Interface,
+                _ => SymbolKind::Type,
+            };
+            simple(kind)
+        }
+
+        (Grammar::Java, "class_declaration" | "record_declaration") => simple(SymbolKind::Class),
+        (Grammar::Java, "interface_declaration" | "annotation_type_declaration") => {
+            simple(SymbolKind::Interface)
+        }
+        (Grammar::Java, "enum_declaration") => simple(SymbolKind::Enum),
+        _ => None,
+    }
+}
+
+/// The declaration from `head` up to its body (or up to the first `{`, or its first line,
+/// without one), on one line.
+fn signature(head: Node, body: Option<Node>, text: &str) -> String {
+    let full = &text[head.start_byte()..head.end_byte()];
+    let raw = match body {
+        Some(body) => &text[head.start_byte()..body.start_byte()],
+        None => full
+            .split_once('{')
+            .map_or(full, |(before, _)| before)
+            .lines()
+            .next()
+            .unwrap_or(""),
+    };
+    raw.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("( ", "(")
+        .replace(" )", ")")
+        .replace(",)", ")")
+        .trim_end_matches([' ', ':', ';', '=', '{'])
+        .to_string()
+}
+
+/// A comment without its markers: `///`, `//` and `/** ... */` with the `*` that start
+/// continuation lines.
+fn comment_text(comment: &str) -> String {
+    let comment = comment.trim_end();
+    match comment.strip_prefix("/*") {
+        Some(block) => {
+            let block = block.strip_suffix("*/").unwrap_or(block);
+            let block = block.strip_prefix('*').unwrap_or(block);
+            block
+                .lines()
+                .map(|line| {
+                    let line = line.trim_start();
+                    let line = line.strip_prefix('*').unwrap_or(line);
+                    line.strip_prefix(' ').unwrap_or(line).trim_end()
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+        None => {
+            let line = comment.trim_start_matches('/');
+            line.strip_prefix(' ').unwrap_or(line).to_string()
+        }
+    }
+}
+
+/// The docstring of a Python function or class: a string as the first statement of its
+/// body, with the common indentation removed.
+fn docstring(definition: Node, text: &str) -> Option<String> {
+    let first = definition.child_by_field_name("body")?.named_child(0)?;
+    let string = first
+        .named_child(0)
+        .filter(|_| first.kind() == "expression_statement")?;
+    if string.kind() != "string" {
+        return None;
+    }
+    let literal = source(string, text).trim_start_matches(|c: char| "rRuUbBfF".contains(c));
+    let quote = ["\"\"\"", "'''", "\"", "'"]
+        .into_iter()
+        .find(|quote| literal.starts_with(quote) && literal.len() >= 2 * quote.len())?;
+    let body = &literal[quote.len()..literal.len() - quote.len()];
+
+    let mut lines = body.lines();
+    let first_line = lines.next().unwrap_or("").trim();
+    let rest: Vec<&str> = lines.collect();
+    let indent = rest
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let mut cleaned = vec![first_line.to_string()];
+    cleaned.extend(
+        rest.iter()
+            .map(|line| line.get(indent..).unwrap_or("").trim_end().to_string()),
+    );
+    Some(cleaned.join("\n").trim().to_string())
+}
+
+fn source<'a>(node: Node, text: &'a str) -> &'a str {
+    &text[node.start_byte()..node.end_byte()]
+}
+
+/// The last row holding part of `node`; a node ending with its newline ends on the row
+/// before.
+fn last_row(node: Node) -> usize {
+    let end = node.end_position();
+    match end.column {
+        0 if end.row > node.start_position().row => end.row - 1,
+        _ => end.row,
+    }
+}
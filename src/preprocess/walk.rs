@@ -0,0 +1,232 @@
+//! Source tree walking shared by every file-based processor.
+//!
+//! A file is processed unless one of these rules skips it, checked in order:
+//! 1. it is inside `.git` or `target`;
+//! 2. the nearest `.llmignore` or `.gitignore` rule matching it (deepest directory first,
+//!    `.llmignore` before `.gitignore` in the same directory) ignores it; a negated `!rule`
+//!    keeps it;
+//! 3. it matches a per-source `exclude` glob;
+//! 4. per-source `include` globs are set and it matches none of them.
+//!
+//! Ignored or excluded directories are reported once and not descended into. The
+//! downloader's [`SOURCE_INDEX_FILE`] at the root is metadata, not content, and is passed
+//! over silently.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use globset::{Glob, GlobBuilder, GlobMatcher};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use tracing::{debug, error, warn};
+
+use crate::contract::{ProcessError, SkipReason, SkippedPath};
+use crate::download::{SourceOptions, SOURCE_INDEX_FILE};
+
+/// Ignore files honoured in every directory, lowest precedence first.
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".llmignore"];
+const BUILT_IN_SKIPS: [&str; 2] = [".git", "target"];
+
+/// Result of [`walk`]: files to process and everything that was left out.
+#[derive(Debug, Default)]
+pub struct Walk {
+    /// Sorted paths relative to the source root.
+    pub files: Vec<PathBuf>,
+    pub skipped: Vec<SkippedPath>,
+}
+
+/// Walk `root` and apply the ignore rules and `options` globs described in the module docs.
+pub fn walk(root: &Path, options: &SourceOptions) -> Result<Walk, ProcessError> {
+    let filter = PathFilter::new(options).map_err(|e| {
+        error!(error = %e, "Invalid include/exclude pattern");
+        ProcessError::Other(format!("invalid include/exclude pattern: {e}"))
+    })?;
+    let mut walk = Walk::default();
+    let mut ignores = Vec::new();
+    if let Err(e) = visit_dir(root, root, &filter, &mut ignores, &mut walk) {
+        error!(error = ?e, path = %root.display(), "Error occurred while walking source files");
+        return Err(e);
+    }
+    walk.files.sort();
+    debug!(
+        files = walk.files.len(),
+        skipped = walk.skipped.len(),
+        "Walked source tree"
+    );
+    Ok(walk)
+}
+
+fn visit_dir(
+    dir: &Path,
+    root: &Path,
+    filter: &PathFilter,
+    ignores: &mut Vec<(Gitignore, String)>,
+    walk: &mut Walk,
+) -> Result<(), ProcessError> {
+    let pushed = ignores.len();
+    for name in IGNORE_FILES {
+        let file = dir.join(name);
+        if file.is_file() {
+            let mut builder = GitignoreBuilder::new(dir);
+            if let Some(e) = builder.add(&file) {
+                warn!(error = %e, path = %file.display(), "Some ignore rules could not be parsed");
+            }
+            match builder.build() {
+                Ok(gitignore) => ignores.push((gitignore, slash_path(root, &file))),
+                Err(e) => {
+                    warn!(error = %e, path = %file.display(), "Ignoring unreadable ignore file")
+                }
+            }
+        }
+    }
+
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+    for path in entries {
+        let is_dir = path.is_dir();
+        if !is_dir && !path.is_file() {
+            continue;
+        }
+        let rel = slash_path(root, &path);
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        if !is_dir && rel == SOURCE_INDEX_FILE {
+            continue;
+        }
+        let reason = if is_dir && BUILT_IN_SKIPS.contains(&name.as_str()) {
+            Some(SkipReason::BuiltIn)
+        } else if let Some(file) = ignored_by(ignores, &path, is_dir) {
+            Some(SkipReason::IgnoreFile(file))
+        } else if let Some(pattern) = filter.excluded(&rel, &name, is_dir) {
+            Some(SkipReason::Excluded(pattern.to_string()))
+        } else if !is_dir && !filter.included(&rel, &name) {
+            Some(SkipReason::NotIncluded)
+        } else {
+            None
+        };
+
+        match reason {
+            Some(reason) => {
+                let path = if is_dir { format!("{rel}/") } else { rel };
+                debug!(path = %path, reason = %reason, "Skipping path");
+                walk.skipped.push(SkippedPath { path, reason });
+            }
+            None if is_dir => visit_dir(&path, root, filter, ignores, walk)?,
+            None => walk.files.push(PathBuf::from(rel)),
+        }
+    }
+    ignores.truncate(pushed);
+    Ok(())
+}
+
+/// The ignore file that decides `path`, if its decision is to ignore it.
+fn ignored_by(ignores: &[(Gitignore, String)], path: &Path, is_dir: bool) -> Option<String> {
+    for (gitignore, file) in ignores.iter().rev() {
+        match gitignore.matched(path, is_dir) {
+            Match::Ignore(_) => return Some(file.clone()),
+            Match::Whitelist(_) => return None,
+            Match::None => {}
+        }
+    }
+    None
+}
+
+/// `/`-separated path of `path` relative to `root`.
+fn slash_path(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Compiled per-source include/exclude globs.
+pub struct PathFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+struct Pattern {
+    source: String,
+    matcher: GlobMatcher,
+    /// For `dir/**` patterns: matches `dir` itself, so the directory is skipped whole.
+    dir_matcher: Option<GlobMatcher>,
+    /// Patterns without `/` match the file or directory name anywhere.
+    name_only: bool,
+}
+
+impl Pattern {
+    fn new(source: &str) -> Result<Self, globset::Error> {
+        let trimmed = source.trim_start_matches('/');
+        let dir_matcher = match trimmed.strip_suffix("/**") {
+            Some(dir) if !dir.is_empty() => Some(PathFilter::glob(dir)?.compile_matcher()),
+            _ => None,
+        };
+        Ok(Pattern {
+            source: source.to_string(),
+            matcher: PathFilter::glob(trimmed)?.compile_matcher(),
+            dir_matcher,
+            name_only: !trimmed.contains('/'),
+        })
+    }
+
+    fn is_match(&self, rel: &str, name: &str, is_dir: bool) -> bool {
+        if self.name_only {
+            return self.matcher.is_match(name);
+        }
+        self.matcher.is_match(rel)
+            || (is_dir && self.dir_matcher.as_ref().is_some_and(|m| m.is_match(rel)))
+    }
+}
+
+impl PathFilter {
+    pub fn new(options: &SourceOptions) -> Result<Self, globset::Error> {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|p| Pattern::new(p))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(PathFilter {
+            include: compile(&options.include)?,
+            exclude: compile(&options.exclude)?,
+        })
+    }
+
+    /// Compile one include/exclude glob; `*` does not match across `/`.
+    pub fn glob(pattern: &str) -> Result<Glob, globset::Error> {
+        GlobBuilder::new(pattern).literal_separator(true).build()
+    }
+
+    /// Whether the walk would keep the file at `rel` as far as the globs go: no exclude
+    /// pattern matches it or a directory above it, and it matches an include pattern when
+    /// there are any. Lets downloaders skip what processing would leave out anyway.
+    pub fn keeps(&self, rel: &str) -> bool {
+        let segments: Vec<&str> = rel.split('/').collect();
+        let Some((name, dirs)) = segments.split_last() else {
+            return false;
+        };
+        let excluded_dir = (1..=dirs.len()).any(|n| {
+            self.excluded(&dirs[..n].join("/"), dirs[n - 1], true)
+                .is_some()
+        });
+        !excluded_dir && self.excluded(rel, name, false).is_none() && self.included(rel, name)
+    }
+
+    fn excluded(&self, rel: &str, name: &str, is_dir: bool) -> Option<&str> {
+        self.exclude
+            .iter()
+            .find(|p| p.is_match(rel, name, is_dir))
+            .map(|p| p.source.as_str())
+    }
+
+    fn included(&self, rel: &str, name: &str) -> bool {
+        self.include.is_empty() || self.include.iter().any(|p| p.is_match(rel, name, false))
+    }
+}
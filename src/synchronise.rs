@@ -3,7 +3,7 @@
 //! This module provides the top-level orchestration logic for "synchronising" all configured sources
 //! as described in the loaded config. It implements a coordinated pipeline that:
 //!   - Downloads each declared source (currently Git or Confluence) into a deterministic folder
-//!   - Processes downloads using a specified processor (e.g., flatten files, README→PDF, etc.)
+//!   - Processes each download with the preprocessor selected for it (e.g., flatten files, README→PDF, etc.)
 //!   - Uploads processed sources/items to a remote API/knowledge base via [`Uploader`] trait object
 //!   - Aggregates and returns a report of what succeeded and failed.
 //!
@@ -29,14 +29,20 @@
 //! Each failed step (download, process, upload) returns immediately with a formatted error; callers should log and surface these to users/test logs
 //!
 //! # Navigation
-//! - Main entrypoint: [`synchronise`], now parameterized over both [`Uploader`] and [`Downloader`] trait objects for full orchestration injection
+//! - Main entrypoint: [`synchronise`] (or [`synchronise_with`] for options such as deduplication), parameterized over a [`PreprocessorSelector`] (any [`Preprocessor`](crate::contract::Preprocessor) selects itself for every source) and an [`Uploader`]
 //! - Supporting types: [`SynchroniseConfig`], [`SynchroniseReport`].
 //!
 
+use std::borrow::Cow;
+
+use base64::prelude::{Engine, BASE64_STANDARD};
 use futures::future::try_join_all;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
-use crate::contract::{Preprocessor, ProcessConfig, ProcessInput, Uploader};
+use crate::contract::{
+    ContentEncoding, DedupOptions, PreprocessorSelector, ProcessConfig, ProcessInput, Uploader,
+};
+use crate::preprocess::dedup::{deduplicate, DedupReport};
 
 extern crate tokio; // Use extern crate for runtime context
 
@@ -47,10 +53,20 @@ pub struct SynchroniseConfig {
     pub process: ProcessConfig,
 }
 
+/// Run-wide settings for [`synchronise_with`].
+#[derive(Debug, Clone, Default)]
+pub struct SyncOptions {
+    /// Remove items whose content another item of the run has before uploading; off
+    /// when `None`.
+    pub dedup: Option<DedupOptions>,
+}
+
 /// Entrypoint: synchronise the pipeline according to config.
 #[derive(Debug)]
 pub struct SynchroniseReport {
     pub sources: Vec<ExternalSourceReport>,
+    /// Items and bytes left out as duplicates; zero without deduplication.
+    pub dedup: DedupReport,
 }
 
 #[derive(Debug)]
@@ -58,48 +74,83 @@ pub struct ExternalSourceReport {
     pub source_id: i64,
     pub source_name: String,
     pub items: Vec<ExternalItemReport>,
+    /// Paths the processor left out, with the reason for each.
+    pub skipped: Vec<crate::contract::SkippedPath>,
+    /// Secrets the processor redacted or dropped files for (path, line and rule only).
+    pub findings: Vec<crate::contract::SecretFinding>,
+    /// Personal data values the processor scrubbed, per category.
+    pub pii: std::collections::BTreeMap<crate::contract::PiiCategory, usize>,
 }
 
 #[derive(Debug)]
 pub struct ExternalItemReport {
     pub item_id: i64,
     pub item_name: String,
+    pub mime_type: Option<String>,
 }
 
 /// Orchestrate the full synchronisation pipeline given a manifest of downloaded sources.
 /// The manifest typically comes from Downloader::download_all().
-pub async fn synchronise<P, U>(
-    preprocessor: &P,
+pub async fn synchronise<S, U>(
+    preprocessors: &S,
+    uploader: &U,
+    downloaded_sources: &[crate::contract::DownloadedSource],
+) -> Result<SynchroniseReport, String>
+where
+    S: PreprocessorSelector + Sync,
+    U: Uploader + Sync,
+{
+    synchronise_with(
+        preprocessors,
+        uploader,
+        downloaded_sources,
+        &SyncOptions::default(),
+    )
+    .await
+}
 
+/// [`synchronise`] with run-wide options. Every source is processed before the bucket is
+/// emptied and anything is uploaded, so deduplication can see all of them and a failed
+/// source leaves the bucket untouched.
+pub async fn synchronise_with<S, U>(
+    preprocessors: &S,
     uploader: &U,
     downloaded_sources: &[crate::contract::DownloadedSource],
+    options: &SyncOptions,
 ) -> Result<SynchroniseReport, String>
 where
-    P: Preprocessor + Sync,
+    S: PreprocessorSelector + Sync,
     U: Uploader + Sync,
 {
     info!("[SYNC] Starting full synchronisation pipeline");
 
-    if let Err(e) = empty_bucket(uploader).await {
-        error!(error = ?e, "[SYNC][ERROR] Failed to empty bucket before sync");
-        return Err(format!("Failed to empty bucket before sync: {e:?}"));
-    }
-    info!("[SYNC] Emptied bucket before sync");
-
-    let mut sources_report: Vec<ExternalSourceReport> = Vec::new();
-
+    let mut processed = Vec::with_capacity(downloaded_sources.len());
     for downloaded in downloaded_sources {
         let process_input = ProcessInput {
             name: downloaded.logical_name.clone(),
             repo_path: downloaded.local_path.clone(),
+            base_url: crate::download::web_base_url(&downloaded.original_source),
+            options: downloaded.original_source.options().clone(),
+            variables: downloaded.original_source.variables(),
         };
         info!(repo_name = %downloaded.logical_name, "[SYNC] Invoking processing step (process strategy)");
+        let preprocessor = preprocessors.select(downloaded);
         let source_for_upload = match preprocessor.process(process_input).await {
             Ok(src) => {
                 info!(
                     items = src.external_items.len(),
+                    skipped = src.skipped.len(),
                     "[SYNC] Processing succeeded"
                 );
+                for skipped in &src.skipped {
+                    debug!(path = %skipped.path, reason = %skipped.reason, "[SYNC] Skipped path");
+                }
+                for finding in &src.findings {
+                    warn!(path = %finding.path, line = finding.line, rule = finding.rule, "[SYNC] Secret redacted");
+                }
+                if !src.pii.is_empty() {
+                    info!(counts = ?src.pii, "[SYNC] Personal data scrubbed");
+                }
                 src
             }
             Err(e) => {
@@ -107,7 +158,31 @@ where
                 return Err(format!("Process step failed: {:?}", e));
             }
         };
+        processed.push(source_for_upload);
+    }
+
+    let dedup = match &options.dedup {
+        Some(dedup) => {
+            let report = deduplicate(&mut processed, dedup);
+            info!(
+                items = report.items,
+                bytes = report.bytes,
+                "[SYNC] Removed duplicate items"
+            );
+            report
+        }
+        None => DedupReport::default(),
+    };
 
+    // Only once every source has processed, so a failure leaves the bucket as it was.
+    if let Err(e) = empty_bucket(uploader).await {
+        error!(error = ?e, "[SYNC][ERROR] Failed to empty bucket before sync");
+        return Err(format!("Failed to empty bucket before sync: {e:?}"));
+    }
+    info!("[SYNC] Emptied bucket before sync");
+
+    let mut sources_report: Vec<ExternalSourceReport> = Vec::new();
+    for source_for_upload in processed {
         // --- Step 3: Upload ---
         let bucket_id: i32 = std::env::var("BUCKET_ID")
             .expect("BUCKET_ID env var must be set for uploader")
@@ -138,10 +213,25 @@ where
         // Upload all items, and record their IDs/names from upload responses
         for ext_item in &source_for_upload.external_items {
             info!(filename = %ext_item.filename, "[SYNC][UPLOAD] Preparing upload for file");
-            let content = String::from_utf8_lossy(&ext_item.content);
+            // Text goes up as-is; binary items (PDFs, uploaded binaries) as base64 rather
+            // than lossily decoded.
+            let (content, encoding) = match std::str::from_utf8(&ext_item.content) {
+                Ok(text) => (Cow::Borrowed(text), ContentEncoding::Utf8),
+                Err(_) => (
+                    Cow::Owned(BASE64_STANDARD.encode(&ext_item.content)),
+                    ContentEncoding::Base64,
+                ),
+            };
             let item_req = crate::contract::NewExternalItem {
                 content: &content,
-                url: &ext_item.filename,
+                encoding,
+                mime_type: ext_item.mime_type.as_deref(),
+                url: ext_item
+                    .metadata
+                    .url
+                    .as_deref()
+                    .unwrap_or(&ext_item.filename),
+                metadata: &ext_item.metadata,
                 bucket_id: bucket_id as i64,
                 external_source_id: ext_source.external_source_id as i64,
                 processing_state: None,
@@ -158,8 +248,9 @@ where
                         }
                     }
                     uploaded_items_report.push(ExternalItemReport {
-                        item_id: resp.external_item_id as i64,
+                        item_id: resp.external_item_id,
                         item_name: ext_item.filename.clone(),
+                        mime_type: ext_item.mime_type.clone(),
                     });
                     resp
                 }
@@ -185,11 +276,15 @@ where
             source_id: ext_source.external_source_id as i64,
             source_name: ext_source.external_source_name.clone(),
             items: uploaded_items_report,
+            skipped: source_for_upload.skipped,
+            findings: source_for_upload.findings,
+            pii: source_for_upload.pii,
         });
     }
 
     Ok(SynchroniseReport {
         sources: sources_report,
+        dedup,
     })
 }
 
@@ -0,0 +1,146 @@
+//! Tests for language detection, syntax highlighting and line numbers in code PDFs.
+
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+mod common;
+use common::{pdf_pages_text, pdf_text_runs};
+
+use llm_bucket::code_to_pdf::highlight::{detect_language, Highlighter, Span, Theme, TokenKind};
+use llm_bucket::code_to_pdf::{code_file_to_pdf_with_options, text_to_pdf, PdfOptions};
+
+fn color_of(runs: &[(String, (f32, f32, f32))], text: &str) -> (f32, f32, f32) {
+    runs.iter()
+        .find(|(t, _)| t == text)
+        .unwrap_or_else(|| panic!("No run {text:?} in {runs:?}"))
+        .1
+}
+
+fn close(a: (f32, f32, f32), b: (f32, f32, f32)) -> bool {
+    (a.0 - b.0).abs() < 0.01 && (a.1 - b.1).abs() < 0.01 && (a.2 - b.2).abs() < 0.01
+}
+
+#[test]
+fn test_detect_language_by_extension_and_shebang() {
+    let name = |path: &str, text: &str| detect_language(Path::new(path), text).map(|l| l.name);
+    assert_eq!(name("src/main.rs", ""), Some("rust"));
+    assert_eq!(name("app/Component.TSX", ""), Some("typescript"));
+    assert_eq!(
+        name("bin/deploy", "#!/usr/bin/env python3\nprint(1)\n"),
+        Some("python")
+    );
+    assert_eq!(
+        name("scripts/run", "#!/bin/bash -e\necho hi\n"),
+        Some("shell")
+    );
+    assert_eq!(name("notes.xyz", "just words"), None);
+}
+
+#[test]
+fn test_highlighter_tracks_block_comments_across_lines() {
+    let rust = detect_language(Path::new("lib.rs"), "");
+    let mut h = Highlighter::new(rust);
+    let first = h.highlight_line("let x = 42; /* start");
+    assert_eq!(
+        first,
+        vec![
+            Span {
+                kind: TokenKind::Keyword,
+                text: "let".into()
+            },
+            Span {
+                kind: TokenKind::Plain,
+                text: " x = ".into()
+            },
+            Span {
+                kind: TokenKind::Number,
+                text: "42".into()
+            },
+            Span {
+                kind: TokenKind::Plain,
+                text: "; ".into()
+            },
+            Span {
+                kind: TokenKind::Comment,
+                text: "/* start".into()
+            },
+        ]
+    );
+    let second = h.highlight_line("still comment */ fn f() -> &'a str { \"s\" }");
+    assert_eq!(second[0].kind, TokenKind::Comment);
+    assert_eq!(second[0].text, "still comment */");
+    assert!(second.contains(&Span {
+        kind: TokenKind::Keyword,
+        text: "fn".into()
+    }));
+    assert!(second.contains(&Span {
+        kind: TokenKind::String,
+        text: "\"s\"".into()
+    }));
+}
+
+#[test]
+fn test_rust_file_is_highlighted_with_selected_theme() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("main.rs");
+    fs::write(
+        &input,
+        "// entry point\nfn main() {\n    println!(\"hi\");\n}\n",
+    )
+    .unwrap();
+
+    for theme in [Theme::Light, Theme::Solarized] {
+        let output = dir.path().join("main.pdf");
+        let options = PdfOptions {
+            theme,
+            ..PdfOptions::default()
+        };
+        code_file_to_pdf_with_options(&input, &output, &options).unwrap();
+        let runs = pdf_text_runs(&fs::read(&output).unwrap());
+
+        assert!(close(
+            color_of(&runs, "fn"),
+            theme.color(TokenKind::Keyword)
+        ));
+        assert!(close(
+            color_of(&runs, "\"hi\""),
+            theme.color(TokenKind::String)
+        ));
+        assert!(close(
+            color_of(&runs, "// entry point"),
+            theme.color(TokenKind::Comment)
+        ));
+        assert!(close(color_of(&runs, "1 "), theme.line_number_color()));
+    }
+}
+
+#[test]
+fn test_unknown_language_falls_back_to_plain_text() {
+    let options = PdfOptions {
+        header: Some("notes.xyz".to_string()),
+        ..PdfOptions::default()
+    };
+    let bytes = text_to_pdf("fn looks like code // but is not\n", &options).unwrap();
+    let runs = pdf_text_runs(&bytes);
+    assert!(close(
+        color_of(&runs, "fn looks like code // but is not"),
+        Theme::Light.color(TokenKind::Plain)
+    ));
+}
+
+#[test]
+fn test_line_numbers_can_be_disabled() {
+    let text: String = (1..=12).map(|i| format!("line {i}\n")).collect();
+    let numbered = pdf_pages_text(&text_to_pdf(&text, &PdfOptions::default()).unwrap()).remove(0);
+    assert!(numbered.contains("\n 9 line 9\n"));
+    assert!(numbered.contains("\n12 line 12\n"));
+
+    let options = PdfOptions {
+        line_numbers: false,
+        ..PdfOptions::default()
+    };
+    let plain = pdf_pages_text(&text_to_pdf(&text, &options).unwrap()).remove(0);
+    assert!(plain.contains("\nline 9\n"));
+    assert!(!plain.contains(" 9 line 9"));
+}
@@ -0,0 +1,181 @@
+//! Helpers shared by integration tests: config fixtures, stage inputs and PDF text.
+//!
+//! lopdf's own `extract_text` rejects the ToUnicode CMaps printpdf writes, so PDF text is
+//! recovered here by decoding each page's content stream and mapping glyph ids back to
+//! Unicode through the font's `bfchar` entries.
+
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use llm_bucket::config::Config;
+use llm_bucket::preprocess::pipeline::{Batch, Item, StageContext};
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+/// A config document with a single git source and `process` as its indented `process:` body.
+pub fn config_yaml(process: &str) -> String {
+    format!(
+        "download:\n  output_dir: out\n  sources:\n    - type: git\n      repo_url: \"git@github.com:org/repo.git\"\nprocess:\n{process}"
+    )
+}
+
+/// Load [`config_yaml`], with the error rendered for message assertions.
+pub fn config(process: &str) -> Result<Config, String> {
+    Config::from_yaml_str(&config_yaml(process)).map_err(|e| e.to_string())
+}
+
+/// A stage context for a source named `demo` checked out at `root`.
+pub fn context(root: &Path) -> StageContext {
+    StageContext {
+        name: "demo".to_string(),
+        repo_path: root.to_path_buf(),
+        base_url: None,
+        options: Default::default(),
+        variables: Default::default(),
+    }
+}
+
+/// A collected item with no breadcrumb, fingerprint or symbol.
+pub fn item(path: &str, content: impl AsRef<[u8]>, mime_type: &str) -> Item {
+    Item {
+        path: path.to_string(),
+        content: content.as_ref().to_vec(),
+        mime_type: Some(mime_type.to_string()),
+        breadcrumb: Vec::new(),
+        origin: path.to_string(),
+        fingerprint: None,
+        symbol: None,
+    }
+}
+
+/// A batch of `items` with nothing skipped or found yet.
+pub fn batch(items: Vec<Item>) -> Batch {
+    Batch {
+        items,
+        ..Batch::default()
+    }
+}
+
+/// Parse a generated PDF and return the extracted text of every page, in order.
+pub fn pdf_pages_text(bytes: &[u8]) -> Vec<String> {
+    let doc = Document::load_mem(bytes).expect("Output should be a parseable PDF");
+    doc.get_pages()
+        .values()
+        .map(|page_id| page_text(&doc, *page_id))
+        .collect()
+}
+
+/// Every text run on every page with the RGB fill colour it was drawn in.
+pub fn pdf_text_runs(bytes: &[u8]) -> Vec<(String, (f32, f32, f32))> {
+    let doc = Document::load_mem(bytes).expect("Output should be a parseable PDF");
+    doc.get_pages()
+        .values()
+        .flat_map(|page_id| page_runs(&doc, *page_id))
+        .collect()
+}
+
+fn page_text(doc: &Document, page_id: ObjectId) -> String {
+    let mut text = String::new();
+    for (run, _) in page_runs(doc, page_id) {
+        text.push_str(&run);
+    }
+    text
+}
+
+/// Decode a page into text runs; line-moving operators become `\n` runs.
+fn page_runs(doc: &Document, page_id: ObjectId) -> Vec<(String, (f32, f32, f32))> {
+    let fonts: HashMap<Vec<u8>, HashMap<u16, String>> = doc
+        .get_page_fonts(page_id)
+        .expect("Page fonts should resolve")
+        .into_iter()
+        .map(|(name, font)| (name, to_unicode_map(doc, font)))
+        .collect();
+    let content = doc
+        .get_and_decode_page_content(page_id)
+        .expect("Page content should decode");
+
+    let mut runs = Vec::new();
+    let mut fill = (0.0, 0.0, 0.0);
+    let mut current: Option<&HashMap<u16, String>> = None;
+    for op in &content.operations {
+        match op.operator.as_str() {
+            "rg" => {
+                let c: Vec<f32> = op
+                    .operands
+                    .iter()
+                    .filter_map(|o| o.as_float().ok())
+                    .collect();
+                if let [r, g, b] = c[..] {
+                    fill = (r, g, b);
+                }
+            }
+            "Tf" => {
+                current = op
+                    .operands
+                    .first()
+                    .and_then(|o| o.as_name().ok())
+                    .and_then(|name| fonts.get(name));
+            }
+            "Tj" | "TJ" => {
+                let strings = op.operands.iter().flat_map(|o| match o {
+                    Object::Array(items) => items.iter().collect::<Vec<_>>(),
+                    other => vec![other],
+                });
+                let mut text = String::new();
+                for s in strings {
+                    if let (Object::String(bytes, _), Some(map)) = (s, current) {
+                        for gid in bytes.chunks(2) {
+                            let gid = u16::from_be_bytes([gid[0], *gid.get(1).unwrap_or(&0)]);
+                            text.push_str(map.get(&gid).map(String::as_str).unwrap_or("?"));
+                        }
+                    }
+                }
+                runs.push((text, fill));
+            }
+            "T*" | "Td" | "TD" | "ET" => runs.push(("\n".to_string(), fill)),
+            _ => {}
+        }
+    }
+    runs
+}
+
+fn to_unicode_map(doc: &Document, font: &Dictionary) -> HashMap<u16, String> {
+    let Some(stream) = font
+        .get(b"ToUnicode")
+        .ok()
+        .and_then(|o| o.as_reference().ok())
+        .and_then(|id| doc.get_object(id).ok())
+        .and_then(|o| o.as_stream().ok())
+    else {
+        return HashMap::new();
+    };
+    let content = stream
+        .decompressed_content()
+        .unwrap_or_else(|_| stream.content.clone());
+    let content = String::from_utf8_lossy(&content);
+
+    let hex = |s: &str| -> Vec<u16> {
+        let s = s.trim_matches(|c| c == '<' || c == '>');
+        (0..s.len() / 4)
+            .filter_map(|i| u16::from_str_radix(&s[i * 4..i * 4 + 4], 16).ok())
+            .collect()
+    };
+    let mut map = HashMap::new();
+    let mut in_bfchar = false;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.ends_with("beginbfchar") {
+            in_bfchar = true;
+        } else if line == "endbfchar" {
+            in_bfchar = false;
+        } else if in_bfchar {
+            if let Some((gid, unicode)) = line.split_once(' ') {
+                if let Some(gid) = hex(gid).first() {
+                    map.insert(*gid, String::from_utf16_lossy(&hex(unicode)));
+                }
+            }
+        }
+    }
+    map
+}
@@ -0,0 +1,171 @@
+//! Tests for loading and validating the YAML config.
+
+use std::fs;
+use tempfile::tempdir;
+
+mod common;
+use common::config_yaml;
+
+use llm_bucket::config::{Config, ConfigError};
+use llm_bucket::contract::{ProcessorKind, UnknownProcessorKind};
+
+const VALID: &str = r#"
+download:
+  output_dir: ./tmp/exports
+  sources:
+    - type: git
+      repo_url: "git@github.com:org/repo.git"
+process:
+  kind: readme2pdf
+"#;
+
+#[test]
+fn test_example_config_is_valid() {
+    let config = Config::load(std::path::Path::new("config.yaml.example"))
+        .expect("config.yaml.example should load and validate");
+    assert!(!config.download.sources.is_empty());
+}
+
+#[test]
+fn test_processor_kind_aliases_are_accepted() {
+    let config = Config::from_yaml_str(VALID).unwrap();
+    assert_eq!(config.process.kind, Some(ProcessorKind::ReadmeToPDF));
+}
+
+#[test]
+fn test_unknown_processor_kind_is_rejected_with_valid_kinds() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("config.yaml");
+    fs::write(&path, VALID.replace("readme2pdf", "readme2pd")).unwrap();
+
+    let err = Config::load(&path).expect_err("A misspelled kind must not load");
+    assert!(matches!(err, ConfigError::Parse(_)), "{err:?}");
+    let message = err.to_string();
+    assert!(
+        message.contains("unknown processor kind `readme2pd`"),
+        "{message}"
+    );
+    for name in [
+        "ReadmeToPDF",
+        "readme2pdf",
+        "FlattenFiles",
+        "CodeToPDF",
+        "DirectoryToPDF",
+    ] {
+        assert!(message.contains(name), "{name} missing from: {message}");
+    }
+}
+
+#[test]
+fn test_processor_kind_parsing_never_falls_back() {
+    assert_eq!(
+        "flatten".parse::<ProcessorKind>(),
+        Err(UnknownProcessorKind("flatten".to_string()))
+    );
+    assert!(ProcessorKind::try_from("").is_err());
+    assert_eq!(
+        ProcessorKind::try_from("flatten_files"),
+        Ok(ProcessorKind::FlattenFiles)
+    );
+}
+
+#[test]
+fn test_validation_reports_every_problem() {
+    let yaml = r#"
+download:
+  output_dir: ./tmp/exports
+  sources:
+    - type: git
+      repo_url: ""
+    - type: database
+      url: "mysql://db/app"
+    - type: s3
+      endpoint: "https://s3.example.com"
+      bucket: docs
+      include: ["docs/[a-"]
+process:
+  kind: FlattenFiles
+"#;
+    let Err(ConfigError::Invalid(problems)) = Config::from_yaml_str(yaml) else {
+        panic!("Config should fail validation");
+    };
+    assert_eq!(problems.len(), 3, "{problems:?}");
+    assert!(problems[0].starts_with("download.sources[0]: `repo_url`"));
+    assert!(problems[1].starts_with("download.sources[1]: `url` must start with"));
+    assert!(problems[2].contains("invalid glob `docs/[a-`"));
+}
+
+#[test]
+fn test_config_without_sources_is_invalid() {
+    let yaml = "download:\n  output_dir: out\n  sources: []\nprocess:\n  kind: FlattenFiles\n";
+    let err = Config::from_yaml_str(yaml).unwrap_err();
+    assert!(err.to_string().contains("at least one source"), "{err}");
+}
+
+#[test]
+fn test_sources_can_set_their_own_process_config() {
+    let yaml = r#"
+download:
+  output_dir: ./tmp/exports
+  sources:
+    - type: git
+      repo_url: "git@github.com:org/repo.git"
+    - type: git
+      repo_url: "git@github.com:org/huge.git"
+      process:
+        kind: ReadmeToPDF
+    - type: git
+      repo_url: "git@github.com:org/docs.git"
+      process:
+        stages:
+          - stage: chunk
+process:
+  kind: FlattenFiles
+"#;
+    let err = Config::from_yaml_str(yaml).expect_err("A source pipeline without a source stage");
+    let ConfigError::Invalid(problems) = err else {
+        panic!("expected validation problems, got {err:?}");
+    };
+    assert_eq!(
+        problems,
+        vec!["download.sources[2].process.stages[0]: the first stage must be a source stage (`collect`, `readme` or `history`)"]
+    );
+
+    let config = Config::from_yaml_str(&yaml.replace("stage: chunk", "stage: collect")).unwrap();
+    let kinds: Vec<_> = config
+        .download
+        .sources
+        .iter()
+        .map(|s| s.options().process.as_ref().map(|p| p.kind))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![None, Some(Some(ProcessorKind::ReadmeToPDF)), Some(None)]
+    );
+}
+
+#[test]
+fn test_misspelled_settings_are_rejected() {
+    let Err(ConfigError::Invalid(problems)) = Config::from_yaml_str(&config_yaml(
+        "  kind: FlattenFiles\n  redcat:\n    strict: true\n",
+    )) else {
+        panic!("A misspelled `redact` block must not load");
+    };
+    assert_eq!(problems, vec!["process: unknown setting `redcat`"]);
+
+    for process in [
+        "  kind: FlattenFiles\n  redact:\n    stict: true\n",
+        "  stages:\n    - stage: collect\n    - stage: redact\n      stirct: true\n",
+        "  stages:\n    - stage: collect\n      max_fil_size: 10\n",
+        "  kind: FlattenFiles\n  pii:\n    mdoe: pseudonymise\n",
+    ] {
+        let err = Config::from_yaml_str(&config_yaml(process)).expect_err(process);
+        assert!(matches!(err, ConfigError::Parse(_)), "{err:?}");
+        assert!(err.to_string().contains("unknown field"), "{err}");
+    }
+
+    let config =
+        Config::from_yaml_str(&config_yaml("  kind: FlattenFiles\n  max_file_size: 10\n")).unwrap();
+    assert_eq!(config.process.files.max_file_size, 10);
+    assert!(config.process.unknown.is_empty());
+}
@@ -55,6 +55,7 @@ fn confl_src_struct(base_url: String, space_key: String) -> ConfluenceSource {
     ConfluenceSource {
         base_url,
         space_key,
+        options: Default::default(),
     }
 }
 
@@ -157,7 +158,7 @@ async fn test_downloads_all_confluence_pages_as_markdown() {
                 let path = entry.path();
                 if path.is_dir() {
                     visit_dirs(&path, files);
-                } else if path.extension().map_or(false, |ext| ext == "md") {
+                } else if path.extension().is_some_and(|ext| ext == "md") {
                     files.push(path);
                 }
             }
@@ -45,6 +45,7 @@ async fn test_download_populates_directory_table_driven() {
                 sources: vec![SourceAction::Git(GitSource {
                     repo_url: repo_url.into(),
                     reference: Some(reference.into()),
+                    options: Default::default(),
                 })],
             },
             expected_dirs: vec![expected_subdir_llm.clone()],
@@ -56,6 +57,7 @@ async fn test_download_populates_directory_table_driven() {
                 sources: vec![SourceAction::Git(GitSource {
                     repo_url: ai_repo_url.into(),
                     reference: Some(ai_reference.into()),
+                    options: Default::default(),
                 })],
             },
             expected_dirs: vec![expected_subdir_ai.clone()],
@@ -67,6 +69,7 @@ async fn test_download_populates_directory_table_driven() {
                 sources: vec![SourceAction::Git(GitSource {
                     repo_url: private_ssh_url.into(),
                     reference: Some(private_reference.into()),
+                    options: Default::default(),
                 })],
             },
             expected_dirs: vec![expected_subdir_private.clone()],
@@ -79,10 +82,12 @@ async fn test_download_populates_directory_table_driven() {
                     SourceAction::Git(GitSource {
                         repo_url: repo_url.into(),
                         reference: Some(reference.into()),
+                        options: Default::default(),
                     }),
                     SourceAction::Git(GitSource {
                         repo_url: ai_repo_url.into(),
                         reference: Some(ai_reference.into()),
+                        options: Default::default(),
                     }),
                 ],
             },
@@ -96,10 +101,12 @@ async fn test_download_populates_directory_table_driven() {
                     SourceAction::Git(GitSource {
                         repo_url: repo_url.into(),
                         reference: Some(reference.into()),
+                        options: Default::default(),
                     }),
                     SourceAction::Git(GitSource {
                         repo_url: repo_url.into(),
                         reference: Some("879e21e".into()),
+                        options: Default::default(),
                     }),
                 ],
             },
@@ -127,7 +134,7 @@ async fn test_download_populates_directory_table_driven() {
 
         // Assert all expected directories exist and are not empty
         for expected_dir in &tc.expected_dirs {
-            let full_source_path = Path::new(output_dir).join(&expected_dir);
+            let full_source_path = Path::new(output_dir).join(expected_dir);
             assert!(
                 full_source_path.exists() && full_source_path.is_dir(),
                 "{}: Source subdirectory ('{}') should exist and be a directory",
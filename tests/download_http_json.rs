@@ -0,0 +1,203 @@
+//! Integration tests for the declarative JSON API source, against a local mock server.
+
+use llm_bucket::download::http_json::{download, render_template};
+use llm_bucket::download::{
+    AuthHeader, HttpJsonSource, IndexedFile, Pagination, SourceIndex, SOURCE_INDEX_FILE,
+};
+use serde_json::json;
+use std::fs;
+use tempfile::tempdir;
+use wiremock::matchers::{header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn incident_source(url: String, pagination: Pagination) -> HttpJsonSource {
+    HttpJsonSource {
+        url,
+        auth_header: None,
+        pagination,
+        max_pages: None,
+        records_pointer: "/data".into(),
+        template: "# {{ title }}\n\nStatus: {{ status }}\nTags: {{ tags }}".into(),
+        name_template: Some("{{ id }}-{{ title }}".into()),
+        url_template: "https://incidents.example.com/i/{{ /id }}".into(),
+        options: Default::default(),
+    }
+}
+
+fn file_names(dir: &std::path::Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name != SOURCE_INDEX_FILE)
+        .collect();
+    names.sort();
+    names
+}
+
+#[tokio::test]
+async fn test_http_json_offset_pagination_with_auth_header() {
+    let server = MockServer::start().await;
+    std::env::set_var("LLM_BUCKET_TEST_INCIDENTS_TOKEN", "Bearer s3cret");
+    Mock::given(method("GET"))
+        .and(path("/api/incidents"))
+        .and(query_param("offset", "0"))
+        .and(query_param("limit", "2"))
+        .and(header("Authorization", "Bearer s3cret"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "data": [
+                {"id": 1, "title": "DB failover", "status": "resolved", "tags": ["db", "p1"]},
+                {"id": 2, "title": "Cert expiry", "status": "open", "tags": []}
+            ]
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/incidents"))
+        .and(query_param("offset", "2"))
+        .and(header("Authorization", "Bearer s3cret"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "data": [{"id": 3, "title": "Queue backlog", "status": "open"}]
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let mut source = incident_source(
+        format!("{}/api/incidents", server.uri()),
+        Pagination::Offset {
+            offset_param: "offset".into(),
+            limit_param: "limit".into(),
+            page_size: 2,
+        },
+    );
+    source.auth_header = Some(AuthHeader {
+        name: "Authorization".into(),
+        value_env: "LLM_BUCKET_TEST_INCIDENTS_TOKEN".into(),
+    });
+
+    let tmp = tempdir().unwrap();
+    let written = download(&source, tmp.path())
+        .await
+        .expect("Download succeeds");
+    assert_eq!(written, 3);
+    assert_eq!(
+        file_names(tmp.path()),
+        vec!["1-DB failover.md", "2-Cert expiry.md", "3-Queue backlog.md"]
+    );
+    let doc = fs::read_to_string(tmp.path().join("1-DB failover.md")).unwrap();
+    assert_eq!(
+        doc,
+        "# DB failover\n\nStatus: resolved\nTags: db, p1\n\nSource: https://incidents.example.com/i/1\n"
+    );
+    let index = SourceIndex::load(tmp.path());
+    assert_eq!(index.files.len(), 3);
+    assert_eq!(
+        index.files["3-Queue backlog.md"],
+        IndexedFile {
+            url: Some("https://incidents.example.com/i/3".into()),
+            modified: None,
+            breadcrumb: Vec::new(),
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_http_json_cursor_pagination() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/adrs"))
+        .and(query_param("cursor", "page2"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "data": [{"id": "adr-2", "title": "Use NATS"}],
+            "meta": {"next": "page2"}
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/adrs"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "data": [{"id": "adr-1", "title": "Use Rust"}],
+            "meta": {"next": "page2"}
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let source = incident_source(
+        format!("{}/adrs", server.uri()),
+        Pagination::Cursor {
+            cursor_param: "cursor".into(),
+            next_cursor_pointer: "/meta/next".into(),
+        },
+    );
+    let tmp = tempdir().unwrap();
+    let written = download(&source, tmp.path())
+        .await
+        .expect("Download succeeds");
+    assert_eq!(written, 2);
+    assert_eq!(
+        file_names(tmp.path()),
+        vec!["adr-1-Use Rust.md", "adr-2-Use NATS.md"],
+        "a repeated cursor ends pagination"
+    );
+}
+
+#[tokio::test]
+async fn test_http_json_link_header_pagination_top_level_array() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/services"))
+        .and(query_param("page", "2"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("Link", r#"<services>; rel="next""#)
+                .set_body_json(json!([{"name": "billing"}])),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/services"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("Link", r#"</services?page=2>; rel="next""#)
+                .set_body_json(json!([{"name": "auth"}])),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let source = HttpJsonSource {
+        url: format!("{}/services", server.uri()),
+        auth_header: None,
+        pagination: Pagination::LinkHeader,
+        max_pages: None,
+        records_pointer: String::new(),
+        template: "Service {{ name }}".into(),
+        name_template: None,
+        url_template: "https://catalog.example.com/{{ name }}".into(),
+        options: Default::default(),
+    };
+    let tmp = tempdir().unwrap();
+    let written = download(&source, tmp.path())
+        .await
+        .expect("Download succeeds");
+    assert_eq!(written, 2);
+    assert_eq!(file_names(tmp.path()), vec!["record_0.md", "record_1.md"]);
+    let doc = fs::read_to_string(tmp.path().join("record_1.md")).unwrap();
+    assert!(doc.starts_with("Service billing"));
+}
+
+#[test]
+fn test_render_template_handles_nested_paths_and_missing_values() {
+    let record = json!({"fields": {"summary": "Slow API", "labels": ["perf"]}, "n": 3});
+    assert_eq!(
+        render_template(
+            "{{fields.summary}} [{{ /fields/labels }}] #{{n}} {{missing}}",
+            &record
+        ),
+        "Slow API [perf] #3 "
+    );
+}
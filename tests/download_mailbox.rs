@@ -0,0 +1,188 @@
+//! Integration tests for the mbox / Maildir mailing list source.
+//!
+//! Fixture mailboxes are written to a temp dir, so no mail server is needed.
+
+use llm_bucket::contract::Downloader;
+use llm_bucket::download::{DefaultDownloader, DownloadConfig, MailboxSource, SourceAction};
+use std::fs;
+use tempfile::tempdir;
+
+const MBOX: &str = "From alice@example.com Mon Mar  4 10:00:00 2024
+Message-ID: <adr-1@example.com>
+From: Alice <alice@example.com>
+To: arch@lists.example.com
+Subject: ADR: adopt NATS for events
+Date: Mon, 4 Mar 2024 10:00:00 +0000
+
+I propose we use NATS JetStream for async events.
+>From experience it is simpler to run than Kafka.
+
+-- 
+Alice, Platform team
+
+From bob@example.com Mon Mar  4 11:00:00 2024
+Message-ID: <adr-2@example.com>
+In-Reply-To: <adr-1@example.com>
+References: <adr-1@example.com>
+From: Bob <bob@example.com>
+Subject: Re: ADR: adopt NATS for events
+Date: Mon, 4 Mar 2024 11:30:00 +0000
+MIME-Version: 1.0
+Content-Type: multipart/alternative; boundary=\"b1\"
+
+--b1
+Content-Type: text/plain; charset=utf-8
+Content-Transfer-Encoding: base64
+
+QWdyZWVkLCBsZXQncyBkb2N1bWVudCByZXRlbnRpb24u
+--b1
+Content-Type: text/html; charset=utf-8
+
+<p>HTML version should not be used</p>
+--b1--
+
+From carol@example.com Mon Mar  4 12:00:00 2024
+Message-ID: <adr-3@example.com>
+In-Reply-To: <adr-2@example.com>
+From: Carol <carol@example.com>
+Subject: Re: Re: ADR: adopt NATS for events
+Date: Mon, 4 Mar 2024 12:00:00 +0000
+Content-Type: text/html; charset=utf-8
+
+<p>Retention is <b>7 days</b> &amp; replicated.</p>
+<p>On Mon, 4 Mar 2024 Bob wrote:</p>
+<blockquote>&gt; Agreed</blockquote>
+
+From dave@example.com Tue Mar  5 09:00:00 2024
+Message-ID: <other@example.com>
+From: Dave <dave@example.com>
+Subject: Lunch on Friday?
+Date: Tue, 5 Mar 2024 09:00:00 +0000
+
+Anyone?
+";
+
+#[tokio::test]
+async fn test_mbox_groups_messages_into_thread_documents() {
+    let tmp = tempdir().unwrap();
+    let mbox_path = tmp.path().join("arch.mbox");
+    fs::write(&mbox_path, MBOX).unwrap();
+
+    let config = DownloadConfig {
+        output_dir: tmp.path().join("out"),
+        sources: vec![SourceAction::Mailbox(MailboxSource {
+            path: mbox_path,
+            format: None,
+            options: Default::default(),
+        })],
+    };
+    let manifest = DefaultDownloader::new(config)
+        .download_all()
+        .await
+        .expect("Mailbox export should succeed");
+    let dir = &manifest.sources[0].local_path;
+
+    let mut files: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    files.sort();
+    assert_eq!(
+        files,
+        vec!["ADR_ adopt NATS for events.md", "Lunch on Friday.md"]
+    );
+
+    let thread = fs::read_to_string(dir.join("ADR_ adopt NATS for events.md")).unwrap();
+    assert!(thread.starts_with("# ADR: adopt NATS for events\n"));
+    assert!(thread.contains(
+        "- Participants: Alice <alice@example.com>, Bob <bob@example.com>, Carol <carol@example.com>"
+    ));
+    assert!(thread.contains("- Messages: 3"));
+    assert!(thread.contains("- Dates: 2024-03-04 10:00 UTC to 2024-03-04 12:00 UTC"));
+    assert!(thread.contains("From experience it is simpler"));
+    assert!(!thread.contains("Platform team"), "Signature is stripped");
+    assert!(thread.contains("Agreed, let's document retention."));
+    assert!(!thread.contains("HTML version"), "text/plain is preferred");
+    assert!(thread.contains("Retention is 7 days & replicated."));
+    assert!(
+        !thread.contains("Bob wrote"),
+        "Quote attribution is stripped"
+    );
+    assert!(!thread.contains("> Agreed"), "Quoted reply is stripped");
+}
+
+#[tokio::test]
+async fn test_maildir_is_detected_and_threaded() {
+    let tmp = tempdir().unwrap();
+    let maildir = tmp.path().join("Maildir");
+    fs::create_dir_all(maildir.join("cur")).unwrap();
+    fs::create_dir_all(maildir.join("new")).unwrap();
+    fs::write(
+        maildir.join("cur/1.eml"),
+        "Message-ID: <a@x>\nFrom: A <a@x>\nSubject: Schema migration\nDate: Wed, 1 May 2024 08:00:00 +0000\n\nShould we squash migrations?\n",
+    )
+    .unwrap();
+    fs::write(
+        maildir.join("new/2.eml"),
+        "Message-ID: <b@x>\nIn-Reply-To: <a@x>\nFrom: B <b@x>\nSubject: RE: Schema migration\nDate: Wed, 1 May 2024 09:15:00 +0000\n\nYes, before the release.\n\n-----Original Message-----\nShould we squash migrations?\n",
+    )
+    .unwrap();
+
+    let out = tmp.path().join("out");
+    let source = MailboxSource {
+        path: maildir,
+        format: None,
+        options: Default::default(),
+    };
+    let config = DownloadConfig {
+        output_dir: out.clone(),
+        sources: vec![SourceAction::Mailbox(source.clone())],
+    };
+    llm_bucket::download::run(&config)
+        .await
+        .expect("Maildir export should succeed");
+
+    let dir = out.join(llm_bucket::download::mailbox::source_dir_name(&source));
+    let thread = fs::read_to_string(dir.join("Schema migration.md")).unwrap();
+    assert!(thread.contains("- Messages: 2"));
+    assert!(thread.contains("## B <b@x> (Wed, 1 May 2024 09:15:00 +0000)"));
+    assert_eq!(thread.matches("Should we squash migrations?").count(), 1);
+}
+
+#[tokio::test]
+async fn test_message_without_id_gets_a_stable_name() {
+    let tmp = tempdir().unwrap();
+    let mbox_path = tmp.path().join("noid.mbox");
+    fs::write(
+        &mbox_path,
+        "From a@x Mon Mar  4 10:00:00 2024\nFrom: A <a@x>\nSubject: Release notes\nDate: Mon, 4 Mar 2024 10:00:00 +0000\n\nDraft attached.\n",
+    )
+    .unwrap();
+    let source = MailboxSource {
+        path: mbox_path,
+        format: None,
+        options: Default::default(),
+    };
+
+    let mut runs = Vec::new();
+    for out in ["first", "second"] {
+        let dest = tmp.path().join(out);
+        fs::create_dir_all(&dest).unwrap();
+        llm_bucket::download::mailbox::download(&source, &dest)
+            .expect("Mailbox export should succeed");
+        let mut files: Vec<(String, String)> = fs::read_dir(&dest)
+            .unwrap()
+            .map(|e| {
+                let e = e.unwrap();
+                (
+                    e.file_name().to_string_lossy().into_owned(),
+                    fs::read_to_string(e.path()).unwrap(),
+                )
+            })
+            .collect();
+        files.sort();
+        runs.push(files);
+    }
+    assert_eq!(runs[0].len(), 1);
+    assert_eq!(runs[0], runs[1], "Output is the same across downloads");
+}
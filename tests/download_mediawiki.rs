@@ -0,0 +1,162 @@
+//! Integration tests for the MediaWiki XML dump source.
+//!
+//! Uses a small hand-written dump fixture, so no wiki or network access is needed.
+
+use llm_bucket::contract::Downloader;
+use llm_bucket::download::mediawiki::wikitext_to_markdown;
+use llm_bucket::download::{DefaultDownloader, DownloadConfig, MediaWikiSource, SourceAction};
+use std::fs;
+use tempfile::tempdir;
+
+const DUMP: &str = r#"<mediawiki xmlns="http://www.mediawiki.org/xml/export-0.10/" version="0.10">
+  <siteinfo>
+    <sitename>Runbooks</sitename>
+    <namespaces>
+      <namespace key="0" case="first-letter" />
+      <namespace key="1" case="first-letter">Talk</namespace>
+      <namespace key="12" case="first-letter">Help</namespace>
+    </namespaces>
+  </siteinfo>
+  <page>
+    <title>Restart the queue</title>
+    <ns>0</ns>
+    <id>1</id>
+    <revision>
+      <id>10</id>
+      <timestamp>2019-01-01T00:00:00Z</timestamp>
+      <text xml:space="preserve">OLD CONTENT</text>
+    </revision>
+    <revision>
+      <id>11</id>
+      <timestamp>2021-06-01T12:00:00Z</timestamp>
+      <text xml:space="preserve">== Steps ==
+* Log in to [[Bastion host|the bastion]]
+* Run {{cmd|systemctl restart queue}}
+# Check '''health'''
+[[Category:Operations]]</text>
+    </revision>
+  </page>
+  <page>
+    <title>Help:Editing</title>
+    <ns>12</ns>
+    <id>2</id>
+    <revision>
+      <id>20</id>
+      <timestamp>2020-01-01T00:00:00Z</timestamp>
+      <text xml:space="preserve">Use ''italics'' sparingly.</text>
+    </revision>
+  </page>
+  <page>
+    <title>Talk:Restart the queue</title>
+    <ns>1</ns>
+    <id>3</id>
+    <revision>
+      <id>30</id>
+      <timestamp>2020-01-01T00:00:00Z</timestamp>
+      <text xml:space="preserve">Does this still work? [[Category:Operations]]</text>
+    </revision>
+  </page>
+  <page>
+    <title>Queue</title>
+    <ns>0</ns>
+    <id>4</id>
+    <redirect title="Restart the queue" />
+    <revision>
+      <id>40</id>
+      <timestamp>2020-01-01T00:00:00Z</timestamp>
+      <text xml:space="preserve">#REDIRECT [[Restart the queue]]</text>
+    </revision>
+  </page>
+</mediawiki>
+"#;
+
+fn write_dump(dir: &std::path::Path) -> std::path::PathBuf {
+    let dump_path = dir.join("dump.xml");
+    fs::write(&dump_path, DUMP).unwrap();
+    dump_path
+}
+
+#[tokio::test]
+async fn test_mediawiki_dump_exports_latest_revision_per_page() {
+    let tmp = tempdir().unwrap();
+    let source = MediaWikiSource {
+        dump_path: write_dump(tmp.path()),
+        namespaces: vec![0, 12],
+        categories: vec![],
+        options: Default::default(),
+    };
+    let config = DownloadConfig {
+        output_dir: tmp.path().join("out"),
+        sources: vec![SourceAction::MediaWiki(source)],
+    };
+
+    let manifest = DefaultDownloader::new(config)
+        .download_all()
+        .await
+        .expect("MediaWiki export should succeed");
+    let dir = &manifest.sources[0].local_path;
+
+    let mut files: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    files.sort();
+    assert_eq!(
+        files,
+        vec!["Help__Editing.md", "Main__Restart the queue.md"]
+    );
+
+    let page = fs::read_to_string(dir.join("Main__Restart the queue.md")).unwrap();
+    assert!(page.starts_with("# Restart the queue"));
+    assert!(page.contains("Last revision: 2021-06-01T12:00:00Z"));
+    assert!(
+        !page.contains("OLD CONTENT"),
+        "Only the latest revision is kept"
+    );
+    assert!(page.contains("Categories: Operations"));
+    assert!(page.contains("## Steps"));
+    assert!(page.contains("- Log in to [the bastion](<Bastion host>)"));
+    assert!(page.contains("- Run cmd (systemctl restart queue)"));
+    assert!(page.contains("1. Check **health**"));
+}
+
+#[tokio::test]
+async fn test_mediawiki_dump_filters_by_category() {
+    let tmp = tempdir().unwrap();
+    let out = tmp.path().join("out");
+    let source = MediaWikiSource {
+        dump_path: write_dump(tmp.path()),
+        namespaces: vec![],
+        categories: vec!["operations".into()],
+        options: Default::default(),
+    };
+    let config = DownloadConfig {
+        output_dir: out.clone(),
+        sources: vec![SourceAction::MediaWiki(source.clone())],
+    };
+
+    llm_bucket::download::run(&config)
+        .await
+        .expect("MediaWiki export should succeed");
+
+    let dir = out.join(llm_bucket::download::mediawiki::source_dir_name(&source));
+    let mut files: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    files.sort();
+    assert_eq!(
+        files,
+        vec!["Main__Restart the queue.md", "Talk__Restart the queue.md"]
+    );
+}
+
+#[test]
+fn test_wikitext_tables_and_external_links_to_markdown() {
+    let wikitext = "{|\n|+ Hosts\n! Name !! Role\n|-\n| db1 || primary\n|-\n| style=\"color:red\" | db2 || [https://status.example.com status]\n|}";
+    let markdown = wikitext_to_markdown(wikitext);
+    assert_eq!(
+        markdown,
+        "**Hosts**\n\n| Name | Role |\n| --- | --- |\n| db1 | primary |\n| db2 | [status](https://status.example.com) |"
+    );
+}
@@ -0,0 +1,205 @@
+//! Integration tests for the S3-compatible object storage source.
+//!
+//! A local wiremock server stands in for MinIO/S3, so no cloud access is needed.
+
+use llm_bucket::download::s3::{download, state_path, S3SyncStats};
+use llm_bucket::download::{S3Source, SourceOptions};
+use llm_bucket::preprocess::walk::walk;
+use std::fs;
+use std::path::PathBuf;
+use tempfile::tempdir;
+use wiremock::matchers::{header_exists, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn listing(objects: &[(&str, &str)]) -> String {
+    let contents: String = objects
+        .iter()
+        .map(|(key, etag)| {
+            format!("<Contents><Key>{key}</Key><ETag>&quot;{etag}&quot;</ETag><Size>1</Size></Contents>")
+        })
+        .collect();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/"><Name>design</Name><Prefix>docs/</Prefix><IsTruncated>false</IsTruncated>{contents}</ListBucketResult>"#
+    )
+}
+
+async fn mount_listing(server: &MockServer, objects: &[(&str, &str)]) {
+    Mock::given(method("GET"))
+        .and(path("/design"))
+        .and(query_param("list-type", "2"))
+        .and(query_param("prefix", "docs/"))
+        .and(header_exists("authorization"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(listing(objects)))
+        .mount(server)
+        .await;
+}
+
+async fn mount_object(server: &MockServer, key: &str, body: &str, times: u64) {
+    Mock::given(method("GET"))
+        .and(path(format!("/design/{key}")))
+        .and(header_exists("authorization"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(body))
+        .expect(times)
+        .mount(server)
+        .await;
+}
+
+fn source(endpoint: String) -> S3Source {
+    S3Source {
+        endpoint,
+        bucket: "design".into(),
+        prefix: "docs/".into(),
+        region: None,
+        access_key_id: Some("minio".into()),
+        secret_access_key: Some("minio-secret".into()),
+        options: SourceOptions {
+            include: vec!["docs/**".into()],
+            exclude: vec!["**/*.log".into()],
+            process: None,
+        },
+    }
+}
+
+#[tokio::test]
+async fn test_s3_download_skips_unchanged_etags_on_rerun() {
+    let server = MockServer::start().await;
+    mount_listing(
+        &server,
+        &[
+            ("docs/", "folder"),
+            ("docs/overview.md", "etag-1"),
+            ("docs/reports/q1.md", "etag-2"),
+            ("docs/debug.log", "etag-3"),
+            ("docs/a//b.md", "etag-4"),
+            ("docs/../escape.md", "etag-5"),
+        ],
+    )
+    .await;
+    // Each object is fetched exactly once across both runs; the log and the keys without
+    // a local path are never fetched.
+    mount_object(&server, "docs/overview.md", "# Overview", 1).await;
+    mount_object(&server, "docs/reports/q1.md", "# Q1", 1).await;
+    mount_object(&server, "docs/debug.log", "noise", 0).await;
+
+    let tmp = tempdir().unwrap();
+    let dest = tmp.path().join("s3_source");
+    let src = source(server.uri());
+
+    let first = download(&src, &dest).await.expect("First sync succeeds");
+    assert_eq!(
+        first,
+        S3SyncStats {
+            downloaded: 2,
+            unchanged: 0,
+            removed: 0
+        }
+    );
+    assert_eq!(
+        fs::read_to_string(dest.join("docs/overview.md")).unwrap(),
+        "# Overview"
+    );
+    assert_eq!(
+        fs::read_to_string(dest.join("docs/reports/q1.md")).unwrap(),
+        "# Q1"
+    );
+    assert!(!dest.join("docs/debug.log").exists());
+    assert!(!dest.join("escape.md").exists());
+    assert!(
+        state_path(&dest).exists(),
+        "ETag state is stored next to the source dir"
+    );
+
+    let second = download(&src, &dest).await.expect("Second sync succeeds");
+    assert_eq!(
+        second,
+        S3SyncStats {
+            downloaded: 0,
+            unchanged: 2,
+            removed: 0
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_s3_download_refetches_changed_and_removes_deleted_objects() {
+    let server = MockServer::start().await;
+    mount_listing(
+        &server,
+        &[("docs/overview.md", "etag-1"), ("docs/old.md", "etag-9")],
+    )
+    .await;
+    mount_object(&server, "docs/overview.md", "# Overview v1", 1).await;
+    mount_object(&server, "docs/old.md", "# Old", 1).await;
+
+    let tmp = tempdir().unwrap();
+    let dest = tmp.path().join("s3_source");
+    let src = source(server.uri());
+    download(&src, &dest).await.expect("First sync succeeds");
+
+    server.verify().await;
+    server.reset().await;
+    mount_listing(&server, &[("docs/overview.md", "etag-2")]).await;
+    mount_object(&server, "docs/overview.md", "# Overview v2", 1).await;
+
+    let stats = download(&src, &dest).await.expect("Second sync succeeds");
+    assert_eq!(
+        stats,
+        S3SyncStats {
+            downloaded: 1,
+            unchanged: 0,
+            removed: 1
+        }
+    );
+    assert_eq!(
+        fs::read_to_string(dest.join("docs/overview.md")).unwrap(),
+        "# Overview v2"
+    );
+    assert!(!dest.join("docs/old.md").exists());
+}
+
+#[tokio::test]
+async fn test_s3_patterns_match_like_the_walk() {
+    let server = MockServer::start().await;
+    mount_listing(
+        &server,
+        &[
+            ("docs/index.md", "etag-1"),
+            ("docs/guides/setup.md", "etag-2"),
+            ("docs/drafts/idea.md", "etag-3"),
+            ("docs/api/reference.md", "etag-4"),
+        ],
+    )
+    .await;
+    mount_object(&server, "docs/index.md", "# Index", 1).await;
+    mount_object(&server, "docs/api/reference.md", "# API", 1).await;
+    mount_object(&server, "docs/guides/setup.md", "# Setup", 0).await;
+    mount_object(&server, "docs/drafts/idea.md", "# Idea", 0).await;
+
+    let tmp = tempdir().unwrap();
+    let dest = tmp.path().join("s3_source");
+    let options = SourceOptions {
+        include: vec!["docs/*.md".into(), "docs/api/**".into(), "idea.md".into()],
+        exclude: vec!["drafts".into()],
+        process: None,
+    };
+    let src = S3Source {
+        options: options.clone(),
+        ..source(server.uri())
+    };
+    let stats = download(&src, &dest).await.expect("Sync succeeds");
+    assert_eq!(
+        stats.downloaded, 2,
+        "`*` stops at `/` and excluded directories are skipped"
+    );
+
+    let walked = walk(&dest, &options).unwrap();
+    assert_eq!(
+        walked.files,
+        vec![
+            PathBuf::from("docs/api/reference.md"),
+            PathBuf::from("docs/index.md")
+        ],
+        "Processing keeps everything that was downloaded"
+    );
+}
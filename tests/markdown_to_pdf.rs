@@ -0,0 +1,261 @@
+//! Tests for the markdown-aware README PDF renderer.
+
+use std::fs;
+use tempfile::tempdir;
+
+mod common;
+use common::{pdf_pages_text, pdf_text_runs};
+
+use llm_bucket::code_to_pdf::highlight::{Theme, TokenKind};
+use llm_bucket::code_to_pdf::markdown::{markdown_to_pdf, MarkdownPdfOptions};
+use llm_bucket::contract::{ProcessConfig, ProcessInput, ProcessorKind};
+use llm_bucket::download::{web_base_url, GitSource, SourceAction};
+use llm_bucket::preprocess::Processor;
+use lopdf::{Document, Object};
+
+/// A 4x4 red RGB PNG.
+const TINY_PNG: &[u8] = &[
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x04, 0x08, 0x02, 0x00, 0x00, 0x00, 0x26, 0x93, 0x09,
+    0x29, 0x00, 0x00, 0x00, 0x10, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x38, 0x21, 0x27, 0x07,
+    0x47, 0x0c, 0xc4, 0x71, 0x00, 0xb1, 0x63, 0x10, 0x41, 0x3b, 0x4b, 0x63, 0x82, 0x00, 0x00, 0x00,
+    0x00, 0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+];
+
+const README: &str = r#"# Project Title
+
+Some *emphasis*, **bold** and `inline code`.
+
+## Install
+
+- first item
+- second item
+  1. nested one
+  2. nested two
+- [x] done task
+
+> Quoted wisdom.
+
+```rust
+fn main() {}
+```
+
+| Name | Value |
+|------|------:|
+| alpha | 1 |
+| beta | 22 |
+
+See [the guide](docs/GUIDE.md) or <https://example.com>.
+
+![Logo](assets/logo.png)
+![Missing](assets/missing.png)
+"#;
+
+/// Every dictionary in the document, including ones nested inline in other objects.
+fn dictionaries(bytes: &[u8]) -> Vec<lopdf::Dictionary> {
+    fn collect(object: &Object, out: &mut Vec<lopdf::Dictionary>) {
+        match object {
+            Object::Dictionary(d) => {
+                out.push(d.clone());
+                d.iter().for_each(|(_, v)| collect(v, out));
+            }
+            Object::Stream(s) => collect(&Object::Dictionary(s.dict.clone()), out),
+            Object::Array(items) => items.iter().for_each(|v| collect(v, out)),
+            _ => {}
+        }
+    }
+    let doc = Document::load_mem(bytes).unwrap();
+    let mut out = Vec::new();
+    doc.objects.values().for_each(|o| collect(o, &mut out));
+    out
+}
+
+fn string_entry(dict: &lopdf::Dictionary, key: &[u8]) -> Option<String> {
+    match dict.get(key).ok()? {
+        // Text strings are either UTF-16BE with a byte order mark or PDFDocEncoding.
+        Object::String(bytes, _) => Some(match bytes.strip_prefix(&[0xfe, 0xff]) {
+            Some(utf16) => String::from_utf16_lossy(
+                &utf16
+                    .chunks(2)
+                    .map(|c| u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)]))
+                    .collect::<Vec<_>>(),
+            ),
+            None => String::from_utf8_lossy(bytes).into_owned(),
+        }),
+        _ => None,
+    }
+}
+
+fn render_readme() -> Vec<u8> {
+    let dir = tempdir().unwrap();
+    fs::create_dir(dir.path().join("assets")).unwrap();
+    fs::write(dir.path().join("assets/logo.png"), TINY_PNG).unwrap();
+    let options = MarkdownPdfOptions {
+        header: Some("repo/README.md".to_string()),
+        base_dir: Some(dir.path().to_path_buf()),
+        base_url: Some("https://github.com/org/repo/blob/main".to_string()),
+        ..MarkdownPdfOptions::default()
+    };
+    markdown_to_pdf(README, &options).unwrap()
+}
+
+#[test]
+fn test_markdown_structure_is_rendered_without_syntax() {
+    let bytes = render_readme();
+    let text = pdf_pages_text(&bytes).join("\n");
+
+    for expected in [
+        "Project Title",
+        "Install",
+        "• first item",
+        "1. nested one",
+        "☑ done task",
+        "Quoted wisdom.",
+        "fn main() {}",
+        "alpha",
+        "22",
+        "[Missing]",
+    ] {
+        assert!(
+            text.contains(expected),
+            "{expected:?} missing from:\n{text}"
+        );
+    }
+    for syntax in [
+        "# Project",
+        "## Install",
+        "|---",
+        "**bold**",
+        "```",
+        "`inline",
+    ] {
+        assert!(
+            !text.contains(syntax),
+            "Raw markdown {syntax:?} leaked:\n{text}"
+        );
+    }
+}
+
+#[test]
+fn test_headings_become_bookmarks() {
+    let titles: Vec<String> = dictionaries(&render_readme())
+        .iter()
+        .filter_map(|d| string_entry(d, b"Title"))
+        .collect();
+    assert!(titles.iter().any(|t| t == "Project Title"), "{titles:?}");
+    assert!(titles.iter().any(|t| t == "Install"), "{titles:?}");
+}
+
+#[test]
+fn test_relative_links_resolve_against_base_url() {
+    let bytes = render_readme();
+    let text = pdf_pages_text(&bytes).join("\n");
+    assert!(
+        text.contains("(https://github.com/org/repo/blob/main/docs/GUIDE.md)"),
+        "{text}"
+    );
+    let uris: Vec<String> = dictionaries(&bytes)
+        .iter()
+        .filter_map(|d| string_entry(d, b"URI"))
+        .collect();
+    assert!(uris.contains(&"https://github.com/org/repo/blob/main/docs/GUIDE.md".to_string()));
+    assert!(uris.contains(&"https://example.com".to_string()));
+}
+
+#[test]
+fn test_local_images_are_embedded() {
+    let images = dictionaries(&render_readme())
+        .iter()
+        .filter(|d| {
+            d.get(b"Subtype")
+                .and_then(|s| s.as_name())
+                .is_ok_and(|n| n == b"Image")
+        })
+        .count();
+    assert_eq!(
+        images, 1,
+        "Only the existing local image should be embedded"
+    );
+}
+
+#[test]
+fn test_fenced_code_is_highlighted() {
+    let runs = pdf_text_runs(&render_readme());
+    let (_, color) = runs
+        .iter()
+        .find(|(t, _)| t == "fn")
+        .expect("Keyword run should exist");
+    let expected = Theme::Light.color(TokenKind::Keyword);
+    assert!((color.0 - expected.0).abs() < 0.01 && (color.2 - expected.2).abs() < 0.01);
+}
+
+#[test]
+fn test_images_outside_base_dir_are_not_embedded() {
+    let dir = tempdir().unwrap();
+    let repo = dir.path().join("repo");
+    fs::create_dir(&repo).unwrap();
+    fs::write(dir.path().join("secret.png"), TINY_PNG).unwrap();
+    let options = MarkdownPdfOptions {
+        base_dir: Some(repo),
+        ..MarkdownPdfOptions::default()
+    };
+    let bytes = markdown_to_pdf("![outside](../secret.png)\n", &options).unwrap();
+    assert!(pdf_pages_text(&bytes).join("").contains("[outside]"));
+    assert!(!dictionaries(&bytes).iter().any(|d| d
+        .get(b"Subtype")
+        .and_then(|s| s.as_name())
+        .is_ok_and(|n| n == b"Image")));
+}
+
+#[test]
+fn test_readme_processor_renders_markdown() {
+    let dir = tempdir().unwrap();
+    fs::write(
+        dir.path().join("README.md"),
+        "# Hello\n\n| a | b |\n|---|---|\n| 1 | 2 |\n",
+    )
+    .unwrap();
+    let processor = Processor::new(ProcessConfig {
+        kind: Some(ProcessorKind::ReadmeToPDF),
+        ..Default::default()
+    });
+    let source = processor
+        .process_sync(ProcessInput {
+            name: "repo".to_string(),
+            repo_path: dir.path().to_path_buf(),
+            base_url: None,
+            options: Default::default(),
+            variables: Default::default(),
+        })
+        .unwrap();
+    let text = pdf_pages_text(&source.external_items[0].content).join("\n");
+    assert!(
+        text.contains("Hello") && !text.contains("# Hello"),
+        "{text}"
+    );
+    assert!(!text.contains("|---|"), "{text}");
+}
+
+#[test]
+fn test_web_base_url_for_git_remotes() {
+    let git = |url: &str, reference: Option<&str>| {
+        web_base_url(&SourceAction::Git(GitSource {
+            repo_url: url.to_string(),
+            reference: reference.map(str::to_string),
+            options: Default::default(),
+        }))
+    };
+    assert_eq!(
+        git("git@github.com:org/repo.git", None).as_deref(),
+        Some("https://github.com/org/repo/blob/main")
+    );
+    assert_eq!(
+        git("https://gitlab.com/group/sub/repo.git", Some("v1.2")).as_deref(),
+        Some("https://gitlab.com/group/sub/repo/blob/v1.2")
+    );
+    assert_eq!(
+        git("ssh://git@example.org:2222/team/repo", None).as_deref(),
+        Some("https://example.org/team/repo/blob/main")
+    );
+    assert_eq!(git("/srv/git/repo", None), None);
+}
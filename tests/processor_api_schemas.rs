@@ -0,0 +1,605 @@
+//! Tests for the API schemas stage: OpenAPI, protobuf and GraphQL files rendered to
+//! per-operation markdown items.
+
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+mod common;
+use common::config;
+
+use llm_bucket::contract::{ApiSchemaOptions, ProcessInput, StageConfig};
+use llm_bucket::preprocess::Processor;
+
+const OPENAPI: &str = r##"openapi: 3.0.3
+info:
+  title: Pet Store
+  version: 1.2.0
+  description: Pets for sale.
+servers:
+  - url: https://api.example.com/v1
+paths:
+  /pets/{petId}:
+    parameters:
+      - $ref: "#/components/parameters/PetId"
+    get:
+      summary: Get a pet
+      description: Returns one pet by id.
+      operationId: getPet
+      tags: [pets]
+      responses:
+        200:
+          description: The pet
+          content:
+            application/json:
+              schema:
+                $ref: "#/components/schemas/Pet"
+        "404":
+          $ref: "#/components/responses/NotFound"
+  /pets:
+    post:
+      summary: Add a pet
+      requestBody:
+        required: true
+        content:
+          application/json:
+            schema:
+              $ref: "#/components/schemas/NewPet"
+      responses:
+        "201":
+          description: Created
+components:
+  parameters:
+    PetId:
+      name: petId
+      in: path
+      required: true
+      description: The pet's id.
+      schema:
+        type: integer
+        format: int64
+  responses:
+    NotFound:
+      description: No such pet
+  schemas:
+    NewPet:
+      type: object
+      required: [name]
+      properties:
+        name:
+          type: string
+          description: What the pet answers to.
+        tag:
+          type: string
+    Pet:
+      description: A pet in the store.
+      allOf:
+        - $ref: "#/components/schemas/NewPet"
+        - type: object
+          properties:
+            id:
+              type: integer
+            owner:
+              $ref: "#/components/schemas/Owner"
+    Owner:
+      type: object
+      properties:
+        name:
+          type: string
+        pets:
+          type: array
+          items:
+            $ref: "#/components/schemas/Pet"
+"##;
+
+const SWAGGER: &str = r##"{
+  "swagger": "2.0",
+  "info": {"title": "Legacy", "version": "1"},
+  "consumes": ["application/json"],
+  "produces": ["application/json"],
+  "paths": {
+    "/users": {
+      "post": {
+        "summary": "Create a user",
+        "parameters": [
+          {"name": "body", "in": "body", "required": true, "schema": {"$ref": "#/definitions/User"}}
+        ],
+        "responses": {"200": {"description": "The user", "schema": {"$ref": "#/definitions/User"}}}
+      }
+    }
+  },
+  "definitions": {
+    "User": {"type": "object", "properties": {"email": {"type": "string", "format": "email"}}}
+  }
+}"##;
+
+const PROTO: &str = r#"syntax = "proto3";
+
+package billing.v1;
+
+import "google/protobuf/timestamp.proto";
+
+option go_package = "example.com/billing";
+
+// Issues and looks up invoices.
+service Invoices {
+  // Creates a draft invoice.
+  rpc CreateInvoice(CreateInvoiceRequest) returns (Invoice) {
+    option (google.api.http) = { post: "/v1/invoices" body: "*" };
+  }
+  rpc WatchInvoices(WatchRequest) returns (stream Invoice);
+}
+
+message CreateInvoiceRequest {
+  string customer_id = 1; // Who pays.
+  repeated Line lines = 2;
+
+  // One billed item.
+  message Line {
+    string sku = 1;
+    int64 cents = 2 [deprecated = true];
+  }
+}
+
+message WatchRequest {}
+
+// An invoice.
+message Invoice {
+  string id = 1;
+  Status status = 2;
+  oneof payer {
+    string customer_id = 3;
+    string account_id = 4;
+  }
+  map<string, string> labels = 5;
+  google.protobuf.Timestamp created = 6;
+  reserved 7, 8;
+}
+
+enum Status {
+  STATUS_UNSPECIFIED = 0;
+  // Not sent yet.
+  DRAFT = 1;
+  PAID = 2;
+}
+"#;
+
+const GRAPHQL: &str = r#"schema {
+  query: Query
+  mutation: Mutation
+}
+
+"The root of all reads."
+type Query {
+  "Look a user up."
+  user(id: ID!): User
+  search(text: String!, first: Int = 10): [SearchResult!]!
+}
+
+type Mutation {
+  createUser(input: CreateUserInput!): User
+}
+
+"""
+Someone with an account.
+
+May own posts.
+"""
+type User implements Node {
+  id: ID!
+  name: String @deprecated(reason: "Use `displayName`.")
+  posts: [Post!]!
+  role: Role
+}
+
+# Written by a user.
+type Post implements Node {
+  id: ID!
+  author: User!
+}
+
+interface Node {
+  id: ID!
+}
+
+input CreateUserInput {
+  name: String!
+  role: Role = READER
+}
+
+enum Role {
+  "Can change anything."
+  ADMIN
+  READER
+}
+
+union SearchResult = User | Post
+
+scalar DateTime
+
+extend type Post {
+  published: DateTime
+}
+"#;
+
+const QUERY: &str = r#"query GetUser($id: ID!) {
+  user(id: $id) { name }
+}
+"#;
+
+fn process(yaml: &str, files: &[(&str, &str)]) -> Vec<(String, String)> {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path();
+    for (path, text) in files {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, text).unwrap();
+    }
+    run(yaml, root)
+}
+
+fn run(yaml: &str, root: &Path) -> Vec<(String, String)> {
+    let config = config(yaml).unwrap();
+    let source = Processor::new(config.process)
+        .process_sync(ProcessInput {
+            name: "api".to_string(),
+            repo_path: root.to_path_buf(),
+            base_url: None,
+            options: Default::default(),
+            variables: Default::default(),
+        })
+        .unwrap();
+    source
+        .external_items
+        .into_iter()
+        .map(|i| (i.filename, String::from_utf8(i.content).unwrap()))
+        .collect()
+}
+
+fn names(items: &[(String, String)]) -> Vec<&str> {
+    items.iter().map(|(name, _)| name.as_str()).collect()
+}
+
+fn content<'a>(items: &'a [(String, String)], name: &str) -> &'a str {
+    &items.iter().find(|(n, _)| n == name).unwrap().1
+}
+
+#[test]
+fn test_openapi_operations_resolve_refs() {
+    let items = process(
+        "  kind: FlattenFiles\n  api_schemas: {}\n",
+        &[("api/openapi.yaml", OPENAPI), ("api/legacy.json", SWAGGER)],
+    );
+    assert_eq!(
+        names(&items),
+        vec![
+            "api__legacy.json.md",
+            "api__legacy.json__POST_users.md",
+            "api__openapi.yaml.md",
+            "api__openapi.yaml__POST_pets.md",
+            "api__openapi.yaml__GET_pets_petId.md",
+        ]
+    );
+    assert_eq!(
+        content(&items, "api__openapi.yaml.md"),
+        "# Pet Store 1.2.0
+
+Pets for sale.
+
+- OpenAPI 3.0.3
+- Servers: `https://api.example.com/v1`
+
+## Operations
+
+- `POST /pets`: Add a pet
+- `GET /pets/{petId}`: Get a pet
+
+## Schemas
+
+- `NewPet` (object)
+- `Owner` (object)
+- `Pet` (all of `NewPet` & object): A pet in the store.
+"
+    );
+    assert_eq!(
+        content(&items, "api__openapi.yaml__GET_pets_petId.md"),
+        "# GET /pets/{petId}
+
+Get a pet
+
+Returns one pet by id.
+
+- API: Pet Store 1.2.0
+- Operation ID: `getPet`
+- Tags: pets
+
+## Parameters
+
+- `petId` (path, integer (int64), required): The pet's id.
+
+## Responses
+
+### 200: The pet
+
+`application/json` (`Pet`):
+
+- `name` (string, required): What the pet answers to.
+- `tag` (string)
+- `id` (integer)
+- `owner` (`Owner`)
+  - `name` (string)
+  - `pets` (array of `Pet`)
+
+### 404: No such pet
+"
+    );
+    assert_eq!(
+        content(&items, "api__legacy.json__POST_users.md"),
+        "# POST /users
+
+Create a user
+
+- API: Legacy 1
+
+## Request body
+
+`application/json` (`User`, required):
+
+- `email` (string (email))
+
+## Responses
+
+### 200: The user
+
+`application/json` (`User`):
+
+- `email` (string (email))
+"
+    );
+}
+
+#[test]
+fn test_protobuf_rpcs_messages_and_enums() {
+    let items = process(
+        "  kind: FlattenFiles\n  api_schemas: {}\n",
+        &[("proto/billing.proto", PROTO)],
+    );
+    assert_eq!(
+        names(&items),
+        vec![
+            "proto__billing.proto.md",
+            "proto__billing.proto__Invoices_CreateInvoice.md",
+            "proto__billing.proto__Invoices_WatchInvoices.md",
+            "proto__billing.proto__CreateInvoiceRequest.md",
+            "proto__billing.proto__CreateInvoiceRequest_Line.md",
+            "proto__billing.proto__WatchRequest.md",
+            "proto__billing.proto__Invoice.md",
+            "proto__billing.proto__Status.md",
+        ]
+    );
+    assert_eq!(
+        content(&items, "proto__billing.proto.md"),
+        "# proto/billing.proto
+
+- Package: `billing.v1`
+- Syntax: proto3
+
+## Services
+
+- `Invoices`: Issues and looks up invoices.
+  - `rpc CreateInvoice(CreateInvoiceRequest) returns (Invoice)`: Creates a draft invoice.
+  - `rpc WatchInvoices(WatchRequest) returns (stream Invoice)`
+
+## Messages
+
+- `CreateInvoiceRequest`
+- `CreateInvoiceRequest.Line`: One billed item.
+- `WatchRequest`
+- `Invoice`: An invoice.
+
+## Enums
+
+- `Status`
+"
+    );
+    assert_eq!(
+        content(&items, "proto__billing.proto__Invoices_CreateInvoice.md"),
+        "# rpc billing.v1.Invoices.CreateInvoice
+
+Creates a draft invoice.
+
+- Signature: `rpc CreateInvoice(CreateInvoiceRequest) returns (Invoice)`
+- Service: `billing.v1.Invoices`: Issues and looks up invoices.
+
+## Request `CreateInvoiceRequest`
+
+- `string customer_id = 1`: Who pays.
+- `repeated Line lines = 2`
+  - `string sku = 1`
+  - `int64 cents = 2`
+
+## Response `Invoice`
+
+An invoice.
+
+- `string id = 1`
+- `Status status = 2`
+  - `STATUS_UNSPECIFIED = 0`
+  - `DRAFT = 1`: Not sent yet.
+  - `PAID = 2`
+- `string customer_id = 3` (one of `payer`)
+- `string account_id = 4` (one of `payer`)
+- `map<string, string> labels = 5`
+- `google.protobuf.Timestamp created = 6`
+"
+    );
+    assert_eq!(
+        content(&items, "proto__billing.proto__Status.md"),
+        "# enum billing.v1.Status
+
+## Values
+
+- `STATUS_UNSPECIFIED = 0`
+- `DRAFT = 1`: Not sent yet.
+- `PAID = 2`
+"
+    );
+}
+
+#[test]
+fn test_graphql_operations_and_types() {
+    let items = process(
+        "  kind: FlattenFiles\n  api_schemas: {}\n",
+        &[("schema.graphql", GRAPHQL), ("queries/user.graphql", QUERY)],
+    );
+    assert_eq!(
+        names(&items),
+        vec![
+            "queries__user.graphql",
+            "schema.graphql.md",
+            "schema.graphql__query_user.md",
+            "schema.graphql__query_search.md",
+            "schema.graphql__mutation_createUser.md",
+            "schema.graphql__User.md",
+            "schema.graphql__Post.md",
+            "schema.graphql__Node.md",
+            "schema.graphql__CreateUserInput.md",
+            "schema.graphql__Role.md",
+            "schema.graphql__SearchResult.md",
+            "schema.graphql__DateTime.md",
+        ]
+    );
+    assert_eq!(
+        content(&items, "queries__user.graphql"),
+        QUERY,
+        "queries are not a schema"
+    );
+    assert_eq!(
+        content(&items, "schema.graphql__mutation_createUser.md"),
+        "# mutation createUser
+
+- Signature: `createUser(input: CreateUserInput!): User`
+
+## Arguments
+
+- `input: CreateUserInput!`
+  - `name: String!`
+  - `role: Role = READER`
+    - `ADMIN`: Can change anything.
+    - `READER`
+
+## Returns type `User`
+
+Someone with an account.
+
+May own posts.
+
+- `id: ID!`
+- `name: String` (deprecated: Use `displayName`.)
+- `posts: [Post!]!`
+  - `id: ID!`
+  - `author: User!`
+  - `published: DateTime`
+- `role: Role`
+  - `ADMIN`: Can change anything.
+  - `READER`
+"
+    );
+    assert_eq!(
+        content(&items, "schema.graphql__Post.md"),
+        "# type Post
+
+Written by a user.
+
+- Implements: `Node`
+
+## Fields
+
+- `id: ID!`
+- `author: User!`
+  - `id: ID!`
+  - `name: String` (deprecated: Use `displayName`.)
+  - `posts: [Post!]!`
+  - `role: Role`
+    - `ADMIN`: Can change anything.
+    - `READER`
+- `published: DateTime`
+"
+    );
+    assert_eq!(
+        content(&items, "schema.graphql__SearchResult.md"),
+        "# union SearchResult
+
+## Members
+
+- `User`: Someone with an account. May own posts.
+- `Post`: Written by a user.
+"
+    );
+}
+
+#[test]
+fn test_keep_files_depth_and_passthrough() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path();
+    fs::write(root.join("billing.proto"), PROTO).unwrap();
+    fs::write(root.join("broken.proto"), "message Oops {").unwrap();
+    fs::write(root.join("values.yaml"), "replicas: 3\n").unwrap();
+    fs::write(root.join("bad.yaml"), "openapi: 3.0.0\npaths: [1, 2]\n").unwrap();
+
+    let config = config(
+        "  kind: FlattenFiles\n  api_schemas:\n    keep_files: true\n    depth: 0\n  redact: {}\n",
+    )
+    .unwrap();
+    let pipeline = config.process.pipeline();
+    assert_eq!(
+        pipeline[1],
+        StageConfig::ApiSchemas(ApiSchemaOptions {
+            keep_files: true,
+            depth: 0,
+        })
+    );
+    assert!(
+        matches!(pipeline[2], StageConfig::Redact(_)),
+        "renderings are scanned for secrets"
+    );
+
+    let items = run(
+        "  kind: FlattenFiles\n  api_schemas:\n    keep_files: true\n    depth: 0\n",
+        root,
+    );
+    assert_eq!(
+        names(&items),
+        vec![
+            "bad.yaml",
+            "billing.proto",
+            "billing.proto.md",
+            "billing.proto__Invoices_CreateInvoice.md",
+            "billing.proto__Invoices_WatchInvoices.md",
+            "billing.proto__CreateInvoiceRequest.md",
+            "billing.proto__CreateInvoiceRequest_Line.md",
+            "billing.proto__WatchRequest.md",
+            "billing.proto__Invoice.md",
+            "billing.proto__Status.md",
+            "broken.proto",
+            "values.yaml",
+        ],
+        "files that fail to parse or are not schemas pass through"
+    );
+    assert_eq!(
+        content(&items, "billing.proto__Invoices_CreateInvoice.md"),
+        "# rpc billing.v1.Invoices.CreateInvoice
+
+Creates a draft invoice.
+
+- Signature: `rpc CreateInvoice(CreateInvoiceRequest) returns (Invoice)`
+- Service: `billing.v1.Invoices`: Issues and looks up invoices.
+
+## Request `CreateInvoiceRequest`
+
+## Response `Invoice`
+
+An invoice.
+"
+    );
+}
@@ -0,0 +1,289 @@
+//! Tests for content sniffing, the size limit and the per-type binary file policy.
+
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+use llm_bucket::config::Config;
+use llm_bucket::contract::{
+    BinaryPolicy, ExternalSourceInput, FilePolicy, ProcessConfig, ProcessInput, ProcessorKind,
+    SkipReason, SkippedPath,
+};
+use llm_bucket::preprocess::content::{sniff, ContentKind};
+use llm_bucket::preprocess::Processor;
+
+const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x01";
+
+fn write(root: &Path, path: &str, content: &[u8]) {
+    let path = root.join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+fn utf16le(text: &str) -> Vec<u8> {
+    let mut bytes = vec![0xff, 0xfe];
+    bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+    bytes
+}
+
+fn process(root: &Path, kind: ProcessorKind, files: FilePolicy) -> ExternalSourceInput {
+    Processor::new(ProcessConfig {
+        kind: Some(kind),
+        files,
+        ..Default::default()
+    })
+    .process_sync(ProcessInput {
+        name: "demo".to_string(),
+        repo_path: root.to_path_buf(),
+        base_url: None,
+        options: Default::default(),
+        variables: Default::default(),
+    })
+    .expect("Processing should succeed")
+}
+
+fn item<'a>(
+    source: &'a ExternalSourceInput,
+    filename: &str,
+) -> &'a llm_bucket::contract::ExternalItemInput {
+    source
+        .external_items
+        .iter()
+        .find(|i| i.filename == filename)
+        .unwrap_or_else(|| panic!("{filename} not in {:?}", source.external_items))
+}
+
+fn skipped(path: &str, reason: SkipReason) -> SkippedPath {
+    SkippedPath {
+        path: path.to_string(),
+        reason,
+    }
+}
+
+#[test]
+fn test_sniff_uses_content_not_extension() {
+    let cases: &[(&str, &[u8], &str, ContentKind)] = &[
+        ("logo.txt", PNG, "image/png", ContentKind::Binary),
+        (
+            "app.jar",
+            b"PK\x03\x04\x14\0",
+            "application/java-archive",
+            ContentKind::Binary,
+        ),
+        (
+            "data.bin",
+            b"SQLite format 3\0...",
+            "application/vnd.sqlite3",
+            ContentKind::Binary,
+        ),
+        (
+            "blob",
+            b"abc\0def",
+            "application/octet-stream",
+            ContentKind::Binary,
+        ),
+        (
+            "main.rs",
+            b"fn main() {}\n",
+            "text/x-rust",
+            ContentKind::Text,
+        ),
+        (
+            "notes",
+            "caf\u{e9}\n".as_bytes(),
+            "text/plain",
+            ContentKind::Text,
+        ),
+        (
+            "old.txt",
+            b"caf\xe9 cr\xe8me\n",
+            "text/plain; charset=iso-8859-1",
+            ContentKind::Latin1,
+        ),
+        (
+            "MZ.txt",
+            b"MZ is a postcode prefix\n",
+            "text/plain",
+            ContentKind::Text,
+        ),
+    ];
+    for (path, bytes, mime_type, kind) in cases {
+        let sniffed = sniff(Path::new(path), bytes);
+        assert_eq!(
+            (sniffed.mime_type.as_str(), sniffed.kind),
+            (*mime_type, *kind),
+            "{path}"
+        );
+    }
+    let sniffed = sniff(Path::new("win.txt"), &utf16le("hi"));
+    assert_eq!(sniffed.kind, ContentKind::Utf16 { big_endian: false });
+}
+
+#[test]
+fn test_flatten_default_policy_skips_binaries_and_converts_text_encodings() {
+    let tmp = tempdir().unwrap();
+    write(tmp.path(), "README.md", b"# Demo\n");
+    write(tmp.path(), "assets/logo.png", PNG);
+    write(tmp.path(), "lib/app.jar", b"PK\x03\x04\x14\0\0\0");
+    write(tmp.path(), "windows.txt", &utf16le("line one\r\n"));
+    write(tmp.path(), "legacy.txt", b"caf\xe9\n");
+    write(tmp.path(), "big.log", &vec![b'x'; 2048]);
+
+    let source = process(
+        tmp.path(),
+        ProcessorKind::FlattenFiles,
+        FilePolicy {
+            max_file_size: 1024,
+            ..FilePolicy::default()
+        },
+    );
+    let filenames: Vec<_> = source
+        .external_items
+        .iter()
+        .map(|i| i.filename.as_str())
+        .collect();
+    assert_eq!(filenames, vec!["README.md", "legacy.txt", "windows.txt"]);
+    assert_eq!(
+        item(&source, "README.md").mime_type.as_deref(),
+        Some("text/markdown")
+    );
+    assert_eq!(item(&source, "windows.txt").content, b"line one\r\n");
+    assert_eq!(
+        item(&source, "legacy.txt").content,
+        "caf\u{e9}\n".as_bytes()
+    );
+
+    for expected in [
+        skipped("assets/logo.png", SkipReason::Binary("image/png".into())),
+        skipped(
+            "lib/app.jar",
+            SkipReason::Binary("application/java-archive".into()),
+        ),
+        skipped(
+            "big.log",
+            SkipReason::TooLarge {
+                size: 2048,
+                limit: 1024,
+            },
+        ),
+    ] {
+        assert!(
+            source.skipped.contains(&expected),
+            "{expected:?} not in {:?}",
+            source.skipped
+        );
+    }
+}
+
+#[test]
+fn test_invalid_utf8_after_the_sniffed_head_is_replaced() {
+    let tmp = tempdir().unwrap();
+    let mut notes = vec![b'a'; 9000];
+    notes.extend(b"\xe9 caf\xc3\xa9\n");
+    write(tmp.path(), "notes.txt", &notes);
+
+    let source = process(
+        tmp.path(),
+        ProcessorKind::FlattenFiles,
+        FilePolicy::default(),
+    );
+    let content = std::str::from_utf8(&item(&source, "notes.txt").content).unwrap();
+    assert_eq!(content.len(), 9000 + "\u{fffd} caf\u{e9}\n".len());
+    assert!(content.ends_with("a\u{fffd} caf\u{e9}\n"), "{content:?}");
+}
+
+#[test]
+fn test_per_type_policy_uploads_raw_bytes() {
+    let tmp = tempdir().unwrap();
+    write(tmp.path(), "assets/logo.png", PNG);
+    write(tmp.path(), "lib/app.jar", b"PK\x03\x04\x14\0\0\0");
+    write(tmp.path(), "src/main.rs", b"fn main() {}\n");
+
+    let policy = FilePolicy {
+        binary: BinaryPolicy::Skip,
+        binary_types: [("image/*".to_string(), BinaryPolicy::Upload)].into(),
+        ..FilePolicy::default()
+    };
+    let source = process(tmp.path(), ProcessorKind::FlattenFiles, policy.clone());
+    let logo = item(&source, "assets__logo.png");
+    assert_eq!(logo.content, PNG);
+    assert_eq!(logo.mime_type.as_deref(), Some("image/png"));
+    assert_eq!(
+        source.skipped,
+        vec![skipped(
+            "lib/app.jar",
+            SkipReason::Binary("application/java-archive".into())
+        )]
+    );
+
+    // PDF processors need text, so an uploadable binary is still skipped there.
+    let source = process(tmp.path(), ProcessorKind::CodeToPDF, policy);
+    assert_eq!(source.external_items.len(), 1);
+    assert_eq!(
+        source.external_items[0].mime_type.as_deref(),
+        Some("application/pdf")
+    );
+    assert!(source.skipped.contains(&skipped(
+        "assets/logo.png",
+        SkipReason::Binary("image/png".into())
+    )));
+}
+
+#[test]
+fn test_sqlite_database_is_converted_to_schema_markdown() {
+    let tmp = tempdir().unwrap();
+    let db = tmp.path().join("data/app.db");
+    fs::create_dir_all(db.parent().unwrap()).unwrap();
+    let conn = rusqlite::Connection::open(&db).unwrap();
+    conn.execute_batch("CREATE TABLE customers (id INTEGER PRIMARY KEY, name TEXT NOT NULL);")
+        .unwrap();
+    drop(conn);
+
+    let source = process(
+        tmp.path(),
+        ProcessorKind::FlattenFiles,
+        FilePolicy::default(),
+    );
+    let schema = item(&source, "data__app.db.md");
+    assert_eq!(schema.mime_type.as_deref(), Some("text/markdown"));
+    let text = String::from_utf8(schema.content.clone()).unwrap();
+    assert!(text.contains("data/app.db"), "{text}");
+    assert!(text.contains("customers"), "{text}");
+}
+
+#[test]
+fn test_file_policy_is_read_from_config() {
+    let yaml = r#"
+download:
+  output_dir: out
+  sources:
+    - type: git
+      repo_url: "git@github.com:org/repo.git"
+process:
+  kind: FlattenFiles
+  max_file_size: 4096
+  binary: skip
+  binary_types:
+    "image/*": upload
+    application/pdf: convert
+"#;
+    let config = Config::from_yaml_str(yaml).unwrap();
+    let files = &config.process.files;
+    assert_eq!(files.max_file_size, 4096);
+    assert_eq!(files.policy_for("image/png"), BinaryPolicy::Upload);
+    assert_eq!(files.policy_for("application/pdf"), BinaryPolicy::Convert);
+    assert_eq!(files.policy_for("application/zip"), BinaryPolicy::Skip);
+
+    let defaults = Config::from_yaml_str(&yaml.replace("  max_file_size: 4096\n", ""))
+        .unwrap()
+        .process
+        .files;
+    assert_eq!(defaults.max_file_size, FilePolicy::DEFAULT_MAX_FILE_SIZE);
+
+    let invalid = yaml.replace("\"image/*\"", "images");
+    let err = Config::from_yaml_str(&invalid).unwrap_err();
+    assert!(
+        err.to_string().contains("`images` is not a MIME type"),
+        "{err}"
+    );
+}
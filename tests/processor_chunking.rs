@@ -0,0 +1,236 @@
+//! Tests for the structure-aware chunking stage.
+
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+use llm_bucket::config::Config;
+use llm_bucket::contract::{ChunkOptions, ChunkUnit, ProcessConfig, ProcessInput, ProcessorKind};
+use llm_bucket::preprocess::chunk::{chunk_name, chunk_text, Chunk};
+use llm_bucket::preprocess::Processor;
+
+fn options(size: usize, overlap: usize) -> ChunkOptions {
+    ChunkOptions {
+        size,
+        overlap,
+        unit: ChunkUnit::Chars,
+    }
+}
+
+fn breadcrumbs(chunks: &[Chunk]) -> Vec<Vec<&str>> {
+    chunks
+        .iter()
+        .map(|c| c.breadcrumb.iter().map(String::as_str).collect())
+        .collect()
+}
+
+const GUIDE: &str = "# Guide
+
+Intro paragraph.
+
+## Install
+
+Run the installer and follow the prompts shown on screen.
+
+```sh
+# not a heading
+make install
+```
+
+### Linux
+
+Use the package manager of your distribution.
+
+## Usage
+
+Start the service and open the dashboard.
+";
+
+#[test]
+fn test_markdown_is_split_at_headings_with_breadcrumbs() {
+    let chunks = chunk_text(Path::new("guide.md"), GUIDE, true, &options(80, 0));
+    assert_eq!(
+        breadcrumbs(&chunks),
+        vec![
+            vec!["Guide"],
+            vec!["Guide", "Install"],
+            vec!["Guide", "Install"],
+            vec!["Guide", "Install", "Linux"],
+            vec!["Guide", "Usage"],
+        ]
+    );
+    assert!(chunks[1].text.starts_with("## Install"));
+    assert!(
+        chunks[2]
+            .text
+            .starts_with("```sh\n# not a heading\nmake install\n```"),
+        "{chunks:#?}"
+    );
+    assert!(chunks[3].text.starts_with("### Linux"));
+    let joined: String = chunks.iter().map(|c| c.text.as_str()).collect();
+    assert_eq!(
+        joined, GUIDE,
+        "Without overlap the chunks reassemble the text"
+    );
+
+    // Sections that fit together share a chunk, under their common headings.
+    let chunks = chunk_text(Path::new("guide.md"), GUIDE, true, &options(1000, 0));
+    assert_eq!(breadcrumbs(&chunks), vec![vec!["Guide"]]);
+}
+
+#[test]
+fn test_code_is_split_at_definitions() {
+    let code = "use std::fmt;
+
+/// Adds numbers.
+#[inline]
+pub fn add(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+struct Point {
+    x: i32,
+}
+
+impl Point {
+    fn new() -> Self {
+        Point { x: 0 }
+    }
+}
+";
+    let chunks = chunk_text(Path::new("src/lib.rs"), code, false, &options(80, 0));
+    let starts: Vec<&str> = chunks
+        .iter()
+        .map(|c| c.text.lines().next().unwrap())
+        .collect();
+    assert_eq!(
+        starts,
+        vec![
+            "use std::fmt;",
+            "/// Adds numbers.",
+            "struct Point {",
+            "impl Point {"
+        ]
+    );
+}
+
+#[test]
+fn test_overlap_repeats_the_end_of_the_previous_chunk() {
+    let text = (1..=6)
+        .map(|i| format!("Paragraph number {i} has a handful of words in it.\n\n"))
+        .collect::<String>();
+    let chunks = chunk_text(Path::new("notes.txt"), &text, false, &options(110, 20));
+    assert!(chunks.len() >= 3, "{chunks:?}");
+    for pair in chunks.windows(2) {
+        let overlap = pair[1].text.split("Paragraph").next().unwrap();
+        assert!(!overlap.is_empty(), "{:?}", pair[1].text);
+        assert!(overlap.chars().count() <= 20, "{overlap:?}");
+        assert!(pair[0].text.trim_end().ends_with(overlap.trim_end()));
+        assert!(
+            !overlap.starts_with(' '),
+            "Overlap starts at a word: {overlap:?}"
+        );
+    }
+
+    let tokens = ChunkOptions {
+        size: 12,
+        overlap: 0,
+        unit: ChunkUnit::Tokens,
+    };
+    let chunks = chunk_text(Path::new("notes.txt"), &text, false, &tokens);
+    // Each paragraph is 12 tokens: 10 words, a digit and the full stop.
+    assert_eq!(chunks.len(), 6);
+}
+
+#[test]
+fn test_oversized_lines_are_cut_at_the_size_limit() {
+    let text = "x".repeat(250);
+    let chunks = chunk_text(Path::new("blob.txt"), &text, false, &options(100, 0));
+    let sizes: Vec<usize> = chunks.iter().map(|c| c.text.len()).collect();
+    assert_eq!(sizes, vec![100, 100, 50]);
+}
+
+#[test]
+fn test_chunk_names_encode_path_and_index() {
+    assert_eq!(chunk_name("docs__guide.md", 2), "docs__guide__chunk002.md");
+    assert_eq!(
+        chunk_name("src__.gitignore", 1),
+        "src__.gitignore__chunk001"
+    );
+    assert_eq!(chunk_name("Makefile", 10), "Makefile__chunk010");
+}
+
+#[test]
+fn test_chunking_stage_runs_after_the_processor() {
+    let tmp = tempdir().unwrap();
+    fs::create_dir_all(tmp.path().join("docs")).unwrap();
+    fs::write(tmp.path().join("docs/guide.md"), GUIDE).unwrap();
+    fs::write(tmp.path().join("short.txt"), "short\n").unwrap();
+
+    let yaml = r#"
+download:
+  output_dir: out
+  sources:
+    - type: git
+      repo_url: "git@github.com:org/repo.git"
+process:
+  kind: FlattenFiles
+  chunking:
+    size: 80
+    overlap: 0
+"#;
+    let config = Config::from_yaml_str(yaml).unwrap();
+    assert_eq!(config.process.chunking, Some(options(80, 0)));
+    let process = |kind| {
+        Processor::new(ProcessConfig {
+            kind,
+            ..config.process.clone()
+        })
+        .process_sync(ProcessInput {
+            name: "demo".to_string(),
+            repo_path: tmp.path().to_path_buf(),
+            base_url: None,
+            options: Default::default(),
+            variables: Default::default(),
+        })
+        .unwrap()
+    };
+
+    let source = process(Some(ProcessorKind::FlattenFiles));
+    let names: Vec<_> = source
+        .external_items
+        .iter()
+        .map(|i| i.filename.as_str())
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            "docs__guide__chunk001.md",
+            "docs__guide__chunk002.md",
+            "docs__guide__chunk003.md",
+            "docs__guide__chunk004.md",
+            "docs__guide__chunk005.md",
+            "short.txt",
+        ]
+    );
+    assert_eq!(
+        source.external_items[4].metadata.breadcrumb,
+        vec!["Guide", "Usage"]
+    );
+    assert_eq!(
+        source.external_items[4].mime_type.as_deref(),
+        Some("text/markdown")
+    );
+
+    // PDFs are not text and pass through whole.
+    let source = process(Some(ProcessorKind::CodeToPDF));
+    assert_eq!(source.external_items.len(), 2);
+
+    let invalid = yaml.replace("overlap: 0", "overlap: 80");
+    let err = Config::from_yaml_str(&invalid).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("overlap (80) must be smaller than size (80)"),
+        "{err}"
+    );
+}
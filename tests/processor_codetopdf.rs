@@ -0,0 +1,212 @@
+//! Tests for the CodeToPDF and DirectoryToPDF processor kinds.
+
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+mod common;
+use common::{config, pdf_pages_text, pdf_text_runs};
+
+use llm_bucket::code_to_pdf::highlight::{Theme, TokenKind};
+use llm_bucket::contract::{ExternalSourceInput, ProcessConfig, ProcessInput, ProcessorKind};
+use llm_bucket::preprocess::Processor;
+
+fn fixture_repo(root: &Path) {
+    fs::create_dir_all(root.join("src/util")).unwrap();
+    fs::create_dir_all(root.join(".git")).unwrap();
+    fs::create_dir_all(root.join("target/debug")).unwrap();
+    fs::write(root.join("Cargo.toml"), "[package]\nname = \"demo\"\n").unwrap();
+    fs::write(
+        root.join("src/main.rs"),
+        "fn main() {\n    util::run();\n}\n",
+    )
+    .unwrap();
+    fs::write(root.join("src/lib.rs"), "pub mod util;\n").unwrap();
+    fs::write(root.join("src/util/mod.rs"), "pub fn run() {}\n").unwrap();
+    fs::write(root.join("src/logo.bin"), [0x89, 0x00, 0x01, 0x02]).unwrap();
+    fs::write(root.join("src/empty.rs"), "").unwrap();
+    fs::write(root.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+    fs::write(root.join("target/debug/out.rs"), "fn generated() {}\n").unwrap();
+}
+
+fn process(kind: ProcessorKind, root: &Path) -> ExternalSourceInput {
+    Processor::new(ProcessConfig {
+        kind: Some(kind),
+        ..Default::default()
+    })
+    .process_sync(ProcessInput {
+        name: "demo".to_string(),
+        repo_path: root.to_path_buf(),
+        base_url: None,
+        options: Default::default(),
+        variables: Default::default(),
+    })
+    .expect("Processing should succeed")
+}
+
+fn filenames(source: &ExternalSourceInput) -> Vec<&str> {
+    source
+        .external_items
+        .iter()
+        .map(|i| i.filename.as_str())
+        .collect()
+}
+
+#[test]
+fn test_code_to_pdf_renders_each_text_file() {
+    let tmp = tempdir().unwrap();
+    fixture_repo(tmp.path());
+
+    let source = process(ProcessorKind::CodeToPDF, tmp.path());
+    assert_eq!(
+        filenames(&source),
+        vec![
+            "Cargo.toml.pdf",
+            "src__lib.rs.pdf",
+            "src__main.rs.pdf",
+            "src__util__mod.rs.pdf",
+        ],
+        "Binary, empty, .git and target files are skipped"
+    );
+
+    let main = &source.external_items[2];
+    let text = pdf_pages_text(&main.content).join("\n");
+    assert!(text.contains("demo/src/main.rs"), "{text}");
+    assert!(text.contains("util::run();"), "{text}");
+}
+
+#[test]
+fn test_directory_to_pdf_concatenates_with_contents() {
+    let tmp = tempdir().unwrap();
+    fixture_repo(tmp.path());
+
+    let source = process(ProcessorKind::DirectoryToPDF, tmp.path());
+    assert_eq!(
+        filenames(&source),
+        vec!["_root.pdf", "src.pdf", "src__util.pdf"]
+    );
+
+    let pages = pdf_pages_text(&source.external_items[1].content);
+    assert_eq!(pages.len(), 3, "Contents page plus one page per file");
+    assert!(pages[0].contains("Contents"), "{}", pages[0]);
+    assert!(pages[0].contains("src/lib.rs"), "{}", pages[0]);
+    assert!(
+        pages[0].contains(". 3"),
+        "main.rs starts on page 3: {}",
+        pages[0]
+    );
+    assert!(pages[1].contains("pub mod util;"), "{}", pages[1]);
+    assert!(pages[2].contains("fn main()"), "{}", pages[2]);
+    assert!(!pages.concat().contains("out.rs"));
+
+    let doc = lopdf::Document::load_mem(&source.external_items[1].content).unwrap();
+    let bookmarks = doc
+        .objects
+        .values()
+        .filter_map(|o| o.as_dict().ok())
+        .filter(|d| d.has(b"Title") && d.has(b"Dest"))
+        .count();
+    assert_eq!(bookmarks, 2, "One bookmark per file");
+}
+
+#[test]
+fn test_directory_to_pdf_can_cover_the_whole_source() {
+    let tmp = tempdir().unwrap();
+    fixture_repo(tmp.path());
+
+    let config = config(
+        "  stages:\n    - stage: collect\n    - stage: directory_to_pdf\n      scope: repository\n",
+    )
+    .unwrap();
+    let source = Processor::new(config.process)
+        .process_sync(ProcessInput {
+            name: "demo".to_string(),
+            repo_path: tmp.path().to_path_buf(),
+            base_url: None,
+            options: Default::default(),
+            variables: Default::default(),
+        })
+        .unwrap();
+    assert_eq!(filenames(&source), vec!["_repository.pdf"]);
+
+    let pages = pdf_pages_text(&source.external_items[0].content);
+    assert_eq!(pages.len(), 5, "Contents page plus one page per file");
+    for path in ["Cargo.toml", "src/lib.rs", "src/main.rs", "src/util/mod.rs"] {
+        assert!(pages[0].contains(path), "{path} missing from {}", pages[0]);
+    }
+    assert!(pages[4].contains("pub fn run()"), "{}", pages[4]);
+
+    let doc = lopdf::Document::load_mem(&source.external_items[0].content).unwrap();
+    let bookmarks = doc
+        .objects
+        .values()
+        .filter_map(|o| o.as_dict().ok())
+        .filter(|d| d.has(b"Title") && d.has(b"Dest"))
+        .count();
+    assert_eq!(bookmarks, 4, "One bookmark per file");
+}
+
+#[test]
+fn test_pdf_stages_take_theme_and_line_numbers() {
+    let tmp = tempdir().unwrap();
+    fixture_repo(tmp.path());
+
+    let run = |stage: &str, filename: &str| {
+        let config = config(&format!(
+            "  stages:\n    - stage: collect\n    - stage: {stage}\n      theme: solarized\n      line_numbers: false\n"
+        ))
+        .unwrap();
+        let source = Processor::new(config.process)
+            .process_sync(ProcessInput {
+                name: "demo".to_string(),
+                repo_path: tmp.path().to_path_buf(),
+                base_url: None,
+                options: Default::default(),
+                variables: Default::default(),
+            })
+            .unwrap();
+        let item = source
+            .external_items
+            .iter()
+            .find(|i| i.filename == filename);
+        item.expect("rendered PDF").content.clone()
+    };
+    let keyword = |pdf: &[u8]| {
+        pdf_text_runs(pdf)
+            .into_iter()
+            .find(|(text, _)| text == "fn")
+            .map(|(_, color)| color)
+            .expect("`fn` is drawn as its own run")
+    };
+    let solarized = Theme::Solarized.color(TokenKind::Keyword);
+    assert_ne!(solarized, Theme::Light.color(TokenKind::Keyword));
+
+    let numbered =
+        pdf_pages_text(&process(ProcessorKind::CodeToPDF, tmp.path()).external_items[2].content)
+            .join("\n");
+    assert!(numbered.contains("1 fn main()"), "{numbered}");
+
+    let code = run("code_to_pdf", "src__main.rs.pdf");
+    let text = pdf_pages_text(&code).join("\n");
+    assert!(text.contains("\nfn main()"), "{text}");
+    assert!(!text.contains("1 fn main()"), "{text}");
+    assert_eq!(keyword(&code), solarized);
+
+    let directory = run("directory_to_pdf", "src.pdf");
+    let text = pdf_pages_text(&directory).join("\n");
+    assert!(text.contains("\nfn main()"), "{text}");
+    assert!(!text.contains("1 fn main()"), "{text}");
+    assert_eq!(keyword(&directory), solarized);
+}
+
+#[test]
+fn test_processor_kind_parses_new_kinds() {
+    for (name, kind) in [
+        ("CodeToPDF", ProcessorKind::CodeToPDF),
+        ("code_to_pdf", ProcessorKind::CodeToPDF),
+        ("DirectoryToPDF", ProcessorKind::DirectoryToPDF),
+        ("directory_to_pdf", ProcessorKind::DirectoryToPDF),
+    ] {
+        assert_eq!(name.parse::<ProcessorKind>(), Ok(kind));
+    }
+}
@@ -26,9 +26,13 @@ fn test_process_flattenfiles_flattens_recursively_with_double_underscore_separat
     let process_input = ProcessInput {
         name: "test_flatten".to_string(),
         repo_path: repo_path.clone(),
+        base_url: None,
+        options: Default::default(),
+        variables: Default::default(),
     };
     let process_config = ProcessConfig {
-        kind: ProcessorKind::FlattenFiles, // <-- This variant must now exist!
+        kind: Some(ProcessorKind::FlattenFiles), // <-- This variant must now exist!
+        ..Default::default()
     };
 
     let processor = Processor::new(process_config);
@@ -105,9 +109,13 @@ fn test_flattenfiles_skips_dotgit_and_target_dirs() {
     let process_input = ProcessInput {
         name: "test_flatten_skip_dotgit_target".to_string(),
         repo_path: repo_path.to_path_buf(),
+        base_url: None,
+        options: Default::default(),
+        variables: Default::default(),
     };
     let process_config = ProcessConfig {
-        kind: ProcessorKind::FlattenFiles,
+        kind: Some(ProcessorKind::FlattenFiles),
+        ..Default::default()
     };
 
     let processor = Processor::new(process_config);
@@ -141,11 +149,8 @@ fn test_flattenfiles_truncates_very_long_filenames() {
 
     // Build a very long path, flattening would produce >255 bytes filename
     let repeat_count = 50;
-    let very_deep_dir = repo_path.join(
-        std::iter::repeat("verylongsegment")
-            .take(repeat_count)
-            .collect::<std::path::PathBuf>(),
-    );
+    let very_deep_dir = repo_path
+        .join(std::iter::repeat_n("verylongsegment", repeat_count).collect::<std::path::PathBuf>());
     create_dir_all(&very_deep_dir).unwrap();
 
     let file_path = very_deep_dir.join("finalfilewithareallylongnametotestthelimit.txt");
@@ -157,9 +162,13 @@ fn test_flattenfiles_truncates_very_long_filenames() {
     let process_input = ProcessInput {
         name: "test_flatten_long_filename".to_string(),
         repo_path: repo_path.to_path_buf(),
+        base_url: None,
+        options: Default::default(),
+        variables: Default::default(),
     };
     let process_config = ProcessConfig {
-        kind: ProcessorKind::FlattenFiles,
+        kind: Some(ProcessorKind::FlattenFiles),
+        ..Default::default()
     };
 
     let processor = Processor::new(process_config);
@@ -0,0 +1,188 @@
+//! Tests for the header stage: templates per source type and formats per content type.
+
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+mod common;
+use common::config;
+
+use llm_bucket::contract::{
+    ExternalSourceInput, HeaderFormat, HeaderOptions, ProcessConfig, ProcessInput, StageConfig,
+};
+use llm_bucket::download::{ConfluenceSource, GitSource, IndexedFile, SourceAction, SourceIndex};
+use llm_bucket::preprocess::Processor;
+
+fn process(config: ProcessConfig, root: &Path, source: &SourceAction) -> ExternalSourceInput {
+    Processor::new(config)
+        .process_sync(ProcessInput {
+            name: "demo".to_string(),
+            repo_path: root.to_path_buf(),
+            base_url: None,
+            options: Default::default(),
+            variables: source.variables(),
+        })
+        .unwrap()
+}
+
+fn contents(source: &ExternalSourceInput) -> Vec<(&str, &str)> {
+    source
+        .external_items
+        .iter()
+        .map(|i| {
+            (
+                i.filename.as_str(),
+                std::str::from_utf8(&i.content).unwrap_or("<binary>"),
+            )
+        })
+        .collect()
+}
+
+fn git_source() -> SourceAction {
+    SourceAction::Git(GitSource {
+        repo_url: "git@github.com:org/repo.git".to_string(),
+        reference: Some("develop".to_string()),
+        options: Default::default(),
+    })
+}
+
+#[test]
+fn test_header_format_follows_content_type() {
+    let config = config(
+        r#"  kind: FlattenFiles
+  binary_types:
+    "image/*": upload
+  chunking:
+    size: 40
+    overlap: 0
+  header:
+    templates:
+      git: "repo: {{ repository }}@{{ branch }}\npath: {{ path }}\ncommit: {{ commit }}\nsection: {{ breadcrumb }}"
+"#,
+    )
+    .unwrap();
+    assert!(matches!(
+        config.process.pipeline().last(),
+        Some(StageConfig::Header(_))
+    ));
+
+    let tmp = tempdir().unwrap();
+    let root = tmp.path();
+    fs::write(
+        root.join("guide.md"),
+        "# Guide\n\nWelcome to the guide.\n\n## Install\n\nRun the installer.\n",
+    )
+    .unwrap();
+    fs::write(root.join("lib.rs"), "pub fn answer() -> u32 {\n    42\n}\n").unwrap();
+    fs::write(root.join("run.sh"), "#!/bin/sh\necho hi\n").unwrap();
+    fs::write(root.join("notes.md"), "---\ntitle: Notes\n---\nShort.\n").unwrap();
+    fs::write(root.join("logo.png"), b"\x89PNG\r\n\x1a\n\0").unwrap();
+
+    let source = process(config.process, root, &git_source());
+    assert_eq!(
+        contents(&source)
+            .into_iter()
+            .filter(|(name, _)| *name != "logo.png")
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                "guide__chunk001.md",
+                "---\nrepo: git@github.com:org/repo.git@develop\npath: guide.md\nsection: Guide\n---\n# Guide\n\nWelcome to the guide.\n\n"
+            ),
+            (
+                "guide__chunk002.md",
+                "---\nrepo: git@github.com:org/repo.git@develop\npath: guide.md\nsection: Guide › Install\n---\n## Install\n\nRun the installer.\n"
+            ),
+            (
+                "lib.rs",
+                "// repo: git@github.com:org/repo.git@develop\n// path: lib.rs\npub fn answer() -> u32 {\n    42\n}\n"
+            ),
+            (
+                "notes.md",
+                "---\nrepo: git@github.com:org/repo.git@develop\npath: notes.md\ntitle: Notes\n---\nShort.\n"
+            ),
+            (
+                "run.sh",
+                "#!/bin/sh\n# repo: git@github.com:org/repo.git@develop\n# path: run.sh\necho hi\n"
+            ),
+        ]
+    );
+    assert_eq!(
+        source
+            .external_items
+            .iter()
+            .find(|i| i.filename == "logo.png")
+            .unwrap()
+            .content,
+        b"\x89PNG\r\n\x1a\n\0",
+        "binary items pass through"
+    );
+}
+
+#[test]
+fn test_confluence_header_has_space_and_page_breadcrumb() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path();
+    fs::write(root.join("Runbooks__Failover.md"), "Switch the primary.\n").unwrap();
+    SourceIndex {
+        files: [(
+            "Runbooks__Failover.md".to_string(),
+            IndexedFile {
+                url: Some("https://wiki.example.com/spaces/OPS/pages/42".to_string()),
+                modified: Some("2024-05-06T07:08:09.000Z".to_string()),
+                breadcrumb: vec!["Runbooks".to_string(), "DB: failover".to_string()],
+            },
+        )]
+        .into(),
+    }
+    .save(root)
+    .unwrap();
+    let confluence = SourceAction::Confluence(ConfluenceSource {
+        base_url: "https://wiki.example.com".to_string(),
+        space_key: "OPS".to_string(),
+        options: Default::default(),
+    });
+    let header = |format| ProcessConfig {
+        stages: vec![
+            StageConfig::Collect(Default::default()),
+            StageConfig::Header(HeaderOptions {
+                templates: Default::default(),
+                format,
+            }),
+        ],
+        ..Default::default()
+    };
+
+    let source = process(header(HeaderFormat::Auto), root, &confluence);
+    assert_eq!(
+        contents(&source),
+        vec![(
+            "Runbooks__Failover.md",
+            "---\nsource: Confluence space OPS\npage: \"Runbooks › DB: failover\"\nurl: https://wiki.example.com/spaces/OPS/pages/42\nmodified: 2024-05-06T07:08:09.000Z\n---\nSwitch the primary.\n"
+        )]
+    );
+
+    let source = process(header(HeaderFormat::MarkdownComment), root, &confluence);
+    assert_eq!(
+        contents(&source)[0].1,
+        "<!--\nsource: Confluence space OPS\npage: Runbooks › DB: failover\nurl: https://wiki.example.com/spaces/OPS/pages/42\nmodified: 2024-05-06T07:08:09.000Z\n-->\nSwitch the primary.\n"
+    );
+}
+
+#[test]
+fn test_invalid_header_templates_are_rejected() {
+    let err = config(
+        "  kind: FlattenFiles\n  header:\n    templates:\n      svn: \"path: {{ path }}\"\n      git: \"repo: {{ repo }}\"\n",
+    )
+    .unwrap_err();
+    assert!(
+        err.contains("process.header.templates: unknown source type `svn`"),
+        "{err}"
+    );
+    assert!(
+        err.contains("process.header.templates.git: unknown variable `repo`"),
+        "{err}"
+    );
+    let err = config("  kind: FlattenFiles\n  header:\n    format: banner\n").unwrap_err();
+    assert!(err.contains("unknown variant `banner`"), "{err}");
+}
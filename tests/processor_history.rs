@@ -0,0 +1,318 @@
+//! Tests for the history stage: commit history of a git source as markdown documents.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::tempdir;
+
+mod common;
+use common::config;
+
+use llm_bucket::contract::{
+    ExternalSourceInput, HistoryGrouping, HistoryOptions, ProcessInput, StageConfig,
+};
+use llm_bucket::preprocess::Processor;
+
+fn process(process: &str, root: &Path) -> ExternalSourceInput {
+    Processor::new(config(process).unwrap().process)
+        .process_sync(ProcessInput {
+            name: "api".to_string(),
+            repo_path: root.to_path_buf(),
+            base_url: Some("https://github.com/org/api/blob/main".to_string()),
+            options: Default::default(),
+            variables: Default::default(),
+        })
+        .unwrap()
+}
+
+fn names(source: &ExternalSourceInput) -> Vec<&str> {
+    source
+        .external_items
+        .iter()
+        .map(|i| i.filename.as_str())
+        .collect()
+}
+
+fn text<'a>(source: &'a ExternalSourceInput, name: &str) -> &'a str {
+    let item = source
+        .external_items
+        .iter()
+        .find(|i| i.filename == name)
+        .unwrap_or_else(|| panic!("no item {name}"));
+    std::str::from_utf8(&item.content).unwrap()
+}
+
+fn git(root: &Path, date: &str, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args([
+            "-c",
+            "user.name=Ada Lovelace",
+            "-c",
+            "user.email=ada@example.com",
+        ])
+        .args(args)
+        .env("GIT_AUTHOR_DATE", date)
+        .env("GIT_COMMITTER_DATE", date)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {args:?} failed");
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+fn commit(root: &Path, date: &str, message: &str) -> String {
+    git(root, date, &["add", "-A"]);
+    git(root, date, &["commit", "-q", "-m", message]);
+    git(root, date, &["rev-parse", "HEAD"])
+}
+
+/// Four commits over three months, released as `v0.1.0` (lightweight) and `v0.2.0`
+/// (annotated); returns their hashes, oldest first.
+fn repository(root: &Path) -> Vec<String> {
+    git(root, "2024-01-01T00:00:00Z", &["init", "-q"]);
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::write(root.join("notes.md"), "# Notes\n").unwrap();
+    fs::write(
+        root.join("src/retry.rs"),
+        "pub fn delay(attempt: u32) -> u64 {\n    500\n}\n",
+    )
+    .unwrap();
+    let first = commit(root, "2024-01-10T09:00:00Z", "Add retry helper");
+    git(root, "2024-01-10T09:00:00Z", &["tag", "v0.1.0"]);
+
+    fs::write(
+        root.join("src/retry.rs"),
+        "pub fn delay(attempt: u32) -> u64 {\n    let base = 100;\n    let factor = 2u64.pow(attempt);\n    base * factor\n}\n",
+    )
+    .unwrap();
+    let second = commit(
+        root,
+        "2024-02-05T14:30:00Z",
+        "Change retry policy to exponential backoff\n\nFixed delays hammered the API during outages.",
+    );
+
+    fs::create_dir_all(root.join("docs")).unwrap();
+    fs::rename(root.join("notes.md"), root.join("docs/notes.md")).unwrap();
+    let third = commit(root, "2024-02-20T08:00:00Z", "Move notes to docs");
+    git(
+        root,
+        "2024-02-21T10:00:00Z",
+        &["tag", "-a", "v0.2.0", "-m", "Release 0.2.0"],
+    );
+
+    fs::write(root.join("docs/notes.md"), "# Notes\n\nRetries back off.\n").unwrap();
+    fs::remove_file(root.join("src/retry.rs")).unwrap();
+    let fourth = commit(root, "2024-03-01T12:00:00Z", "Document retries");
+    vec![first, second, third, fourth]
+}
+
+#[test]
+fn test_history_by_month() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path();
+    let hashes = repository(root);
+    fs::write(root.join("README.md"), "# API\n").unwrap();
+
+    let source = process(
+        "  stages:\n    - stage: collect\n    - stage: history\n",
+        root,
+    );
+    assert_eq!(
+        names(&source),
+        vec![
+            "README.md",
+            "docs__notes.md",
+            "_history__2024-03.md",
+            "_history__2024-02.md",
+            "_history__2024-01.md",
+        ]
+    );
+    let link = |hash: &str| {
+        format!(
+            "[`{}`](https://github.com/org/api/commit/{hash})",
+            &hash[..10]
+        )
+    };
+    assert_eq!(
+        text(&source, "_history__2024-02.md"),
+        format!(
+            "# History of api: 2024-02
+
+Commits authored in 2024-02; 2 commits, newest first.
+
+## Move notes to docs
+
+- Commit: {}
+- Author: Ada Lovelace
+- Date: 2024-02-20T08:00:00+00:00
+- Tags: `v0.2.0`
+
+Changed files:
+
+- R `notes.md` → `docs/notes.md`
+
+## Change retry policy to exponential backoff
+
+- Commit: {}
+- Author: Ada Lovelace
+- Date: 2024-02-05T14:30:00+00:00
+
+Fixed delays hammered the API during outages.
+
+Changed files:
+
+- M `src/retry.rs`
+",
+            link(&hashes[2]),
+            link(&hashes[1])
+        )
+    );
+    assert!(
+        text(&source, "_history__2024-03.md").contains("- M `docs/notes.md`\n- D `src/retry.rs`\n"),
+        "{}",
+        text(&source, "_history__2024-03.md")
+    );
+    let item = &source.external_items[2];
+    assert_eq!(item.mime_type.as_deref(), Some("text/markdown"));
+    assert_eq!(item.metadata.path, "");
+}
+
+#[test]
+fn test_history_by_release_with_diffs() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path();
+    repository(root);
+
+    let config =
+        config("  kind: history\n  history:\n    group_by: tag\n    diff_lines: 3\n").unwrap();
+    assert_eq!(
+        config.process.pipeline(),
+        vec![StageConfig::History(HistoryOptions {
+            group_by: HistoryGrouping::Tag,
+            diff_lines: 3,
+            ..HistoryOptions::default()
+        })],
+        "the preset's stage takes the options"
+    );
+
+    let source = process(
+        "  kind: history\n  history:\n    group_by: tag\n    diff_lines: 3\n",
+        root,
+    );
+    assert_eq!(
+        names(&source),
+        vec![
+            "_history__unreleased.md",
+            "_history__v0.2.0.md",
+            "_history__v0.1.0.md",
+        ]
+    );
+    let release = text(&source, "_history__v0.2.0.md");
+    assert!(
+        release.starts_with(
+            "# History of api: v0.2.0\n\nCommits first released in `v0.2.0`, tagged 2024-02-21; 2 commits, newest first.\n"
+        ),
+        "{release}"
+    );
+    assert!(
+        release.contains(
+            "`src/retry.rs`:\n\n```diff\n@@ -1,3 +1,5 @@\n pub fn delay(attempt: u32) -> u64 {\n-    500\n… 4 more lines\n```\n"
+        ),
+        "{release}"
+    );
+    assert!(
+        !release.contains("`docs/notes.md`:"),
+        "pure renames have no diff"
+    );
+    assert!(text(&source, "_history__v0.1.0.md").contains("## Add retry helper\n"));
+    assert!(text(&source, "_history__unreleased.md").starts_with(
+        "# History of api: unreleased\n\nCommits not yet in a release tag; 1 commit, newest first.\n\n## Document retries\n"
+    ));
+}
+
+#[test]
+fn test_history_bounds() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path();
+    repository(root);
+    let subjects = |process: &str| -> Vec<String> {
+        let source = self::process(process, root);
+        source
+            .external_items
+            .iter()
+            .flat_map(|item| {
+                std::str::from_utf8(&item.content)
+                    .unwrap()
+                    .lines()
+                    .filter_map(|line| line.strip_prefix("## "))
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    };
+
+    assert_eq!(
+        subjects("  kind: history\n  history:\n    max_commits: 1\n"),
+        vec!["Document retries"]
+    );
+    assert_eq!(
+        subjects(
+            "  kind: history\n  history:\n    since: \"2024-02-01\"\n    until: \"2024-02-28\"\n"
+        ),
+        vec![
+            "Move notes to docs",
+            "Change retry policy to exponential backoff"
+        ]
+    );
+    assert_eq!(
+        subjects("  kind: history\n  history:\n    paths: [src]\n"),
+        vec![
+            "Document retries",
+            "Change retry policy to exponential backoff",
+            "Add retry helper"
+        ]
+    );
+}
+
+#[test]
+fn test_history_of_a_plain_directory_is_empty() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path();
+    fs::write(root.join("notes.txt"), "Not a repository.\n").unwrap();
+    let source = process(
+        "  stages:\n    - stage: collect\n    - stage: history\n",
+        root,
+    );
+    assert_eq!(names(&source), vec!["notes.txt"]);
+
+    git(root, "2024-01-01T00:00:00Z", &["init", "-q"]);
+    let source = process("  kind: history\n", root);
+    assert!(source.external_items.is_empty(), "no commits yet");
+}
+
+#[test]
+fn test_invalid_history_config_is_rejected() {
+    let err = config("  kind: FlattenFiles\n  history:\n    max_commits: 0\n    since: \"\"\n")
+        .unwrap_err();
+    assert!(
+        err.contains("process.history.max_commits must be greater than 0"),
+        "{err}"
+    );
+    assert!(
+        err.contains("process.history.since must not be empty"),
+        "{err}"
+    );
+    let err =
+        config("  stages:\n    - stage: collect\n    - stage: history\n      paths: [\"\"]\n")
+            .unwrap_err();
+    assert!(
+        err.contains("process.stages[1].paths must not contain empty paths"),
+        "{err}"
+    );
+    let err = config("  stages:\n    - stage: history\n    - stage: collect\n").unwrap_err();
+    assert!(
+        err.contains("process.stages[1]: source stages (`collect`, `readme`) can only come first"),
+        "{err}"
+    );
+}
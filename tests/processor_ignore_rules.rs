@@ -0,0 +1,205 @@
+//! Tests for .gitignore/.llmignore handling and per-source include/exclude globs.
+
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+use llm_bucket::config::Config;
+use llm_bucket::contract::{
+    ExternalSourceInput, ProcessConfig, ProcessInput, ProcessorKind, SkipReason, SkippedPath,
+};
+use llm_bucket::download::{SourceAction, SourceOptions};
+use llm_bucket::preprocess::Processor;
+
+fn write(root: &Path, path: &str, content: &str) {
+    let path = root.join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+fn fixture_repo(root: &Path) {
+    write(
+        root,
+        ".gitignore",
+        "node_modules/\n/dist\n*.log\n!keep.log\n",
+    );
+    write(root, ".llmignore", "secrets/\n");
+    write(root, "README.md", "# Demo\n");
+    write(root, "Cargo.lock", "# lock\n");
+    write(root, "app.log", "noise\n");
+    write(root, "keep.log", "kept\n");
+    write(
+        root,
+        "node_modules/left-pad/index.js",
+        "module.exports = 1;\n",
+    );
+    write(root, "dist/bundle.js", "bundle\n");
+    write(root, "secrets/prod.env", "TOKEN=1\n");
+    write(root, "src/main.rs", "fn main() {}\n");
+    write(root, "src/.gitignore", "generated.rs\n");
+    write(root, "src/generated.rs", "// generated\n");
+    write(
+        root,
+        "src/dist/keep.rs",
+        "// only /dist at the root is ignored\n",
+    );
+    write(root, "vendor/lib/mod.rs", "// vendored\n");
+    write(root, ".git/HEAD", "ref: refs/heads/main\n");
+}
+
+fn flatten(root: &Path, options: SourceOptions) -> ExternalSourceInput {
+    Processor::new(ProcessConfig {
+        kind: Some(ProcessorKind::FlattenFiles),
+        ..Default::default()
+    })
+    .process_sync(ProcessInput {
+        name: "demo".to_string(),
+        repo_path: root.to_path_buf(),
+        base_url: None,
+        options,
+        variables: Default::default(),
+    })
+    .expect("Flattening should succeed")
+}
+
+fn filenames(source: &ExternalSourceInput) -> Vec<&str> {
+    source
+        .external_items
+        .iter()
+        .map(|i| i.filename.as_str())
+        .collect()
+}
+
+fn skipped(path: &str, reason: SkipReason) -> SkippedPath {
+    SkippedPath {
+        path: path.to_string(),
+        reason,
+    }
+}
+
+#[test]
+fn test_flatten_honours_gitignore_and_llmignore() {
+    let tmp = tempdir().unwrap();
+    fixture_repo(tmp.path());
+
+    let source = flatten(tmp.path(), SourceOptions::default());
+    assert_eq!(
+        filenames(&source),
+        vec![
+            ".gitignore",
+            ".llmignore",
+            "Cargo.lock",
+            "README.md",
+            "keep.log",
+            "src__.gitignore",
+            "src__dist__keep.rs",
+            "src__main.rs",
+            "vendor__lib__mod.rs",
+        ]
+    );
+
+    let ignored = |file: &str| SkipReason::IgnoreFile(file.to_string());
+    for expected in [
+        skipped(".git/", SkipReason::BuiltIn),
+        skipped("app.log", ignored(".gitignore")),
+        skipped("dist/", ignored(".gitignore")),
+        skipped("node_modules/", ignored(".gitignore")),
+        skipped("secrets/", ignored(".llmignore")),
+        skipped("src/generated.rs", ignored("src/.gitignore")),
+    ] {
+        assert!(
+            source.skipped.contains(&expected),
+            "{expected:?} not in {:?}",
+            source.skipped
+        );
+    }
+    assert_eq!(source.skipped.len(), 6, "{:?}", source.skipped);
+}
+
+#[test]
+fn test_llmignore_can_reinclude_gitignored_files() {
+    let tmp = tempdir().unwrap();
+    write(tmp.path(), ".gitignore", "*.log\n");
+    write(tmp.path(), ".llmignore", "!important.log\n");
+    write(tmp.path(), "important.log", "keep me\n");
+    write(tmp.path(), "debug.log", "drop me\n");
+
+    let source = flatten(tmp.path(), SourceOptions::default());
+    assert_eq!(
+        filenames(&source),
+        vec![".gitignore", ".llmignore", "important.log"]
+    );
+}
+
+#[test]
+fn test_exclude_and_include_globs() {
+    let tmp = tempdir().unwrap();
+    fixture_repo(tmp.path());
+
+    let source = flatten(
+        tmp.path(),
+        SourceOptions {
+            include: vec![],
+            exclude: vec!["*.lock".into(), "vendor/**".into(), ".*ignore".into()],
+            process: None,
+        },
+    );
+    assert_eq!(
+        filenames(&source),
+        vec![
+            "README.md",
+            "keep.log",
+            "src__dist__keep.rs",
+            "src__main.rs"
+        ]
+    );
+    assert!(source.skipped.contains(&skipped(
+        "Cargo.lock",
+        SkipReason::Excluded("*.lock".into())
+    )));
+    assert!(source.skipped.contains(&skipped(
+        "vendor/",
+        SkipReason::Excluded("vendor/**".into())
+    )));
+
+    let source = flatten(
+        tmp.path(),
+        SourceOptions {
+            include: vec!["src/*.rs".into()],
+            exclude: vec![],
+            process: None,
+        },
+    );
+    assert_eq!(filenames(&source), vec!["src__main.rs"]);
+    assert!(source
+        .skipped
+        .contains(&skipped("README.md", SkipReason::NotIncluded)));
+    assert!(source
+        .skipped
+        .contains(&skipped("src/dist/keep.rs", SkipReason::NotIncluded)));
+}
+
+#[test]
+fn test_source_options_are_read_from_config() {
+    let yaml = r#"
+download:
+  output_dir: out
+  sources:
+    - type: git
+      repo_url: "git@github.com:org/repo.git"
+      include: ["src/**"]
+      exclude: ["*.lock"]
+process:
+  kind: FlattenFiles
+"#;
+    let config = Config::from_yaml_str(yaml).unwrap();
+    let SourceAction::Git(git) = &config.download.sources[0] else {
+        panic!("Expected a git source");
+    };
+    assert_eq!(git.options.include, vec!["src/**"]);
+    assert_eq!(config.download.sources[0].options().exclude, vec!["*.lock"]);
+
+    let invalid = yaml.replace("*.lock", "[a-");
+    let err = Config::from_yaml_str(&invalid).unwrap_err();
+    assert!(err.to_string().contains("invalid glob `[a-`"), "{err}");
+}
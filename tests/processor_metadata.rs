@@ -0,0 +1,173 @@
+//! Tests for item metadata: canonical links, commits, timestamps, languages and hashes.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::tempdir;
+
+use llm_bucket::contract::{
+    ChunkOptions, ChunkUnit, ProcessConfig, ProcessInput, ProcessorKind, StageConfig,
+};
+use llm_bucket::download::{IndexedFile, SourceIndex, SOURCE_INDEX_FILE};
+use llm_bucket::preprocess::Processor;
+use sha2::{Digest, Sha256};
+
+const GUIDE: &str = "# Guide\n\nWelcome to the guide.\n\n## Install\n\nRun the installer first.\n";
+const LIB: &str = "pub fn answer() -> u32 {\n    42\n}\n";
+
+fn git(repo: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .env("GIT_AUTHOR_DATE", "2024-03-01T12:00:00Z")
+        .env("GIT_COMMITTER_DATE", "2024-03-01T12:00:00Z")
+        .output()
+        .expect("git runs");
+    assert!(output.status.success(), "git {args:?} failed");
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+fn input(root: &Path, base_url: Option<&str>) -> ProcessInput {
+    ProcessInput {
+        name: "demo".to_string(),
+        repo_path: root.to_path_buf(),
+        base_url: base_url.map(str::to_string),
+        options: Default::default(),
+        variables: Default::default(),
+    }
+}
+
+fn sha256(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[test]
+fn test_git_items_link_to_the_downloaded_commit() {
+    let tmp = tempdir().unwrap();
+    let repo = tmp.path();
+    fs::create_dir_all(repo.join("docs")).unwrap();
+    fs::create_dir_all(repo.join("src")).unwrap();
+    fs::write(repo.join("docs/guide.md"), GUIDE).unwrap();
+    fs::write(repo.join("src/lib.rs"), LIB).unwrap();
+    git(repo, &["init", "-q"]);
+    git(repo, &["add", "."]);
+    git(repo, &["commit", "-q", "-m", "Initial commit"]);
+    let commit = git(repo, &["rev-parse", "HEAD"]);
+
+    let config = ProcessConfig {
+        stages: vec![
+            StageConfig::Collect(Default::default()),
+            StageConfig::Chunk(ChunkOptions {
+                size: 40,
+                overlap: 0,
+                unit: ChunkUnit::Chars,
+            }),
+        ],
+        ..Default::default()
+    };
+    let source = Processor::new(config)
+        .process_sync(input(repo, Some("https://github.com/org/repo/blob/main")))
+        .unwrap();
+
+    let names: Vec<_> = source
+        .external_items
+        .iter()
+        .map(|i| i.filename.as_str())
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            "docs__guide__chunk001.md",
+            "docs__guide__chunk002.md",
+            "src__lib.rs"
+        ]
+    );
+
+    let lib = &source.external_items[2].metadata;
+    assert_eq!(lib.path, "src/lib.rs");
+    assert_eq!(
+        lib.url.as_deref(),
+        Some(format!("https://github.com/org/repo/blob/{commit}/src/lib.rs").as_str())
+    );
+    assert_eq!(lib.commit.as_deref(), Some(commit.as_str()));
+    assert_eq!(lib.language.as_deref(), Some("rust"));
+    assert_eq!(lib.size, LIB.len() as u64);
+    assert_eq!(lib.content_hash, sha256(LIB.as_bytes()));
+    assert_eq!(lib.modified.as_deref(), Some("2024-03-01T12:00:00+00:00"));
+
+    for item in &source.external_items[..2] {
+        let metadata = &item.metadata;
+        assert_eq!(metadata.path, "docs/guide.md");
+        assert_eq!(
+            metadata.url.as_deref(),
+            Some(
+                format!(
+                    "https://github.com/org/repo/blob/{commit}/docs/guide.md#{}",
+                    item.filename
+                )
+                .as_str()
+            ),
+            "chunks of one document get distinct links"
+        );
+        assert_eq!(metadata.content_hash, sha256(&item.content));
+        assert_eq!(metadata.size, item.content.len() as u64);
+    }
+}
+
+#[test]
+fn test_downloader_index_supplies_links_and_timestamps() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path();
+    fs::write(root.join("Runbooks__Failover.md"), GUIDE).unwrap();
+    fs::write(root.join("notes.txt"), "Loose notes.\n").unwrap();
+    let index = SourceIndex {
+        files: [(
+            "Runbooks__Failover.md".to_string(),
+            IndexedFile {
+                url: Some("https://wiki.example.com/spaces/OPS/pages/42".to_string()),
+                modified: Some("2024-05-06T07:08:09.000Z".to_string()),
+                breadcrumb: Vec::new(),
+            },
+        )]
+        .into(),
+    };
+    index.save(root).unwrap();
+
+    let source = Processor::new(ProcessConfig {
+        kind: Some(ProcessorKind::CodeToPDF),
+        ..Default::default()
+    })
+    .process_sync(input(root, None))
+    .unwrap();
+
+    let names: Vec<_> = source
+        .external_items
+        .iter()
+        .map(|i| i.filename.as_str())
+        .collect();
+    assert_eq!(names, vec!["Runbooks__Failover.md.pdf", "notes.txt.pdf"]);
+    assert!(!names.iter().any(|n| n.contains(SOURCE_INDEX_FILE)));
+
+    let page = &source.external_items[0].metadata;
+    assert_eq!(page.path, "Runbooks__Failover.md");
+    assert_eq!(
+        page.url.as_deref(),
+        Some("https://wiki.example.com/spaces/OPS/pages/42")
+    );
+    assert_eq!(page.modified.as_deref(), Some("2024-05-06T07:08:09.000Z"));
+    assert_eq!(page.language, None, "prose has no highlighting language");
+    assert_eq!(page.commit, None);
+
+    let notes = &source.external_items[1].metadata;
+    assert_eq!(notes.url, None);
+    let modified = notes.modified.as_deref().expect("file time as a fallback");
+    assert!(
+        modified.ends_with('Z') && modified.len() == 20,
+        "{modified}"
+    );
+}
@@ -0,0 +1,283 @@
+//! Tests for the notebooks stage: Jupyter notebooks converted to markdown items.
+
+use std::fs;
+use tempfile::tempdir;
+
+mod common;
+use common::config;
+
+use llm_bucket::contract::{FilePolicy, NotebookOptions, ProcessInput, RichOutputs, StageConfig};
+use llm_bucket::preprocess::notebook::notebook_markdown;
+use llm_bucket::preprocess::Processor;
+
+const NOTEBOOK: &str = r##"{
+ "cells": [
+  {
+   "cell_type": "markdown",
+   "metadata": {},
+   "source": ["# Sales analysis\n", "\n", "Monthly revenue by region."],
+   "attachments": {"chart.png": {"image/png": "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNk"}}
+  },
+  {
+   "cell_type": "code",
+   "execution_count": 1,
+   "metadata": {},
+   "source": "import pandas as pd\ndf = pd.read_csv(\"sales.csv\")\nprint(len(df))\ndf.head(2)",
+   "outputs": [
+    {"output_type": "stream", "name": "stdout", "text": ["120\n"]},
+    {
+     "output_type": "execute_result",
+     "execution_count": 1,
+     "metadata": {},
+     "data": {
+      "text/html": "<table><tr><td>north</td></tr></table>",
+      "text/plain": ["  region  revenue\n", "0  north     1200\n", "1  south      900"]
+     }
+    }
+   ]
+  },
+  {
+   "cell_type": "code",
+   "execution_count": 2,
+   "metadata": {},
+   "source": ["df.plot()"],
+   "outputs": [
+    {
+     "output_type": "display_data",
+     "metadata": {},
+     "data": {
+      "image/png": "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNk",
+      "text/plain": "<Figure size 640x480 with 1 Axes>"
+     }
+    },
+    {
+     "output_type": "display_data",
+     "metadata": {},
+     "data": {
+      "application/vnd.jupyter.widget-view+json": {"model_id": "abc123", "version_major": 2},
+      "text/plain": "IntSlider(value=3)"
+     }
+    }
+   ]
+  },
+  {
+   "cell_type": "code",
+   "execution_count": 3,
+   "metadata": {},
+   "source": "df[\"missing\"]",
+   "outputs": [
+    {
+     "output_type": "error",
+     "ename": "KeyError",
+     "evalue": "'missing'",
+     "traceback": [
+      "\u001b[0;31m---------------------------------------------------------------------------\u001b[0m",
+      "\u001b[0;31mKeyError\u001b[0m: 'missing'"
+     ]
+    }
+   ]
+  },
+  {"cell_type": "code", "execution_count": null, "metadata": {}, "source": [], "outputs": []},
+  {"cell_type": "raw", "metadata": {}, "source": "Generated by the reporting job."}
+ ],
+ "metadata": {
+  "kernelspec": {"display_name": "Python 3", "language": "python", "name": "python3"},
+  "widgets": {"application/vnd.jupyter.widget-state+json": {"state": {"abc123": {"model_name": "IntSliderModel"}}}}
+ },
+ "nbformat": 4,
+ "nbformat_minor": 5
+}"##;
+
+#[test]
+fn test_notebook_markdown() {
+    assert_eq!(
+        notebook_markdown(NOTEBOOK, &NotebookOptions::default()).unwrap(),
+        r#"# Sales analysis
+
+Monthly revenue by region.
+
+```python
+import pandas as pd
+df = pd.read_csv("sales.csv")
+print(len(df))
+df.head(2)
+```
+
+Output:
+
+```text
+120
+  region  revenue
+0  north     1200
+1  south      900
+```
+
+```python
+df.plot()
+```
+
+Output:
+
+```text
+<Figure size 640x480 with 1 Axes>
+IntSlider(value=3)
+```
+
+[image/png output]
+
+[interactive widget]
+
+```python
+df["missing"]
+```
+
+Output:
+
+```text
+---------------------------------------------------------------------------
+KeyError: 'missing'
+```
+
+```
+Generated by the reporting job.
+```
+"#
+    );
+}
+
+#[test]
+fn test_outputs_are_cut_and_rich_outputs_dropped() {
+    let options = NotebookOptions {
+        output_chars: 6,
+        rich_outputs: RichOutputs::Drop,
+    };
+    let markdown = notebook_markdown(NOTEBOOK, &options).unwrap();
+    assert!(
+        markdown.contains("Output:\n\n```text\n120\n  \n… 51 more characters\n```\n"),
+        "{markdown}"
+    );
+    assert!(!markdown.contains("[image/png output]"), "{markdown}");
+    assert!(!markdown.contains("iVBOR"), "no base64 survives");
+
+    let options = NotebookOptions {
+        output_chars: 0,
+        ..NotebookOptions::default()
+    };
+    let markdown = notebook_markdown(NOTEBOOK, &options).unwrap();
+    assert!(!markdown.contains("Output:"), "{markdown}");
+    assert!(!markdown.contains("[interactive widget]"), "{markdown}");
+
+    assert!(notebook_markdown("{\"worksheets\": []}", &options).is_err());
+    assert!(notebook_markdown("not json", &options).is_err());
+}
+
+#[test]
+fn test_notebooks_stage_replaces_notebook_items() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path();
+    fs::create_dir_all(root.join("analysis")).unwrap();
+    fs::write(root.join("analysis/sales.ipynb"), NOTEBOOK).unwrap();
+    fs::write(root.join("analysis/broken.ipynb"), "{\"cells\": ").unwrap();
+    fs::write(root.join("data.json"), "{\"cells\": []}").unwrap();
+
+    let config =
+        config("  kind: FlattenFiles\n  notebooks:\n    output_chars: 500\n  redact: {}\n")
+            .unwrap();
+    let pipeline = config.process.pipeline();
+    assert_eq!(
+        pipeline[1],
+        StageConfig::Notebooks(NotebookOptions {
+            output_chars: 500,
+            rich_outputs: RichOutputs::Summarise,
+        })
+    );
+    assert!(
+        matches!(pipeline[2], StageConfig::Redact(_)),
+        "outputs are scanned for secrets"
+    );
+
+    let source = Processor::new(config.process)
+        .process_sync(ProcessInput {
+            name: "x".to_string(),
+            repo_path: root.to_path_buf(),
+            base_url: None,
+            options: Default::default(),
+            variables: Default::default(),
+        })
+        .unwrap();
+    let items: Vec<(&str, Option<&str>, &str)> = source
+        .external_items
+        .iter()
+        .map(|i| {
+            (
+                i.filename.as_str(),
+                i.mime_type.as_deref(),
+                i.metadata.path.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        items,
+        vec![
+            (
+                "analysis__broken.ipynb",
+                Some("text/plain"),
+                "analysis/broken.ipynb"
+            ),
+            (
+                "analysis__sales.ipynb.md",
+                Some("text/markdown"),
+                "analysis/sales.ipynb"
+            ),
+            ("data.json", Some("application/json"), "data.json"),
+        ]
+    );
+    assert!(std::str::from_utf8(&source.external_items[1].content)
+        .unwrap()
+        .starts_with("# Sales analysis\n"));
+}
+
+#[test]
+fn test_notebooks_over_the_size_limit_are_converted() {
+    let tmp = tempdir().unwrap();
+    let image = "iVBORw0KGgo".repeat(200_000);
+    let notebook = format!(
+        r#"{{"cells": [{{"cell_type": "code", "source": "plot()", "outputs": [{{"output_type": "display_data", "data": {{"image/png": "{image}"}}}}]}}], "metadata": {{}}}}"#
+    );
+    assert!(notebook.len() as u64 > FilePolicy::DEFAULT_MAX_FILE_SIZE);
+    fs::write(tmp.path().join("plots.ipynb"), &notebook).unwrap();
+    fs::write(tmp.path().join("dump.json"), &notebook).unwrap();
+
+    let process = |yaml: &str| {
+        let config = config(yaml).unwrap();
+        Processor::new(config.process)
+            .process_sync(ProcessInput {
+                name: "x".to_string(),
+                repo_path: tmp.path().to_path_buf(),
+                base_url: None,
+                options: Default::default(),
+                variables: Default::default(),
+            })
+            .unwrap()
+    };
+
+    let source = process("  kind: FlattenFiles\n  notebooks: {}\n");
+    assert_eq!(source.external_items.len(), 1);
+    assert_eq!(source.external_items[0].filename, "plots.ipynb.md");
+    assert_eq!(
+        std::str::from_utf8(&source.external_items[0].content).unwrap(),
+        "```\nplot()\n```\n\n[image/png output]\n"
+    );
+    assert_eq!(
+        source
+            .skipped
+            .iter()
+            .map(|s| s.path.as_str())
+            .collect::<Vec<_>>(),
+        vec!["dump.json"]
+    );
+
+    let source = process("  kind: FlattenFiles\n");
+    assert!(source.external_items.is_empty());
+    assert_eq!(source.skipped.len(), 2);
+}
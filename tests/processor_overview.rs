@@ -0,0 +1,287 @@
+//! Tests for the overview stage: a generated summary item per source.
+
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+mod common;
+use common::config;
+
+use llm_bucket::config::Config;
+use llm_bucket::contract::{ExternalSourceInput, OverviewOptions, StageConfig};
+use llm_bucket::preprocess::Processor;
+
+fn process(config: Config, root: &Path) -> ExternalSourceInput {
+    Processor::new(config.process)
+        .process_sync(llm_bucket::contract::ProcessInput {
+            name: "git@github.com:org/shop.git".to_string(),
+            repo_path: root.to_path_buf(),
+            base_url: None,
+            options: Default::default(),
+            variables: Default::default(),
+        })
+        .unwrap()
+}
+
+fn write(root: &Path, path: &str, content: &str) {
+    let path = root.join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+fn shop(root: &Path) {
+    write(
+        root,
+        "README.md",
+        "[![CI](https://ci.example.com/badge.svg)](https://ci.example.com)\n\n\
+         # Shop\n\n\
+         Shop is an online store backend.\nIt serves the catalogue and checkout.\n\n\
+         Orders are stored in PostgreSQL.\n\n\
+         ## Install\n\nRun `cargo install`.\n",
+    );
+    write(root, ".gitignore", "secrets.txt\n");
+    write(root, "secrets.txt", "hunter2\n");
+    write(
+        root,
+        "Cargo.toml",
+        r#"[package]
+name = "shop"
+version = "0.1.0"
+
+[dependencies]
+serde = { version = "1.0", features = ["derive"] }
+tokio = "1.37"
+shop-core = { path = "core" }
+
+[dev-dependencies]
+tempfile = "3"
+
+[[bin]]
+name = "shop-admin"
+path = "tools/admin.rs"
+"#,
+    );
+    write(root, "src/main.rs", "fn main() {\n    shop::run();\n}\n");
+    write(root, "src/lib.rs", "pub fn run() {}\n");
+    write(root, "src/bin/migrate.rs", "fn main() {}\n");
+    write(root, "tools/admin.rs", "fn main() {}\n");
+    write(
+        root,
+        "web/package.json",
+        r#"{
+  "name": "shop-web",
+  "main": "./dist/index.js",
+  "bin": { "shop": "bin/shop.js" },
+  "dependencies": { "react": "^18.2.0" },
+  "devDependencies": { "typescript": "~5.4.0" }
+}"#,
+    );
+    write(
+        root,
+        "web/src/index.ts",
+        "export const x = 1;\nexport const y = 2;\n",
+    );
+    write(
+        root,
+        "worker/go.mod",
+        "module github.com/org/shop/worker\n\ngo 1.22\n\nrequire github.com/lib/pq v1.10.9\n\nrequire (\n\tgithub.com/google/uuid v1.6.0\n\tgolang.org/x/sys v0.20.0 // indirect\n)\n",
+    );
+    write(
+        root,
+        "worker/cmd/worker/main.go",
+        "package main\n\nfunc main() {\n}\n",
+    );
+    write(
+        root,
+        "reports/pom.xml",
+        r#"<project>
+  <parent><groupId>org.parent</groupId><artifactId>parent</artifactId></parent>
+  <groupId>org.shop</groupId>
+  <artifactId>reports</artifactId>
+  <dependencies>
+    <dependency>
+      <groupId>com.google.guava</groupId>
+      <artifactId>guava</artifactId>
+      <version>33.0.0-jre</version>
+    </dependency>
+    <!-- <dependency><groupId>old</groupId><artifactId>gone</artifactId></dependency> -->
+    <dependency>
+      <groupId>junit</groupId>
+      <artifactId>junit</artifactId>
+      <scope>test</scope>
+    </dependency>
+  </dependencies>
+</project>
+"#,
+    );
+    write(
+        root,
+        "reports/src/main/java/org/shop/Report.java",
+        "public class Report {\n    public static void main(String[] args) {\n    }\n}\n",
+    );
+    write(
+        root,
+        "scripts/seed.py",
+        "def seed():\n    pass\n\n\nif __name__ == \"__main__\":\n    seed()\n",
+    );
+}
+
+#[test]
+fn test_overview_summarises_the_checkout() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path();
+    shop(root);
+    let config = config("  kind: FlattenFiles\n  overview:\n    depth: 2\n").unwrap();
+    assert_eq!(
+        config.process.pipeline()[1],
+        StageConfig::Overview(OverviewOptions { depth: 2 })
+    );
+
+    let source = process(config, root);
+    let overview = &source.external_items[0];
+    assert_eq!(overview.filename, "_overview.md");
+    assert_eq!(overview.mime_type.as_deref(), Some("text/markdown"));
+    assert_eq!(overview.metadata.path, "");
+    let text = std::str::from_utf8(&overview.content).unwrap();
+    assert_eq!(
+        text,
+        r#"# git@github.com:org/shop.git
+
+Overview generated from the source: README introduction, languages, build manifests, dependencies, entry points and directory tree.
+
+## Introduction
+
+Shop is an online store backend.
+It serves the catalogue and checkout.
+
+Orders are stored in PostgreSQL.
+
+## Languages
+
+| Language | Files | Lines |
+| --- | ---: | ---: |
+| python | 1 | 6 |
+| rust | 4 | 6 |
+| go | 1 | 4 |
+| java | 1 | 4 |
+| typescript | 1 | 2 |
+
+## Build manifests
+
+### `Cargo.toml` (Cargo)
+
+- Name: `shop`
+- dependencies: `serde 1.0`, `shop-core`, `tokio 1.37`
+- dev-dependencies: `tempfile 3`
+
+### `reports/pom.xml` (Maven)
+
+- Name: `org.shop:reports`
+- compile dependencies: `com.google.guava:guava 33.0.0-jre`
+- test dependencies: `junit:junit`
+
+### `web/package.json` (npm)
+
+- Name: `shop-web`
+- dependencies: `react ^18.2.0`
+- devDependencies: `typescript ~5.4.0`
+
+### `worker/go.mod` (Go modules)
+
+- Name: `github.com/org/shop/worker`
+- require: `github.com/lib/pq v1.10.9`, `github.com/google/uuid v1.6.0`, `golang.org/x/sys v0.20.0 (indirect)`
+
+## Entry points
+
+- `reports/src/main/java/org/shop/Report.java`: Java `main` method
+- `scripts/seed.py`: Python script
+- `src/bin/migrate.rs`: Rust binary
+- `src/lib.rs`: Rust library
+- `src/main.rs`: Rust binary
+- `tools/admin.rs`: Rust binary
+- `web/bin/shop.js`: npm `bin` `shop`
+- `web/dist/index.js`: npm `main`
+- `worker/cmd/worker/main.go`: Go `main` package
+
+## Directory tree
+
+```text
+./
+├── reports/
+│   ├── src/ (1 file)
+│   └── pom.xml
+├── scripts/
+│   └── seed.py
+├── src/
+│   ├── bin/ (1 file)
+│   ├── lib.rs
+│   └── main.rs
+├── tools/
+│   └── admin.rs
+├── web/
+│   ├── src/ (1 file)
+│   └── package.json
+├── worker/
+│   ├── cmd/ (1 file)
+│   └── go.mod
+├── .gitignore
+├── Cargo.toml
+└── README.md
+```
+"#
+    );
+    assert!(!text.contains("secrets"), "ignored files stay out");
+
+    let names: Vec<&str> = source
+        .external_items
+        .iter()
+        .map(|i| i.filename.as_str())
+        .collect();
+    assert_eq!(names.len(), 15, "{names:?}");
+    assert_eq!(
+        process(
+            self::config("  kind: FlattenFiles\n  overview:\n    depth: 2\n").unwrap(),
+            root
+        )
+        .external_items[0]
+            .content,
+        overview.content,
+        "the overview is deterministic"
+    );
+}
+
+#[test]
+fn test_overview_of_a_bare_source_and_in_stages() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path();
+    write(root, "notes/todo.txt", "Buy milk.\n");
+    let config = config_stages(
+        "    - stage: collect\n    - stage: overview\n      depth: 1\n    - stage: markdown_to_pdf\n",
+    );
+    let source = process(config, root);
+    let names: Vec<&str> = source
+        .external_items
+        .iter()
+        .map(|i| i.filename.as_str())
+        .collect();
+    assert_eq!(names, vec!["_overview.pdf", "notes__todo.txt"]);
+}
+
+fn config_stages(stages: &str) -> Config {
+    config(&format!("  stages:\n{stages}")).unwrap()
+}
+
+#[test]
+fn test_invalid_overview_config_is_rejected() {
+    let err = config("  kind: FlattenFiles\n  overview:\n    depth: 0\n").unwrap_err();
+    assert!(
+        err.contains("process.overview.depth must be greater than 0"),
+        "{err}"
+    );
+    let err = config("  stages:\n    - stage: collect\n    - stage: overview\n      depth: 0\n")
+        .unwrap_err();
+    assert!(
+        err.contains("process.stages[1].depth must be greater than 0"),
+        "{err}"
+    );
+}
@@ -0,0 +1,200 @@
+//! Tests for the PII scrubbing stage.
+
+use std::collections::BTreeMap;
+use std::fs;
+use tempfile::tempdir;
+
+mod common;
+use common::{batch, config, context, item};
+
+use llm_bucket::config::Config;
+use llm_bucket::contract::{PiiCategory, PiiMode, PiiOptions, ProcessInput, StageConfig};
+use llm_bucket::preprocess::pii::PiiScrubber;
+use llm_bucket::preprocess::pipeline::{Item, Stage};
+use llm_bucket::preprocess::Processor;
+use regex::Regex;
+
+const THREAD: &str = "From: Jane Doe <Jane.Doe@example.com>\n\
+                      To: support@example.com\n\
+                      \n\
+                      Hi, Jane Doe here. Please refund to NL91 ABNA 0417 1643 00 or call\n\
+                      +31 6 1234 5678. Bob will follow up.\n\
+                      Released 2024-01-15 as version 1.2.3, ticket 1234567890.\n";
+
+const PAGE: &str = "Owner: jane.doe@example.com, phone (555) 123-4567.\n\
+                    Not an IBAN: NL91ABNA0417164301.\n";
+
+fn text(item: &Item) -> &str {
+    std::str::from_utf8(&item.content).unwrap()
+}
+
+#[test]
+fn test_pseudonyms_are_consistent_within_a_run() {
+    let scrubber = PiiScrubber {
+        options: PiiOptions {
+            names: vec!["Bob".to_string()],
+            ..PiiOptions::default()
+        },
+    };
+    let output = scrubber
+        .run(
+            &context(&std::env::temp_dir()),
+            batch(vec![
+                item("thread.md", THREAD, "text/markdown"),
+                item("page.md", PAGE, "text/markdown"),
+            ]),
+        )
+        .unwrap();
+    let thread = text(&output.items[0]);
+    let page = text(&output.items[1]);
+
+    for value in ["Jane", "example.com", "NL91", "6 1234", "Bob"] {
+        assert!(!thread.contains(value), "{value} leaked:\n{thread}");
+    }
+    for value in ["jane", "555"] {
+        assert!(!page.contains(value), "{value} leaked:\n{page}");
+    }
+    assert!(thread.contains("Released 2024-01-15 as version 1.2.3, ticket 1234567890."));
+    assert!(page.contains("Not an IBAN: NL91ABNA0417164301."));
+
+    let token = |text: &str, category: &str| -> Vec<String> {
+        Regex::new(&format!(r"\[{category}:[0-9a-f]{{16}}\]"))
+            .unwrap()
+            .find_iter(text)
+            .map(|m| m.as_str().to_string())
+            .collect()
+    };
+    let thread_emails = token(thread, "EMAIL");
+    let page_emails = token(page, "EMAIL");
+    assert_eq!(thread_emails.len(), 2);
+    assert_ne!(thread_emails[0], thread_emails[1]);
+    assert_eq!(
+        page_emails,
+        vec![thread_emails[0].clone()],
+        "the same address, differently cased, gets the same token in another item"
+    );
+    let names = token(thread, "NAME");
+    assert_eq!(names.len(), 3);
+    assert_eq!(names[0], names[1], "Jane Doe is one person");
+    assert_ne!(names[0], names[2]);
+
+    assert_eq!(
+        output.pii,
+        BTreeMap::from([
+            (PiiCategory::Email, 3),
+            (PiiCategory::Phone, 2),
+            (PiiCategory::Iban, 1),
+            (PiiCategory::Name, 3),
+        ])
+    );
+}
+
+#[test]
+fn test_display_names_are_collected_per_source() {
+    let scrubber = PiiScrubber {
+        options: PiiOptions {
+            mode: PiiMode::Mask,
+            names: vec!["Bob".to_string()],
+            ..PiiOptions::default()
+        },
+    };
+    let other = "Jane Doe and Bob reviewed this.\n";
+    let output = scrubber
+        .run(
+            &context(&std::env::temp_dir()),
+            batch(vec![item("notes.md", other, "text/markdown")]),
+        )
+        .unwrap();
+    assert_eq!(
+        text(&output.items[0]),
+        "Jane Doe and [NAME] reviewed this.\n",
+        "without an address in this source, only configured names are known"
+    );
+}
+
+#[test]
+fn test_mask_mode_and_category_selection() {
+    let scrubber = PiiScrubber {
+        options: PiiOptions {
+            categories: vec![PiiCategory::Email, PiiCategory::Iban],
+            mode: PiiMode::Mask,
+            names: Vec::new(),
+        },
+    };
+    let output = scrubber
+        .run(
+            &context(&std::env::temp_dir()),
+            batch(vec![item("page.md", PAGE, "text/markdown")]),
+        )
+        .unwrap();
+    assert_eq!(
+        text(&output.items[0]),
+        "Owner: [EMAIL], phone (555) 123-4567.\nNot an IBAN: NL91ABNA0417164301.\n"
+    );
+    assert_eq!(output.pii, BTreeMap::from([(PiiCategory::Email, 1)]));
+}
+
+#[test]
+fn test_pii_is_enabled_per_source() {
+    let config = Config::from_yaml_str(
+        r#"
+download:
+  output_dir: out
+  sources:
+    - type: git
+      repo_url: "git@github.com:org/code.git"
+    - type: mailbox
+      path: ./archives/support.mbox
+      process:
+        kind: FlattenFiles
+        pii:
+          mode: mask
+          categories: [email, name]
+process:
+  kind: FlattenFiles
+"#,
+    )
+    .unwrap();
+    assert_eq!(config.process.pipeline().len(), 1);
+    let process = config.download.sources[1]
+        .options()
+        .process
+        .clone()
+        .unwrap();
+    let pipeline = process.pipeline();
+    assert!(matches!(pipeline[1], StageConfig::Pii(_)));
+
+    let tmp = tempdir().unwrap();
+    fs::write(tmp.path().join("thread.md"), THREAD).unwrap();
+    let source = Processor::new(process)
+        .process_sync(ProcessInput {
+            name: "support".to_string(),
+            repo_path: tmp.path().to_path_buf(),
+            base_url: None,
+            options: Default::default(),
+            variables: Default::default(),
+        })
+        .unwrap();
+    let scrubbed = std::str::from_utf8(&source.external_items[0].content).unwrap();
+    assert!(scrubbed.starts_with("From: [NAME] <[EMAIL]>\nTo: [EMAIL]\n"));
+    assert_eq!(
+        source.pii,
+        BTreeMap::from([(PiiCategory::Email, 2), (PiiCategory::Name, 2)])
+    );
+}
+
+#[test]
+fn test_invalid_pii_config_is_rejected() {
+    let config = |pii: &str| config(&format!("  kind: FlattenFiles\n  pii:\n{pii}"));
+    let err = config("    categories: []\n    names: [\" \"]\n").unwrap_err();
+    assert!(
+        err.contains("process.pii.categories must list at least one category"),
+        "{err}"
+    );
+    assert!(
+        err.contains("process.pii.names must not contain empty names"),
+        "{err}"
+    );
+    let err = config("    categories: [ssn]\n").unwrap_err();
+    assert!(err.contains("unknown variant `ssn`"), "{err}");
+}
@@ -0,0 +1,253 @@
+//! Tests for processing pipelines: stages in isolation, presets and YAML configuration.
+
+use std::fs;
+use tempfile::tempdir;
+
+mod common;
+use common::{config, context, item};
+
+use llm_bucket::contract::{
+    ChunkOptions, ChunkUnit, FilePolicy, ProcessError, ProcessInput, ProcessorKind, SkipReason,
+    SkippedPath, StageConfig,
+};
+use llm_bucket::preprocess::chunk::Chunker;
+use llm_bucket::preprocess::pipeline::{Batch, Pipeline, Stage, StageContext};
+use llm_bucket::preprocess::stages::{CodeToPdf, Collect, MarkdownToPdf};
+use llm_bucket::preprocess::Processor;
+
+fn paths(batch: &Batch) -> Vec<&str> {
+    batch.items.iter().map(|i| i.path.as_str()).collect()
+}
+
+#[test]
+fn test_stages_run_in_isolation() {
+    let tmp = tempdir().unwrap();
+    let ctx = context(tmp.path());
+    let batch = Batch {
+        items: vec![
+            item(
+                "docs/guide.md",
+                b"# Guide\n\nWelcome.\n\n## Install\n\nRun the installer first.\n",
+                "text/markdown",
+            ),
+            item("src/main.rs", b"fn main() {}\n", "text/x-rust"),
+            item("logo.png", b"\x89PNG\r\n\x1a\n", "image/png"),
+        ],
+        skipped: Vec::new(),
+        findings: Vec::new(),
+        pii: Default::default(),
+    };
+
+    let rendered = MarkdownToPdf.run(&ctx, batch.clone()).unwrap();
+    assert_eq!(
+        paths(&rendered),
+        vec!["docs/guide.pdf", "src/main.rs", "logo.png"]
+    );
+    assert!(rendered.items[0].content.starts_with(b"%PDF-"));
+
+    let rendered = CodeToPdf::default().run(&ctx, batch.clone()).unwrap();
+    assert_eq!(
+        paths(&rendered),
+        vec!["docs/guide.md.pdf", "src/main.rs.pdf"]
+    );
+    assert_eq!(
+        rendered.skipped,
+        vec![SkippedPath {
+            path: "logo.png".to_string(),
+            reason: SkipReason::Binary("image/png".to_string()),
+        }]
+    );
+
+    let chunker = Chunker {
+        options: ChunkOptions {
+            size: 40,
+            overlap: 0,
+            unit: ChunkUnit::Chars,
+        },
+    };
+    let chunked = chunker.run(&ctx, batch).unwrap();
+    assert_eq!(
+        paths(&chunked),
+        vec![
+            "docs/guide__chunk001.md",
+            "docs/guide__chunk002.md",
+            "src/main.rs",
+            "logo.png",
+        ]
+    );
+    assert_eq!(chunked.items[1].breadcrumb, vec!["Guide", "Install"]);
+}
+
+/// A stage defined outside the crate, to show pipelines accept any [`Stage`].
+struct Uppercase;
+
+impl Stage for Uppercase {
+    fn name(&self) -> &'static str {
+        "uppercase"
+    }
+
+    fn run(&self, _context: &StageContext, mut batch: Batch) -> Result<Batch, ProcessError> {
+        for item in &mut batch.items {
+            item.content.make_ascii_uppercase();
+        }
+        Ok(batch)
+    }
+}
+
+#[test]
+fn test_pipeline_runs_stages_in_order() {
+    let tmp = tempdir().unwrap();
+    fs::write(tmp.path().join("notes.txt"), "hello\n").unwrap();
+    fs::write(tmp.path().join("big.txt"), "x".repeat(100)).unwrap();
+
+    let pipeline = Pipeline::new(vec![
+        Box::new(Collect {
+            policy: FilePolicy {
+                max_file_size: 50,
+                ..FilePolicy::default()
+            },
+            notebooks: false,
+        }),
+        Box::new(Uppercase),
+    ]);
+    assert_eq!(pipeline.stage_names(), vec!["collect", "uppercase"]);
+    let batch = pipeline.run(&context(tmp.path())).unwrap();
+    assert_eq!(paths(&batch), vec!["notes.txt"]);
+    assert_eq!(batch.items[0].content, b"HELLO\n");
+    assert_eq!(
+        batch.skipped[0].reason,
+        SkipReason::TooLarge {
+            size: 100,
+            limit: 50
+        }
+    );
+}
+
+#[test]
+fn test_kinds_are_presets() {
+    let files = FilePolicy::default();
+    let collect = StageConfig::Collect(files.clone());
+    assert_eq!(
+        ProcessorKind::ReadmeToPDF.preset(&files),
+        vec![StageConfig::Readme, StageConfig::MarkdownToPdf]
+    );
+    assert_eq!(
+        ProcessorKind::FlattenFiles.preset(&files),
+        vec![collect.clone()]
+    );
+    assert_eq!(
+        ProcessorKind::CodeToPDF.preset(&files),
+        vec![collect.clone(), StageConfig::CodeToPdf(Default::default())]
+    );
+    assert_eq!(
+        ProcessorKind::DirectoryToPDF.preset(&files),
+        vec![collect, StageConfig::DirectoryToPdf(Default::default())]
+    );
+
+    let config =
+        config("  kind: FlattenFiles\n  chunking:\n    size: 500\n    overlap: 50\n").unwrap();
+    assert_eq!(
+        config.process.pipeline(),
+        vec![
+            StageConfig::Collect(files),
+            StageConfig::Chunk(ChunkOptions {
+                size: 500,
+                overlap: 50,
+                unit: ChunkUnit::Chars,
+            }),
+        ]
+    );
+}
+
+#[test]
+fn test_stages_are_configured_in_yaml() {
+    let config = config(
+        r#"  stages:
+    - stage: collect
+      max_file_size: 4096
+      binary_types:
+        "image/*": upload
+    - stage: markdown_to_pdf
+    - stage: chunk
+      size: 40
+      overlap: 0
+"#,
+    )
+    .unwrap();
+    assert_eq!(config.process.kind, None);
+    assert_eq!(config.process.stages.len(), 3);
+
+    let tmp = tempdir().unwrap();
+    fs::create_dir_all(tmp.path().join("docs")).unwrap();
+    fs::write(tmp.path().join("docs/guide.md"), "# Guide\n\nHello.\n").unwrap();
+    fs::write(
+        tmp.path().join("notes.txt"),
+        "First paragraph of notes.\n\nSecond paragraph of notes.\n",
+    )
+    .unwrap();
+    fs::write(tmp.path().join("logo.png"), b"\x89PNG\r\n\x1a\n\0").unwrap();
+
+    let source = Processor::new(config.process)
+        .process_sync(ProcessInput {
+            name: "demo".to_string(),
+            repo_path: tmp.path().to_path_buf(),
+            base_url: None,
+            options: Default::default(),
+            variables: Default::default(),
+        })
+        .unwrap();
+    let items: Vec<_> = source
+        .external_items
+        .iter()
+        .map(|i| (i.filename.as_str(), i.mime_type.as_deref().unwrap()))
+        .collect();
+    assert_eq!(
+        items,
+        vec![
+            ("docs__guide.pdf", "application/pdf"),
+            ("logo.png", "image/png"),
+            ("notes__chunk001.txt", "text/plain"),
+            ("notes__chunk002.txt", "text/plain"),
+        ]
+    );
+}
+
+#[test]
+fn test_invalid_pipelines_are_rejected() {
+    let cases = [
+        ("  chunking:\n    size: 10\n", "set `kind` to a preset or list `stages`"),
+        (
+            "  kind: FlattenFiles\n  stages:\n    - stage: collect\n",
+            "set either `kind` or `stages`, not both",
+        ),
+        (
+            "  stages:\n    - stage: code_to_pdf\n",
+            "process.stages[0]: the first stage must be a source stage",
+        ),
+        (
+            "  stages:\n    - stage: collect\n    - stage: readme\n",
+            "process.stages[1]: source stages (`collect`, `readme`) can only come first",
+        ),
+        (
+            "  max_file_size: 10\n  stages:\n    - stage: collect\n",
+            "only apply to `kind` presets",
+        ),
+        (
+            "  stages:\n    - stage: collect\n    - stage: chunk\n      size: 10\n      overlap: 10\n",
+            "process.stages[1].overlap (10) must be smaller than size (10)",
+        ),
+        (
+            "  stages:\n    - stage: collect\n    - stage: code_to_pdf\n    - stage: redact\n    - stage: pii\n",
+            "process.stages[2]: `redact` must come before `code_to_pdf`",
+        ),
+        (
+            "  stages:\n    - stage: readme\n    - stage: markdown_to_pdf\n    - stage: pii\n",
+            "process.stages[2]: `pii` must come before `markdown_to_pdf`",
+        ),
+        ("  stages:\n    - stage: shred\n", "unknown variant `shred`"),
+    ];
+    for (process, expected) in cases {
+        let err = config(process).unwrap_err();
+        assert!(err.contains(expected), "{process}: {err}");
+    }
+}
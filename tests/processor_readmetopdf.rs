@@ -18,9 +18,13 @@ fn test_process_readmetopdf_single_source_to_pdf_item() {
     let process_input = ProcessInput {
         name: "test_repo".to_string(),
         repo_path: repo_path.clone(),
+        base_url: None,
+        options: Default::default(),
+        variables: Default::default(),
     };
     let process_config = ProcessConfig {
-        kind: ProcessorKind::ReadmeToPDF,
+        kind: Some(ProcessorKind::ReadmeToPDF),
+        ..Default::default()
     };
 
     let processor = Processor::new(process_config);
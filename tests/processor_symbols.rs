@@ -0,0 +1,514 @@
+//! Tests for the symbols stage: one item per top-level declaration plus a file outline.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::tempdir;
+
+mod common;
+use common::config;
+
+use llm_bucket::config::Config;
+use llm_bucket::contract::{
+    ExternalSourceInput, ProcessConfig, ProcessInput, StageConfig, Symbol, SymbolKind,
+    SymbolOptions,
+};
+use llm_bucket::preprocess::symbols::extract_symbols;
+use llm_bucket::preprocess::Processor;
+
+const LIB_RS: &str = r#"//! Demo crate.
+
+use std::fmt;
+
+/// Settings for the demo.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub name: String,
+}
+
+// Not documentation.
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Returns the answer.
+///
+/// Always 42.
+pub fn answer(
+    question: &str,
+) -> u32 {
+    42
+}
+
+mod tests;
+"#;
+
+fn symbols(
+    path: &str,
+    text: &str,
+) -> Vec<(SymbolKind, String, String, Option<String>, usize, usize)> {
+    extract_symbols(path, text)
+        .unwrap()
+        .into_iter()
+        .map(|s| (s.kind, s.name, s.signature, s.doc, s.start_line, s.end_line))
+        .collect()
+}
+
+fn process(config: ProcessConfig, root: &Path, base_url: Option<&str>) -> ExternalSourceInput {
+    Processor::new(config)
+        .process_sync(ProcessInput {
+            name: "demo".to_string(),
+            repo_path: root.to_path_buf(),
+            base_url: base_url.map(str::to_string),
+            options: Default::default(),
+            variables: Default::default(),
+        })
+        .unwrap()
+}
+
+#[test]
+fn test_rust_symbols() {
+    assert_eq!(
+        symbols("src/lib.rs", LIB_RS),
+        vec![
+            (
+                SymbolKind::Struct,
+                "Config".to_string(),
+                "pub struct Config".to_string(),
+                Some("Settings for the demo.".to_string()),
+                5,
+                9
+            ),
+            (
+                SymbolKind::Impl,
+                "impl fmt::Display for Config".to_string(),
+                "impl fmt::Display for Config".to_string(),
+                None,
+                11,
+                16
+            ),
+            (
+                SymbolKind::Function,
+                "answer".to_string(),
+                "pub fn answer(question: &str) -> u32".to_string(),
+                Some("Returns the answer.\n\nAlways 42.".to_string()),
+                18,
+                25
+            ),
+            (
+                SymbolKind::Module,
+                "tests".to_string(),
+                "mod tests".to_string(),
+                None,
+                27,
+                27
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_python_typescript_go_and_java_symbols() {
+    let python = r#"import os
+
+
+@dataclass
+class Point:
+    """A point.
+
+    In two dimensions.
+    """
+
+    x: int
+
+
+async def fetch(url: str) -> bytes:
+    '''Download url.'''
+    return b""
+"#;
+    assert_eq!(
+        symbols("app/geo.py", python),
+        vec![
+            (
+                SymbolKind::Class,
+                "Point".to_string(),
+                "class Point".to_string(),
+                Some("A point.\n\nIn two dimensions.".to_string()),
+                4,
+                11
+            ),
+            (
+                SymbolKind::Function,
+                "fetch".to_string(),
+                "async def fetch(url: str) -> bytes".to_string(),
+                Some("Download url.".to_string()),
+                14,
+                16
+            ),
+        ]
+    );
+
+    let typescript = r#"import { x } from "./x";
+
+/** A user account. */
+export interface User {
+  id: number;
+}
+
+export type Id = string | number;
+
+/**
+ * Load a user.
+ */
+export async function load(id: Id): Promise<User> {
+  return { id: 1 };
+}
+
+export const handler = async (req: Request) => {
+  return new Response();
+};
+
+const limit = 10;
+
+namespace Internal {
+  export const x = 1;
+}
+"#;
+    assert_eq!(
+        symbols("web/user.ts", typescript),
+        vec![
+            (
+                SymbolKind::Interface,
+                "User".to_string(),
+                "export interface User".to_string(),
+                Some("A user account.".to_string()),
+                3,
+                6
+            ),
+            (
+                SymbolKind::Type,
+                "Id".to_string(),
+                "export type Id = string | number".to_string(),
+                None,
+                8,
+                8
+            ),
+            (
+                SymbolKind::Function,
+                "load".to_string(),
+                "export async function load(id: Id): Promise<User>".to_string(),
+                Some("Load a user.".to_string()),
+                10,
+                15
+            ),
+            (
+                SymbolKind::Function,
+                "handler".to_string(),
+                "export const handler = async (req: Request) =>".to_string(),
+                None,
+                17,
+                19
+            ),
+            (
+                SymbolKind::Module,
+                "Internal".to_string(),
+                "namespace Internal".to_string(),
+                None,
+                23,
+                25
+            ),
+        ]
+    );
+
+    let go = r#"package server
+
+// Server serves requests.
+type Server struct {
+	addr string
+}
+
+type (
+	// ID identifies a request.
+	ID string
+	Handler interface {
+		Serve() error
+	}
+)
+
+// Start listens on the address.
+// It blocks.
+func (s *Server) Start() error {
+	return nil
+}
+
+func New(addr string) *Server { return &Server{addr} }
+"#;
+    assert_eq!(
+        symbols("server/server.go", go),
+        vec![
+            (
+                SymbolKind::Struct,
+                "Server".to_string(),
+                "type Server struct".to_string(),
+                Some("Server serves requests.".to_string()),
+                3,
+                6
+            ),
+            (
+                SymbolKind::Type,
+                "ID".to_string(),
+                "ID string".to_string(),
+                Some("ID identifies a request.".to_string()),
+                9,
+                10
+            ),
+            (
+                SymbolKind::Interface,
+                "Handler".to_string(),
+                "Handler interface".to_string(),
+                None,
+                11,
+                13
+            ),
+            (
+                SymbolKind::Method,
+                "Server.Start".to_string(),
+                "func (s *Server) Start() error".to_string(),
+                Some("Start listens on the address.\nIt blocks.".to_string()),
+                16,
+                20
+            ),
+            (
+                SymbolKind::Function,
+                "New".to_string(),
+                "func New(addr string) *Server".to_string(),
+                None,
+                22,
+                22
+            ),
+        ]
+    );
+
+    let java = r#"package demo;
+
+import java.util.List;
+
+/**
+ * Greets people.
+ */
+@Deprecated
+public class Greeter {
+    public String greet(String name) {
+        return "Hello " + name;
+    }
+}
+
+enum Mood { HAPPY, SAD }
+"#;
+    assert_eq!(
+        symbols("src/demo/Greeter.java", java),
+        vec![
+            (
+                SymbolKind::Class,
+                "Greeter".to_string(),
+                "@Deprecated public class Greeter".to_string(),
+                Some("Greets people.".to_string()),
+                5,
+                13
+            ),
+            (
+                SymbolKind::Enum,
+                "Mood".to_string(),
+                "enum Mood".to_string(),
+                None,
+                15,
+                15
+            ),
+        ]
+    );
+
+    assert_eq!(extract_symbols("README.md", "# Readme\n"), None);
+    assert_eq!(extract_symbols("build.kt", "fun main() {}\n"), None);
+}
+
+#[test]
+fn test_symbols_preset_emits_symbol_items_and_outlines() {
+    let config = config("  kind: symbols\n").unwrap();
+    assert_eq!(
+        config.process.pipeline()[1],
+        StageConfig::Symbols(SymbolOptions {
+            outline: true,
+            keep_files: false,
+        })
+    );
+
+    let tmp = tempdir().unwrap();
+    let root = tmp.path();
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::write(root.join("src/lib.rs"), LIB_RS).unwrap();
+    fs::write(
+        root.join("src/dup.rs"),
+        "impl A {}\nimpl A {}\nconst X: u8 = 1;\n",
+    )
+    .unwrap();
+    fs::write(root.join("src/consts.rs"), "const X: u8 = 1;\n").unwrap();
+    fs::write(root.join("notes.txt"), "Plain notes.\n").unwrap();
+
+    let source = process(config.process, root, None);
+    let names: Vec<&str> = source
+        .external_items
+        .iter()
+        .map(|i| i.filename.as_str())
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            "notes.txt",
+            "src__consts.rs",
+            "src__dup.rs.outline.md",
+            "src__dup__impl_A.rs",
+            "src__dup__impl_A_2.rs",
+            "src__dup__remainder.rs",
+            "src__lib.rs.outline.md",
+            "src__lib__Config.rs",
+            "src__lib__impl_fmt_Display_for_Config.rs",
+            "src__lib__answer.rs",
+            "src__lib__tests.rs",
+            "src__lib__remainder.rs",
+        ]
+    );
+
+    let item = |name: &str| {
+        source
+            .external_items
+            .iter()
+            .find(|i| i.filename == name)
+            .unwrap()
+    };
+    assert_eq!(
+        std::str::from_utf8(&item("src__lib.rs.outline.md").content).unwrap(),
+        "# Outline of src/lib.rs\n\n\
+         - struct `Config`, lines 5-9: `pub struct Config`\n  Settings for the demo.\n\
+         - impl `impl fmt::Display for Config`, lines 11-16: `impl fmt::Display for Config`\n\
+         - function `answer`, lines 18-25: `pub fn answer(question: &str) -> u32`\n  Returns the answer.\n\
+         - module `tests`, lines 27-27: `mod tests`\n"
+    );
+    let config_item = item("src__lib__Config.rs");
+    assert_eq!(
+        std::str::from_utf8(&config_item.content).unwrap(),
+        "/// Settings for the demo.\n#[derive(Debug, Clone)]\npub struct Config {\n    pub name: String,\n}\n"
+    );
+    assert_eq!(config_item.metadata.breadcrumb, vec!["Config"]);
+    assert_eq!(config_item.metadata.path, "src/lib.rs");
+    assert_eq!(
+        config_item.metadata.symbol,
+        Some(Symbol {
+            kind: SymbolKind::Struct,
+            name: "Config".to_string(),
+            signature: "pub struct Config".to_string(),
+            doc: Some("Settings for the demo.".to_string()),
+            start_line: 5,
+            end_line: 9,
+        })
+    );
+    assert_eq!(item("src__lib.rs.outline.md").metadata.symbol, None);
+    assert_eq!(
+        item("src__dup__remainder.rs").content,
+        b"const X: u8 = 1;\n",
+        "top-level lines that are not symbols survive"
+    );
+    assert_eq!(
+        std::str::from_utf8(&item("src__lib__remainder.rs").content).unwrap(),
+        "//! Demo crate.\n\nuse std::fmt;\n"
+    );
+    assert_eq!(item("src__lib__remainder.rs").metadata.symbol, None);
+}
+
+#[test]
+fn test_symbol_items_link_to_their_lines_and_get_headers() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path();
+    fs::write(root.join("lib.rs"), LIB_RS).unwrap();
+    for args in [
+        &["init", "-q"][..],
+        &["add", "."],
+        &["commit", "-q", "-m", "Initial commit"],
+    ] {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(root)
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?} failed");
+    }
+    let config = Config::from_yaml_str(
+        r#"download:
+  output_dir: out
+  sources:
+    - type: git
+      repo_url: "x"
+process:
+  stages:
+    - stage: collect
+    - stage: symbols
+      outline: false
+      keep_files: true
+    - stage: header
+      templates:
+        default: "path: {{ path }}\nsymbol: {{ symbol }}\nlines: {{ lines }}"
+"#,
+    )
+    .unwrap();
+    let source = process(
+        config.process,
+        root,
+        Some("https://github.com/org/repo/blob/main"),
+    );
+    let names: Vec<&str> = source
+        .external_items
+        .iter()
+        .map(|i| i.filename.as_str())
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            "lib.rs",
+            "lib__Config.rs",
+            "lib__impl_fmt_Display_for_Config.rs",
+            "lib__answer.rs",
+            "lib__tests.rs",
+        ]
+    );
+    let tests = std::str::from_utf8(&source.external_items[4].content).unwrap();
+    assert_eq!(
+        tests,
+        "// path: lib.rs\n// symbol: mod tests\n// lines: 27-27\nmod tests;\n"
+    );
+    let whole = std::str::from_utf8(&source.external_items[0].content).unwrap();
+    assert!(
+        whole.starts_with("// path: lib.rs\n//! Demo crate."),
+        "{whole}"
+    );
+
+    let commit = source.external_items[0].metadata.commit.clone().unwrap();
+    let urls: Vec<&str> = source
+        .external_items
+        .iter()
+        .map(|i| i.metadata.url.as_deref().unwrap())
+        .collect();
+    let blob = format!("https://github.com/org/repo/blob/{commit}/lib.rs");
+    assert_eq!(
+        urls,
+        vec![
+            blob.clone(),
+            format!("{blob}#L5-L9"),
+            format!("{blob}#L11-L16"),
+            format!("{blob}#L18-L25"),
+            format!("{blob}#L27-L27"),
+        ]
+    );
+}
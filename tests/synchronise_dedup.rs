@@ -0,0 +1,280 @@
+//! Tests for content-hash deduplication across sources.
+
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+mod common;
+use common::config;
+
+use llm_bucket::config::Config;
+use llm_bucket::contract::{
+    DedupOptions, DownloadedSource, ExternalItem, ExternalItemInput, ExternalSource,
+    ExternalSourceInput, HeaderOptions, ItemMetadata, MockPreprocessor, MockUploader,
+    NewExternalItem, NewExternalSource, ProcessConfig, ProcessInput, ProcessorKind, SkipReason,
+    SkippedPath,
+};
+use llm_bucket::download::{GitSource, SourceAction};
+use llm_bucket::preprocess::dedup::{deduplicate, fingerprint, DedupReport};
+use llm_bucket::preprocess::Processor;
+use llm_bucket::synchronise::{synchronise_with, SyncOptions};
+use serial_test::serial;
+
+const LICENSE: &str = "MIT License\n\nPermission is hereby granted, free of charge.\n";
+
+fn item(source: &str, path: &str, content: &str) -> ExternalItemInput {
+    ExternalItemInput {
+        filename: path.replace('/', "__"),
+        content: content.as_bytes().to_vec(),
+        mime_type: Some("text/plain".to_string()),
+        metadata: ItemMetadata {
+            path: path.to_string(),
+            url: Some(format!("https://github.com/org/{source}/blob/main/{path}")),
+            ..ItemMetadata::default()
+        },
+    }
+}
+
+fn source(name: &str, items: Vec<ExternalItemInput>) -> ExternalSourceInput {
+    ExternalSourceInput {
+        name: name.to_string(),
+        external_items: items,
+        skipped: Vec::new(),
+        findings: Vec::new(),
+        pii: Default::default(),
+    }
+}
+
+fn names(source: &ExternalSourceInput) -> Vec<&str> {
+    source
+        .external_items
+        .iter()
+        .map(|i| i.filename.as_str())
+        .collect()
+}
+
+#[test]
+fn test_fingerprint_ignores_line_endings_and_trailing_whitespace() {
+    let plain = fingerprint(LICENSE.as_bytes());
+    let windows = format!("\u{feff}{}\r\n\r\n", LICENSE.replace('\n', "  \r\n"));
+    assert_eq!(fingerprint(windows.as_bytes()), plain);
+    assert_ne!(fingerprint(b"MIT License\n"), plain);
+    assert_ne!(
+        fingerprint(LICENSE.replace("free", "  free").as_bytes()),
+        plain,
+        "leading and inner whitespace still count"
+    );
+}
+
+#[test]
+fn test_first_or_preferred_copy_is_kept() {
+    let readme = "# Shared tooling\n";
+    let mut sources = vec![
+        source(
+            "api",
+            vec![
+                item("api", "LICENSE", LICENSE),
+                item("api", "vendor/README.md", readme),
+                item("api", "src/main.rs", "fn main() {}\n"),
+            ],
+        ),
+        source(
+            "web",
+            vec![
+                item("web", "LICENSE", &LICENSE.replace('\n', "\r\n")),
+                item("web", "README.md", readme),
+            ],
+        ),
+        source("tools", vec![item("tools", "README.md", readme)]),
+    ];
+
+    let report = deduplicate(
+        &mut sources,
+        &DedupOptions {
+            prefer: vec!["tools".to_string()],
+            aliases: true,
+        },
+    );
+
+    assert_eq!(names(&sources[0]), vec!["LICENSE", "src__main.rs"]);
+    assert_eq!(names(&sources[1]), Vec::<&str>::new());
+    assert_eq!(names(&sources[2]), vec!["README.md"]);
+    assert_eq!(
+        report,
+        DedupReport {
+            items: 3,
+            bytes: (readme.len() * 2 + LICENSE.len() + 3) as u64,
+        }
+    );
+    assert_eq!(
+        sources[0].skipped,
+        vec![SkippedPath {
+            path: "vendor/README.md".to_string(),
+            reason: SkipReason::Duplicate(
+                "https://github.com/org/tools/blob/main/README.md".to_string()
+            ),
+        }]
+    );
+    assert_eq!(
+        sources[1].skipped[0].reason.to_string(),
+        "duplicate of https://github.com/org/api/blob/main/LICENSE"
+    );
+    assert_eq!(
+        sources[2].external_items[0].metadata.aliases,
+        vec![
+            "https://github.com/org/api/blob/main/vendor/README.md",
+            "https://github.com/org/web/blob/main/README.md",
+        ]
+    );
+    assert_eq!(
+        sources[0].external_items[0].metadata.aliases,
+        vec!["https://github.com/org/web/blob/main/LICENSE"]
+    );
+}
+
+#[test]
+fn test_copies_match_through_headers_and_rendering() {
+    let process = |root: &Path| {
+        fs::write(root.join("LICENSE"), LICENSE).unwrap();
+        Processor::new(ProcessConfig {
+            kind: Some(ProcessorKind::CodeToPDF),
+            header: Some(HeaderOptions::default()),
+            ..Default::default()
+        })
+        .process_sync(ProcessInput {
+            name: root.display().to_string(),
+            repo_path: root.to_path_buf(),
+            base_url: None,
+            options: Default::default(),
+            variables: Default::default(),
+        })
+        .unwrap()
+    };
+    let (a, b) = (tempdir().unwrap(), tempdir().unwrap());
+    let mut sources = vec![process(a.path()), process(b.path())];
+    assert_ne!(
+        sources[0].external_items[0].content, sources[1].external_items[0].content,
+        "headers and page titles name the source"
+    );
+
+    let report = deduplicate(&mut sources, &DedupOptions::default());
+    assert_eq!(report.items, 1);
+    assert_eq!(sources[0].external_items.len(), 1);
+    assert!(sources[1].external_items.is_empty());
+    assert!(
+        sources[0].external_items[0].metadata.aliases.is_empty(),
+        "aliases are off by default"
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_synchronise_uploads_each_content_once() {
+    std::env::set_var("BUCKET_ID", "1");
+    let config = Config::from_yaml_str(
+        r#"
+download:
+  output_dir: out
+  sources:
+    - type: git
+      repo_url: "git@github.com:org/api.git"
+    - type: git
+      repo_url: "git@github.com:org/web.git"
+process:
+  kind: FlattenFiles
+dedup:
+  prefer: ["git@github.com:org/web.git"]
+"#,
+    )
+    .unwrap();
+    let options: SyncOptions = config.sync_options();
+    assert!(options.dedup.is_some());
+
+    let downloaded: Vec<DownloadedSource> = config
+        .download
+        .sources
+        .iter()
+        .map(|source| {
+            let SourceAction::Git(GitSource { repo_url, .. }) = source else {
+                unreachable!()
+            };
+            DownloadedSource {
+                logical_name: repo_url.clone(),
+                local_path: tempdir().unwrap().path().to_path_buf(),
+                original_source: source.clone(),
+            }
+        })
+        .collect();
+
+    let mut preprocessor = MockPreprocessor::new();
+    preprocessor.expect_process().returning(|input| {
+        let repo = if input.name.contains("api") {
+            "api"
+        } else {
+            "web"
+        };
+        let mut items = vec![item(repo, "LICENSE", LICENSE)];
+        if repo == "api" {
+            items.push(item(repo, "src/main.rs", "fn main() {}\n"));
+        }
+        Ok(source(&input.name, items))
+    });
+
+    let mut uploader = MockUploader::new();
+    uploader.expect_list_sources().return_once(|| Ok(vec![]));
+    uploader
+        .expect_create_source()
+        .returning(|req: NewExternalSource<'_>| {
+            Ok(ExternalSource {
+                bucket_id: req.bucket_id,
+                external_source_id: 1,
+                external_source_name: req.name.to_owned(),
+                updated_by: 1,
+                updated_datetime: None,
+            })
+        });
+    uploader
+        .expect_create_item()
+        .times(2)
+        .returning(|req: NewExternalItem<'_>| {
+            Ok(ExternalItem {
+                content_hash: "hash".to_string(),
+                external_item_id: 1,
+                external_source_id: req.external_source_id,
+                processing_state: "Submitted".to_string(),
+                state: "active".to_string(),
+                updated_datetime: None,
+                url: req.url.to_owned(),
+            })
+        });
+
+    let report = synchronise_with(&preprocessor, &uploader, &downloaded, &options)
+        .await
+        .expect("Synchronise should succeed");
+    let uploaded: Vec<Vec<&str>> = report
+        .sources
+        .iter()
+        .map(|s| s.items.iter().map(|i| i.item_name.as_str()).collect())
+        .collect();
+    assert_eq!(uploaded, vec![vec!["src__main.rs"], vec!["LICENSE"]]);
+    assert_eq!(
+        report.dedup,
+        DedupReport {
+            items: 1,
+            bytes: LICENSE.len() as u64,
+        }
+    );
+    assert_eq!(
+        report.sources[0].skipped[0].reason,
+        SkipReason::Duplicate("https://github.com/org/web/blob/main/LICENSE".to_string())
+    );
+}
+
+#[test]
+fn test_invalid_dedup_config_is_rejected() {
+    let err = config("  kind: FlattenFiles\ndedup:\n  prefer: [\"\"]\n").unwrap_err();
+    assert!(
+        err.contains("dedup.prefer must not contain empty source names"),
+        "{err}"
+    );
+}
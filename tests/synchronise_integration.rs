@@ -5,20 +5,29 @@ use llm_bucket::contract::{
     ExternalItemInput,
     ExternalSource,
     ExternalSourceInput,
+    ItemMetadata,
     MockDownloader,
     MockPreprocessor, // <-- Import the test mock!
     MockUploader,
     NewExternalItem,
     NewExternalSource,
+    ProcessConfig,
+    ProcessError,
+    ProcessorKind,
+    SecretFinding,
 };
+use llm_bucket::preprocess::Processors;
 
 use serial_test::serial;
 use std::path::Path;
+use std::sync::atomic::{AtomicI32, Ordering};
 use tempfile::tempdir;
 
 use llm_bucket::contract::Downloader;
 
-use llm_bucket::download::{ConfluenceSource, DownloadConfig, GitSource, SourceAction};
+use llm_bucket::download::{
+    ConfluenceSource, DownloadConfig, GitSource, SourceAction, SourceOptions,
+};
 use llm_bucket::synchronise::{empty_bucket, synchronise};
 
 fn ensure_env_loaded_from_workspace() {
@@ -39,6 +48,7 @@ async fn test_synchronise_readme_to_pdf_upload() {
     let git_source = SourceAction::Git(GitSource {
         repo_url: "git@github.com:kasbuunk/llm-bucket.git".to_string(),
         reference: None,
+        options: Default::default(),
     });
     let git_dir = output_dir.join("git_git@github.com_kasbuunk_llm-bucket.git_main");
     let downloaded_manifest = DownloadedManifest {
@@ -92,7 +102,12 @@ async fn test_synchronise_readme_to_pdf_upload() {
             external_items: vec![ExternalItemInput {
                 filename: "README.pdf".to_string(),
                 content: vec![1, 2, 3, 4],
+                mime_type: None,
+                metadata: Default::default(),
             }],
+            skipped: Vec::new(),
+            findings: Vec::new(),
+            pii: Default::default(),
         })
     });
 
@@ -187,6 +202,7 @@ async fn test_synchronise_confluence_to_pdf_upload() {
     let confluence_source = SourceAction::Confluence(ConfluenceSource {
         base_url: "https://dummy.atlassian.net/wiki".to_string(),
         space_key: "DUMMY".to_string(),
+        options: Default::default(),
     });
 
     let confluence_dir = output_dir.join("confluence_https___dummy.atlassian.net_wiki_DUMMY");
@@ -237,7 +253,12 @@ async fn test_synchronise_confluence_to_pdf_upload() {
                 content: "# Confluence Export\nSome content here."
                     .as_bytes()
                     .to_vec(),
+                mime_type: None,
+                metadata: Default::default(),
             }],
+            skipped: Vec::new(),
+            findings: Vec::new(),
+            pii: Default::default(),
         })
     });
 
@@ -347,6 +368,7 @@ async fn test_synchronise_removes_existing_sources_before_upload() {
         sources: vec![SourceAction::Git(GitSource {
             repo_url: "git@github.com:kasbuunk/llm-bucket.git".to_string(),
             reference: None,
+            options: Default::default(),
         })],
     };
 
@@ -374,7 +396,12 @@ async fn test_synchronise_removes_existing_sources_before_upload() {
             external_items: vec![ExternalItemInput {
                 filename: "README.pdf".to_string(),
                 content: vec![6, 6, 6],
+                mime_type: None,
+                metadata: Default::default(),
             }],
+            skipped: Vec::new(),
+            findings: Vec::new(),
+            pii: Default::default(),
         })
     });
 
@@ -413,11 +440,13 @@ async fn test_synchronise_multiple_sources_reports_each_uploaded() {
     let git_source = SourceAction::Git(GitSource {
         repo_url: "git@github.com:kasbuunk/llm-bucket.git".to_string(),
         reference: None,
+        options: Default::default(),
     });
 
     let confluence_source = SourceAction::Confluence(ConfluenceSource {
         base_url: "https://dummy.atlassian.net/wiki".to_string(),
         space_key: "DUMMY".to_string(),
+        options: Default::default(),
     });
 
     let mut uploader = MockUploader::new();
@@ -484,16 +513,23 @@ async fn test_synchronise_multiple_sources_reports_each_uploaded() {
             vec![ExternalItemInput {
                 filename: "lib.rs".to_string(),
                 content: b"// mock rust lib file".to_vec(),
+                mime_type: None,
+                metadata: Default::default(),
             }]
         } else {
             vec![ExternalItemInput {
                 filename: "main.md".to_string(),
                 content: b"# Main Markdown".to_vec(),
+                mime_type: None,
+                metadata: Default::default(),
             }]
         };
         Ok(ExternalSourceInput {
             name: input.name,
             external_items: out_items,
+            skipped: Vec::new(),
+            findings: Vec::new(),
+            pii: Default::default(),
         })
     });
 
@@ -538,6 +574,7 @@ async fn test_synchronise_flattenfiles_uploads_codebase_files() {
     let git_source = SourceAction::Git(GitSource {
         repo_url: "git@github.com:kasbuunk/llm-bucket.git".to_string(),
         reference: None,
+        options: Default::default(),
     });
     let git_dir = output_dir.join("git_git@github.com_kasbuunk_llm-bucket.git_main");
     let downloaded_manifest = DownloadedManifest {
@@ -585,12 +622,19 @@ async fn test_synchronise_flattenfiles_uploads_codebase_files() {
                 ExternalItemInput {
                     filename: "main.rs".to_string(),
                     content: b"// main rust file".to_vec(),
+                    mime_type: None,
+                    metadata: Default::default(),
                 },
                 ExternalItemInput {
                     filename: "lib.rs".to_string(),
                     content: b"// lib rust file".to_vec(),
+                    mime_type: None,
+                    metadata: Default::default(),
                 },
             ],
+            skipped: Vec::new(),
+            findings: Vec::new(),
+            pii: Default::default(),
         })
     });
 
@@ -636,3 +680,312 @@ async fn test_synchronise_flattenfiles_uploads_codebase_files() {
         }
     }
 }
+
+#[tokio::test]
+#[serial]
+async fn test_synchronise_uses_each_sources_process_config() {
+    ensure_env_loaded_from_workspace();
+    let temp_out = tempdir().unwrap();
+    let code_dir = temp_out.path().join("code");
+    let docs_dir = temp_out.path().join("docs");
+    for dir in [&code_dir, &docs_dir] {
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("README.md"), "# Readme\n\nHello.\n").unwrap();
+        std::fs::write(dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+    }
+
+    let code_source = SourceAction::Git(GitSource {
+        repo_url: "git@github.com:org/code.git".to_string(),
+        reference: None,
+        options: Default::default(),
+    });
+    let docs_source = SourceAction::Git(GitSource {
+        repo_url: "git@github.com:org/docs.git".to_string(),
+        reference: None,
+        options: SourceOptions {
+            process: Some(ProcessConfig {
+                kind: Some(ProcessorKind::ReadmeToPDF),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    });
+    let sources = vec![
+        DownloadedSource {
+            logical_name: "git@github.com:org/code.git".into(),
+            local_path: code_dir,
+            original_source: code_source,
+        },
+        DownloadedSource {
+            logical_name: "git@github.com:org/docs.git".into(),
+            local_path: docs_dir,
+            original_source: docs_source,
+        },
+    ];
+
+    let mut uploader = MockUploader::new();
+    uploader.expect_list_sources().return_once(|| Ok(vec![]));
+    let source_ids = AtomicI32::new(0);
+    uploader
+        .expect_create_source()
+        .returning(move |req: NewExternalSource<'_>| {
+            Ok(ExternalSource {
+                bucket_id: req.bucket_id,
+                external_source_id: source_ids.fetch_add(1, Ordering::SeqCst) + 1,
+                external_source_name: req.name.to_owned(),
+                updated_by: 1,
+                updated_datetime: None,
+            })
+        });
+    uploader
+        .expect_create_item()
+        .returning(|req: NewExternalItem<'_>| {
+            Ok(ExternalItem {
+                content_hash: "hash".to_string(),
+                external_item_id: 1,
+                external_source_id: req.external_source_id,
+                processing_state: "Submitted".to_string(),
+                state: "active".to_string(),
+                updated_datetime: None,
+                url: req.url.to_owned(),
+            })
+        });
+
+    let processors = Processors::new(ProcessConfig {
+        kind: Some(ProcessorKind::FlattenFiles),
+        ..Default::default()
+    });
+    let report = synchronise(&processors, &uploader, &sources)
+        .await
+        .expect("Synchronise should succeed");
+
+    let names: Vec<Vec<&str>> = report
+        .sources
+        .iter()
+        .map(|src| src.items.iter().map(|i| i.item_name.as_str()).collect())
+        .collect();
+    assert_eq!(
+        names,
+        vec![vec!["README.md", "src__main.rs"], vec!["README.pdf"]],
+        "The first source uses the global FlattenFiles, the second its own ReadmeToPDF"
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_synchronise_reports_secret_findings() {
+    ensure_env_loaded_from_workspace();
+    let sources = vec![DownloadedSource {
+        logical_name: "git@github.com:org/code.git".into(),
+        local_path: tempdir().unwrap().path().to_path_buf(),
+        original_source: SourceAction::Git(GitSource {
+            repo_url: "git@github.com:org/code.git".to_string(),
+            reference: None,
+            options: Default::default(),
+        }),
+    }];
+
+    let mut uploader = MockUploader::new();
+    uploader.expect_list_sources().return_once(|| Ok(vec![]));
+    uploader
+        .expect_create_source()
+        .returning(|req: NewExternalSource<'_>| {
+            Ok(ExternalSource {
+                bucket_id: req.bucket_id,
+                external_source_id: 1,
+                external_source_name: req.name.to_owned(),
+                updated_by: 1,
+                updated_datetime: None,
+            })
+        });
+    uploader
+        .expect_create_item()
+        .returning(|req: NewExternalItem<'_>| {
+            Ok(ExternalItem {
+                content_hash: "hash".to_string(),
+                external_item_id: 1,
+                external_source_id: req.external_source_id,
+                processing_state: "Submitted".to_string(),
+                state: "active".to_string(),
+                updated_datetime: None,
+                url: req.url.to_owned(),
+            })
+        });
+
+    let mut mock_preprocessor = MockPreprocessor::new();
+    mock_preprocessor.expect_process().returning(|input| {
+        Ok(ExternalSourceInput {
+            name: input.name,
+            external_items: vec![ExternalItemInput {
+                filename: ".env".to_string(),
+                content: b"TOKEN=[REDACTED:github_token]\n".to_vec(),
+                mime_type: Some("text/plain".to_string()),
+                metadata: Default::default(),
+            }],
+            skipped: Vec::new(),
+            findings: vec![SecretFinding {
+                path: ".env".to_string(),
+                line: 1,
+                rule: "github_token",
+            }],
+            pii: Default::default(),
+        })
+    });
+
+    let report = synchronise(&mock_preprocessor, &uploader, &sources)
+        .await
+        .expect("Synchronise should succeed");
+    assert_eq!(
+        report.sources[0]
+            .findings
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        vec![".env:1: github_token"]
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_synchronise_failed_processing_leaves_bucket_untouched() {
+    ensure_env_loaded_from_workspace();
+    let sources: Vec<DownloadedSource> = ["git@github.com:org/a.git", "git@github.com:org/b.git"]
+        .into_iter()
+        .map(|url| DownloadedSource {
+            logical_name: url.into(),
+            local_path: tempdir().unwrap().path().to_path_buf(),
+            original_source: SourceAction::Git(GitSource {
+                repo_url: url.to_string(),
+                reference: None,
+                options: Default::default(),
+            }),
+        })
+        .collect();
+
+    // No expectations: listing, deleting or creating anything fails the test.
+    let uploader = MockUploader::new();
+    let mut mock_preprocessor = MockPreprocessor::new();
+    mock_preprocessor.expect_process().returning(|input| {
+        if input.name.ends_with("b.git") {
+            return Err(ProcessError::Secrets(vec![SecretFinding {
+                path: ".env".to_string(),
+                line: 1,
+                rule: "github_token",
+            }]));
+        }
+        Ok(ExternalSourceInput {
+            name: input.name,
+            external_items: Vec::new(),
+            skipped: Vec::new(),
+            findings: Vec::new(),
+            pii: Default::default(),
+        })
+    });
+
+    let err = synchronise(&mock_preprocessor, &uploader, &sources)
+        .await
+        .expect_err("the second source fails");
+    assert!(err.starts_with("Process step failed"), "{err}");
+}
+
+#[tokio::test]
+#[serial]
+async fn test_synchronise_uploads_canonical_urls_and_metadata() {
+    ensure_env_loaded_from_workspace();
+    let sources = vec![DownloadedSource {
+        logical_name: "git@github.com:org/code.git".into(),
+        local_path: tempdir().unwrap().path().to_path_buf(),
+        original_source: SourceAction::Git(GitSource {
+            repo_url: "git@github.com:org/code.git".to_string(),
+            reference: None,
+            options: Default::default(),
+        }),
+    }];
+
+    let mut uploader = MockUploader::new();
+    uploader.expect_list_sources().return_once(|| Ok(vec![]));
+    uploader
+        .expect_create_source()
+        .returning(|req: NewExternalSource<'_>| {
+            Ok(ExternalSource {
+                bucket_id: req.bucket_id,
+                external_source_id: 1,
+                external_source_name: req.name.to_owned(),
+                updated_by: 1,
+                updated_datetime: None,
+            })
+        });
+    let uploaded = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorded = uploaded.clone();
+    uploader
+        .expect_create_item()
+        .returning(move |req: NewExternalItem<'_>| {
+            recorded
+                .lock()
+                .unwrap()
+                .push((req.url.to_owned(), req.metadata.clone()));
+            Ok(ExternalItem {
+                content_hash: req.metadata.content_hash.clone(),
+                external_item_id: 1,
+                external_source_id: req.external_source_id,
+                processing_state: "Submitted".to_string(),
+                state: "active".to_string(),
+                updated_datetime: None,
+                url: req.url.to_owned(),
+            })
+        });
+
+    let metadata = ItemMetadata {
+        path: "src/main.rs".to_string(),
+        url: Some("https://github.com/org/code/blob/abc123/src/main.rs".to_string()),
+        language: Some("rust".to_string()),
+        size: 13,
+        content_hash: "e3b0c442".to_string(),
+        modified: Some("2024-03-01T12:00:00+00:00".to_string()),
+        commit: Some("abc123".to_string()),
+        breadcrumb: vec!["Entry point".to_string(), "Startup".to_string()],
+        fingerprint: String::new(),
+        aliases: Vec::new(),
+        symbol: None,
+    };
+    let item_metadata = metadata.clone();
+    let mut mock_preprocessor = MockPreprocessor::new();
+    mock_preprocessor.expect_process().returning(move |input| {
+        Ok(ExternalSourceInput {
+            name: input.name,
+            external_items: vec![
+                ExternalItemInput {
+                    filename: "src__main.rs".to_string(),
+                    content: b"fn main() {}\n".to_vec(),
+                    mime_type: Some("text/x-rust".to_string()),
+                    metadata: item_metadata.clone(),
+                },
+                ExternalItemInput {
+                    filename: "notes.txt".to_string(),
+                    content: b"notes\n".to_vec(),
+                    mime_type: Some("text/plain".to_string()),
+                    metadata: Default::default(),
+                },
+            ],
+            skipped: Vec::new(),
+            findings: Vec::new(),
+            pii: Default::default(),
+        })
+    });
+
+    synchronise(&mock_preprocessor, &uploader, &sources)
+        .await
+        .expect("Synchronise should succeed");
+    let uploaded = uploaded.lock().unwrap();
+    assert_eq!(
+        uploaded[0],
+        (
+            "https://github.com/org/code/blob/abc123/src/main.rs".to_string(),
+            metadata
+        )
+    );
+    assert_eq!(
+        uploaded[1].0, "notes.txt",
+        "items without a known link fall back to their filename"
+    );
+}
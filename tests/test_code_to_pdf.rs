@@ -2,8 +2,13 @@ use std::fs::{self, File};
 use std::io::Write;
 use tempfile::tempdir;
 
+mod common;
+use common::pdf_pages_text;
+
 // Assume your public API looks like this and handles font management internally.
-use llm_bucket::code_to_pdf::code_file_to_pdf;
+use llm_bucket::code_to_pdf::{
+    code_file_to_pdf, code_file_to_pdf_with_options, text_to_pdf, CodeToPdfError, PdfOptions,
+};
 
 #[test]
 fn test_code_file_to_pdf_creates_valid_pdf() {
@@ -30,3 +35,62 @@ fn test_code_file_to_pdf_creates_valid_pdf() {
     let pdf_bytes = fs::read(&output_path).unwrap();
     assert_eq!(&pdf_bytes[0..4], b"%PDF", "PDF file missing magic header");
 }
+
+#[test]
+fn test_code_file_to_pdf_paginates_with_header_and_page_numbers() {
+    let dir = tempdir().unwrap();
+    let input_path = dir.path().join("big.rs");
+    let output_path = dir.path().join("big.pdf");
+    let code: String = (1..=150)
+        .map(|i| format!("let line_{i} = {i};\n"))
+        .collect();
+    fs::write(&input_path, code).unwrap();
+
+    code_file_to_pdf_with_options(
+        &input_path,
+        &output_path,
+        &PdfOptions {
+            header: Some("acme/service/src/big.rs".to_string()),
+            ..PdfOptions::default()
+        },
+    )
+    .expect("PDF conversion failed");
+
+    let pages = pdf_pages_text(&fs::read(&output_path).unwrap());
+    assert!(pages.len() >= 2, "150 lines should not fit on one page");
+    let total = pages.len();
+    for (i, text) in pages.iter().enumerate() {
+        assert!(
+            text.contains("acme/service/src/big.rs"),
+            "Header on every page"
+        );
+        assert!(text.contains(&format!("Page {} of {total}", i + 1)));
+    }
+    assert!(pages[0].contains("let line_1 = 1;"));
+    assert!(pages[total - 1].contains("let line_150 = 150;"));
+}
+
+#[test]
+fn test_text_to_pdf_wraps_long_lines_and_keeps_unicode() {
+    let long = format!("// {}END", "x".repeat(200));
+    let text = format!("fn grüße() {{ \"Ωμέγα ✓\" }}\n{long}\n");
+    let options = PdfOptions {
+        header: Some("unicode.rs".to_string()),
+        ..PdfOptions::default()
+    };
+    let bytes = text_to_pdf(&text, &options).expect("PDF conversion failed");
+
+    let page = pdf_pages_text(&bytes).remove(0);
+    assert!(page.contains("grüße"));
+    assert!(page.contains("Ωμέγα ✓"));
+    assert!(page.contains("↪"), "Long line continues on a marked line");
+    assert!(page.contains("END"));
+}
+
+#[test]
+fn test_text_to_pdf_rejects_empty_input() {
+    assert!(matches!(
+        text_to_pdf(" \n\t\n", &PdfOptions::default()),
+        Err(CodeToPdfError::EmptyInput)
+    ));
+}